@@ -0,0 +1,10 @@
+fn main() {
+    // The gRPC interface is optional; without the feature there is nothing to generate.
+    if std::env::var_os("CARGO_FEATURE_GRPC").is_none() { return; }
+    println!("cargo:rerun-if-changed=proto/think_todo.proto");
+    #[cfg(feature = "grpc")]
+    {
+        std::env::set_var("PROTOC", protoc_bin_vendored::protoc_bin_path().expect("no vendored protoc for this platform"));
+        tonic_prost_build::compile_protos("proto/think_todo.proto").expect("failed to compile proto/think_todo.proto");
+    }
+}
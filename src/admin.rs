@@ -1,30 +1,100 @@
-use crate::tmux::Tmux;
+use crate::session::{self, SessionSpec};
 use crate::db::Db;
-use anyhow::Result;
+use crate::error::Result;
+use crate::i18n::tr;
+use crate::scope::Scope;
+use rusqlite::params_from_iter;
 use std::fs;
 use std::path::PathBuf;
 
-pub struct Admin { pub session_name: String, pub work_dir: PathBuf }
+/// The hq-admin supervisor, or a named one (`hq-admin-<name>`) that only looks after the tasks
+/// in its scope and reads the `admin-<name>` mailbox.
+pub struct Admin { pub session_name: String, pub work_dir: PathBuf, pub name: Option<String> }
 
 impl Admin {
-    pub fn new(work_dir: PathBuf) -> Self { Self { session_name: "hq-admin".to_string(), work_dir } }
-    pub fn start(&self) -> Result<()> {
-        if Tmux::has_session(&self.session_name) { println!("Admin already running."); return Ok(()); }
+    pub fn new(work_dir: PathBuf) -> Self { Self { session_name: "hq-admin".to_string(), work_dir, name: None } }
+    pub fn named(work_dir: PathBuf, name: Option<String>) -> Self {
+        match name {
+            Some(name) => Self { session_name: format!("hq-admin-{}", name), work_dir, name: Some(name) },
+            None => Self::new(work_dir),
+        }
+    }
+    /// Where mail for this admin is delivered.
+    pub fn mailbox(&self) -> String {
+        self.name.as_ref().map_or_else(|| "admin".to_string(), |n| format!("admin-{}", n))
+    }
+    /// Starts the session; a named admin is registered with `scope` (everything when `None`).
+    pub fn start(&self, scope: Option<&Scope>) -> Result<()> {
+        let backend = session::backend(&self.work_dir)?;
+        if backend.exists(&self.session_name) { println!("{}", tr!("Admin already running.")); return Ok(()); }
         let prompt_path = self.work_dir.join("prompts").join("admin.md");
         let mut instruction = fs::read_to_string(prompt_path).unwrap_or_else(|_| "You are Think Todo Admin.".to_string());
         let db = Db::new(self.work_dir.clone())?;
-        let mut stmt = db.conn.prepare("SELECT id, title FROM tasks WHERE status = 'open'")?;
-        let tasks = stmt.query_map([], |row| Ok(format!("- [{}] {}", row.get::<_, String>(0)?, row.get::<_, String>(1)?)))?;
-        instruction.push_str("\n\nPending Tasks:\n");
-        for t in tasks { instruction.push_str(&t?); instruction.push_str("\n"); }
-        let admin_dir = self.work_dir.join("admin");
+        let scope = scope.cloned().unwrap_or_else(Scope::all);
+        if let Some(name) = &self.name {
+            instruction.push_str(&format!(
+                "\n\nYou are the '{}' admin. Your scope is `{}`: only the tasks in it are yours; other admins handle the rest. Your mailbox is '{}'.",
+                name, scope, self.mailbox()));
+        }
+        let (cond, params) = scope.condition();
+        let mut stmt = db.conn.prepare(&format!("SELECT id, title FROM tasks WHERE status = 'open' AND {} ORDER BY priority DESC, rowid", cond))?;
+        let tasks = stmt.query_map(params_from_iter(params), |row| Ok(format!("- [{}] {}", row.get::<_, String>(0)?, row.get::<_, String>(1)?)))?;
+        instruction.push_str("\n\nPending Tasks (most urgent first):\n");
+        for t in tasks { instruction.push_str(&t?); instruction.push('\n'); }
+        // Requests mailed while the admin was offline.
+        let mut stmt = db.conn.prepare("SELECT id, sender, subject, body FROM messages WHERE receiver = ?1 AND status = 'unread' ORDER BY id")?;
+        let unread = stmt.query_map([self.mailbox()], |row| Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?, row.get::<_, String>(2)?, row.get::<_, String>(3)?)))?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        if !unread.is_empty() {
+            instruction.push_str("\nUnread Mail:\n");
+            for (id, sender, subject, body) in &unread {
+                instruction.push_str(&format!("- #{} from {}: {}\n  {}\n", id, sender, subject, digest_body(body)));
+            }
+        }
+        let admin_dir = self.work_dir.join(self.session_name.trim_start_matches("hq-"));
         let _ = fs::create_dir_all(&admin_dir);
-        let cmd = format!("cd {} && gemini --approval-mode yolo \"{}\"", admin_dir.display(), instruction.replace("\"", "\\\""));
-        Tmux::new_session(&self.session_name, &cmd)?;
-        println!("🚀 Think Todo Admin is online!");
+        db.ensure_not_frozen()?;
+        backend.probe("gemini")?;
+        let args = ["--approval-mode".to_string(), "yolo".to_string(), instruction];
+        // tt run by the admin acts as its mailbox name, so what it delegates is traceable to it.
+        let env = [
+            ("TT_AGENT", self.mailbox()),
+            (session::WORKSPACE_VAR, self.work_dir.display().to_string()),
+            (session::TOKEN_VAR, db.issue_token(&self.mailbox())?),
+        ];
+        backend.start(&SessionSpec { name: &self.session_name, dir: &admin_dir, program: "gemini", args: &args, log: None, env: &env })?;
+        if let Some(name) = &self.name { db.register_admin(name, &scope)?; }
+        for (id, ..) in &unread {
+            db.conn.execute("UPDATE messages SET status = 'delivered' WHERE id = ?1", [id])?;
+        }
+        println!("{}", tr!("🚀 Think Todo Admin is online!"));
         Ok(())
     }
+    pub fn stop(&self) -> Result<()> {
+        let killed = session::backend(&self.work_dir)?.kill(&self.session_name);
+        let db = Db::new(self.work_dir.clone())?;
+        // Stopped by hand means the monitor leaves it stopped.
+        db.clear_setting(&self.idle_key())?;
+        // Stopped means its tasks go back to hq-admin, even if the session was already gone.
+        if let Some(name) = &self.name { db.remove_admin(name)?; }
+        killed
+    }
+    /// Settings key set while the monitor has this admin stopped for being idle.
+    pub fn idle_key(&self) -> String { format!("idle_stopped:{}", self.session_name) }
     pub fn attach(&self) -> Result<()> {
-        let _ = std::process::Command::new("tmux").args(&["attach-session", "-t", &self.session_name]).status()?; Ok(())
+        session::backend(&self.work_dir)?.attach(&self.session_name)?;
+        Ok(())
+    }
+}
+
+/// Longest mail body quoted in the admin's startup digest.
+const DIGEST_BODY_CHARS: usize = 500;
+
+/// A mail body on one line, cut to `DIGEST_BODY_CHARS`.
+fn digest_body(body: &str) -> String {
+    let flat = body.split_whitespace().collect::<Vec<_>>().join(" ");
+    match flat.char_indices().nth(DIGEST_BODY_CHARS) {
+        Some((cut, _)) => format!("{}…", &flat[..cut]),
+        None => flat,
     }
 }
@@ -0,0 +1,81 @@
+use crate::error::{Result, ThinkTodoError};
+use crate::git::Git;
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use sha2::{Digest, Sha256};
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+
+/// Worker directories saved by `tt done`: `.logs/artifacts/<task_id>.tar.gz`.
+pub fn dir(work_dir: &Path) -> PathBuf { work_dir.join(".logs").join("artifacts") }
+
+fn snapshot(work_dir: &Path, task_id: &str) -> PathBuf { dir(work_dir).join(format!("{}.tar.gz", task_id)) }
+
+/// Keeps what the worker left behind before its directory is removed. A rig worktree commits
+/// its leftovers to the task branch, which outlives the worktree; any other directory is packed
+/// into a snapshot. Returns the snapshot, if one was written.
+pub fn collect(work_dir: &Path, task_id: &str, worker_path: &Path) -> Result<Option<PathBuf>> {
+    if !worker_path.is_dir() { return Ok(None); }
+    if Git::current_branch(worker_path).ok().as_deref() == Some(Git::task_branch(task_id).as_str()) {
+        Git::checkpoint(worker_path, &format!("tt done: {}", task_id))?;
+        return Ok(None);
+    }
+    let dest = snapshot(work_dir, task_id);
+    fs::create_dir_all(dir(work_dir))?;
+    let mut tar = tar::Builder::new(GzEncoder::new(File::create(&dest)?, Compression::default()));
+    tar.append_dir_all(".", worker_path)?;
+    tar.into_inner()?.finish()?;
+    Ok(Some(dest))
+}
+
+/// Recreates the worker directory from the task branch of `rig` or from the snapshot.
+/// Returns where the work came from, or None when there was nothing to restore.
+pub fn restore(work_dir: &Path, task_id: &str, rig: Option<&Path>, worker_path: &Path) -> Result<Option<String>> {
+    if let Some(parent) = worker_path.parent() { fs::create_dir_all(parent)?; }
+    let branch = Git::task_branch(task_id);
+    if let Some(rig) = rig.filter(|r| Git::branch_exists(r, &branch)) {
+        Git::add_worktree(rig, worker_path, &branch)?;
+        return Ok(Some(format!("branch {}", branch)));
+    }
+    fs::create_dir_all(worker_path)?;
+    let src = snapshot(work_dir, task_id);
+    if !src.exists() { return Ok(None); }
+    tar::Archive::new(GzDecoder::new(File::open(&src)?)).unpack(worker_path)?;
+    Ok(Some(src.display().to_string()))
+}
+
+/// Files shared between agents (e.g. mail attachments), stored once under their SHA-256:
+/// `.logs/artifacts/store/<id>`.
+fn store_dir(work_dir: &Path) -> PathBuf { dir(work_dir).join("store") }
+
+pub fn stored(work_dir: &Path, id: &str) -> PathBuf { store_dir(work_dir).join(id) }
+
+/// Copies a file into the store; returns its id and size.
+pub fn store(work_dir: &Path, path: &Path) -> Result<(String, u64)> {
+    if !path.is_file() { return Err(ThinkTodoError::InvalidArgument(format!("'{}' is not a file", path.display()))); }
+    let bytes = fs::read(path)?;
+    let id = format!("{:x}", Sha256::digest(&bytes));
+    let dest = stored(work_dir, &id);
+    if !dest.exists() {
+        fs::create_dir_all(store_dir(work_dir))?;
+        // Write then rename, so a half-copied file never carries a valid id.
+        let tmp = dest.with_extension("part");
+        fs::write(&tmp, &bytes)?;
+        fs::rename(&tmp, &dest)?;
+    }
+    Ok((id, bytes.len() as u64))
+}
+
+/// The full id of a stored artifact from its id or a unique prefix of at least 8 characters.
+pub fn resolve(work_dir: &Path, id: &str) -> Result<String> {
+    let unknown = || ThinkTodoError::InvalidArgument(format!("'{}' is neither a file nor a stored artifact id", id));
+    if id.len() < 8 || !id.chars().all(|c| c.is_ascii_hexdigit()) { return Err(unknown()); }
+    let id = id.to_ascii_lowercase();
+    let Ok(entries) = fs::read_dir(store_dir(work_dir)) else { return Err(unknown()) };
+    let matches: Vec<String> = entries.flatten().map(|e| e.file_name().to_string_lossy().to_string())
+        .filter(|n| n.starts_with(&id) && !n.ends_with(".part")).collect();
+    match matches.as_slice() {
+        [one] => Ok(one.clone()),
+        [] => Err(unknown()),
+        many => Err(ThinkTodoError::InvalidArgument(format!("artifact id '{}' is ambiguous ({} matches)", id, many.len()))),
+    }
+}
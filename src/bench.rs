@@ -0,0 +1,195 @@
+use crate::config::Config;
+use crate::db::Db;
+use crate::error::{Result, ThinkTodoError};
+use crate::service::{self, NewTask, Sling};
+use crate::session;
+use crate::worker::Worker;
+use rusqlite::params;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+/// A benchmark task: what the engine is asked to do, the files its worker directory starts
+/// with, and the witness, a shell command run in that directory that exits 0 when the work is right.
+pub struct Case {
+    pub id: &'static str,
+    pub instruction: &'static str,
+    pub seed: &'static [(&'static str, &'static str)],
+    pub witness: &'static str,
+}
+
+const BASIC: &[Case] = &[
+    Case {
+        id: "hello",
+        instruction: "Create a file named hello.txt containing exactly one line: hello, bench",
+        seed: &[],
+        witness: "grep -qx 'hello, bench' hello.txt",
+    },
+    Case {
+        id: "sort",
+        instruction: "numbers.txt holds one integer per line. Write them sorted from smallest to largest, one per line, to sorted.txt.",
+        seed: &[("numbers.txt", "42\n7\n-3\n19\n0\n108\n7\n")],
+        witness: "sort -n numbers.txt | cmp -s - sorted.txt",
+    },
+    Case {
+        id: "json",
+        instruction: "Create config.json holding a JSON object whose \"name\" is the string \"bench\" and whose \"retries\" is the number 3.",
+        seed: &[],
+        witness: "grep -Eq '\"name\" *: *\"bench\"' config.json && grep -Eq '\"retries\" *: *3[^0-9]' config.json",
+    },
+];
+
+pub const SUITES: &[(&str, &[Case])] = &[("basic", BASIC)];
+
+pub fn suite(name: &str) -> Result<&'static [Case]> {
+    SUITES.iter().find(|(n, _)| *n == name).map(|(_, cases)| *cases).ok_or_else(|| ThinkTodoError::InvalidArgument(format!(
+        "unknown bench suite '{}' (expected {})", name, SUITES.iter().map(|(n, _)| *n).collect::<Vec<_>>().join(", "))))
+}
+
+/// One case dispatched to one engine; `error` is set when it could not be started.
+pub struct Dispatch {
+    pub task_id: String,
+    pub engine: String,
+    pub case_id: &'static str,
+    pub error: Option<String>,
+}
+
+/// Dispatches every case of `suite_name` to every engine. Each pair is a task tagged `bench`,
+/// worked on by an agent of the same name in a fresh directory; the monitor scores it with the
+/// witness once it is done (see `score`). Returns the run id.
+pub fn run(db: &Db, work_dir: &Path, suite_name: &str, engines: &[String], actor: &str) -> Result<(String, Vec<Dispatch>)> {
+    let cases = suite(suite_name)?;
+    db.ensure_not_frozen()?;
+    let run_id = chrono::Local::now().format("%m%d%H%M%S").to_string();
+    let mut out = Vec::new();
+    for engine in engines {
+        for case in cases {
+            let task_id = format!("bench-{}-{}-{}", run_id, engine, case.id);
+            service::add_task(db, &NewTask {
+                id: task_id.clone(), title: format!("bench {}/{} on {}", suite_name, case.id, engine),
+                rig: None, due: None, tags: vec!["bench".to_string()], priority: None, force_title_update: false, force: true, estimate: None,
+            })?;
+            let dir = work_dir.join("workers").join(&task_id);
+            fs::create_dir_all(&dir)?;
+            for (name, content) in case.seed { fs::write(dir.join(name), content)?; }
+            db.conn.execute("INSERT INTO bench_results (task_id, run_id, suite, case_id, engine, started_at) VALUES (?1, ?2, ?3, ?4, ?5, strftime('%s','now'))",
+                params![task_id, run_id, suite_name, case.id, engine])?;
+            let req = Sling {
+                task_id: task_id.clone(), agent: task_id.clone(), engine: engine.clone(), replace: false, allow_dirty: false, dry_run: false,
+                brief: Some(format!("## BENCHMARK\n{}\nWork only in your worker directory. When you are done, print [TASK_DONE].", case.instruction)),
+            };
+            let error = service::sling(db, work_dir, &req, actor).err().map(|e| e.to_string());
+            if let Some(e) = &error { finish(db, &task_id, "error", e)?; }
+            out.push(Dispatch { task_id, engine: engine.clone(), case_id: case.id, error });
+        }
+    }
+    db.log_audit(actor, "bench_run", &run_id, &format!("{} x {}", suite_name, engines.join(",")))?;
+    Ok((run_id, out))
+}
+
+fn finish(db: &Db, task_id: &str, outcome: &str, detail: &str) -> Result<()> {
+    db.conn.execute(
+        "UPDATE bench_results SET ended_at = strftime('%s','now'), outcome = ?2, detail = ?3,
+             cost_usd = (SELECT COALESCE(SUM(cost_usd), 0) FROM costs WHERE task_id = ?1) WHERE task_id = ?1",
+        params![task_id, outcome, detail])?;
+    db.conn.execute("UPDATE tasks SET status = 'closed' WHERE id = ?1", [task_id])?;
+    Ok(())
+}
+
+/// Scores the cases that finished: closed tasks by their witness, failed launches and attempts as
+/// `fail`, and cases past `[bench] timeout_mins` as `timeout`. Returns how many were scored.
+pub fn score(db: &Db, work_dir: &Path) -> Result<usize> {
+    let timeout = Config::load(work_dir)?.bench.timeout_mins * 60;
+    let mut stmt = db.conn.prepare(
+        "SELECT b.task_id, b.suite, b.case_id, strftime('%s','now') - b.started_at, t.status,
+             (SELECT g.status FROM agents g WHERE g.name = b.task_id),
+             (SELECT a.outcome FROM attempts a WHERE a.task_id = b.task_id ORDER BY a.id DESC LIMIT 1)
+         FROM bench_results b LEFT JOIN tasks t ON t.id = b.task_id WHERE b.outcome IS NULL")?;
+    let pending = stmt.query_map([], |row| Ok((
+        row.get::<_, String>(0)?, row.get::<_, String>(1)?, row.get::<_, String>(2)?, row.get::<_, i64>(3)?,
+        row.get::<_, Option<String>>(4)?, row.get::<_, Option<String>>(5)?, row.get::<_, Option<String>>(6)?,
+    )))?.collect::<rusqlite::Result<Vec<_>>>()?;
+    let backend = session::backend(work_dir)?;
+    let mut scored = 0;
+    for (task_id, suite_name, case_id, age, task_status, agent_status, attempt) in pending {
+        let Some(case) = suite(&suite_name).ok().and_then(|cases| cases.iter().find(|c| c.id == case_id)) else { continue };
+        let (outcome, detail) = if task_status.is_none() {
+            ("error", "the task was deleted".to_string())
+        } else if task_status.as_deref() == Some("closed") {
+            witness(&work_dir.join("workers").join(&task_id), case.witness)
+        } else if agent_status.as_deref() == Some("failed_launch") || matches!(attempt.as_deref(), Some("failed" | "lost")) {
+            ("fail", "the worker failed before finishing".to_string())
+        } else if timeout > 0 && age > timeout {
+            ("timeout", format!("not done after {}", crate::period::format_span(age)))
+        } else {
+            continue;
+        };
+        db.end_attempts(Some(&task_id), None, if outcome == "pass" { "done" } else { "failed" })?;
+        let _ = backend.kill(&Worker::session_name(&task_id));
+        finish(db, &task_id, outcome, &detail)?;
+        tracing::info!(task = %task_id, outcome, "bench case scored");
+        scored += 1;
+    }
+    Ok(scored)
+}
+
+/// Runs the witness in `dir`: `pass` when it exits 0, else `fail` with what it printed.
+fn witness(dir: &Path, check: &str) -> (&'static str, String) {
+    match Command::new("sh").args(["-c", check]).current_dir(dir).output() {
+        Ok(out) if out.status.success() => ("pass", String::new()),
+        Ok(out) => {
+            let said = format!("{}{}", String::from_utf8_lossy(&out.stdout), String::from_utf8_lossy(&out.stderr));
+            match said.trim() {
+                "" => ("fail", format!("witness failed: {}", check)),
+                said => ("fail", format!("witness failed: {}", said.chars().take(200).collect::<String>())),
+            }
+        }
+        Err(e) => ("fail", format!("witness could not run: {}", e)),
+    }
+}
+
+pub struct CaseResult {
+    pub case_id: String,
+    pub engine: String,
+    pub outcome: Option<String>,
+    pub secs: Option<i64>,
+    pub cost_usd: f64,
+    pub detail: Option<String>,
+}
+
+/// The results of `run_id`, or of the latest run.
+pub fn results(db: &Db, run_id: Option<&str>) -> Result<Option<(String, Vec<CaseResult>)>> {
+    let run_id: Option<String> = match run_id {
+        Some(r) => Some(r.to_string()),
+        None => db.conn.query_row("SELECT run_id FROM bench_results ORDER BY started_at DESC, run_id DESC LIMIT 1", [], |row| row.get(0)).ok(),
+    };
+    let Some(run_id) = run_id else { return Ok(None) };
+    let mut stmt = db.conn.prepare(
+        "SELECT case_id, engine, outcome, ended_at - started_at, cost_usd, detail FROM bench_results WHERE run_id = ?1 ORDER BY engine, case_id")?;
+    let rows = stmt.query_map([&run_id], |row| Ok(CaseResult {
+        case_id: row.get(0)?, engine: row.get(1)?, outcome: row.get(2)?, secs: row.get(3)?, cost_usd: row.get(4)?, detail: row.get(5)?,
+    }))?.collect::<rusqlite::Result<Vec<_>>>()?;
+    if rows.is_empty() { return Err(ThinkTodoError::InvalidArgument(format!("no bench run '{}'", run_id))); }
+    Ok(Some((run_id, rows)))
+}
+
+/// Scored cases of one engine over every run of a suite.
+pub struct EngineScore {
+    pub engine: String,
+    pub cases: i64,
+    pub passed: i64,
+    /// Average wall time of the cases that passed.
+    pub avg_pass_secs: Option<f64>,
+    pub avg_cost_usd: f64,
+}
+
+/// Engines by pass rate over every run of `suite_name`.
+pub fn summary(db: &Db, suite_name: &str) -> Result<Vec<EngineScore>> {
+    let mut stmt = db.conn.prepare(
+        "SELECT engine, COUNT(*), SUM(outcome = 'pass'), AVG(CASE WHEN outcome = 'pass' THEN ended_at - started_at END), AVG(cost_usd)
+         FROM bench_results WHERE suite = ?1 AND outcome IS NOT NULL GROUP BY engine ORDER BY SUM(outcome = 'pass') * 1.0 / COUNT(*) DESC, engine")?;
+    let rows = stmt.query_map([suite_name], |row| Ok(EngineScore {
+        engine: row.get(0)?, cases: row.get(1)?, passed: row.get(2)?, avg_pass_secs: row.get(3)?, avg_cost_usd: row.get(4)?,
+    }))?;
+    Ok(rows.collect::<rusqlite::Result<Vec<_>>>()?)
+}
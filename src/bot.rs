@@ -0,0 +1,140 @@
+use crate::config::Config;
+use crate::db::Db;
+use crate::error::{Result, ThinkTodoError};
+use crate::health;
+use crate::i18n::tr;
+use crate::service;
+use serde_json::{json, Value};
+use std::env;
+use std::path::Path;
+use std::thread;
+use std::time::Duration;
+
+/// Settings key of the next update to fetch, so a restarted bot doesn't run a command twice.
+const OFFSET_KEY: &str = "telegram:offset";
+/// Seconds Telegram holds a getUpdates request open when nothing happens.
+const POLL_SECS: u64 = 30;
+/// Telegram refuses messages over 4096 characters.
+const MAX_REPLY: usize = 4000;
+
+const HELP: &str = "/status - warnings and whether dispatch is frozen
+/beads - tasks by status, and who is on what
+/approve - lift the freeze so dispatch resumes
+/approve <mail id> [note] - answer an escalation with an approval
+/freeze [reason] - stop all new agent activity
+/nudge <agent> <message> - flash a message in the agent's session
+/done <task> - close a task and tear down its worker";
+
+/// Answers commands sent to the bot by the chats in `[telegram] chats` until stopped, or for one
+/// batch of updates with `once`.
+pub fn telegram(db: &Db, work_dir: &Path, token: Option<String>, once: bool) -> Result<()> {
+    let cfg = Config::load(work_dir)?.telegram;
+    let token = token.or_else(|| env::var(&cfg.token_env).ok()).filter(|t| !t.is_empty())
+        .ok_or_else(|| ThinkTodoError::Config(format!("no bot token: pass --token or set ${}", cfg.token_env)))?;
+    let root = format!("{}/bot{}", cfg.api.trim_end_matches('/'), token);
+    let agent = ureq::AgentBuilder::new().timeout_read(Duration::from_secs(POLL_SECS + 10)).build();
+    loop {
+        let offset: i64 = db.get_setting(OFFSET_KEY)?.and_then(|v| v.parse().ok()).unwrap_or(0);
+        let wait = if once { 0 } else { POLL_SECS };
+        let updates = agent.get(&format!("{}/getUpdates", root))
+            .query("offset", &offset.to_string()).query("timeout", &wait.to_string())
+            .call().map_err(|e| ThinkTodoError::remote_hiding(e, &token)).and_then(|r| Ok(r.into_json::<Value>()?));
+        let updates = match updates {
+            Ok(u) => u,
+            Err(e) if !once => {
+                tracing::warn!("telegram: getUpdates failed, retrying: {}", e);
+                thread::sleep(Duration::from_secs(5));
+                continue;
+            }
+            Err(e) => return Err(e),
+        };
+        for update in updates["result"].as_array().into_iter().flatten() {
+            let id = update["update_id"].as_i64().unwrap_or(offset);
+            let message = &update["message"];
+            if let (Some(chat), Some(text)) = (message["chat"]["id"].as_i64(), message["text"].as_str()) {
+                let reply = if cfg.chats.contains(&chat) {
+                    let who = message["from"]["username"].as_str().map(str::to_string).unwrap_or_else(|| chat.to_string());
+                    handle(db, work_dir, text, &format!("telegram:{}", who))
+                } else {
+                    tracing::warn!(chat, "telegram: command from a chat not in [telegram] chats ignored");
+                    tr!("This chat ({}) may not control tt. Add it to [telegram] chats in think.toml.", chat)
+                };
+                let reply: String = reply.chars().take(MAX_REPLY).collect();
+                if let Err(e) = agent.post(&format!("{}/sendMessage", root)).send_json(json!({ "chat_id": chat, "text": reply })) {
+                    tracing::warn!(chat, "telegram: reply not sent: {}", ThinkTodoError::remote_hiding(e, &token));
+                }
+            }
+            db.set_setting(OFFSET_KEY, &(id + 1).to_string())?;
+        }
+        if once { return Ok(()); }
+    }
+}
+
+/// Runs one command and says how it went.
+fn handle(db: &Db, work_dir: &Path, text: &str, actor: &str) -> String {
+    let mut words = text.split_whitespace();
+    // In groups commands arrive as /status@botname.
+    let command = words.next().unwrap_or("").split('@').next().unwrap_or("");
+    let args: Vec<&str> = words.collect();
+    tracing::info!(actor, command, "telegram command");
+    let result = match (command, args.as_slice()) {
+        ("/status", _) => status(db, work_dir),
+        ("/beads", _) => beads(db),
+        ("/approve", []) => service::unfreeze(db, work_dir, actor)
+            .map(|lifted| if lifted { tr!("🔥 Unfrozen. Dispatch is enabled again.") } else { tr!("Workspace is not frozen.") }),
+        ("/approve", [id, note @ ..]) => approve(db, id, &note.join(" "), actor),
+        ("/freeze", reason) => {
+            let reason = if reason.is_empty() { tr!("frozen by {}", actor) } else { reason.join(" ") };
+            service::freeze(db, &reason, actor).map(|was| match was {
+                Some(current) => tr!("🧊 Already frozen: {}", current),
+                None => tr!("🧊 Frozen: {}. No agent will be started until /approve.", reason),
+            })
+        }
+        ("/nudge", [agent, message @ ..]) if !message.is_empty() => service::nudge(db, work_dir, agent, &message.join(" "), actor)
+            .map(|shown| if shown { tr!("🔔 Nudged {}.", agent) } else { tr!("📬 {} has no live session; the nudge went to its inbox.", agent) }),
+        ("/done", [task]) => service::done(db, work_dir, task, actor).map(|closed| {
+            let mut out = tr!("✅ {} closed.", task);
+            if let Some(mc) = closed.conflicts { out.push_str(&tr!(" ⚠️ {} file(s) conflict with the rig.", mc.conflicts.len())); }
+            out
+        }),
+        _ => Ok(crate::i18n::text(HELP).to_string()),
+    };
+    result.unwrap_or_else(|e| format!("❌ {}", e))
+}
+
+fn status(db: &Db, work_dir: &Path) -> Result<String> {
+    let mut out = match db.frozen()? {
+        Some(reason) => tr!("🧊 Frozen: {}\n", reason),
+        None => tr!("▶️ Dispatching\n"),
+    };
+    let warnings = health::warnings(db, work_dir)?;
+    if warnings.is_empty() { out.push_str(&tr!("No warnings.")); }
+    for w in warnings { out.push_str(&format!("⚠️ [{}] {}: {}\n", w.kind, w.target, health::localize(&w.message))); }
+    Ok(out.trim_end().to_string())
+}
+
+fn beads(db: &Db) -> Result<String> {
+    let mut stmt = db.conn.prepare("SELECT status, COUNT(*) FROM tasks GROUP BY status ORDER BY status")?;
+    let counts = stmt.query_map([], |row| Ok(format!("{} {}", row.get::<_, String>(0)?, row.get::<_, i64>(1)?)))?.collect::<rusqlite::Result<Vec<_>>>()?;
+    if counts.is_empty() { return Ok(tr!("No tasks.")); }
+    let mut out = counts.join(" · ");
+    let mut stmt = db.conn.prepare("SELECT id, title, assignee FROM tasks WHERE status = 'in_progress' ORDER BY id")?;
+    let active = stmt.query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?, row.get::<_, Option<String>>(2)?)))?;
+    for row in active {
+        let (id, title, assignee) = row?;
+        out.push_str(&format!("\n🔨 {} {} ({})", id, title, assignee.unwrap_or_else(|| tr!("unassigned"))));
+    }
+    Ok(out)
+}
+
+/// Marks the mail read and answers its sender with the approval.
+fn approve(db: &Db, id: &str, note: &str, actor: &str) -> Result<String> {
+    let id: i64 = id.trim_start_matches('#').parse().map_err(|_| ThinkTodoError::InvalidArgument(format!("'{}' is not a mail id", id)))?;
+    let (sender, subject): (String, String) = db.conn.query_row("SELECT sender, subject FROM messages WHERE id = ?1", [id], |row| Ok((row.get(0)?, row.get(1)?)))
+        .map_err(|_| ThinkTodoError::InvalidArgument(format!("no mail #{}", id)))?;
+    db.conn.execute("UPDATE messages SET status = 'read' WHERE id = ?1", [id])?;
+    let body = if note.is_empty() { tr!("Approved.") } else { tr!("Approved. {}", note) };
+    db.send_mail(actor, &sender, &format!("Re: {}", subject), &body)?;
+    db.log_audit(actor, "approved", &id.to_string(), &sender)?;
+    Ok(tr!("👍 Approved #{} ({}); {} was told.", id, subject, sender))
+}
@@ -0,0 +1,60 @@
+use crate::db::Db;
+use crate::error::Result;
+use crate::period;
+use chrono::{DateTime, Local, Utc};
+
+/// An iCalendar (RFC 5545) feed of every unfinished task with a due date.
+/// Due dates at local midnight become all-day events, anything else a 30 minute slot.
+pub fn ics(db: &Db) -> Result<String> {
+    let mut stmt = db.conn.prepare(
+        "SELECT id, title, status, assignee, due_at FROM tasks WHERE due_at IS NOT NULL AND status != 'closed' ORDER BY due_at")?;
+    let rows = stmt.query_map([], |row| Ok((
+        row.get::<_, String>(0)?, row.get::<_, String>(1)?, row.get::<_, String>(2)?,
+        row.get::<_, Option<String>>(3)?, row.get::<_, i64>(4)?,
+    )))?;
+    let stamp = Utc::now().format("%Y%m%dT%H%M%SZ").to_string();
+    let mut lines = vec![
+        "BEGIN:VCALENDAR".to_string(),
+        "VERSION:2.0".to_string(),
+        "PRODID:-//think-todo//tt//EN".to_string(),
+        "X-WR-CALNAME:Think Todo".to_string(),
+    ];
+    for r in rows {
+        let (id, title, status, assignee, due) = r?;
+        let Some(due_utc) = DateTime::from_timestamp(due, 0) else { continue };
+        let local = due_utc.with_timezone(&Local);
+        lines.push("BEGIN:VEVENT".to_string());
+        lines.push(format!("UID:{}-due@think-todo", id));
+        lines.push(format!("DTSTAMP:{}", stamp));
+        if period::is_all_day(due) {
+            lines.push(format!("DTSTART;VALUE=DATE:{}", local.format("%Y%m%d")));
+        } else {
+            lines.push(format!("DTSTART:{}", due_utc.format("%Y%m%dT%H%M%SZ")));
+            lines.push("DURATION:PT30M".to_string());
+        }
+        lines.push(format!("SUMMARY:{}", escape(&format!("Due: [{}] {}", id, title))));
+        lines.push(format!("DESCRIPTION:{}", escape(&format!("status: {}\nassignee: {}", status, assignee.as_deref().unwrap_or("-")))));
+        lines.push("END:VEVENT".to_string());
+    }
+    lines.push("END:VCALENDAR".to_string());
+    Ok(lines.iter().map(|l| fold(l)).collect())
+}
+
+/// Content lines longer than 75 octets continue on the next line after a single space.
+fn fold(line: &str) -> String {
+    let mut out = String::new();
+    let mut width = 0;
+    for c in line.chars() {
+        if width + c.len_utf8() > 75 {
+            out.push_str("\r\n ");
+            width = 1;
+        }
+        out.push(c);
+        width += c.len_utf8();
+    }
+    out + "\r\n"
+}
+
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace(';', "\\;").replace(',', "\\,").replace('\n', "\\n")
+}
@@ -0,0 +1,72 @@
+use crate::config::Config;
+use crate::db::Db;
+use crate::error::Result;
+use crate::session::SessionBackend;
+use crate::worker::Worker;
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+
+/// Settings key holding when a frozen session is resumed.
+const STALL_PREFIX: &str = "chaos_stall:";
+
+/// True with probability `chance`.
+fn roll(chance: f64) -> bool {
+    if chance <= 0.0 { return false; }
+    let mut h = RandomState::new().build_hasher();
+    h.write_i64(chrono::Local::now().timestamp_nanos_opt().unwrap_or_default());
+    (h.finish() as f64 / u64::MAX as f64) < chance
+}
+
+/// Sessions frozen by chaos, with the time each is due to resume.
+fn stalled(db: &Db) -> Result<Vec<(String, i64)>> {
+    let mut stmt = db.conn.prepare("SELECT substr(key, ?1), CAST(value AS INTEGER) FROM settings WHERE key LIKE ?2")?;
+    let rows = stmt.query_map(rusqlite::params![STALL_PREFIX.len() as i64 + 1, format!("{}%", STALL_PREFIX)], |row| Ok((row.get(0)?, row.get(1)?)))?;
+    Ok(rows.collect::<rusqlite::Result<Vec<_>>>()?)
+}
+
+/// Resumes frozen sessions whose stall is over, or all of them when chaos is off, so a daemon
+/// restarted without `--chaos` never leaves a worker frozen.
+pub fn resume(db: &Db, backend: &dyn SessionBackend, enabled: bool) -> Result<()> {
+    let now = chrono::Local::now().timestamp();
+    for (session, until) in stalled(db)? {
+        if enabled && until > now { continue; }
+        if backend.exists(&session) {
+            if let Err(e) = backend.pause(&session, false) { tracing::warn!(session = %session, "chaos: could not resume: {}", e); }
+        }
+        db.clear_setting(&format!("{}{}", STALL_PREFIX, session))?;
+        tracing::info!(session = %session, "chaos: resumed");
+    }
+    Ok(())
+}
+
+/// One round of fault injection: each worker with a live session on an in-progress task may be
+/// killed or frozen, as `[chaos]` says. Every fault is recorded as a monitor decision, so
+/// `tt replay` shows what was injected next to how the daemon reacted.
+pub fn inject(db: &Db, config: &Config, backend: &dyn SessionBackend) -> Result<()> {
+    let chaos = &config.chaos;
+    let stall_secs = if chaos.stall_secs > 0 { chaos.stall_secs } else { config.health.stall_minutes * 60 + 60 };
+    let frozen: Vec<String> = stalled(db)?.into_iter().map(|(s, _)| s).collect();
+    let mut stmt = db.conn.prepare("SELECT id, assignee FROM tasks WHERE status = 'in_progress' AND assignee IS NOT NULL")?;
+    let active = stmt.query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)))?.collect::<rusqlite::Result<Vec<_>>>()?;
+    let now = chrono::Local::now().timestamp();
+    for (task_id, agent) in active {
+        let session = Worker::session_name(&agent);
+        if !backend.exists(&session) || frozen.contains(&session) { continue; }
+        if roll(chaos.kill_chance) {
+            backend.kill(&session)?;
+            db.log_decision("monitor", "chaos_kill", &session, "killed", serde_json::json!({ "task": task_id, "agent": agent, "kill_chance": chaos.kill_chance }))?;
+            tracing::warn!(agent = %agent, task = %task_id, "chaos: killed session");
+        } else if roll(chaos.stall_chance) {
+            if let Err(e) = backend.pause(&session, true) {
+                tracing::warn!(session = %session, "chaos: could not freeze: {}", e);
+                continue;
+            }
+            db.set_setting(&format!("{}{}", STALL_PREFIX, session), &(now + stall_secs).to_string())?;
+            db.log_decision("monitor", "chaos_stall", &session, "frozen", serde_json::json!({
+                "task": task_id, "agent": agent, "stall_chance": chaos.stall_chance, "stall_secs": stall_secs, "now": now,
+            }))?;
+            tracing::warn!(agent = %agent, task = %task_id, stall_secs, "chaos: froze session");
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,50 @@
+use crate::error::{Result, ThinkTodoError};
+use crate::session;
+use clap_complete::engine::{ArgValueCandidates, CompletionCandidate};
+use clap_complete::env::Shells;
+use rusqlite::{Connection, OpenFlags};
+use std::env;
+use std::io;
+use std::path::PathBuf;
+
+/// Environment variable the shell sets to ask tt for completions instead of running a command.
+pub const VAR: &str = "COMPLETE";
+
+/// Writes the script that hooks `shell` up to tt's dynamic completion, for `eval` or the shell's
+/// completion directory.
+pub fn registration(shell: &str) -> Result<()> {
+    let shells = Shells::builtins();
+    let completer = shells.completer(shell)
+        .ok_or_else(|| ThinkTodoError::InvalidArgument(format!("no completions for '{}' (expected bash, zsh, fish, elvish or powershell)", shell)))?;
+    let exe = env::current_exe()?;
+    let bin = exe.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_else(|| "tt".to_string());
+    completer.write_registration(VAR, "tt", &bin, &exe.to_string_lossy(), &mut io::stdout())?;
+    Ok(())
+}
+
+/// Task ids, with their titles as the description.
+pub fn tasks() -> ArgValueCandidates {
+    ArgValueCandidates::new(|| candidates("SELECT id, title || ' (' || status || ')' FROM tasks ORDER BY id"))
+}
+
+/// Agents that have been spawned, with the task they are on.
+pub fn agents() -> ArgValueCandidates {
+    ArgValueCandidates::new(|| candidates("SELECT name, task_id FROM agents ORDER BY name"))
+}
+
+/// Registered rigs, with their paths.
+pub fn rigs() -> ArgValueCandidates {
+    ArgValueCandidates::new(|| candidates("SELECT name, path FROM rigs ORDER BY name"))
+}
+
+/// Rows of `sql` (value, description) from the workspace's think.db. Completion must never fail or
+/// create a database, so anything missing yields nothing.
+fn candidates(sql: &str) -> Vec<CompletionCandidate> {
+    let work_dir = env::var_os(session::WORKSPACE_VAR).map(PathBuf::from).or_else(|| env::current_dir().ok());
+    let Some(db) = work_dir.map(|d| d.join("think.db")).filter(|p| p.is_file()) else { return Vec::new() };
+    let Ok(conn) = Connection::open_with_flags(db, OpenFlags::SQLITE_OPEN_READ_ONLY) else { return Vec::new() };
+    let Ok(mut stmt) = conn.prepare(sql) else { return Vec::new() };
+    stmt.query_map([], |row| Ok(CompletionCandidate::new(row.get::<_, String>(0)?).help(Some(row.get::<_, String>(1)?.into()))))
+        .map(|rows| rows.flatten().collect())
+        .unwrap_or_default()
+}
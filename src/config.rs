@@ -0,0 +1,478 @@
+use crate::error::{Result, ThinkTodoError};
+use crate::period;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+/// Workspace configuration loaded from `think.toml`. Every section is optional.
+#[derive(Deserialize, Default, Debug, Clone)]
+#[serde(default)]
+pub struct Config {
+    /// Language of CLI output and worker prompts: "en" (default) or "zh".
+    pub lang: String,
+    /// How timestamps print: "local" (default), "utc" or "epoch". `--utc` and `--epoch` override it.
+    pub time: String,
+    pub recover: RecoverConfig,
+    pub session: SessionConfig,
+    pub budget: BudgetConfig,
+    pub digest: DigestConfig,
+    pub rig: RigConfig,
+    pub checkpoint: CheckpointConfig,
+    pub health: HealthConfig,
+    pub server: ServerConfig,
+    pub undo: UndoConfig,
+    pub archive: ArchiveConfig,
+    pub tasks: TasksConfig,
+    pub plan: PlanConfig,
+    pub logs: LogsConfig,
+    pub prompt: PromptConfig,
+    pub chaos: ChaosConfig,
+    pub bench: BenchConfig,
+    pub telemetry: TelemetryConfig,
+    pub email: EmailConfig,
+    pub telegram: TelegramConfig,
+    pub daily: DailyConfig,
+    pub theme: ThemeConfig,
+    /// `[usage.<engine>]`: how to read token usage and cost from that engine's log output.
+    pub usage: HashMap<String, UsagePattern>,
+    /// `[[sla]]` entries, one per tag.
+    pub sla: Vec<SlaPolicy>,
+    /// `[[escalation]]` steps taken when a question or escalation stays unread.
+    pub escalation: Vec<EscalationStep>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct RecoverConfig {
+    /// "respawn" restarts the worker with resume context, "reopen" puts the task back in the backlog.
+    pub mode: String,
+    /// Number of log lines handed to a respawned worker as resume context.
+    pub context_lines: usize,
+}
+
+impl Default for RecoverConfig {
+    fn default() -> Self { Self { mode: "reopen".to_string(), context_lines: 40 } }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct SessionConfig {
+    /// "tmux", "process" or "mock" (tests). Defaults to tmux, or process on Windows where tmux isn't available.
+    pub backend: String,
+    /// Prepended to every tmux session name so workspaces sharing a machine don't collide,
+    /// e.g. "tt-{workspace}-" ({workspace} is the workspace directory's name).
+    pub prefix: String,
+    /// Directories appended to PATH in tmux sessions, ':'-separated, for engines installed outside
+    /// the PATH tmux starts with (e.g. "/home/me/.bun/bin").
+    pub extra_path: String,
+    /// Seconds a new worker session has to show it is running before it's reported; 0 skips the check.
+    pub startup_secs: u64,
+    /// Seconds a spawned agent has to run `tt agent checkin` before the monitor reports a failed launch; 0 disables it.
+    pub checkin_secs: i64,
+    /// Seconds without a check-in or log output before the monitor kills the session of an agent
+    /// whose task is closed or whose launch failed; 0 disables it.
+    pub reap_idle_secs: i64,
+    /// Seconds an admin session may show no new output, with nothing open in its scope, before the
+    /// monitor stops it; it is started again when a task arrives. 0 disables it.
+    pub supervisor_idle_secs: i64,
+}
+
+impl Default for SessionConfig {
+    fn default() -> Self {
+        Self { backend: if cfg!(windows) { "process" } else { "tmux" }.to_string(), prefix: String::new(), extra_path: String::new(), startup_secs: 5, checkin_secs: 300, reap_idle_secs: 900, supervisor_idle_secs: 0 }
+    }
+}
+
+#[derive(Deserialize, Default, Debug, Clone)]
+#[serde(default)]
+pub struct BudgetConfig {
+    /// Daily spend cap in USD; 0 disables it. Exceeding it freezes dispatch until `tt unfreeze`.
+    pub daily_usd_cap: f64,
+    /// Per-task spend in USD above which the board warns; 0 disables it.
+    pub task_usd_cap: f64,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct DigestConfig {
+    /// Include a repo digest (README head, tree, recent commits) when a task belongs to a rig.
+    pub enabled: bool,
+    pub readme_lines: usize,
+    pub tree_depth: usize,
+    pub max_entries: usize,
+    pub commits: usize,
+}
+
+impl Default for DigestConfig {
+    fn default() -> Self { Self { enabled: true, readme_lines: 30, tree_depth: 2, max_entries: 80, commits: 10 } }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct RigConfig {
+    /// What to do when a task's rig has uncommitted changes or is behind upstream: "warn", "refuse" or "ignore".
+    pub dirty_policy: String,
+    /// Run `git fetch` before checking how far the rig is behind.
+    pub fetch: bool,
+}
+
+impl Default for RigConfig {
+    fn default() -> Self { Self { dirty_policy: "warn".to_string(), fetch: false } }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct CheckpointConfig {
+    /// Seconds between automatic commits of a worker's worktree to its task branch; 0 disables.
+    pub interval_secs: i64,
+}
+
+impl Default for CheckpointConfig {
+    fn default() -> Self { Self { interval_secs: 600 } }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct HealthConfig {
+    /// Minutes without log output before an active worker counts as stalled; 0 disables it.
+    pub stall_minutes: i64,
+    /// Seconds since the monitor's last pass before it counts as down.
+    pub daemon_timeout_secs: i64,
+    /// Warn when the disk holding the workspace has less free space than this; 0 disables it.
+    pub min_free_mb: u64,
+    /// Warn when logs, worker directories and artifacts together take more than this; 0 disables it.
+    pub max_usage_mb: u64,
+}
+
+impl Default for HealthConfig {
+    fn default() -> Self { Self { stall_minutes: 30, daemon_timeout_secs: 60, min_free_mb: 1024, max_usage_mb: 0 } }
+}
+
+/// `[email]`: mail to `human` or to an address also goes out by SMTP, and replies come back
+/// through an IMAP poll. Off while `smtp_host` is empty.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct EmailConfig {
+    /// Address that mail to `human` is sent to.
+    pub human: String,
+    pub from: String,
+    pub smtp_host: String,
+    pub smtp_port: u16,
+    /// Empty leaves replies unread.
+    pub imap_host: String,
+    pub imap_port: u16,
+    /// Implicit TLS (ports 465 and 993); false only for servers on a trusted network.
+    pub tls: bool,
+    pub user: String,
+    /// Environment variable holding the mail account's password.
+    pub password_env: String,
+    /// Seconds between IMAP polls.
+    pub poll_secs: i64,
+}
+
+impl Default for EmailConfig {
+    fn default() -> Self {
+        Self {
+            human: String::new(), from: String::new(), smtp_host: String::new(), smtp_port: 465, imap_host: String::new(), imap_port: 993,
+            tls: true, user: String::new(), password_env: "TT_EMAIL_PASSWORD".to_string(), poll_secs: 60,
+        }
+    }
+}
+
+/// `[telegram]`: who may drive `tt bot telegram`.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct TelegramConfig {
+    /// Chat ids the bot answers; everyone else is ignored.
+    pub chats: Vec<i64>,
+    /// Environment variable holding the bot token when `--token` isn't given.
+    pub token_env: String,
+    /// Bot API root.
+    pub api: String,
+}
+
+impl Default for TelegramConfig {
+    fn default() -> Self {
+        Self { chats: Vec::new(), token_env: "TT_TELEGRAM_TOKEN".to_string(), api: "https://api.telegram.org".to_string() }
+    }
+}
+
+/// `[daily]`: the morning digest the monitor sends once a day.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct DailyConfig {
+    /// Local time of day ("08:30") the digest goes out; empty disables it.
+    pub at: String,
+    /// tt mail receiver; empty skips tt mail.
+    pub mail: String,
+    /// Address to email it to through `[email]`.
+    pub email: String,
+    /// URL that gets it POSTed as JSON `{"text": ...}` (Slack and Mattermost incoming webhooks).
+    pub webhook: String,
+}
+
+impl Default for DailyConfig {
+    fn default() -> Self { Self { at: String::new(), mail: "admin".to_string(), email: String::new(), webhook: String::new() } }
+}
+
+/// `[theme]`: how board, trail, task list and mail output look.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct ThemeConfig {
+    /// Keep the emoji in messages; off leaves plain text for logs and screen readers.
+    pub emoji: bool,
+    /// Color of headings: red, green, yellow, blue, magenta, cyan or none. Never used when
+    /// `NO_COLOR` is set or output isn't a terminal.
+    pub accent: String,
+    /// Draw boxes, bullets and arrows with ASCII instead of Unicode.
+    pub ascii: bool,
+}
+
+impl Default for ThemeConfig {
+    fn default() -> Self { Self { emoji: true, accent: "cyan".to_string(), ascii: false } }
+}
+
+/// `[telemetry]`: where the monitor streams events, costs and finished attempts.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct TelemetryConfig {
+    /// "clickhouse" or "bigquery"; empty disables the sink.
+    pub sink: String,
+    /// ClickHouse HTTP endpoint, or the BigQuery API root.
+    pub url: String,
+    /// ClickHouse database or BigQuery dataset.
+    pub database: String,
+    /// BigQuery project.
+    pub project: String,
+    /// ClickHouse user.
+    pub user: String,
+    /// Environment variable holding the ClickHouse password or a BigQuery OAuth access token.
+    pub secret_env: String,
+    /// Seconds between shipments.
+    pub interval_secs: i64,
+    /// Delete shipped events and costs older than this from think.db (e.g. "90d"); empty keeps them.
+    pub prune_after: String,
+}
+
+impl Default for TelemetryConfig {
+    fn default() -> Self {
+        Self {
+            sink: String::new(), url: String::new(), database: "tt".to_string(), project: String::new(), user: "default".to_string(),
+            secret_env: "TT_TELEMETRY_SECRET".to_string(), interval_secs: 60, prune_after: String::new(),
+        }
+    }
+}
+
+/// `tt bench run`.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct BenchConfig {
+    /// Engines a run dispatches every case to, unless `--engines` is given.
+    pub engines: Vec<String>,
+    /// Minutes a case may run before it is scored as timed out.
+    pub timeout_mins: i64,
+}
+
+impl Default for BenchConfig {
+    fn default() -> Self { Self { engines: vec!["claude".to_string(), "gemini".to_string(), "opencode".to_string()], timeout_mins: 30 } }
+}
+
+/// Fault injection for `tt monitor start --chaos`; ignored without the flag.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct ChaosConfig {
+    /// Chance per monitor pass that an active worker's session is killed.
+    pub kill_chance: f64,
+    /// Chance per monitor pass that an active worker is frozen and stops writing its log.
+    pub stall_chance: f64,
+    /// How long a frozen worker stays frozen; 0 means just past `[health] stall_minutes`.
+    pub stall_secs: i64,
+}
+
+impl Default for ChaosConfig {
+    fn default() -> Self { Self { kill_chance: 0.0, stall_chance: 0.0, stall_secs: 0 } }
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct ServerConfig {
+    /// Origins allowed to call the API from a browser, e.g. "https://ops.example.com"; "*" allows any. Empty disables CORS.
+    pub cors_origins: Vec<String>,
+    /// Path prefix the dashboard and API are served under, e.g. "/tt". `tt serve --base-path` overrides it.
+    pub base_path: String,
+    /// Take the client address and prefix from X-Forwarded-* headers set by a reverse proxy. Off by
+    /// default: without a proxy in front, any client could set them.
+    pub trust_forwarded: bool,
+    /// `[[server.tenants]]`: other workspaces served next to this one.
+    pub tenants: Vec<TenantConfig>,
+    /// Serve the read-only `/status` page (progress and throughput, no controls or logs) without sign-in.
+    pub status_page: bool,
+}
+
+/// A workspace `tt serve` hosts under `/w/<name>/`, with its own database and work dir.
+#[derive(Deserialize, Default, Debug, Clone)]
+#[serde(default)]
+pub struct TenantConfig {
+    pub name: String,
+    /// Workspace directory; relative to the one `tt serve` runs in.
+    pub dir: String,
+    /// Environment variable holding its API token. When set, requests to `/w/<name>/` need
+    /// `Authorization: Bearer <token>`, and requests elsewhere bearing it go to this workspace.
+    pub token_env: String,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct UndoConfig {
+    /// How long after `tt done` a task can be reopened with `tt undone`, e.g. "7d"; "0s" disables it.
+    pub window: String,
+}
+
+impl Default for UndoConfig {
+    fn default() -> Self { Self { window: "7d".to_string() } }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct TasksConfig {
+    /// Start of the ids `tt task add` makes up when none is given: "TT-" gives TT-001, TT-002...
+    pub id_prefix: String,
+    /// Digits the number is padded to; larger numbers just grow.
+    pub id_digits: usize,
+}
+
+impl Default for TasksConfig {
+    fn default() -> Self { Self { id_prefix: "TT-".to_string(), id_digits: 3 } }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct PlanConfig {
+    /// Shell command `tt plan from-file` runs: it gets the planning prompt on stdin and prints the plan.
+    pub command: String,
+    /// Seconds the command gets to answer before it is killed; 0 waits as long as it takes.
+    pub timeout_secs: u64,
+}
+
+impl Default for PlanConfig {
+    fn default() -> Self { Self { command: "claude -p".to_string(), timeout_secs: 600 } }
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct ArchiveConfig {
+    /// How long after closing the monitor archives a task, e.g. "30d"; empty keeps closed tasks listed.
+    pub after: String,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct LogsConfig {
+    /// Most megabytes a task's live log directory may hold before the monitor cuts it back to its latest lines; 0 disables it.
+    pub task_max_mb: u64,
+}
+
+impl Default for LogsConfig {
+    fn default() -> Self { Self { task_max_mb: 100 } }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct PromptConfig {
+    /// Append the generated tt conventions (markers, commands, identity, limits) to every worker prompt.
+    pub preamble: bool,
+}
+
+impl Default for PromptConfig {
+    fn default() -> Self { Self { preamble: true } }
+}
+
+/// A regex over an engine's log output with named groups `cost`, `input`, `output` and
+/// optionally `model`. Without a `cost` group the cost is worked out from the token prices.
+#[derive(Deserialize, Default, Debug, Clone)]
+#[serde(default)]
+pub struct UsagePattern {
+    pub pattern: String,
+    /// Model name recorded when the pattern has no `model` group; defaults to the engine name.
+    pub model: Option<String>,
+    pub input_usd_per_mtok: f64,
+    pub output_usd_per_mtok: f64,
+}
+
+/// Service level for tasks carrying `tag`, e.g. `start_within = "30m"`, `finish_within = "4h"` (measured from creation).
+#[derive(Deserialize, Debug, Clone)]
+pub struct SlaPolicy {
+    pub tag: String,
+    #[serde(default)]
+    pub start_within: Option<String>,
+    #[serde(default)]
+    pub finish_within: Option<String>,
+}
+
+/// One step of the escalation ladder: once a question or escalation has been unread for `after`,
+/// the monitor takes every action set here, once per mail.
+#[derive(Deserialize, Default, Debug, Clone)]
+#[serde(default)]
+pub struct EscalationStep {
+    pub after: String,
+    /// Flash a reminder in the receiver's session (hq-admin for `admin`).
+    pub nudge: bool,
+    /// Address to email it to through `[email]`.
+    pub email: String,
+    /// URL that gets it POSTed as JSON `{"text": ...}` (Slack and Mattermost incoming webhooks).
+    pub webhook: String,
+}
+
+impl Config {
+    pub fn load(work_dir: &Path) -> Result<Self> {
+        let path = work_dir.join("think.toml");
+        if !path.exists() { return Ok(Self::default()); }
+        let content = fs::read_to_string(&path)?;
+        let config: Self = toml::from_str(&content).map_err(|e| ThinkTodoError::Config(format!("Invalid {}: {}", path.display(), e)))?;
+        let sla_spans = config.sla.iter().flat_map(|p| [&p.start_within, &p.finish_within]).flatten();
+        let prune = Some(&config.telemetry.prune_after).filter(|p| !p.is_empty());
+        let ladder = config.escalation.iter().map(|s| &s.after);
+        let archive = Some(&config.archive.after).filter(|a| !a.is_empty());
+        for span in sla_spans.chain([&config.undo.window]).chain(prune).chain(ladder).chain(archive) {
+            if period::span_secs(span).is_none() {
+                return Err(ThinkTodoError::Config(format!("Invalid {}: '{}' is not a span like 30m, 4h or 2d", path.display(), span)));
+            }
+        }
+        if crate::ids::task_id(&format!("{}1", config.tasks.id_prefix)).is_err() {
+            return Err(ThinkTodoError::Config(format!("Invalid {}: [tasks] id_prefix '{}' can't start a task id", path.display(), config.tasks.id_prefix)));
+        }
+        for (engine, usage) in &config.usage {
+            if let Err(e) = regex::Regex::new(&usage.pattern) {
+                return Err(ThinkTodoError::Config(format!("Invalid {}: [usage.{}] pattern: {}", path.display(), engine, e)));
+            }
+        }
+        if !["", "clickhouse", "bigquery"].contains(&config.telemetry.sink.as_str()) {
+            return Err(ThinkTodoError::Config(format!("Invalid {}: [telemetry] sink must be clickhouse or bigquery", path.display())));
+        }
+        if !["warn", "refuse", "ignore"].contains(&config.rig.dirty_policy.as_str()) {
+            return Err(ThinkTodoError::Config(format!("Invalid {}: [rig] dirty_policy must be warn, refuse or ignore", path.display())));
+        }
+        if crate::i18n::parse(&config.lang).is_err() {
+            return Err(ThinkTodoError::Config(format!("Invalid {}: lang must be en or zh", path.display())));
+        }
+        if crate::output::accent_code(&config.theme.accent).is_none() {
+            return Err(ThinkTodoError::Config(format!("Invalid {}: [theme] accent must be red, green, yellow, blue, magenta, cyan or none", path.display())));
+        }
+        if period::parse_time_style(&config.time).is_err() {
+            return Err(ThinkTodoError::Config(format!("Invalid {}: time must be local, utc or epoch", path.display())));
+        }
+        if !config.daily.at.is_empty() && chrono::NaiveTime::parse_from_str(&config.daily.at, "%H:%M").is_err() {
+            return Err(ThinkTodoError::Config(format!("Invalid {}: [daily] at must be a time like \"08:30\"", path.display())));
+        }
+        if !config.email.smtp_host.is_empty() && !crate::ids::is_address(&config.email.from) {
+            return Err(ThinkTodoError::Config(format!("Invalid {}: [email] from must be an email address", path.display())));
+        }
+        for (key, chance) in [("kill_chance", config.chaos.kill_chance), ("stall_chance", config.chaos.stall_chance)] {
+            if !(0.0..=1.0).contains(&chance) {
+                return Err(ThinkTodoError::Config(format!("Invalid {}: [chaos] {} must be between 0 and 1", path.display(), key)));
+            }
+        }
+        Ok(config)
+    }
+}
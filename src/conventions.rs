@@ -0,0 +1,120 @@
+use crate::config::Config;
+use crate::i18n::{self, tr};
+use clap::CommandFactory;
+use serde::Serialize;
+
+/// A line an agent prints at the start of a line in its output; the monitor acts on it.
+pub struct Marker {
+    pub token: &'static str,
+    pub effect: &'static str,
+}
+
+pub const TASK_DONE: &str = "[TASK_DONE]";
+pub const QUESTION: &str = "[QUESTION]";
+pub const RESULT: &str = "[RESULT]";
+
+pub const MARKERS: &[Marker] = &[
+    Marker { token: TASK_DONE, effect: "closes your task (same as `tt done`)" },
+    Marker { token: QUESTION, effect: "mails the rest of the line to the admin as a question; keep working on what you can meanwhile" },
+    Marker { token: RESULT, effect: "records the rest of the line as your task's result in the audit trail" },
+];
+
+/// The commands agents are expected to use, with why they would.
+const AGENT_COMMANDS: &[(&str, &str)] = &[
+    ("agent checkin", "first thing after you start, and now and then as a heartbeat"),
+    ("done", "when the task is finished"),
+    ("mail inbox", "messages for you"),
+    ("mail read", "one message; --save-attachments copies its files"),
+    ("mail send", "reach the admin or another agent"),
+    ("board list", "the whole backlog and what needs attention"),
+    ("peek", "another agent's latest output"),
+    ("task show", "a task and its attempts"),
+];
+
+/// The marker at the start of `line`, if any, and the text after it.
+pub fn marker(line: &str) -> Option<(&'static str, &str)> {
+    let line = line.trim_start();
+    MARKERS.iter().find_map(|m| line.strip_prefix(m.token).map(|rest| (m.token, rest.trim())))
+}
+
+/// `tt <path>` with its arguments, read from the CLI definition so it can't go stale.
+fn usage(path: &str) -> Option<String> {
+    let mut cmd = crate::Cli::command();
+    for name in path.split_whitespace() {
+        cmd = cmd.find_subcommand(name)?.clone();
+    }
+    let mut out = format!("tt {}", path);
+    for arg in cmd.get_arguments().filter(|a| !a.is_global_set() && a.get_id() != "help") {
+        let name = arg.get_id().as_str().to_uppercase();
+        match arg.get_long() {
+            None => out.push_str(&format!(" <{}>", name)),
+            Some(long) if arg.is_required_set() => out.push_str(&format!(" --{} <{}>", long, name)),
+            Some(_) => {}
+        }
+    }
+    Some(out)
+}
+
+/// Who the agent is, as far as tt is concerned.
+pub struct Identity<'a> {
+    pub agent: &'a str,
+    pub task: &'a str,
+}
+
+#[derive(Serialize)]
+pub struct Entry {
+    pub name: String,
+    pub description: String,
+}
+
+fn entry(name: impl Into<String>, description: impl Into<String>) -> Entry {
+    Entry { name: name.into(), description: description.into() }
+}
+
+/// The tt conventions: environment, markers, commands and limits, all taken from the code and
+/// `config`. `tt conventions` prints them; worker prompts embed them with the identity filled in.
+#[derive(Serialize)]
+pub struct Conventions {
+    pub identity: Vec<Entry>,
+    pub markers: Vec<Entry>,
+    pub commands: Vec<Entry>,
+    pub limits: Vec<String>,
+}
+
+pub fn collect(config: &Config, identity: Option<&Identity>) -> Conventions {
+    let (agent, task) = identity.map_or(("<agent>", "<task_id>"), |i| (i.agent, i.task));
+    let mut limits = Vec::new();
+    if config.session.checkin_secs > 0 {
+        limits.push(tr!("Run `tt agent checkin` within {}s of starting or the launch counts as failed.", config.session.checkin_secs));
+    }
+    if config.logs.task_max_mb > 0 {
+        limits.push(tr!("Your task's log is cut back past {} MB; don't rely on old output staying in it.", config.logs.task_max_mb));
+    }
+    limits.push(tr!("Stay inside your worker directory."));
+    Conventions {
+        identity: vec![
+            entry(format!("TT_AGENT={}", agent), tr!("your agent name")),
+            entry(format!("TT_TASK={}", task), tr!("your task")),
+            entry("TT_WORKSPACE", tr!("the workspace; tt commands run from your worker directory find it through this")),
+        ],
+        markers: MARKERS.iter().map(|m| entry(format!("{} ...", m.token), i18n::text(m.effect))).collect(),
+        commands: AGENT_COMMANDS.iter().filter_map(|(path, why)| Some(entry(usage(path)?, i18n::text(why)))).collect(),
+        limits,
+    }
+}
+
+impl Conventions {
+    pub fn markdown(&self) -> String {
+        let list = |entries: &[Entry]| entries.iter().map(|e| format!("- `{}`: {}\n", e.name, e.description)).collect::<String>();
+        let mut md = tr!("## TT CONVENTIONS\n\n");
+        md.push_str(&tr!("### Identity\n{}\n", list(&self.identity)));
+        md.push_str(&tr!("### Markers\nPrint these at the start of a line:\n{}\n", list(&self.markers)));
+        md.push_str(&tr!("### Commands\n{}\n", list(&self.commands)));
+        md.push_str(&tr!("### Limits\n"));
+        for l in &self.limits { md.push_str(&format!("- {}\n", l)); }
+        md
+    }
+}
+
+/// The conventions as markdown, for worker prompts.
+pub fn render(config: &Config, identity: Option<&Identity>) -> String { collect(config, identity).markdown() }
@@ -0,0 +1,122 @@
+use crate::config::Config;
+use crate::db::Db;
+use crate::email;
+use crate::error::{Result, ThinkTodoError};
+use crate::i18n::tr;
+use crate::period;
+use chrono::{Local, NaiveTime, TimeZone};
+use std::path::Path;
+
+/// Settings key holding when the last digest went out; the next one covers what happened since.
+pub const LAST_KEY: &str = "daily:last";
+const DAY: i64 = 24 * 3600;
+
+/// The digest of what happened since `since`: closed tasks, spend, failures, what is waiting on a
+/// human, and what falls due in the next day.
+pub fn compose(db: &Db, since: i64, now: i64) -> Result<String> {
+    let mut out = tr!("Since {}\n", period::format_time(since));
+
+    let mut stmt = db.conn.prepare(
+        "SELECT l.target, COALESCE(t.title, '') FROM audit_logs l LEFT JOIN tasks t ON t.id = l.target
+         WHERE l.action = 'task_closed' AND l.timestamp > ?1 AND l.timestamp <= ?2 ORDER BY l.timestamp")?;
+    let closed = stmt.query_map([since, now], |row| Ok(format!("  [{}] {}", row.get::<_, String>(0)?, row.get::<_, String>(1)?)))?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    section(&mut out, &tr!("Completed ({})", closed.len()), &closed);
+
+    let (total, tasks): (f64, i64) = db.conn.query_row(
+        "SELECT COALESCE(SUM(cost_usd), 0), COUNT(DISTINCT task_id) FROM costs WHERE timestamp > ?1 AND timestamp <= ?2", [since, now],
+        |row| Ok((row.get(0)?, row.get(1)?)))?;
+    let mut stmt = db.conn.prepare(
+        "SELECT COALESCE(model, '?'), SUM(cost_usd) FROM costs WHERE timestamp > ?1 AND timestamp <= ?2 GROUP BY model ORDER BY SUM(cost_usd) DESC")?;
+    let by_model = stmt.query_map([since, now], |row| Ok(format!("  {}: ${:.2}", row.get::<_, String>(0)?, row.get::<_, f64>(1)?)))?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    section(&mut out, &tr!("Spend: ${:.2} over {} task(s)", total, tasks), &by_model);
+
+    let mut stmt = db.conn.prepare(
+        "SELECT task_id, agent_name, outcome FROM attempts WHERE outcome IN ('failed', 'lost') AND ended_at > ?1 AND ended_at <= ?2 ORDER BY ended_at")?;
+    let failed = stmt.query_map([since, now], |row| Ok(format!("  [{}] {} {}", row.get::<_, String>(0)?, row.get::<_, String>(1)?, row.get::<_, String>(2)?)))?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    section(&mut out, &tr!("Failures ({})", failed.len()), &failed);
+
+    // Escalations and URGENT mail still unread, as on the board.
+    let mut waiting = Vec::new();
+    if let Some(reason) = db.frozen()? { waiting.push(tr!("  Dispatch is frozen: {} (tt unfreeze)", reason)); }
+    let mut stmt = db.conn.prepare(
+        "SELECT id, sender, subject FROM messages WHERE status = 'unread' AND (sender = 'monitor' OR subject LIKE '%URGENT%') ORDER BY id")?;
+    waiting.extend(stmt.query_map([], |row| Ok(tr!("  #{} from {}: {} (tt mail read {})",
+        row.get::<_, i64>(0)?, row.get::<_, String>(1)?, row.get::<_, String>(2)?, row.get::<_, i64>(0)?)))?
+        .collect::<rusqlite::Result<Vec<_>>>()?);
+    section(&mut out, &tr!("Awaiting you ({})", waiting.len()), &waiting);
+
+    let mut stmt = db.conn.prepare("SELECT id, title, due_at FROM tasks WHERE status != 'closed' AND due_at IS NOT NULL AND due_at < ?1 ORDER BY due_at")?;
+    let due = stmt.query_map([now + DAY], |row| {
+        let due: i64 = row.get(2)?;
+        Ok(format!("  [{}] {} {}{}", row.get::<_, String>(0)?, row.get::<_, String>(1)?, period::format_due(due),
+            if period::is_overdue(due, now) { " (overdue)" } else { "" }))
+    })?.collect::<rusqlite::Result<Vec<_>>>()?;
+    section(&mut out, &tr!("Due by tomorrow ({})", due.len()), &due);
+    Ok(out.trim_end().to_string())
+}
+
+fn section(out: &mut String, title: &str, lines: &[String]) {
+    out.push_str(&format!("\n{}\n", title));
+    for l in lines { out.push_str(l); out.push('\n'); }
+}
+
+/// Sends the digest to every channel in `[daily]`: tt mail, email and the webhook. Returns the
+/// channels it reached; a channel that fails doesn't stop the others.
+pub fn deliver(db: &Db, config: &Config, subject: &str, text: &str) -> Result<Vec<&'static str>> {
+    let cfg = &config.daily;
+    let mut sent = Vec::new();
+    let mut failed = Vec::new();
+    if !cfg.mail.is_empty() {
+        db.send_mail("monitor", &cfg.mail, subject, text)?;
+        sent.push("mail");
+    }
+    if !cfg.email.is_empty() {
+        match email::send(&config.email, &cfg.email, subject, text) {
+            Ok(()) => sent.push("email"),
+            Err(e) => failed.push(format!("email: {}", e)),
+        }
+    }
+    if !cfg.webhook.is_empty() {
+        match crate::remote::post(&cfg.webhook).send_json(serde_json::json!({ "text": format!("*{}*\n{}", subject, text) })) {
+            Ok(_) => sent.push("webhook"),
+            Err(e) => failed.push(format!("webhook: {}", ThinkTodoError::remote_hiding(e, crate::error::url_secret(&cfg.webhook)))),
+        }
+    }
+    for f in &failed { tracing::warn!("daily digest not delivered by {}", f); }
+    if sent.is_empty() && !failed.is_empty() { return Err(ThinkTodoError::Remote(failed.join("; "))); }
+    Ok(sent)
+}
+
+/// Sends the digest once the day's `[daily] at` has passed and it hasn't gone out since.
+pub fn send_due(db: &Db, work_dir: &Path) -> Result<()> {
+    let config = Config::load(work_dir)?;
+    let Ok(at) = NaiveTime::parse_from_str(&config.daily.at, "%H:%M") else { return Ok(()) };
+    let now = Local::now();
+    let Some(slot) = Local.from_local_datetime(&now.date_naive().and_time(at)).earliest() else { return Ok(()) };
+    let last: Option<i64> = db.get_setting(LAST_KEY)?.and_then(|v| v.parse().ok());
+    if now < slot || last.is_some_and(|l| l >= slot.timestamp()) { return Ok(()); }
+
+    let text = compose(db, since(db, now.timestamp())?, now.timestamp())?;
+    // Marked first: a channel that is down shouldn't make every later pass mail the others again.
+    db.set_setting(LAST_KEY, &now.timestamp().to_string())?;
+    match deliver(db, &config, &subject(now.timestamp()), &text) {
+        Ok(sent) => {
+            db.log_audit("monitor", "daily_digest", "workspace", &sent.join(","))?;
+            tracing::info!(channels = %sent.join(","), "daily digest sent");
+        }
+        Err(e) => tracing::warn!("daily digest not sent: {}", e),
+    }
+    Ok(())
+}
+
+pub fn subject(now: i64) -> String {
+    tr!("Daily digest {}", Local.timestamp_opt(now, 0).single().map(|t| t.format("%Y-%m-%d").to_string()).unwrap_or_default())
+}
+
+/// Where the next digest starts: the last one sent, or a day ago.
+pub fn since(db: &Db, now: i64) -> Result<i64> {
+    Ok(db.get_setting(LAST_KEY)?.and_then(|v| v.parse().ok()).unwrap_or(now - DAY))
+}
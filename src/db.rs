@@ -1,47 +1,786 @@
-use rusqlite::{params, Connection, Result};
+use crate::error::{Result, ThinkTodoError};
+use crate::ids;
+use crate::scope::{self, Scope};
+use rusqlite::types::Value;
+use rusqlite::{params, params_from_iter, Connection, ErrorCode, OptionalExtension};
 use std::path::PathBuf;
+use std::time::Duration;
 
 pub struct Db {
     pub conn: Connection,
+    pub work_dir: PathBuf,
 }
 
+/// Actors that are people (or their front ends) rather than agents.
+pub const HUMAN_ACTORS: &[&str] = &["user", "web", "grpc"];
+
+/// A named group of agents (`tt team create`) with an optional lead and scope.
+pub struct Team {
+    pub name: String,
+    pub lead: Option<String>,
+    pub scope: Option<Scope>,
+    pub members: Vec<String>,
+}
+
+/// Held while a task or worker is being torn down; see `Db::lock`.
+pub struct Lock<'a> { db: &'a Db, target: String }
+
+impl Drop for Lock<'_> {
+    fn drop(&mut self) {
+        let _ = self.db.conn.execute("DELETE FROM locks WHERE target = ?1", [&self.target]);
+    }
+}
+
+/// Seconds after which a lock counts as abandoned.
+const LOCK_EXPIRY_SECS: i64 = 600;
+
+/// Kill switch: while this file exists in the workspace nothing new is dispatched. Its contents,
+/// if any, are the reason. Lets an operator halt everything with `touch` when tt itself won't run.
+pub const FREEZE_FILE: &str = ".tt-freeze";
+/// Settings key of the day and spend at the last unfreeze, "2026-10-16 12.50": the budget cap
+/// only freezes again once that day's spend goes beyond it.
+pub const BUDGET_ALLOWED_KEY: &str = "budget:allowed";
+
+#[derive(Default)]
+pub struct CostFilter {
+    pub task: Option<String>,
+    pub agent: Option<String>,
+    pub model: Option<String>,
+    pub since: Option<i64>,
+    pub limit: Option<usize>,
+}
+
+pub struct CostTotals {
+    pub rows: i64,
+    pub input_tokens: i64,
+    pub output_tokens: i64,
+    pub cost_usd: f64,
+}
+
+/// One spawn of a worker on a task, from launch until it was closed, replaced or lost.
+pub struct Attempt {
+    pub id: i64,
+    pub agent_name: String,
+    pub engine: String,
+    pub model: Option<String>,
+    pub started_at: i64,
+    pub ended_at: Option<i64>,
+    pub outcome: Option<String>,
+    pub cost_usd: f64,
+}
+
+/// Attempts grouped by engine and model, for `tt stats engines`.
+pub struct EngineStats {
+    pub engine: String,
+    pub model: Option<String>,
+    pub attempts: i64,
+    pub finished: i64,
+    pub done: i64,
+    pub cost_usd: f64,
+    /// Total wall time of the attempts that ended in `done`.
+    pub done_secs: i64,
+}
+
+pub struct DueTask {
+    pub id: String,
+    pub title: String,
+    pub assignee: Option<String>,
+    pub due_at: i64,
+}
+
+/// When a task was created, first picked up and closed.
+pub struct TaskTimeline {
+    pub id: String,
+    pub status: String,
+    pub created_at: Option<i64>,
+    pub started_at: Option<i64>,
+    pub closed_at: Option<i64>,
+}
+
+pub struct CostRow {
+    pub task_id: String,
+    pub agent_name: String,
+    pub model: String,
+    pub input_tokens: i64,
+    pub output_tokens: i64,
+    pub cost_usd: f64,
+}
+
+/// A file attached to a message, kept in the artifact store under `artifact`.
+pub struct Attachment {
+    pub name: String,
+    pub artifact: String,
+    pub size: u64,
+    /// Where the file was in the sender's worker directory, relative to it.
+    pub path: Option<String>,
+    /// The task the sender was working on when it attached the file.
+    pub task_id: Option<String>,
+}
+
+/// How long a connection waits for another writer's lock.
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
 impl Db {
+    /// Opens the workspace database. Waits up to `BUSY_TIMEOUT` for another writer (e.g. the
+    /// monitor) before failing with `DbLocked`.
+    pub fn new(work_dir: PathBuf) -> Result<Self> {
+        let conn = Connection::open(work_dir.join("think.db"))?;
+        conn.busy_timeout(BUSY_TIMEOUT)?;
+        Self::init(conn, work_dir)
+    }
+    fn init(conn: Connection, work_dir: PathBuf) -> Result<Self> {
         conn.execute("CREATE TABLE IF NOT EXISTS tasks (id TEXT PRIMARY KEY, title TEXT, status TEXT DEFAULT 'open', assignee TEXT, engine TEXT, role TEXT, created_at INTEGER)", [])?;
         // Migration: Ensure columns exist
         let _ = conn.execute("ALTER TABLE tasks ADD COLUMN engine TEXT", []);
         let _ = conn.execute("ALTER TABLE tasks ADD COLUMN role TEXT", []);
+        let _ = conn.execute("ALTER TABLE tasks ADD COLUMN rig TEXT", []);
+        let _ = conn.execute("ALTER TABLE tasks ADD COLUMN due_at INTEGER", []);
+        let _ = conn.execute("ALTER TABLE tasks ADD COLUMN delegation TEXT", []);
+        let _ = conn.execute("ALTER TABLE tasks ADD COLUMN priority INTEGER DEFAULT 1", []);
+        let _ = conn.execute("ALTER TABLE tasks ADD COLUMN archived_at INTEGER", []);
+        let _ = conn.execute("ALTER TABLE tasks ADD COLUMN estimate TEXT", []);
         conn.execute("CREATE TABLE IF NOT EXISTS audit_logs (id INTEGER PRIMARY KEY AUTOINCREMENT, actor TEXT, action TEXT, target TEXT, status TEXT, timestamp INTEGER)", [])?;
+        let _ = conn.execute("ALTER TABLE audit_logs ADD COLUMN metadata TEXT", []);
         conn.execute("CREATE TABLE IF NOT EXISTS messages (id INTEGER PRIMARY KEY AUTOINCREMENT, sender TEXT, receiver TEXT, subject TEXT, body TEXT, status TEXT DEFAULT 'unread', timestamp INTEGER)", [])?;
+        conn.execute("CREATE TABLE IF NOT EXISTS attachments (message_id INTEGER, name TEXT, artifact TEXT, size INTEGER)", [])?;
+        let _ = conn.execute("ALTER TABLE attachments ADD COLUMN path TEXT", []);
+        let _ = conn.execute("ALTER TABLE attachments ADD COLUMN task_id TEXT", []);
         conn.execute("CREATE TABLE IF NOT EXISTS rigs (name TEXT PRIMARY KEY, path TEXT, repo TEXT, status TEXT DEFAULT 'active', last_sync INTEGER)", [])?;
         conn.execute("CREATE TABLE IF NOT EXISTS costs (id INTEGER PRIMARY KEY AUTOINCREMENT, task_id TEXT, agent_name TEXT, model TEXT, input_tokens INTEGER, output_tokens INTEGER, cost_usd REAL, timestamp INTEGER)", [])?;
-        Ok(Self { conn })
+        let _ = conn.execute("ALTER TABLE costs ADD COLUMN attempt_id INTEGER", []);
+        conn.execute("CREATE TABLE IF NOT EXISTS settings (key TEXT PRIMARY KEY, value TEXT, updated_at INTEGER)", [])?;
+        conn.execute("CREATE TABLE IF NOT EXISTS prompts (id INTEGER PRIMARY KEY AUTOINCREMENT, task_id TEXT, agent_name TEXT, engine TEXT, instruction TEXT, timestamp INTEGER)", [])?;
+        let _ = conn.execute("ALTER TABLE prompts ADD COLUMN attempt_id INTEGER", []);
+        conn.execute("CREATE TABLE IF NOT EXISTS task_tags (task_id TEXT, tag TEXT, PRIMARY KEY (task_id, tag))", [])?;
+        conn.execute("CREATE TABLE IF NOT EXISTS task_deps (task_id TEXT, depends_on TEXT, PRIMARY KEY (task_id, depends_on))", [])?;
+        conn.execute("CREATE TABLE IF NOT EXISTS attempts (id INTEGER PRIMARY KEY AUTOINCREMENT, task_id TEXT, agent_name TEXT, engine TEXT, model TEXT, started_at INTEGER, ended_at INTEGER, outcome TEXT, cost_usd REAL DEFAULT 0)", [])?;
+        conn.execute("CREATE TABLE IF NOT EXISTS locks (target TEXT PRIMARY KEY, actor TEXT, action TEXT, acquired_at INTEGER)", [])?;
+        conn.execute("CREATE TABLE IF NOT EXISTS agents (name TEXT PRIMARY KEY, task_id TEXT, engine TEXT, status TEXT, spawned_at INTEGER, checked_in_at INTEGER)", [])?;
+        conn.execute("CREATE TABLE IF NOT EXISTS scopes (name TEXT PRIMARY KEY, scope TEXT)", [])?;
+        conn.execute("CREATE TABLE IF NOT EXISTS notifications (id INTEGER PRIMARY KEY AUTOINCREMENT, kind TEXT, target TEXT, message TEXT, created_at INTEGER, read_at INTEGER, resolved_at INTEGER)", [])?;
+        conn.execute("CREATE TABLE IF NOT EXISTS tokens (actor TEXT PRIMARY KEY, token TEXT UNIQUE, issued_at INTEGER)", [])?;
+        conn.execute("CREATE TABLE IF NOT EXISTS users (name TEXT PRIMARY KEY, token TEXT UNIQUE, created_at INTEGER)", [])?;
+        conn.execute("CREATE TABLE IF NOT EXISTS teams (name TEXT PRIMARY KEY, lead TEXT, scope TEXT, created_at INTEGER)", [])?;
+        conn.execute("CREATE TABLE IF NOT EXISTS team_members (team TEXT, agent TEXT, PRIMARY KEY (team, agent))", [])?;
+        conn.execute("CREATE TABLE IF NOT EXISTS admins (name TEXT PRIMARY KEY, scope TEXT, started_at INTEGER)", [])?;
+        conn.execute("CREATE TABLE IF NOT EXISTS bench_results (task_id TEXT PRIMARY KEY, run_id TEXT, suite TEXT, case_id TEXT, engine TEXT, started_at INTEGER, ended_at INTEGER, outcome TEXT, detail TEXT, cost_usd REAL DEFAULT 0)", [])?;
+        // Full-text indexes for `tt search`, kept in step by triggers; built from the tables on first use.
+        let indexed: i64 = conn.query_row("SELECT COUNT(*) FROM sqlite_master WHERE name = 'task_fts'", [], |row| row.get(0))?;
+        conn.execute_batch("
+            CREATE VIRTUAL TABLE IF NOT EXISTS task_fts USING fts5(id, title, content='tasks', content_rowid='rowid');
+            CREATE VIRTUAL TABLE IF NOT EXISTS mail_fts USING fts5(subject, body, content='messages', content_rowid='id');
+            CREATE VIRTUAL TABLE IF NOT EXISTS audit_fts USING fts5(action, target, content='audit_logs', content_rowid='id');
+            CREATE TRIGGER IF NOT EXISTS task_fts_insert AFTER INSERT ON tasks BEGIN
+                INSERT INTO task_fts (rowid, id, title) VALUES (new.rowid, new.id, new.title); END;
+            CREATE TRIGGER IF NOT EXISTS task_fts_delete AFTER DELETE ON tasks BEGIN
+                INSERT INTO task_fts (task_fts, rowid, id, title) VALUES ('delete', old.rowid, old.id, old.title); END;
+            CREATE TRIGGER IF NOT EXISTS task_fts_update AFTER UPDATE OF id, title ON tasks BEGIN
+                INSERT INTO task_fts (task_fts, rowid, id, title) VALUES ('delete', old.rowid, old.id, old.title);
+                INSERT INTO task_fts (rowid, id, title) VALUES (new.rowid, new.id, new.title); END;
+            CREATE TRIGGER IF NOT EXISTS mail_fts_insert AFTER INSERT ON messages BEGIN
+                INSERT INTO mail_fts (rowid, subject, body) VALUES (new.id, new.subject, new.body); END;
+            CREATE TRIGGER IF NOT EXISTS mail_fts_delete AFTER DELETE ON messages BEGIN
+                INSERT INTO mail_fts (mail_fts, rowid, subject, body) VALUES ('delete', old.id, old.subject, old.body); END;
+            CREATE TRIGGER IF NOT EXISTS mail_fts_update AFTER UPDATE OF subject, body ON messages BEGIN
+                INSERT INTO mail_fts (mail_fts, rowid, subject, body) VALUES ('delete', old.id, old.subject, old.body);
+                INSERT INTO mail_fts (rowid, subject, body) VALUES (new.id, new.subject, new.body); END;
+            CREATE TRIGGER IF NOT EXISTS audit_fts_insert AFTER INSERT ON audit_logs BEGIN
+                INSERT INTO audit_fts (rowid, action, target) VALUES (new.id, new.action, new.target); END;
+            CREATE TRIGGER IF NOT EXISTS audit_fts_delete AFTER DELETE ON audit_logs BEGIN
+                INSERT INTO audit_fts (audit_fts, rowid, action, target) VALUES ('delete', old.id, old.action, old.target); END;
+        ")?;
+        if indexed == 0 {
+            conn.execute_batch("
+                INSERT INTO task_fts (task_fts) VALUES ('rebuild');
+                INSERT INTO mail_fts (mail_fts) VALUES ('rebuild');
+                INSERT INTO audit_fts (audit_fts) VALUES ('rebuild');
+            ")?;
+        }
+        Ok(Self { conn, work_dir })
     }
     pub fn add_task(&self, id: &str, title: &str) -> Result<()> {
-        self.conn.execute("INSERT INTO tasks (id, title, created_at) VALUES (?1, ?2, strftime('%s','now'))", params![id, title])?;
+        match self.conn.execute("INSERT INTO tasks (id, title, created_at) VALUES (?1, ?2, strftime('%s','now'))", params![id, title]) {
+            Ok(_) => Ok(()),
+            Err(e) if e.sqlite_error_code() == Some(ErrorCode::ConstraintViolation) => {
+                let (status, assignee) = self.conn.query_row(
+                    "SELECT status, assignee FROM tasks WHERE id = ?1", params![id],
+                    |row| Ok((row.get::<_, String>(0)?, row.get::<_, Option<String>>(1)?))
+                )?;
+                Err(ThinkTodoError::TaskExists { id: id.to_string(), status, assignee })
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+    pub fn update_task_title(&self, id: &str, title: &str) -> Result<()> {
+        if self.conn.execute("UPDATE tasks SET title = ?1 WHERE id = ?2", params![title, id])? == 0 {
+            return Err(ThinkTodoError::TaskNotFound(id.to_string()));
+        }
         Ok(())
     }
+    /// Next id in the `prefix` sequence, zero-padded to `digits`: one past the highest such id in
+    /// use or deleted, so a deleted task's id isn't handed out again.
+    pub fn next_task_id(&self, prefix: &str, digits: usize) -> Result<String> {
+        let mut stmt = self.conn.prepare(
+            "SELECT id FROM tasks WHERE substr(id, 1, length(?1)) = ?1
+             UNION SELECT target FROM audit_logs WHERE action = 'task_deleted' AND substr(target, 1, length(?1)) = ?1")?;
+        let used = stmt.query_map(params![prefix], |row| row.get::<_, String>(0))?.collect::<rusqlite::Result<Vec<_>>>()?;
+        let highest = used.iter().filter_map(|id| id.strip_prefix(prefix)?.parse::<u64>().ok()).max().unwrap_or(0);
+        Ok(format!("{}{:0width$}", prefix, highest + 1, width = digits))
+    }
+    /// First unused id derived from `id`: "T7" -> "T8", "login" -> "login-2". When that would be
+    /// longer than `ids::MAX_LEN`, the stem is cut short so the number still fits.
+    pub fn next_free_id(&self, id: &str) -> Result<String> {
+        let stem = id.trim_end_matches(|c: char| c.is_ascii_digit());
+        let (prefix, mut n) = match id[stem.len()..].parse::<u64>().ok().and_then(|n| n.checked_add(1)) {
+            Some(n) => (stem.to_string(), n),
+            None => (format!("{}-", id), 2),
+        };
+        loop {
+            let digits = n.to_string();
+            let keep = prefix.len().min(ids::MAX_LEN.saturating_sub(digits.len()));
+            let candidate = ids::task_id(&format!("{}{}", &prefix[..keep], digits))?;
+            let taken: bool = self.conn.query_row("SELECT EXISTS(SELECT 1 FROM tasks WHERE id = ?1)", params![candidate], |row| row.get(0))?;
+            if !taken { return Ok(candidate); }
+            n += 1;
+        }
+    }
     pub fn log_audit(&self, actor: &str, action: &str, target: &str, status: &str) -> Result<()> {
         self.conn.execute("INSERT INTO audit_logs (actor, action, target, status, timestamp) VALUES (?1, ?2, ?3, ?4, strftime('%s','now'))", params![actor, action, target, status])?;
         Ok(())
     }
+    /// Like `log_audit`, with details (e.g. what a destructive operation removed, as JSON).
+    pub fn log_audit_meta(&self, actor: &str, action: &str, target: &str, status: &str, metadata: &str) -> Result<()> {
+        self.conn.execute("INSERT INTO audit_logs (actor, action, target, status, timestamp, metadata) VALUES (?1, ?2, ?3, ?4, strftime('%s','now'), ?5)", params![actor, action, target, status, metadata])?;
+        Ok(())
+    }
+
+    /// Records a decision the daemon took together with the inputs it was based on (see `tt replay`).
+    pub fn log_decision(&self, actor: &str, action: &str, target: &str, status: &str, inputs: serde_json::Value) -> Result<()> {
+        self.log_audit_meta(actor, action, target, status, &serde_json::json!({ "inputs": inputs }).to_string())
+    }
 
+    /// Records spend, attributed to the agent's open attempt on the task (or its latest one if it already ended).
     pub fn log_cost(&self, task_id: &str, agent_name: &str, model: &str, input: i32, output: i32, cost: f64) -> Result<()> {
         self.conn.execute(
-            "INSERT INTO costs (task_id, agent_name, model, input_tokens, output_tokens, cost_usd, timestamp) VALUES (?1, ?2, ?3, ?4, ?5, ?6, strftime('%s','now'))",
+            "INSERT INTO costs (task_id, agent_name, model, input_tokens, output_tokens, cost_usd, timestamp, attempt_id)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, strftime('%s','now'),
+                     (SELECT id FROM attempts WHERE task_id = ?1 AND agent_name = ?2 ORDER BY ended_at IS NULL DESC, id DESC LIMIT 1))",
             params![task_id, agent_name, model, input, output, cost]
         )?;
         Ok(())
     }
 
+    /// Keeps the exact instruction a worker was launched with.
+    pub fn record_prompt(&self, attempt_id: i64, task_id: &str, agent_name: &str, engine: &str, instruction: &str) -> Result<()> {
+        self.conn.execute(
+            "INSERT INTO prompts (attempt_id, task_id, agent_name, engine, instruction, timestamp) VALUES (?1, ?2, ?3, ?4, ?5, strftime('%s','now'))",
+            params![attempt_id, task_id, agent_name, engine, instruction]
+        )?;
+        Ok(())
+    }
+
+    /// Opens an attempt for a freshly spawned worker. Whatever the agent was still running counts as replaced.
+    pub fn start_attempt(&self, task_id: &str, agent_name: &str, engine: &str) -> Result<i64> {
+        self.end_attempts(None, Some(agent_name), "replaced")?;
+        self.conn.execute(
+            "INSERT INTO attempts (task_id, agent_name, engine, started_at) VALUES (?1, ?2, ?3, strftime('%s','now'))",
+            params![task_id, agent_name, engine]
+        )?;
+        Ok(self.conn.last_insert_rowid())
+    }
+
+    /// Seconds since the task's first attempt started (None before its first spawn) and its spend so far.
+    pub fn task_lifecycle(&self, task_id: &str) -> Result<(Option<i64>, f64)> {
+        Ok(self.conn.query_row(
+            "SELECT strftime('%s','now') - (SELECT MIN(started_at) FROM attempts WHERE task_id = ?1),
+                    (SELECT COALESCE(SUM(cost_usd), 0) FROM costs WHERE task_id = ?1)",
+            params![task_id], |row| Ok((row.get(0)?, row.get(1)?)))?)
+    }
+
+    /// Closes the open attempts of a task and/or agent with `outcome`, totalling the costs logged meanwhile.
+    pub fn end_attempts(&self, task_id: Option<&str>, agent_name: Option<&str>, outcome: &str) -> Result<usize> {
+        Ok(self.conn.execute(
+            "UPDATE attempts SET ended_at = strftime('%s','now'), outcome = ?3,
+                 cost_usd = (SELECT COALESCE(SUM(c.cost_usd), 0) FROM costs c WHERE c.attempt_id = attempts.id),
+                 model = COALESCE(model, (SELECT c.model FROM costs c WHERE c.attempt_id = attempts.id GROUP BY c.model ORDER BY COUNT(*) DESC LIMIT 1))
+             WHERE ended_at IS NULL AND (?1 IS NULL OR task_id = ?1) AND (?2 IS NULL OR agent_name = ?2)",
+            params![task_id, agent_name, outcome]
+        )?)
+    }
+
+    /// Registers a freshly spawned agent as `launching` until it runs `tt agent checkin`.
+    pub fn register_agent(&self, name: &str, task_id: &str, engine: &str) -> Result<()> {
+        self.conn.execute(
+            "INSERT OR REPLACE INTO agents (name, task_id, engine, status, spawned_at, checked_in_at) VALUES (?1, ?2, ?3, 'launching', strftime('%s','now'), NULL)",
+            params![name, task_id, engine]
+        )?;
+        Ok(())
+    }
+
+    /// Marks the agent as running and records the time; returns the task it was spawned for and
+    /// whether this was its first check-in (later ones are heartbeats).
+    pub fn agent_checkin(&self, name: &str) -> Result<(String, bool)> {
+        let (task_id, before): (String, Option<i64>) = self.conn.query_row(
+            "SELECT task_id, checked_in_at FROM agents WHERE name = ?1", [name], |row| Ok((row.get(0)?, row.get(1)?))
+        ).optional()?.ok_or_else(|| ThinkTodoError::InvalidArgument(format!("no agent named '{}' was spawned in this workspace", name)))?;
+        self.conn.execute("UPDATE agents SET status = 'running', checked_in_at = strftime('%s','now') WHERE name = ?1", [name])?;
+        Ok((task_id, before.is_none()))
+    }
+
+    /// Agents (name, task) still `launching` more than `secs` seconds after they were spawned.
+    pub fn missed_checkins(&self, secs: i64) -> Result<Vec<(String, String)>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT name, task_id FROM agents WHERE status = 'launching' AND spawned_at <= strftime('%s','now') - ?1 ORDER BY name")?;
+        let rows = stmt.query_map([secs], |row| Ok((row.get(0)?, row.get(1)?)))?;
+        Ok(rows.collect::<rusqlite::Result<Vec<_>>>()?)
+    }
+
+    pub fn set_agent_status(&self, name: &str, status: &str) -> Result<()> {
+        self.conn.execute("UPDATE agents SET status = ?2 WHERE name = ?1", params![name, status])?;
+        Ok(())
+    }
+
+    pub fn save_scope(&self, name: &str, scope: &Scope) -> Result<()> {
+        self.conn.execute("INSERT OR REPLACE INTO scopes (name, scope) VALUES (?1, ?2)", params![name, scope.to_string()])?;
+        Ok(())
+    }
+
+    pub fn saved_scope(&self, name: &str) -> Result<Option<String>> {
+        Ok(self.conn.query_row("SELECT scope FROM scopes WHERE name = ?1", [name], |row| row.get(0)).optional()?)
+    }
+
+    /// Saved scopes (name, scope), by name.
+    pub fn saved_scopes(&self) -> Result<Vec<(String, String)>> {
+        let mut stmt = self.conn.prepare("SELECT name, scope FROM scopes ORDER BY name")?;
+        let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?;
+        Ok(rows.collect::<rusqlite::Result<Vec<_>>>()?)
+    }
+
+    /// Whether a scope by that name existed.
+    pub fn remove_scope(&self, name: &str) -> Result<bool> {
+        Ok(self.conn.execute("DELETE FROM scopes WHERE name = ?1", [name])? > 0)
+    }
+
+    /// Creates the team, or replaces its lead, scope and members.
+    pub fn save_team(&self, name: &str, lead: Option<&str>, scope: Option<&Scope>, members: &[String]) -> Result<()> {
+        self.conn.execute("INSERT OR REPLACE INTO teams (name, lead, scope, created_at) VALUES (?1, ?2, ?3, strftime('%s','now'))",
+            params![name, lead, scope.map(|s| s.to_string())])?;
+        self.conn.execute("DELETE FROM team_members WHERE team = ?1", [name])?;
+        for m in members {
+            self.conn.execute("INSERT OR IGNORE INTO team_members (team, agent) VALUES (?1, ?2)", params![name, m])?;
+        }
+        Ok(())
+    }
+
+    /// Whether a team by that name existed.
+    pub fn delete_team(&self, name: &str) -> Result<bool> {
+        self.conn.execute("DELETE FROM team_members WHERE team = ?1", [name])?;
+        Ok(self.conn.execute("DELETE FROM teams WHERE name = ?1", [name])? > 0)
+    }
+
+    /// Teams by name, or only those `agent` is a member of.
+    pub fn teams(&self, agent: Option<&str>) -> Result<Vec<Team>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT name, lead, scope FROM teams
+             WHERE ?1 IS NULL OR name IN (SELECT team FROM team_members WHERE agent = ?1) ORDER BY name")?;
+        let rows = stmt.query_map([agent], |row| Ok((row.get::<_, String>(0)?, row.get::<_, Option<String>>(1)?, row.get::<_, Option<String>>(2)?)))?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        let mut members = self.conn.prepare("SELECT agent FROM team_members WHERE team = ?1 ORDER BY agent")?;
+        rows.into_iter().map(|(name, lead, scope)| Ok(Team {
+            members: members.query_map([&name], |row| row.get(0))?.collect::<rusqlite::Result<Vec<_>>>()?,
+            scope: scope.as_deref().map(scope::parse).transpose()?,
+            name, lead,
+        })).collect()
+    }
+
+    /// Work of a team's members: tasks in progress, tasks closed, and their total cost.
+    pub fn team_stats(&self, team: &str) -> Result<(i64, i64, f64)> {
+        Ok(self.conn.query_row(
+            "SELECT (SELECT COUNT(*) FROM tasks WHERE status = 'in_progress' AND assignee IN (SELECT agent FROM team_members WHERE team = ?1)),
+                    (SELECT COUNT(*) FROM tasks WHERE status = 'closed' AND assignee IN (SELECT agent FROM team_members WHERE team = ?1)),
+                    (SELECT COALESCE(SUM(cost_usd), 0) FROM costs WHERE agent_name IN (SELECT agent FROM team_members WHERE team = ?1))",
+            [team], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?)
+    }
+
+    pub fn register_admin(&self, name: &str, scope: &Scope) -> Result<()> {
+        self.conn.execute("INSERT OR REPLACE INTO admins (name, scope, started_at) VALUES (?1, ?2, strftime('%s','now'))", params![name, scope.to_string()])?;
+        Ok(())
+    }
+
+    pub fn remove_admin(&self, name: &str) -> Result<()> {
+        self.conn.execute("DELETE FROM admins WHERE name = ?1", [name])?;
+        Ok(())
+    }
+
+    /// Named admins and their scopes, by name.
+    pub fn admins(&self) -> Result<Vec<(String, Scope)>> {
+        let mut stmt = self.conn.prepare("SELECT name, scope FROM admins ORDER BY name")?;
+        let rows = stmt.query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)))?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        rows.into_iter().map(|(name, scope)| Ok((name, scope::parse(&scope)?))).collect()
+    }
+
+    /// The mailbox for news about `task_id`: the first named admin whose scope covers it, else `admin`.
+    pub fn admin_for(&self, task_id: &str) -> Result<String> {
+        for (name, scope) in self.admins()? {
+            if scope.matches(self, task_id)? { return Ok(format!("admin-{}", name)); }
+        }
+        Ok("admin".to_string())
+    }
+
+    /// A fresh token for `actor`, replacing any earlier one so a replaced session's token stops working.
+    pub fn issue_token(&self, actor: &str) -> Result<String> {
+        let token = new_token()?;
+        self.conn.execute("INSERT OR REPLACE INTO tokens (actor, token, issued_at) VALUES (?1, ?2, strftime('%s','now'))", params![actor, token])?;
+        Ok(token)
+    }
+
+    /// The actor a token was issued to.
+    pub fn token_actor(&self, token: &str) -> Result<Option<String>> {
+        Ok(self.conn.query_row("SELECT actor FROM tokens WHERE token = ?1", [token], |row| row.get(0)).optional()?)
+    }
+
+    /// Adds a web user, or gives an existing one a new token; returns the token.
+    pub fn add_user(&self, name: &str) -> Result<String> {
+        let token = new_token()?;
+        self.conn.execute(
+            "INSERT INTO users (name, token, created_at) VALUES (?1, ?2, strftime('%s','now')) ON CONFLICT (name) DO UPDATE SET token = ?2",
+            params![name, token])?;
+        Ok(token)
+    }
+
+    /// Whether there was such a user.
+    pub fn remove_user(&self, name: &str) -> Result<bool> {
+        Ok(self.conn.execute("DELETE FROM users WHERE name = ?1", [name])? > 0)
+    }
+
+    /// Web users and when they were added, by name.
+    pub fn users(&self) -> Result<Vec<(String, i64)>> {
+        let mut stmt = self.conn.prepare("SELECT name, created_at FROM users ORDER BY name")?;
+        let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?;
+        Ok(rows.collect::<rusqlite::Result<Vec<_>>>()?)
+    }
+
+    /// The web user a token belongs to.
+    pub fn token_user(&self, token: &str) -> Result<Option<String>> {
+        Ok(self.conn.query_row("SELECT name FROM users WHERE token = ?1", [token], |row| row.get(0)).optional()?)
+    }
+
+    /// Who `actor` acts for, ending with `actor`: an agent working on a task inherits the chain
+    /// that task was delegated through; anyone else (e.g. an admin session) acts for the user.
+    pub fn delegation_chain(&self, actor: &str) -> Result<Vec<String>> {
+        let inherited: Option<String> = self.conn.query_row(
+            "SELECT t.delegation FROM agents a JOIN tasks t ON t.id = a.task_id WHERE a.name = ?1", [actor], |row| row.get(0)
+        ).optional()?.flatten();
+        let mut chain: Vec<String> = match inherited {
+            Some(json) => serde_json::from_str(&json).unwrap_or_default(),
+            None if HUMAN_ACTORS.contains(&actor) => Vec::new(),
+            None => vec!["user".to_string()],
+        };
+        if chain.last().map(String::as_str) != Some(actor) { chain.push(actor.to_string()); }
+        Ok(chain)
+    }
+
+    pub fn set_task_delegation(&self, id: &str, chain: &[String]) -> Result<()> {
+        self.conn.execute("UPDATE tasks SET delegation = ?1 WHERE id = ?2", params![serde_json::to_string(chain).unwrap_or_default(), id])?;
+        Ok(())
+    }
+
+    /// The chain a task was delegated through, from the human down to its assignee.
+    pub fn task_delegation(&self, id: &str) -> Result<Vec<String>> {
+        let json: Option<String> = self.conn.query_row("SELECT delegation FROM tasks WHERE id = ?1", [id], |row| row.get(0)).optional()?.flatten();
+        Ok(json.and_then(|j| serde_json::from_str(&j).ok()).unwrap_or_default())
+    }
+
+    /// Every attempt on a task, oldest first, with the cost attributed to it so far.
+    pub fn task_attempts(&self, task_id: &str) -> Result<Vec<Attempt>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT id, agent_name, engine,
+                 COALESCE(model, (SELECT c.model FROM costs c WHERE c.attempt_id = attempts.id GROUP BY c.model ORDER BY COUNT(*) DESC LIMIT 1)),
+                 started_at, ended_at, outcome,
+                 (SELECT COALESCE(SUM(c.cost_usd), 0) FROM costs c WHERE c.attempt_id = attempts.id)
+             FROM attempts WHERE task_id = ?1 ORDER BY id")?;
+        let rows = stmt.query_map(params![task_id], |row| Ok(Attempt {
+            id: row.get(0)?, agent_name: row.get(1)?, engine: row.get(2)?, model: row.get(3)?,
+            started_at: row.get(4)?, ended_at: row.get(5)?, outcome: row.get(6)?, cost_usd: row.get(7)?,
+        }))?;
+        Ok(rows.collect::<rusqlite::Result<Vec<_>>>()?)
+    }
+
+    /// Attempts per engine/model: how many ran, how many ended, how many ended in `done`, and their total cost.
+    pub fn engine_stats(&self, since: Option<i64>) -> Result<Vec<EngineStats>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT a.engine, a.model, COUNT(*), COUNT(a.ended_at), SUM(a.outcome = 'done'),
+                 COALESCE(SUM((SELECT SUM(c.cost_usd) FROM costs c WHERE c.attempt_id = a.id)), 0),
+                 COALESCE(SUM(CASE WHEN a.outcome = 'done' THEN a.ended_at - a.started_at END), 0)
+             FROM (SELECT id, engine, ended_at, outcome, started_at,
+                       COALESCE(model, (SELECT c.model FROM costs c WHERE c.attempt_id = attempts.id GROUP BY c.model ORDER BY COUNT(*) DESC LIMIT 1)) AS model
+                   FROM attempts) a
+             WHERE ?1 IS NULL OR a.started_at >= ?1
+             GROUP BY a.engine, a.model ORDER BY a.engine, a.model")?;
+        let rows = stmt.query_map(params![since], |row| Ok(EngineStats {
+            engine: row.get(0)?, model: row.get(1)?, attempts: row.get(2)?, finished: row.get(3)?,
+            done: row.get::<_, Option<i64>>(4)?.unwrap_or(0), cost_usd: row.get(5)?, done_secs: row.get(6)?,
+        }))?;
+        Ok(rows.collect::<rusqlite::Result<Vec<_>>>()?)
+    }
+
+    /// Instructions sent for a task, oldest first: (agent, engine, timestamp, instruction).
+    pub fn task_prompts(&self, task_id: &str) -> Result<Vec<(String, String, i64, String)>> {
+        let mut stmt = self.conn.prepare("SELECT agent_name, engine, timestamp, instruction FROM prompts WHERE task_id = ?1 ORDER BY id")?;
+        let rows = stmt.query_map(params![task_id], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)))?;
+        Ok(rows.collect::<rusqlite::Result<Vec<_>>>()?)
+    }
+
+    pub fn set_task_priority(&self, id: &str, priority: i64) -> Result<()> {
+        if self.conn.execute("UPDATE tasks SET priority = ?1 WHERE id = ?2", params![priority, id])? == 0 {
+            return Err(ThinkTodoError::TaskNotFound(id.to_string()));
+        }
+        Ok(())
+    }
+    /// Stores an estimate as written by `service::Estimate`; None removes it.
+    pub fn set_task_estimate(&self, id: &str, estimate: Option<&str>) -> Result<()> {
+        if self.conn.execute("UPDATE tasks SET estimate = ?1 WHERE id = ?2", params![estimate, id])? == 0 {
+            return Err(ThinkTodoError::TaskNotFound(id.to_string()));
+        }
+        Ok(())
+    }
+    pub fn task_estimate(&self, id: &str) -> Result<Option<String>> {
+        self.conn.query_row("SELECT estimate FROM tasks WHERE id = ?1", params![id], |row| row.get(0))
+            .optional()?.ok_or_else(|| ThinkTodoError::TaskNotFound(id.to_string()))
+    }
+    pub fn set_task_due(&self, id: &str, due: Option<i64>) -> Result<()> {
+        if self.conn.execute("UPDATE tasks SET due_at = ?1 WHERE id = ?2", params![due, id])? == 0 {
+            return Err(ThinkTodoError::TaskNotFound(id.to_string()));
+        }
+        Ok(())
+    }
+
+    pub fn add_task_tags(&self, id: &str, tags: &[String]) -> Result<()> {
+        for tag in tags {
+            self.conn.execute("INSERT OR IGNORE INTO task_tags (task_id, tag) VALUES (?1, ?2)", params![id, tag])?;
+        }
+        Ok(())
+    }
+    pub fn remove_task_tags(&self, id: &str, tags: &[String]) -> Result<()> {
+        for tag in tags {
+            self.conn.execute("DELETE FROM task_tags WHERE task_id = ?1 AND tag = ?2", params![id, tag])?;
+        }
+        Ok(())
+    }
+    pub fn task_tags(&self, id: &str) -> Result<Vec<String>> {
+        let mut stmt = self.conn.prepare("SELECT tag FROM task_tags WHERE task_id = ?1 ORDER BY tag")?;
+        let rows = stmt.query_map(params![id], |row| row.get(0))?;
+        Ok(rows.collect::<rusqlite::Result<Vec<_>>>()?)
+    }
+
+    /// Records that `id` can't be finished before `on`.
+    pub fn add_task_dependency(&self, id: &str, on: &str) -> Result<()> {
+        self.conn.execute("INSERT OR IGNORE INTO task_deps (task_id, depends_on) VALUES (?1, ?2)", params![id, on])?;
+        Ok(())
+    }
+    /// Tasks `id` depends on.
+    pub fn task_dependencies(&self, id: &str) -> Result<Vec<String>> {
+        let mut stmt = self.conn.prepare("SELECT depends_on FROM task_deps WHERE task_id = ?1 ORDER BY depends_on")?;
+        let rows = stmt.query_map(params![id], |row| row.get(0))?;
+        Ok(rows.collect::<rusqlite::Result<Vec<_>>>()?)
+    }
+
+    /// Timelines of every task, or of those carrying `tag`, from the audit log:
+    /// started is the first `task_started` entry, closed the last `task_closed` one.
+    pub fn timelines(&self, tag: Option<&str>) -> Result<Vec<TaskTimeline>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT t.id, t.status, t.created_at,
+                 (SELECT MIN(l.timestamp) FROM audit_logs l WHERE l.target = t.id AND l.action = 'task_started'),
+                 (SELECT MAX(l.timestamp) FROM audit_logs l WHERE l.target = t.id AND l.action = 'task_closed')
+             FROM tasks t
+             WHERE ?1 IS NULL OR EXISTS (SELECT 1 FROM task_tags g WHERE g.task_id = t.id AND g.tag = ?1)
+             ORDER BY t.id")?;
+        let rows = stmt.query_map(params![tag], |row| Ok(TaskTimeline {
+            id: row.get(0)?, status: row.get(1)?, created_at: row.get(2)?, started_at: row.get(3)?, closed_at: row.get(4)?,
+        }))?;
+        Ok(rows.collect::<rusqlite::Result<Vec<_>>>()?)
+    }
+
+    /// Unfinished tasks whose due date has passed, earliest first.
+    pub fn overdue_tasks(&self) -> Result<Vec<DueTask>> {
+        let now = chrono::Local::now().timestamp();
+        let mut stmt = self.conn.prepare("SELECT id, title, assignee, due_at FROM tasks WHERE due_at IS NOT NULL AND status != 'closed' ORDER BY due_at")?;
+        let rows = stmt.query_map([], |row| Ok(DueTask { id: row.get(0)?, title: row.get(1)?, assignee: row.get(2)?, due_at: row.get(3)? }))?;
+        Ok(rows.collect::<rusqlite::Result<Vec<_>>>()?.into_iter().filter(|t| crate::period::is_overdue(t.due_at, now)).collect())
+    }
+
+    /// Current assignee of a task; `TaskNotFound` if the id doesn't exist.
+    pub fn task_assignee(&self, id: &str) -> Result<Option<String>> {
+        self.conn.query_row("SELECT assignee FROM tasks WHERE id = ?1", params![id], |row| row.get(0))
+            .optional()?
+            .ok_or_else(|| ThinkTodoError::TaskNotFound(id.to_string()))
+    }
+
+    /// Path of a registered rig; `InvalidArgument` if it isn't registered.
+    pub fn rig_path(&self, rig: &str) -> Result<PathBuf> {
+        self.conn.query_row("SELECT path FROM rigs WHERE name = ?1", params![rig], |row| row.get::<_, String>(0))
+            .optional()?
+            .map(PathBuf::from)
+            .ok_or_else(|| ThinkTodoError::InvalidArgument(format!("rig '{}' is not registered (see tt rig list)", rig)))
+    }
+
+    /// Attaches a task to a registered rig.
+    pub fn set_task_rig(&self, id: &str, rig: &str) -> Result<()> {
+        self.rig_path(rig)?;
+        self.conn.execute("UPDATE tasks SET rig = ?1 WHERE id = ?2", params![rig, id])?;
+        Ok(())
+    }
+
+    /// Name and path of the rig a task belongs to, if any.
+    pub fn task_rig(&self, id: &str) -> Result<Option<(String, PathBuf)>> {
+        Ok(self.conn.query_row(
+            "SELECT r.name, r.path FROM tasks t JOIN rigs r ON r.name = t.rig WHERE t.id = ?1", params![id],
+            |row| Ok((row.get::<_, String>(0)?, PathBuf::from(row.get::<_, String>(1)?)))
+        ).optional()?)
+    }
+
+    /// Fails with `AgentBusy` if the agent is already working on a different task.
+    pub fn ensure_agent_free(&self, agent: &str, task_id: &str) -> Result<()> {
+        let busy: Option<String> = self.conn.query_row(
+            "SELECT id FROM tasks WHERE assignee = ?1 AND status = 'in_progress' AND id != ?2",
+            params![agent, task_id], |row| row.get(0)
+        ).optional()?;
+        match busy {
+            Some(task) => Err(ThinkTodoError::AgentBusy { agent: agent.to_string(), task }),
+            None => Ok(()),
+        }
+    }
+
+    /// Cost rows matching `filter`, newest first, plus totals over all matches (ignoring `limit`).
+    pub fn list_costs(&self, filter: &CostFilter) -> Result<(Vec<CostRow>, CostTotals)> {
+        let mut clauses = Vec::new();
+        let mut args: Vec<Value> = Vec::new();
+        for (col, val) in [("task_id", &filter.task), ("agent_name", &filter.agent), ("model", &filter.model)] {
+            if let Some(v) = val { args.push(Value::Text(v.clone())); clauses.push(format!("{} = ?{}", col, args.len())); }
+        }
+        if let Some(ts) = filter.since { args.push(Value::Integer(ts)); clauses.push(format!("timestamp >= ?{}", args.len())); }
+        let where_sql = if clauses.is_empty() { String::new() } else { format!(" WHERE {}", clauses.join(" AND ")) };
+
+        let totals = self.conn.query_row(
+            &format!("SELECT COUNT(*), COALESCE(SUM(input_tokens), 0), COALESCE(SUM(output_tokens), 0), COALESCE(SUM(cost_usd), 0.0) FROM costs{}", where_sql),
+            params_from_iter(args.iter()), |row| Ok(CostTotals { rows: row.get(0)?, input_tokens: row.get(1)?, output_tokens: row.get(2)?, cost_usd: row.get(3)? })
+        )?;
+        let limit = filter.limit.map(|n| format!(" LIMIT {}", n)).unwrap_or_default();
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT task_id, agent_name, model, input_tokens, output_tokens, cost_usd FROM costs{} ORDER BY timestamp DESC{}", where_sql, limit
+        ))?;
+        let rows = stmt.query_map(params_from_iter(args.iter()), |row| Ok(CostRow {
+            task_id: row.get(0)?,
+            agent_name: row.get(1)?,
+            model: row.get(2)?,
+            input_tokens: row.get(3)?,
+            output_tokens: row.get(4)?,
+            cost_usd: row.get(5)?,
+        }))?.collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok((rows, totals))
+    }
+
+    /// Spend since local midnight.
+    pub fn today_spend(&self) -> Result<f64> {
+        let midnight = chrono::Local::now().date_naive().and_hms_opt(0, 0, 0)
+            .and_then(|dt| dt.and_local_timezone(chrono::Local).earliest())
+            .map(|dt| dt.timestamp())
+            .unwrap_or(0);
+        Ok(self.conn.query_row("SELECT COALESCE(SUM(cost_usd), 0.0) FROM costs WHERE timestamp >= ?1", params![midnight], |row| row.get(0))?)
+    }
+
+    // Settings helpers (small key/value state such as the freeze flag)
+    pub fn get_setting(&self, key: &str) -> Result<Option<String>> {
+        Ok(self.conn.query_row("SELECT value FROM settings WHERE key = ?1", params![key], |row| row.get(0)).optional()?)
+    }
+    pub fn set_setting(&self, key: &str, value: &str) -> Result<()> {
+        self.conn.execute("INSERT OR REPLACE INTO settings (key, value, updated_at) VALUES (?1, ?2, strftime('%s','now'))", params![key, value])?;
+        Ok(())
+    }
+    pub fn clear_setting(&self, key: &str) -> Result<bool> {
+        Ok(self.conn.execute("DELETE FROM settings WHERE key = ?1", params![key])? > 0)
+    }
+
+    /// Reason the workspace is frozen, if it is: by `FREEZE_FILE`, `tt freeze` or the budget cap.
+    pub fn frozen(&self) -> Result<Option<String>> {
+        if let Ok(reason) = std::fs::read_to_string(self.work_dir.join(FREEZE_FILE)) {
+            let reason = reason.trim();
+            return Ok(Some(if reason.is_empty() { format!("{} is present", FREEZE_FILE) } else { reason.to_string() }));
+        }
+        self.get_setting("freeze")
+    }
+    /// Fails with `Frozen` while a freeze is in effect.
+    pub fn ensure_not_frozen(&self) -> Result<()> {
+        match self.frozen()? {
+            Some(reason) => Err(ThinkTodoError::Frozen(reason)),
+            None => Ok(()),
+        }
+    }
+
+    /// Takes the advisory lock on `target` (e.g. "task T1") for a destructive `action`, so a second
+    /// caller gets `Locked` instead of racing on the filesystem. Released when the guard drops.
+    pub fn lock(&self, target: &str, actor: &str, action: &str) -> Result<Lock<'_>> {
+        // A holder that crashed never releases its lock.
+        self.conn.execute("DELETE FROM locks WHERE target = ?1 AND acquired_at < strftime('%s','now') - ?2", params![target, LOCK_EXPIRY_SECS])?;
+        match self.conn.execute(
+            "INSERT INTO locks (target, actor, action, acquired_at) VALUES (?1, ?2, ?3, strftime('%s','now'))", params![target, actor, action]
+        ) {
+            Ok(_) => Ok(Lock { db: self, target: target.to_string() }),
+            Err(e) if e.sqlite_error_code() == Some(ErrorCode::ConstraintViolation) => {
+                let (actor, action) = self.conn.query_row(
+                    "SELECT actor, action FROM locks WHERE target = ?1", [target], |row| Ok((row.get(0)?, row.get(1)?))
+                ).optional()?.unwrap_or_else(|| ("someone".to_string(), "unknown".to_string()));
+                Err(ThinkTodoError::Locked { target: target.to_string(), actor, action })
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+
     // Mail helpers
-    pub fn send_mail(&self, sender: &str, receiver: &str, subject: &str, body: &str) -> Result<()> {
+    /// Returns the new message's id.
+    pub fn send_mail(&self, sender: &str, receiver: &str, subject: &str, body: &str) -> Result<i64> {
         self.conn.execute(
             "INSERT INTO messages (sender, receiver, subject, body, timestamp) VALUES (?1, ?2, ?3, ?4, strftime('%s','now'))",
             params![sender, receiver, subject, body]
         )?;
+        Ok(self.conn.last_insert_rowid())
+    }
+
+    pub fn add_attachment(&self, message_id: i64, att: &Attachment) -> Result<()> {
+        self.conn.execute("INSERT INTO attachments (message_id, name, artifact, size, path, task_id) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![message_id, att.name, att.artifact, att.size as i64, att.path, att.task_id])?;
         Ok(())
     }
 
+    pub fn attachments(&self, message_id: i64) -> Result<Vec<Attachment>> {
+        let mut stmt = self.conn.prepare("SELECT name, artifact, size, path, task_id FROM attachments WHERE message_id = ?1 ORDER BY rowid")?;
+        let rows = stmt.query_map(params![message_id], attachment)?;
+        Ok(rows.collect::<rusqlite::Result<Vec<_>>>()?)
+    }
+
+    /// Files attached by agents while they worked on the task, with the message each came with
+    /// (id, sender, time), oldest first; an artifact attached to several receivers is listed once.
+    pub fn task_artifacts(&self, task_id: &str) -> Result<Vec<(i64, String, i64, Attachment)>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT a.name, a.artifact, a.size, a.path, a.task_id, m.id, m.sender, m.timestamp FROM attachments a JOIN messages m ON m.id = a.message_id
+             WHERE a.task_id = ?1 AND a.rowid = (SELECT MIN(b.rowid) FROM attachments b WHERE b.artifact = a.artifact AND b.task_id = ?1) ORDER BY a.rowid")?;
+        let rows = stmt.query_map(params![task_id], |row| Ok((row.get(5)?, row.get(6)?, row.get(7)?, attachment(row)?)))?;
+        Ok(rows.collect::<rusqlite::Result<Vec<_>>>()?)
+    }
+
+    /// The latest attachment of a stored artifact that was made while working on a task.
+    pub fn task_attachment(&self, artifact: &str) -> Result<Option<Attachment>> {
+        Ok(self.conn.query_row(
+            "SELECT name, artifact, size, path, task_id FROM attachments WHERE artifact = ?1 AND task_id IS NOT NULL ORDER BY rowid DESC LIMIT 1",
+            params![artifact], attachment).optional()?)
+    }
+
+    /// The name a stored artifact was last attached under, if any.
+    pub fn artifact_name(&self, artifact: &str) -> Result<Option<String>> {
+        Ok(self.conn.query_row("SELECT name FROM attachments WHERE artifact = ?1 ORDER BY rowid DESC LIMIT 1", params![artifact], |row| row.get(0)).optional()?)
+    }
+
     // Rig helpers
     pub fn add_rig(&self, name: &str, path: &str, repo: &str) -> Result<()> {
         self.conn.execute(
@@ -51,3 +790,14 @@ impl Db {
         Ok(())
     }
 }
+
+fn attachment(row: &rusqlite::Row) -> rusqlite::Result<Attachment> {
+    Ok(Attachment { name: row.get(0)?, artifact: row.get(1)?, size: row.get::<_, i64>(2)? as u64, path: row.get(3)?, task_id: row.get(4)? })
+}
+
+/// 32 random hex digits from the operating system's generator.
+pub fn new_token() -> Result<String> {
+    let mut bytes = [0u8; 16];
+    getrandom::fill(&mut bytes).map_err(|e| std::io::Error::other(format!("no randomness for a token: {}", e)))?;
+    Ok(bytes.iter().map(|b| format!("{:02x}", b)).collect())
+}
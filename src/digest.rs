@@ -0,0 +1,41 @@
+use crate::config::DigestConfig;
+use crate::git::Git;
+use std::fs;
+use std::path::Path;
+
+const SKIP_DIRS: [&str; 5] = [".git", "target", "node_modules", "workers", ".logs"];
+
+/// A short orientation for an agent about to work in `path`: README head, directory tree and recent commits.
+pub fn rig_digest(name: &str, path: &Path, cfg: &DigestConfig) -> String {
+    let mut out = format!("## REPO DIGEST ({})\nPath: {}\n", name, path.display());
+
+    let readme = ["README.md", "README", "readme.md"].iter().find_map(|f| fs::read_to_string(path.join(f)).ok());
+    if let Some(readme) = readme {
+        let head: Vec<&str> = readme.lines().take(cfg.readme_lines).collect();
+        out.push_str(&format!("\n### README (first {} lines)\n{}\n", head.len(), head.join("\n")));
+    }
+
+    let mut entries = Vec::new();
+    walk(path, 0, cfg.tree_depth, &mut entries, cfg.max_entries);
+    let truncated = if entries.len() >= cfg.max_entries { "\n..." } else { "" };
+    out.push_str(&format!("\n### Tree (depth {})\n{}{}\n", cfg.tree_depth, entries.join("\n"), truncated));
+
+    if let Ok(log) = Git::recent_commits(path, cfg.commits) {
+        if !log.is_empty() { out.push_str(&format!("\n### Recent commits\n{}\n", log)); }
+    }
+    out
+}
+
+fn walk(dir: &Path, depth: usize, max_depth: usize, out: &mut Vec<String>, max: usize) {
+    let Ok(read) = fs::read_dir(dir) else { return };
+    let mut children: Vec<_> = read.flatten().collect();
+    children.sort_by_key(|e| e.file_name());
+    for entry in children {
+        if out.len() >= max { return; }
+        let name = entry.file_name().to_string_lossy().to_string();
+        if SKIP_DIRS.contains(&name.as_str()) || name == ".DS_Store" { continue; }
+        let is_dir = entry.path().is_dir();
+        out.push(format!("{}{}{}", "  ".repeat(depth), name, if is_dir { "/" } else { "" }));
+        if is_dir && depth + 1 < max_depth { walk(&entry.path(), depth + 1, max_depth, out, max); }
+    }
+}
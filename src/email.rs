@@ -0,0 +1,336 @@
+use crate::config::{Config, EmailConfig};
+use crate::db::{self, Db};
+use crate::error::{Result, ThinkTodoError};
+use crate::ids;
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use regex::Regex;
+use rusqlite::OptionalExtension;
+use sha2::{Digest, Sha256};
+use subtle::ConstantTimeEq;
+use std::env;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpStream;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+const SENT_KEY: &str = "email:sent";
+const POLLED_KEY: &str = "email:polled";
+pub const ERROR_KEY: &str = "email:error";
+/// Signs the `[tt #N.<signature>]` tag of outgoing mail, so only a reply to that mail is heard.
+const SECRET_KEY: &str = "email:secret";
+
+/// Sends tt mail addressed to `human` or to an address out by SMTP, then, every `poll_secs`,
+/// turns unread replies in the IMAP inbox back into tt mail. Failures are retried on the next
+/// pass and kept under `ERROR_KEY` meanwhile.
+pub fn bridge(db: &Db, work_dir: &Path) -> Result<()> {
+    let cfg = Config::load(work_dir)?.email;
+    if cfg.smtp_host.is_empty() { return Ok(()); }
+    let result = relay(db, &cfg).and_then(|_| poll(db, &cfg));
+    match result {
+        Ok(()) => { db.clear_setting(ERROR_KEY)?; }
+        Err(e) => {
+            tracing::warn!(smtp = %cfg.smtp_host, "email bridge: {}", e);
+            db.set_setting(ERROR_KEY, &e.to_string())?;
+        }
+    }
+    Ok(())
+}
+
+fn relay(db: &Db, cfg: &EmailConfig) -> Result<()> {
+    // The first pass starts from the current mail, so turning the bridge on doesn't mail old history.
+    let after: i64 = match db.get_setting(SENT_KEY)?.and_then(|v| v.parse().ok()) {
+        Some(id) => id,
+        None => {
+            let last: i64 = db.conn.query_row("SELECT COALESCE(MAX(id), 0) FROM messages", [], |row| row.get(0))?;
+            db.set_setting(SENT_KEY, &last.to_string())?;
+            last
+        }
+    };
+    let mut stmt = db.conn.prepare(
+        "SELECT id, sender, receiver, subject, body, (SELECT COUNT(*) FROM attachments a WHERE a.message_id = messages.id)
+         FROM messages WHERE id > ?1 AND (receiver = 'human' OR receiver LIKE '%_@_%.%') ORDER BY id")?;
+    let pending = stmt.query_map([after], |row| Ok((
+        row.get::<_, i64>(0)?, row.get::<_, String>(1)?, row.get::<_, String>(2)?, row.get::<_, String>(3)?, row.get::<_, String>(4)?, row.get::<_, i64>(5)?,
+    )))?.collect::<rusqlite::Result<Vec<_>>>()?;
+    for (id, sender, receiver, subject, body, attachments) in pending {
+        let to = if receiver == "human" { cfg.human.clone() } else { receiver };
+        if !to.is_empty() && !ids::is_address(&to) {
+            tracing::warn!(message = id, to = %to, "not a mail address; message not sent by email");
+        } else if !to.is_empty() {
+            let mut text = body;
+            if attachments > 0 { text.push_str(&format!("\n\n({} attachment(s): tt mail read {} --save-attachments)", attachments, id)); }
+            text.push_str(&format!("\n\n-- \nFrom {} via tt. Reply to this email to answer.", sender));
+            send(cfg, &to, &format!("[tt #{}.{}] {}", id, signature(&secret(db)?, id), subject), &text)?;
+            tracing::info!(message = id, to = %to, "mail sent by email");
+        }
+        db.set_setting(SENT_KEY, &id.to_string())?;
+    }
+    Ok(())
+}
+
+/// A plain or TLS connection to a mail server.
+enum Stream {
+    Plain(TcpStream),
+    Tls(Box<rustls::StreamOwned<rustls::ClientConnection, TcpStream>>),
+}
+
+impl Read for Stream {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        match self { Self::Plain(s) => s.read(buf), Self::Tls(s) => s.read(buf) }
+    }
+}
+
+impl Write for Stream {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match self { Self::Plain(s) => s.write(buf), Self::Tls(s) => s.write(buf) }
+    }
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self { Self::Plain(s) => s.flush(), Self::Tls(s) => s.flush() }
+    }
+}
+
+fn connect(host: &str, port: u16, tls: bool) -> Result<BufReader<Stream>> {
+    let tcp = TcpStream::connect((host, port)).map_err(|e| ThinkTodoError::Remote(format!("{}:{}: {}", host, port, e)))?;
+    tcp.set_read_timeout(Some(Duration::from_secs(30)))?;
+    if !tls { return Ok(BufReader::new(Stream::Plain(tcp))); }
+    let roots = rustls::RootCertStore { roots: webpki_roots::TLS_SERVER_ROOTS.to_vec() };
+    let config = rustls::ClientConfig::builder_with_provider(Arc::new(rustls::crypto::ring::default_provider()))
+        .with_safe_default_protocol_versions().map_err(|e| ThinkTodoError::Remote(e.to_string()))?
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+    let name = rustls::pki_types::ServerName::try_from(host.to_string()).map_err(|e| ThinkTodoError::Remote(format!("{}: {}", host, e)))?;
+    let conn = rustls::ClientConnection::new(Arc::new(config), name).map_err(|e| ThinkTodoError::Remote(e.to_string()))?;
+    Ok(BufReader::new(Stream::Tls(Box::new(rustls::StreamOwned::new(conn, tcp)))))
+}
+
+fn password(cfg: &EmailConfig) -> String { env::var(&cfg.password_env).unwrap_or_default() }
+
+/// Reads an SMTP reply (all its lines) and fails unless its code starts with `expect`.
+fn smtp_reply(conn: &mut BufReader<Stream>, expect: char) -> Result<()> {
+    loop {
+        let mut line = String::new();
+        if conn.read_line(&mut line)? == 0 { return Err(ThinkTodoError::Remote("SMTP server closed the connection".to_string())); }
+        if !line.starts_with(expect) { return Err(ThinkTodoError::Remote(format!("SMTP: {}", line.trim()))); }
+        if line.as_bytes().get(3) != Some(&b'-') { return Ok(()); }
+    }
+}
+
+fn smtp(conn: &mut BufReader<Stream>, command: &str, expect: char) -> Result<()> {
+    write!(conn.get_mut(), "{}\r\n", command)?;
+    smtp_reply(conn, expect)
+}
+
+/// A header value; non-ASCII text, and text with line breaks or other control characters that
+/// could end the header, is sent as an RFC 2047 encoded word.
+fn header_text(text: &str) -> String {
+    if text.is_ascii() && !text.chars().any(|c| c.is_ascii_control()) { text.to_string() } else { format!("=?UTF-8?B?{}?=", BASE64.encode(text)) }
+}
+
+/// The workspace's signing secret, made on first use.
+fn secret(db: &Db) -> Result<String> {
+    if let Some(secret) = db.get_setting(SECRET_KEY)? { return Ok(secret); }
+    let secret = db::new_token()?;
+    db.set_setting(SECRET_KEY, &secret)?;
+    Ok(secret)
+}
+
+/// What proves a reply answers message `id`: only tt, holding the secret, can make it.
+fn signature(secret: &str, id: i64) -> String {
+    Sha256::digest(format!("{}:{}", secret, id)).iter().take(8).map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Sends one plain-text email through the `[email]` SMTP server.
+pub fn send(cfg: &EmailConfig, to: &str, subject: &str, body: &str) -> Result<()> {
+    // Goes into RCPT TO and the To: header as is.
+    if !ids::is_address(to) { return Err(ThinkTodoError::InvalidArgument(format!("'{}' is not a mail address", to))); }
+    let mut conn = connect(&cfg.smtp_host, cfg.smtp_port, cfg.tls)?;
+    smtp_reply(&mut conn, '2')?;
+    smtp(&mut conn, "EHLO tt", '2')?;
+    if !cfg.user.is_empty() {
+        smtp(&mut conn, &format!("AUTH PLAIN {}", BASE64.encode(format!("\0{}\0{}", cfg.user, password(cfg)))), '2')?;
+    }
+    smtp(&mut conn, &format!("MAIL FROM:<{}>", cfg.from), '2')?;
+    smtp(&mut conn, &format!("RCPT TO:<{}>", to), '2')?;
+    smtp(&mut conn, "DATA", '3')?;
+    let mut data = format!(
+        "From: {}\r\nTo: {}\r\nSubject: {}\r\nDate: {}\r\nMIME-Version: 1.0\r\nContent-Type: text/plain; charset=utf-8\r\nContent-Transfer-Encoding: 8bit\r\n\r\n",
+        cfg.from, to, header_text(subject), chrono::Local::now().to_rfc2822());
+    for line in body.lines() {
+        // Dot-stuffing: a lone "." would end the message.
+        if line.starts_with('.') { data.push('.'); }
+        data.push_str(line);
+        data.push_str("\r\n");
+    }
+    data.push_str(".\r\n");
+    conn.get_mut().write_all(data.as_bytes())?;
+    smtp_reply(&mut conn, '2')?;
+    let _ = smtp(&mut conn, "QUIT", '2');
+    Ok(())
+}
+
+/// Sends a tagged IMAP command; returns the untagged lines, each with the literal that followed it.
+fn imap(conn: &mut BufReader<Stream>, tag: &str, command: &str) -> Result<Vec<(String, Vec<u8>)>> {
+    write!(conn.get_mut(), "{} {}\r\n", tag, command)?;
+    let literal = Regex::new(r"\{(\d+)\}\r?\n?$").expect("literal regex");
+    let mut out = Vec::new();
+    loop {
+        let mut line = String::new();
+        if conn.read_line(&mut line)? == 0 { return Err(ThinkTodoError::Remote("IMAP server closed the connection".to_string())); }
+        if let Some(rest) = line.strip_prefix(&format!("{} ", tag)) {
+            if rest.starts_with("OK") { return Ok(out); }
+            return Err(ThinkTodoError::Remote(format!("IMAP {}: {}", command.split_whitespace().next().unwrap_or(""), rest.trim())));
+        }
+        let mut data = Vec::new();
+        if let Some(n) = literal.captures(&line).and_then(|c| c[1].parse::<usize>().ok()) {
+            data = vec![0; n];
+            conn.read_exact(&mut data)?;
+        }
+        out.push((line.trim_end().to_string(), data));
+    }
+}
+
+fn quoted(s: &str) -> String { format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\"")) }
+
+fn poll(db: &Db, cfg: &EmailConfig) -> Result<()> {
+    if cfg.imap_host.is_empty() { return Ok(()); }
+    let now = chrono::Local::now().timestamp();
+    let last: i64 = db.get_setting(POLLED_KEY)?.and_then(|v| v.parse().ok()).unwrap_or(0);
+    if now - last < cfg.poll_secs { return Ok(()); }
+    db.set_setting(POLLED_KEY, &now.to_string())?;
+
+    let mut conn = connect(&cfg.imap_host, cfg.imap_port, cfg.tls)?;
+    let mut greeting = String::new();
+    conn.read_line(&mut greeting)?;
+    imap(&mut conn, "a1", &format!("LOGIN {} {}", quoted(&cfg.user), quoted(&password(cfg))))?;
+    imap(&mut conn, "a2", "SELECT INBOX")?;
+    let uids: Vec<String> = imap(&mut conn, "a3", "UID SEARCH UNSEEN")?.into_iter()
+        .filter_map(|(line, _)| line.strip_prefix("* SEARCH").map(|r| r.split_whitespace().map(str::to_string).collect::<Vec<_>>()))
+        .flatten().collect();
+    for (i, uid) in uids.iter().enumerate() {
+        let fetched = imap(&mut conn, &format!("f{}", i), &format!("UID FETCH {} (BODY.PEEK[])", uid))?;
+        if let Some((_, raw)) = fetched.into_iter().find(|(_, data)| !data.is_empty()) {
+            match accept(db, cfg, &String::from_utf8_lossy(&raw))? {
+                Some(id) => tracing::info!(message = id, "email reply received"),
+                None => tracing::warn!(uid = %uid, "email that isn't a signed reply to tt mail ignored"),
+            }
+        }
+        imap(&mut conn, &format!("s{}", i), &format!("UID STORE {} +FLAGS (\\Seen)", uid))?;
+    }
+    let _ = imap(&mut conn, "z", "LOGOUT");
+    Ok(())
+}
+
+/// Files a received email as tt mail. Only replies are heard: the subject must carry the signed
+/// `[tt #N.<signature>]` tag of message N, and the email must come from the address N went to
+/// (`From:` alone is easy to forge, the signature isn't). The reply goes to whoever wrote message N.
+/// Returns the new message id, or None when the email isn't such a reply.
+fn accept(db: &Db, cfg: &EmailConfig, raw: &str) -> Result<Option<i64>> {
+    let (headers, body) = split_message(raw);
+    let from = header(&headers, "from").map(|f| address(&f)).unwrap_or_default();
+    let subject = decode_words(&header(&headers, "subject").unwrap_or_default());
+    let tag = Regex::new(r"\[tt #(\d+)\.([0-9a-f]+)\]\s*").expect("tag regex");
+    let Some((id, given)) = tag.captures(&subject).and_then(|c| Some((c[1].parse::<i64>().ok()?, c[2].to_string()))) else { return Ok(None) };
+    if !bool::from(signature(&secret(db)?, id).as_bytes().ct_eq(given.as_bytes())) { return Ok(None); }
+    let Some((asker, to)) = db.conn.query_row("SELECT sender, receiver FROM messages WHERE id = ?1", [id],
+        |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))).optional()? else { return Ok(None) };
+    let sender = match to.as_str() {
+        "human" if !cfg.human.is_empty() && from.eq_ignore_ascii_case(&cfg.human) => "human".to_string(),
+        address if ids::is_address(&from) && from.eq_ignore_ascii_case(address) => from.to_lowercase(),
+        _ => return Ok(None),
+    };
+    let subject = tag.replace(&subject, "").to_string();
+    let text = strip_quote(&plain_text(&headers, &body));
+    Ok(Some(db.send_mail(&sender, &asker, &subject, &text)?))
+}
+
+/// Headers (unfolded, lowercase names) and body of a MIME entity.
+fn split_message(raw: &str) -> (Vec<(String, String)>, String) {
+    let raw = raw.replace("\r\n", "\n");
+    let (head, body) = raw.split_once("\n\n").unwrap_or((&raw, ""));
+    let mut headers: Vec<(String, String)> = Vec::new();
+    for line in head.lines() {
+        if line.starts_with([' ', '\t']) {
+            if let Some(last) = headers.last_mut() { last.1.push(' '); last.1.push_str(line.trim()); }
+        } else if let Some((k, v)) = line.split_once(':') {
+            headers.push((k.trim().to_lowercase(), v.trim().to_string()));
+        }
+    }
+    (headers, body.to_string())
+}
+
+fn header(headers: &[(String, String)], name: &str) -> Option<String> {
+    headers.iter().find(|(k, _)| k == name).map(|(_, v)| v.clone())
+}
+
+/// `Name <user@host>` -> `user@host`.
+fn address(from: &str) -> String {
+    match (from.rfind('<'), from.rfind('>')) {
+        (Some(a), Some(b)) if a < b => from[a + 1..b].trim().to_string(),
+        _ => from.trim().to_string(),
+    }
+}
+
+/// The text/plain content of a message, decoding base64 and quoted-printable.
+fn plain_text(headers: &[(String, String)], body: &str) -> String {
+    let content_type = header(headers, "content-type").unwrap_or_default();
+    if content_type.to_lowercase().starts_with("multipart/") {
+        let boundary = Regex::new(r#"(?i)boundary="?([^";]+)"?"#).expect("boundary regex");
+        if let Some(b) = boundary.captures(&content_type).map(|c| c[1].to_string()) {
+            let delimiter = format!("--{}", b);
+            let parts: Vec<(Vec<(String, String)>, String)> = body.split(&delimiter).skip(1).filter(|p| !p.starts_with("--"))
+                .map(|p| split_message(p.trim_start_matches('\n'))).collect();
+            let plain = parts.iter().find(|(h, _)| header(h, "content-type").is_none_or(|t| t.to_lowercase().starts_with("text/plain")))
+                .or_else(|| parts.first());
+            if let Some((h, b)) = plain { return plain_text(h, b); }
+        }
+    }
+    match header(headers, "content-transfer-encoding").map(|e| e.to_lowercase()).as_deref() {
+        Some("base64") => BASE64.decode(body.split_whitespace().collect::<String>()).map(|b| String::from_utf8_lossy(&b).to_string()).unwrap_or_default(),
+        Some("quoted-printable") => quoted_printable(body),
+        _ => body.to_string(),
+    }
+}
+
+fn quoted_printable(text: &str) -> String {
+    let mut bytes = Vec::new();
+    let text = text.replace("=\n", "");
+    let mut chars = text.bytes().peekable();
+    while let Some(c) = chars.next() {
+        if c == b'=' {
+            let hex: Vec<u8> = chars.by_ref().take(2).collect();
+            match std::str::from_utf8(&hex).ok().and_then(|h| u8::from_str_radix(h, 16).ok()) {
+                Some(b) => bytes.push(b),
+                None => { bytes.push(b'='); bytes.extend(hex); }
+            }
+        } else {
+            bytes.push(c);
+        }
+    }
+    String::from_utf8_lossy(&bytes).to_string()
+}
+
+/// Decodes RFC 2047 encoded words (`=?UTF-8?B?...?=`, `=?UTF-8?Q?...?=`) in a header.
+fn decode_words(text: &str) -> String {
+    let word = Regex::new(r"=\?[^?]+\?([bBqQ])\?([^?]*)\?=\s*").expect("encoded word regex");
+    word.replace_all(text, |c: &regex::Captures| {
+        if c[1].eq_ignore_ascii_case("b") {
+            BASE64.decode(&c[2]).map(|b| String::from_utf8_lossy(&b).to_string()).unwrap_or_default()
+        } else {
+            quoted_printable(&c[2].replace('_', " "))
+        }
+    }).trim().to_string()
+}
+
+/// The reply without the quoted message under it.
+fn strip_quote(text: &str) -> String {
+    let wrote = Regex::new(r"^On .+ wrote:$|^-+ ?Original Message ?-+$|^在.+写道").expect("quote regex");
+    let mut kept = Vec::new();
+    for line in text.lines() {
+        let line = line.trim_end();
+        if line.starts_with('>') || wrote.is_match(line) { break; }
+        kept.push(line);
+    }
+    kept.join("\n").trim().to_string()
+}
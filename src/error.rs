@@ -0,0 +1,114 @@
+use rusqlite::ErrorCode;
+use thiserror::Error;
+
+/// Failure kinds of the library layer (db, worker, sessions, config), so the
+/// CLI and server can branch on them instead of matching strings.
+#[derive(Debug, Error)]
+pub enum ThinkTodoError {
+    #[error("task '{0}' not found")]
+    TaskNotFound(String),
+    #[error("task '{id}' already exists (status: {status}, assignee: {})", assignee.as_deref().unwrap_or("none"))]
+    TaskExists { id: String, status: String, assignee: Option<String> },
+    #[error("'{title}' looks like a duplicate of {}; pass --force to add it anyway", similar.iter().map(|(id, _)| id.as_str()).collect::<Vec<_>>().join(", "))]
+    DuplicateTask { title: String, similar: Vec<(String, String)> },
+    #[error("agent '{agent}' is busy with task '{task}'")]
+    AgentBusy { agent: String, task: String },
+    #[error("session '{0}' not found")]
+    SessionMissing(String),
+    #[error("session '{session}' already exists{}; pass --replace to kill and respawn it", task.as_ref().map(|t| format!(" (running task '{}')", t)).unwrap_or_default())]
+    SessionExists { session: String, task: Option<String> },
+    #[error("database is locked by another process, try again")]
+    DbLocked,
+    #[error("engine '{engine}' is not available: {reason}")]
+    EngineMissing { engine: String, reason: String },
+    #[error("invalid {kind} '{value}': use only letters, digits, '-' and '_' (max 64 chars)")]
+    InvalidName { kind: &'static str, value: String },
+    #[error("{0}")]
+    InvalidArgument(String),
+    #[error("dispatch is frozen ({0}); run 'tt unfreeze' to resume")]
+    Frozen(String),
+    #[error("session error: {0}")]
+    Session(String),
+    #[error("engine '{engine}' exited while starting up:\n{output}")]
+    EngineCrashed { engine: String, output: String },
+    #[error("{0}; commit or pull first, or pass --allow-dirty")]
+    RigDirty(String),
+    #[error("'{branch}' conflicts with '{base}' in {} file(s)", files.len())]
+    MergeConflict { branch: String, base: String, files: Vec<String> },
+    #[error("{target} is being finalized by {actor} ({action}); try again once it finishes")]
+    Locked { target: String, actor: String, action: String },
+    #[error("git error: {0}")]
+    Git(String),
+    #[error("config error: {0}")]
+    Config(String),
+    #[error("{0}")]
+    Remote(String),
+    #[error("database error: {0}")]
+    Db(rusqlite::Error),
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+}
+
+pub type Result<T> = std::result::Result<T, ThinkTodoError>;
+
+impl From<rusqlite::Error> for ThinkTodoError {
+    fn from(e: rusqlite::Error) -> Self {
+        match e.sqlite_error_code() {
+            Some(ErrorCode::DatabaseBusy) | Some(ErrorCode::DatabaseLocked) => Self::DbLocked,
+            _ => Self::Db(e),
+        }
+    }
+}
+
+impl From<ureq::Error> for ThinkTodoError {
+    fn from(e: ureq::Error) -> Self {
+        match e {
+            ureq::Error::Status(code, resp) => {
+                let url = resp.get_url().to_string();
+                let body = resp.into_string().unwrap_or_default();
+                Self::Remote(format!("{} answered {}: {}", url, code, body.trim().chars().take(300).collect::<String>()))
+            }
+            other => Self::Remote(other.to_string()),
+        }
+    }
+}
+
+impl ThinkTodoError {
+    /// A failed request whose URL carries a credential (a bot token, a webhook's path), with the
+    /// credential blanked out of the message that names the URL.
+    pub fn remote_hiding(e: ureq::Error, secret: &str) -> Self {
+        match Self::from(e) {
+            Self::Remote(message) if !secret.is_empty() => Self::Remote(message.replace(secret, "***")),
+            other => other,
+        }
+    }
+}
+
+/// The part of a webhook URL after its host, which is all the credential Slack and the like ask for.
+pub fn url_secret(url: &str) -> &str {
+    let rest = url.split_once("://").map_or(url, |(_, rest)| rest);
+    rest.find('/').map_or("", |i| &rest[i + 1..])
+}
+
+impl ThinkTodoError {
+    /// Process exit code used by the CLI.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Self::Config(_) | Self::InvalidName { .. } | Self::InvalidArgument(_) => 2,
+            Self::TaskNotFound(_) => 3,
+            Self::AgentBusy { .. } => 4,
+            Self::SessionMissing(_) => 5,
+            Self::DbLocked => 6,
+            Self::EngineMissing { .. } => 7,
+            Self::TaskExists { .. } => 8,
+            Self::SessionExists { .. } => 9,
+            Self::Frozen(_) => 10,
+            Self::RigDirty(_) => 11,
+            Self::MergeConflict { .. } => 12,
+            Self::EngineCrashed { .. } => 13,
+            Self::Locked { .. } => 14,
+            Self::DuplicateTask { .. } => 15,
+            Self::Session(_) | Self::Git(_) | Self::Db(_) | Self::Io(_) | Self::Remote(_) => 1,
+        }
+    }
+}
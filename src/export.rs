@@ -0,0 +1,192 @@
+use crate::db::Db;
+use crate::error::{Result, ThinkTodoError};
+use crate::output::Progress;
+use rusqlite::types::ValueRef;
+use serde_json::{Map, Value};
+use std::fs;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+/// A table `tt export` can write. Append-only tables export incrementally by `cursor`; the
+/// others are written in full every time.
+struct Table {
+    name: &'static str,
+    /// Column for `--since <date>`.
+    time: &'static str,
+    /// Ever-growing id that `--since last` resumes after.
+    cursor: Option<&'static str>,
+}
+
+const TABLES: &[Table] = &[
+    Table { name: "costs", time: "timestamp", cursor: Some("id") },
+    Table { name: "audit_logs", time: "timestamp", cursor: Some("id") },
+    Table { name: "tasks", time: "created_at", cursor: None },
+];
+
+pub const TABLE_NAMES: &[&str] = &["costs", "audit_logs", "tasks"];
+
+/// Which rows to export.
+#[derive(Clone)]
+pub enum Since {
+    All,
+    /// Rows added since the previous export of the table.
+    Last,
+    From(i64),
+}
+
+/// clap value parser for `--since`: `last`, or a time as for `period::parse_since`.
+pub fn parse_since(value: &str) -> Result<Since> {
+    if value == "last" { return Ok(Since::Last); }
+    crate::period::parse_since(value).map(Since::From)
+}
+
+pub struct Exported {
+    pub table: &'static str,
+    pub rows: usize,
+    /// None when there was nothing new to write.
+    pub path: Option<PathBuf>,
+    /// Every row of the table was written; `--since last` can't resume tables without a cursor.
+    pub full: bool,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Kind { Int, Real, Text }
+
+#[cfg_attr(not(feature = "parquet"), allow(dead_code))]
+struct Column { name: String, kind: Kind }
+
+fn columns(db: &Db, table: &str) -> Result<Vec<Column>> {
+    let mut stmt = db.conn.prepare(&format!("PRAGMA table_info({})", table))?;
+    let cols = stmt.query_map([], |row| {
+        let ty: String = row.get(2)?;
+        let kind = match ty.to_uppercase().as_str() {
+            "INTEGER" => Kind::Int,
+            "REAL" => Kind::Real,
+            _ => Kind::Text,
+        };
+        Ok(Column { name: row.get(1)?, kind })
+    })?;
+    Ok(cols.collect::<rusqlite::Result<Vec<_>>>()?)
+}
+
+fn cursor_key(table: &str) -> String { format!("export:{}", table) }
+
+/// Writes each table to `<out>/<table>-<stamp>.<format>` and moves the table's `--since last`
+/// cursor past what was written.
+pub fn export(db: &Db, tables: &[String], format: &str, since: &Since, out: &Path, progress: &Progress) -> Result<Vec<Exported>> {
+    match format {
+        "jsonl" => {}
+        "parquet" if cfg!(feature = "parquet") => {}
+        "parquet" => return Err(ThinkTodoError::InvalidArgument("this tt was built without parquet support; rebuild with --features parquet or use --format jsonl".to_string())),
+        other => return Err(ThinkTodoError::InvalidArgument(format!("unknown export format '{}' (expected jsonl or parquet)", other))),
+    }
+    let stamp = chrono::Local::now().format("%Y%m%d-%H%M%S%3f").to_string();
+    let mut done = Vec::new();
+    for name in tables {
+        let table = TABLES.iter().find(|t| t.name == name).ok_or_else(|| ThinkTodoError::InvalidArgument(format!(
+            "cannot export '{}' (expected {})", name, TABLE_NAMES.join(", "))))?;
+        progress.start(table.name);
+        let cols = columns(db, table.name)?;
+        let mut sql = format!("SELECT {} FROM {}", cols.iter().map(|c| c.name.as_str()).collect::<Vec<_>>().join(", "), table.name);
+        let mut params: Vec<i64> = Vec::new();
+        match (since, table.cursor) {
+            (Since::Last, Some(cursor)) => {
+                sql.push_str(&format!(" WHERE {} > ?", cursor));
+                params.push(db.get_setting(&cursor_key(table.name))?.and_then(|v| v.parse().ok()).unwrap_or(0));
+            }
+            (Since::From(ts), _) => { sql.push_str(&format!(" WHERE {} >= ?", table.time)); params.push(*ts); }
+            _ => {}
+        }
+        sql.push_str(&format!(" ORDER BY {}", table.cursor.unwrap_or("rowid")));
+        let mut stmt = db.conn.prepare(&sql)?;
+        let rows = stmt.query_map(rusqlite::params_from_iter(&params), |row| {
+            (0..cols.len()).map(|i| Ok(json(row.get_ref(i)?))).collect::<rusqlite::Result<Vec<Value>>>()
+        })?.collect::<rusqlite::Result<Vec<_>>>()?;
+
+        let full = match since {
+            Since::All => true,
+            Since::Last => table.cursor.is_none(),
+            Since::From(_) => false,
+        };
+        if rows.is_empty() {
+            done.push(Exported { table: table.name, rows: 0, path: None, full });
+            progress.inc();
+            continue;
+        }
+        fs::create_dir_all(out)?;
+        let path = out.join(format!("{}-{}.{}", table.name, stamp, format));
+        if format == "parquet" { write_parquet(&path, &cols, &rows)?; } else { write_jsonl(&path, &cols, &rows)?; }
+        if let Some(cursor) = table.cursor {
+            let at = cols.iter().position(|c| c.name == cursor).expect("cursor column");
+            if let Some(last) = rows.iter().filter_map(|r| r[at].as_i64()).max() {
+                db.set_setting(&cursor_key(table.name), &last.to_string())?;
+            }
+        }
+        done.push(Exported { table: table.name, rows: rows.len(), path: Some(path), full });
+        progress.inc();
+    }
+    Ok(done)
+}
+
+fn json(value: ValueRef) -> Value {
+    match value {
+        ValueRef::Null => Value::Null,
+        ValueRef::Integer(n) => Value::from(n),
+        ValueRef::Real(f) => Value::from(f),
+        ValueRef::Text(t) | ValueRef::Blob(t) => Value::from(String::from_utf8_lossy(t).to_string()),
+    }
+}
+
+/// Rows of `sql` as JSON objects keyed by column name.
+pub fn select_json(db: &Db, sql: &str, params: &[i64]) -> Result<Vec<Map<String, Value>>> {
+    let mut stmt = db.conn.prepare(sql)?;
+    let names: Vec<String> = stmt.column_names().iter().map(|n| n.to_string()).collect();
+    let rows = stmt.query_map(rusqlite::params_from_iter(params), |row| {
+        names.iter().enumerate().map(|(i, n)| Ok((n.clone(), json(row.get_ref(i)?)))).collect::<rusqlite::Result<Map<_, _>>>()
+    })?;
+    Ok(rows.collect::<rusqlite::Result<Vec<_>>>()?)
+}
+
+fn write_jsonl(path: &Path, cols: &[Column], rows: &[Vec<Value>]) -> Result<()> {
+    let mut f = BufWriter::new(fs::File::create(path)?);
+    for row in rows {
+        let obj: Map<String, Value> = cols.iter().zip(row).map(|(c, v)| (c.name.clone(), v.clone())).collect();
+        writeln!(f, "{}", Value::Object(obj))?;
+    }
+    f.flush()?;
+    Ok(())
+}
+
+#[cfg(feature = "parquet")]
+fn write_parquet(path: &Path, cols: &[Column], rows: &[Vec<Value>]) -> Result<()> {
+    use arrow_array::{ArrayRef, Float64Array, Int64Array, RecordBatch, StringArray};
+    use arrow_schema::{DataType, Field, Schema};
+    use std::sync::Arc;
+
+    let fields: Vec<Field> = cols.iter().map(|c| Field::new(&c.name, match c.kind {
+        Kind::Int => DataType::Int64,
+        Kind::Real => DataType::Float64,
+        Kind::Text => DataType::Utf8,
+    }, true)).collect();
+    let arrays: Vec<ArrayRef> = cols.iter().enumerate().map(|(i, c)| -> ArrayRef {
+        let values = rows.iter().map(|r| &r[i]);
+        match c.kind {
+            Kind::Int => Arc::new(values.map(Value::as_i64).collect::<Int64Array>()),
+            Kind::Real => Arc::new(values.map(Value::as_f64).collect::<Float64Array>()),
+            Kind::Text => Arc::new(values.map(|v| match v {
+                Value::Null => None,
+                Value::String(s) => Some(s.clone()),
+                other => Some(other.to_string()),
+            }).collect::<StringArray>()),
+        }
+    }).collect();
+    let schema = Arc::new(Schema::new(fields));
+    let batch = RecordBatch::try_new(schema.clone(), arrays).map_err(std::io::Error::other)?;
+    let mut writer = parquet::arrow::ArrowWriter::try_new(fs::File::create(path)?, schema, None).map_err(std::io::Error::other)?;
+    writer.write(&batch).map_err(std::io::Error::other)?;
+    writer.close().map_err(std::io::Error::other)?;
+    Ok(())
+}
+
+#[cfg(not(feature = "parquet"))]
+fn write_parquet(_path: &Path, _cols: &[Column], _rows: &[Vec<Value>]) -> Result<()> { unreachable!("checked in export") }
@@ -0,0 +1,149 @@
+use crate::error::{Result, ThinkTodoError};
+use serde::Serialize;
+use std::fs;
+use std::io::Read;
+use std::path::{Component, Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+/// Entries listed before the tree is cut off, so a worker that vendored a dependency tree
+/// doesn't make the request crawl.
+const MAX_ENTRIES: usize = 2000;
+/// Bytes of a file returned by `preview`; the rest is reported as truncated.
+pub const MAX_PREVIEW: u64 = 256 * 1024;
+/// Directories that are never listed.
+const SKIPPED: [&str; 2] = [".git", ".DS_Store"];
+
+/// One file or directory under a worker directory.
+#[derive(Serialize)]
+pub struct Node {
+    pub name: String,
+    /// Relative to the worker directory, with `/` separators.
+    pub path: String,
+    pub dir: bool,
+    pub size: u64,
+    /// Unix seconds.
+    pub mtime: Option<i64>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub children: Vec<Node>,
+}
+
+/// The tree under `root`, directories first then by name. Stops after `MAX_ENTRIES` entries and
+/// says so with the flag.
+pub fn tree(root: &Path) -> (Vec<Node>, bool) {
+    let mut left = MAX_ENTRIES;
+    let nodes = walk(root, "", &mut left);
+    (nodes, left == 0)
+}
+
+fn walk(dir: &Path, prefix: &str, left: &mut usize) -> Vec<Node> {
+    let Ok(entries) = fs::read_dir(dir) else { return Vec::new() };
+    let mut entries: Vec<_> = entries.flatten()
+        .filter(|e| e.file_name().to_str().is_some_and(|n| !SKIPPED.contains(&n)))
+        .collect();
+    entries.sort_by_key(|e| (!e.file_type().is_ok_and(|t| t.is_dir()), e.file_name()));
+    let mut nodes = Vec::new();
+    for entry in entries {
+        if *left == 0 { break; }
+        *left -= 1;
+        let name = entry.file_name().to_string_lossy().into_owned();
+        let path = format!("{}{}", prefix, name);
+        // Symlinks are listed but not followed: they may point out of the worker directory.
+        let meta = entry.path().symlink_metadata().ok();
+        let dir = meta.as_ref().is_some_and(|m| m.is_dir());
+        let children = if dir { walk(&entry.path(), &format!("{}/", path), left) } else { Vec::new() };
+        nodes.push(Node {
+            name,
+            dir,
+            size: meta.as_ref().map(|m| if dir { 0 } else { m.len() }).unwrap_or(0),
+            mtime: meta.and_then(|m| m.modified().ok()).and_then(|t| t.duration_since(UNIX_EPOCH).ok()).map(|d| d.as_secs() as i64),
+            path,
+            children,
+        });
+    }
+    nodes
+}
+
+/// The start of a file under a worker directory, for display.
+#[derive(Serialize)]
+pub struct Preview {
+    pub path: String,
+    pub size: u64,
+    pub mtime: Option<i64>,
+    /// False for binary files, whose content is left out.
+    pub text: bool,
+    /// The file is larger than `MAX_PREVIEW` and only its start is included.
+    pub truncated: bool,
+    /// Highlighting hint from the file name, e.g. "rust" or "markdown".
+    pub language: Option<&'static str>,
+    pub content: Option<String>,
+}
+
+/// Reads `rel` under `root`. `rel` has to stay inside `root`: absolute paths, `..` and symlinks
+/// leading out are refused.
+pub fn preview(root: &Path, rel: &str) -> Result<Preview> {
+    let path = inside(root, rel)?;
+    let meta = fs::metadata(&path)?;
+    if !meta.is_file() { return Err(ThinkTodoError::InvalidArgument(format!("'{}' is not a file", rel))); }
+    let mut head = Vec::new();
+    fs::File::open(&path)?.take(MAX_PREVIEW).read_to_end(&mut head)?;
+    let truncated = meta.len() > MAX_PREVIEW;
+    let content = text(&head, truncated);
+    Ok(Preview {
+        path: rel.to_string(),
+        size: meta.len(),
+        mtime: meta.modified().ok().and_then(|t| t.duration_since(UNIX_EPOCH).ok()).map(|d| d.as_secs() as i64),
+        text: content.is_some(),
+        truncated,
+        language: language(&path),
+        content,
+    })
+}
+
+fn inside(root: &Path, rel: &str) -> Result<PathBuf> {
+    let outside = || ThinkTodoError::InvalidArgument(format!("'{}' is outside the worker directory", rel));
+    if rel.is_empty() || !Path::new(rel).components().all(|c| matches!(c, Component::Normal(_))) { return Err(outside()); }
+    let root = root.canonicalize()?;
+    let path = root.join(rel).canonicalize()
+        .map_err(|_| ThinkTodoError::InvalidArgument(format!("no file '{}' in the worker directory", rel)))?;
+    if !path.starts_with(&root) { return Err(outside()); }
+    Ok(path)
+}
+
+/// `bytes` as text, or None when they look binary: a NUL byte, or invalid UTF-8 other than a
+/// character cut in half at the end of a truncated read.
+fn text(bytes: &[u8], truncated: bool) -> Option<String> {
+    if bytes.contains(&0) { return None; }
+    match std::str::from_utf8(bytes) {
+        Ok(s) => Some(s.to_string()),
+        Err(e) if truncated && e.error_len().is_none() => Some(String::from_utf8_lossy(&bytes[..e.valid_up_to()]).into_owned()),
+        Err(_) => None,
+    }
+}
+
+fn language(path: &Path) -> Option<&'static str> {
+    let name = path.file_name()?.to_str()?;
+    if name == "Dockerfile" { return Some("dockerfile"); }
+    if name == "Makefile" { return Some("makefile"); }
+    Some(match path.extension()?.to_str()? {
+        "rs" => "rust",
+        "py" => "python",
+        "js" | "mjs" | "cjs" => "javascript",
+        "ts" | "tsx" => "typescript",
+        "go" => "go",
+        "java" => "java",
+        "c" | "h" => "c",
+        "cc" | "cpp" | "hpp" => "cpp",
+        "rb" => "ruby",
+        "sh" | "bash" => "bash",
+        "md" => "markdown",
+        "json" => "json",
+        "toml" => "toml",
+        "yml" | "yaml" => "yaml",
+        "html" | "htm" => "html",
+        "css" => "css",
+        "sql" => "sql",
+        "proto" => "protobuf",
+        "txt" | "log" => "plaintext",
+        _ => return None,
+    })
+}
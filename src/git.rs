@@ -0,0 +1,80 @@
+use crate::error::{Result, ThinkTodoError};
+use std::path::Path;
+use std::process::Command;
+
+pub struct Git;
+
+/// Working-tree state relevant before dispatching an agent into a repo.
+pub struct RepoState {
+    pub dirty: Vec<String>,
+    pub behind: u32,
+}
+
+impl Git {
+    pub fn run(dir: &Path, args: &[&str]) -> Result<String> {
+        let (ok, stdout, stderr) = Self::output(dir, args)?;
+        if !ok { return Err(ThinkTodoError::Git(format!("git {}: {}", args.join(" "), stderr.trim()))); }
+        Ok(stdout)
+    }
+    /// Like `run` but hands back (success, stdout, stderr) for commands whose exit status carries meaning.
+    pub fn output(dir: &Path, args: &[&str]) -> Result<(bool, String, String)> {
+        let output = Command::new("git").arg("-C").arg(dir).args(args).output()
+            .map_err(|e| ThinkTodoError::Git(format!("git {:?} failed: {}", args, e)))?;
+        Ok((output.status.success(), String::from_utf8_lossy(&output.stdout).trim_end().to_string(), String::from_utf8_lossy(&output.stderr).to_string()))
+    }
+    /// A repository with at least one commit (worktrees need something to branch from).
+    pub fn has_commits(dir: &Path) -> bool { Self::run(dir, &["rev-parse", "--verify", "--quiet", "HEAD"]).is_ok() }
+    /// Branch a task's work lives on inside its rig.
+    pub fn task_branch(task_id: &str) -> String { format!("tt/{}", task_id) }
+    pub fn current_branch(dir: &Path) -> Result<String> { Self::run(dir, &["symbolic-ref", "--short", "HEAD"]) }
+    pub fn branch_exists(dir: &Path, branch: &str) -> bool {
+        Self::run(dir, &["rev-parse", "--verify", "--quiet", &format!("refs/heads/{}", branch)]).is_ok()
+    }
+
+    /// Checks out `branch` (created from HEAD if missing) as a worktree at `path`.
+    pub fn add_worktree(repo: &Path, path: &Path, branch: &str) -> Result<()> {
+        let path = path.to_string_lossy();
+        if Self::branch_exists(repo, branch) {
+            Self::run(repo, &["worktree", "add", "--force", &path, branch])?;
+        } else {
+            Self::run(repo, &["worktree", "add", "-b", branch, &path])?;
+        }
+        Ok(())
+    }
+    /// Detaches the worktree at `path` from its repository (the branch is kept). No-op for plain directories.
+    pub fn remove_worktree(path: &Path) -> Result<()> {
+        let Ok(common) = Self::run(path, &["rev-parse", "--path-format=absolute", "--git-common-dir"]) else { return Ok(()) };
+        let Some(repo) = Path::new(&common).parent().map(Path::to_path_buf) else { return Ok(()) };
+        if repo == path { return Ok(()); } // the rig itself, not a worktree
+        Self::run(&repo, &["worktree", "remove", "--force", &path.to_string_lossy()])?;
+        Ok(())
+    }
+
+    /// Commits everything in the working tree at `path`. Returns false when there was nothing to commit.
+    pub fn checkpoint(path: &Path, message: &str) -> Result<bool> {
+        if Self::run(path, &["status", "--porcelain"])?.is_empty() { return Ok(false); }
+        Self::run(path, &["add", "-A"])?;
+        Self::run(path, &["-c", "user.name=think-todo", "-c", "user.email=tt@localhost", "commit", "-q", "--no-verify", "-m", message])?;
+        Ok(true)
+    }
+
+    /// Merges `branch` into `base` in memory and returns the conflicting paths (empty when it merges cleanly).
+    pub fn trial_merge(repo: &Path, base: &str, branch: &str) -> Result<Vec<String>> {
+        let (ok, stdout, stderr) = Self::output(repo, &["merge-tree", "--write-tree", "--name-only", base, branch])?;
+        if ok { return Ok(Vec::new()); }
+        if stdout.is_empty() { return Err(ThinkTodoError::Git(format!("merge-tree: {}", stderr.trim()))); }
+        // Output: tree id, conflicted paths, blank line, informational messages.
+        Ok(stdout.lines().skip(1).take_while(|l| !l.is_empty()).map(|l| l.to_string()).collect())
+    }
+    pub fn ahead(repo: &Path, base: &str, branch: &str) -> Result<u32> {
+        Ok(Self::run(repo, &["rev-list", "--count", &format!("{}..{}", base, branch)])?.trim().parse().unwrap_or(0))
+    }
+    /// Uncommitted paths and how far HEAD trails its upstream (0 without an upstream).
+    pub fn state(dir: &Path, fetch: bool) -> Result<RepoState> {
+        if fetch { let _ = Self::run(dir, &["fetch", "--quiet"]); }
+        let dirty = Self::run(dir, &["status", "--porcelain"])?.lines().map(|l| l.to_string()).collect();
+        let behind = Self::run(dir, &["rev-list", "--count", "HEAD..@{u}"]).ok().and_then(|n| n.trim().parse().ok()).unwrap_or(0);
+        Ok(RepoState { dirty, behind })
+    }
+    pub fn recent_commits(dir: &Path, n: usize) -> Result<String> { Self::run(dir, &["log", "--oneline", "-n", &n.to_string()]) }
+}
@@ -0,0 +1,194 @@
+use crate::db::Db;
+use crate::error::{Result, ThinkTodoError};
+use crate::ids;
+use crate::service::{self, NewTask, Sling};
+use rusqlite::params;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// What the grooming worker writes its suggestions to, in its worker directory.
+pub const PATCH_FILE: &str = "GROOM.txt";
+
+/// One line of a grooming patch.
+#[derive(Debug, PartialEq)]
+pub enum Change {
+    /// Close `from` as a duplicate of `into`, which takes over its tags and, if higher, its priority.
+    Merge { from: String, into: String },
+    Priority { task: String, priority: i64 },
+    Tag { task: String, tags: Vec<String> },
+    Depends { task: String, on: String },
+}
+
+impl std::fmt::Display for Change {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Change::Merge { from, into } => write!(f, "merge {} into {}", from, into),
+            Change::Priority { task, priority } => write!(f, "priority {} {}", task, service::priority_name(*priority)),
+            Change::Tag { task, tags } => write!(f, "tag {} {}", task, tags.join(" ")),
+            Change::Depends { task, on } => write!(f, "depends {} on {}", task, on),
+        }
+    }
+}
+
+/// Spawns a worker on `engine` that reads the unfinished backlog and writes `PATCH_FILE`.
+/// Returns the grooming task's id, which is also the worker's name.
+pub fn start(db: &Db, work_dir: &Path, engine: &str, actor: &str) -> Result<String> {
+    let mut stmt = db.conn.prepare(
+        "SELECT id, title, status, COALESCE(priority, 1) FROM tasks
+         WHERE status != 'closed' AND NOT EXISTS (SELECT 1 FROM task_tags g WHERE g.task_id = tasks.id AND g.tag = 'groom')
+         ORDER BY priority DESC, rowid")?;
+    let tasks = stmt.query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?, row.get::<_, String>(2)?, row.get::<_, i64>(3)?)))?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    if tasks.is_empty() { return Err(ThinkTodoError::InvalidArgument("there are no unfinished tasks to groom".to_string())); }
+    let mut backlog = String::from("# Unfinished tasks, highest priority first\n\n");
+    for (id, title, status, priority) in &tasks {
+        let tags: String = db.task_tags(id)?.iter().map(|t| format!(" #{}", t)).collect();
+        let deps = db.task_dependencies(id)?;
+        let deps = if deps.is_empty() { String::new() } else { format!(" (depends on {})", deps.join(", ")) };
+        backlog.push_str(&format!("- [{}] {} ({}, {}){}{}\n", id, title, status, service::priority_name(*priority), tags, deps));
+    }
+
+    let task_id = format!("groom-{}", chrono::Local::now().format("%m%d%H%M%S"));
+    service::add_task(db, &NewTask {
+        id: task_id.clone(), title: format!("groom the backlog ({} tasks)", tasks.len()),
+        rig: None, due: None, tags: vec!["groom".to_string()], priority: None, force_title_update: false, force: true, estimate: None,
+    })?;
+    let dir = work_dir.join("workers").join(&task_id);
+    fs::create_dir_all(&dir)?;
+    fs::write(dir.join("BACKLOG.md"), backlog)?;
+    let brief = format!("## BACKLOG GROOMING
+BACKLOG.md in your worker directory lists every unfinished task. Suggest where duplicates should be merged, \
+which priorities and tags are wrong or missing, and which tasks depend on others. Write the suggestions to {} \
+in your worker directory, one per line, using only these forms:
+  merge <task> into <task>
+  priority <task> low|normal|high|urgent
+  tag <task> <tag> [<tag>...]
+  depends <task> on <task>
+Put your reasoning on comment lines starting with #. Change nothing else. When {} is written, print [TASK_DONE].", PATCH_FILE, PATCH_FILE);
+    let req = Sling { task_id: task_id.clone(), agent: task_id.clone(), engine: engine.to_string(), replace: false, allow_dirty: false, dry_run: false, brief: Some(brief) };
+    service::sling(db, work_dir, &req, actor)?;
+    db.log_audit(actor, "groom_started", &task_id, &format!("{} tasks", tasks.len()))?;
+    Ok(task_id)
+}
+
+/// The newest grooming run, or the one named.
+pub fn run_id(db: &Db, id: Option<&str>) -> Result<String> {
+    if let Some(id) = id {
+        ids::task_id(id)?;
+        if !id.starts_with("groom-") { return Err(ThinkTodoError::InvalidArgument(format!("{} is not a grooming run (groom-...)", id))); }
+        return Ok(id.to_string());
+    }
+    db.conn.query_row("SELECT id FROM tasks WHERE id LIKE 'groom-%' ORDER BY rowid DESC LIMIT 1", [], |row| row.get(0))
+        .map_err(|_| ThinkTodoError::InvalidArgument("no grooming run yet (tt groom)".to_string()))
+}
+
+pub fn patch_path(work_dir: &Path, id: &str) -> PathBuf { work_dir.join("workers").join(id).join(PATCH_FILE) }
+
+/// The run's patch as written (or since edited), or an error saying why there is none yet.
+pub fn read_patch(db: &Db, work_dir: &Path, id: &str) -> Result<String> {
+    let path = patch_path(work_dir, id);
+    match fs::read_to_string(&path) {
+        Ok(text) => Ok(text),
+        Err(_) => {
+            let status = db.conn.query_row("SELECT status FROM tasks WHERE id = ?1", [id], |row| row.get::<_, String>(0))
+                .map_err(|_| ThinkTodoError::TaskNotFound(id.to_string()))?;
+            Err(ThinkTodoError::InvalidArgument(format!("{} has no {} yet (task {})", id, PATCH_FILE, status)))
+        }
+    }
+}
+
+/// Parses a patch, skipping blank and `#` lines; errors name the line.
+pub fn parse(text: &str) -> Result<Vec<Change>> {
+    let mut changes = Vec::new();
+    for (n, line) in text.lines().enumerate() {
+        let line = line.split('#').next().unwrap_or("").trim();
+        if line.is_empty() { continue; }
+        let bad = |why: &str| ThinkTodoError::InvalidArgument(format!("line {}: {}: '{}'", n + 1, why, line));
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let change = match words.as_slice() {
+            ["merge", from, "into", into] => Change::Merge { from: from.to_string(), into: into.to_string() },
+            ["priority", task, level] => Change::Priority { task: task.to_string(), priority: service::parse_priority(level).map_err(|e| bad(&e.to_string()))? },
+            ["tag", task, tags @ ..] if !tags.is_empty() => {
+                for tag in tags { ids::tag(tag).map_err(|e| bad(&e.to_string()))?; }
+                Change::Tag { task: task.to_string(), tags: tags.iter().map(|t| t.to_string()).collect() }
+            }
+            ["depends", task, "on", on] => Change::Depends { task: task.to_string(), on: on.to_string() },
+            _ => return Err(bad("not a merge, priority, tag or depends line")),
+        };
+        changes.push(change);
+    }
+    Ok(changes)
+}
+
+/// Checks every change against the tasks as they are now, then applies them all.
+pub fn apply(db: &Db, id: &str, changes: &[Change], actor: &str) -> Result<()> {
+    let key = format!("groom_applied:{}", id);
+    if db.get_setting(&key)?.is_some() { return Err(ThinkTodoError::InvalidArgument(format!("{} was already applied", id))); }
+    let merged_away: Vec<&String> = changes.iter().filter_map(|c| match c { Change::Merge { from, .. } => Some(from), _ => None }).collect();
+    let mut deps: Vec<(String, String)> = db.conn.prepare("SELECT task_id, depends_on FROM task_deps")?
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect::<rusqlite::Result<_>>()?;
+    for change in changes {
+        let (task, other) = match change {
+            Change::Merge { from, into } => (from, Some(into)),
+            Change::Depends { task, on } => (task, Some(on)),
+            Change::Priority { task, .. } | Change::Tag { task, .. } => (task, None),
+        };
+        for t in std::iter::once(task).chain(other) {
+            ids::task_id(t)?;
+            db.task_assignee(t)?;
+        }
+        if other == Some(task) { return Err(ThinkTodoError::InvalidArgument(format!("'{}' refers to {} twice", change, task))); }
+        let status = |t: &str| db.conn.query_row("SELECT status FROM tasks WHERE id = ?1", [t], |row| row.get::<_, String>(0));
+        match change {
+            Change::Merge { from, into } => {
+                if status(from)? == "in_progress" { return Err(ThinkTodoError::InvalidArgument(format!("'{}': {} is in progress", change, from))); }
+                if merged_away.contains(&into) { return Err(ThinkTodoError::InvalidArgument(format!("'{}': {} is merged away by this patch", change, into))); }
+                if status(into)? == "closed" { return Err(ThinkTodoError::InvalidArgument(format!("'{}': {} is closed", change, into))); }
+            }
+            Change::Depends { task, on } => {
+                if depends_on(&deps, on, task) { return Err(ThinkTodoError::InvalidArgument(format!("'{}' would make a dependency cycle", change))); }
+                deps.push((task.clone(), on.clone()));
+            }
+            _ => {}
+        }
+    }
+    for change in changes {
+        match change {
+            Change::Merge { from, into } => {
+                db.add_task_tags(into, &db.task_tags(from)?)?;
+                db.conn.execute(
+                    "UPDATE tasks SET priority = MAX(COALESCE(priority, 1), (SELECT COALESCE(priority, 1) FROM tasks WHERE id = ?2)) WHERE id = ?1",
+                    params![into, from])?;
+                db.conn.execute("UPDATE OR IGNORE task_deps SET depends_on = ?2 WHERE depends_on = ?1", params![from, into])?;
+                db.conn.execute("DELETE FROM task_deps WHERE depends_on = ?1 OR task_id = depends_on", params![from])?;
+                db.conn.execute("UPDATE tasks SET status = 'closed' WHERE id = ?1", params![from])?;
+                db.log_audit(actor, "task_merged", from, &format!("into {}", into))?;
+                db.log_audit(actor, "task_closed", from, &format!("merged into {}", into))?;
+            }
+            Change::Priority { task, priority } => db.set_task_priority(task, *priority)?,
+            Change::Tag { task, tags } => db.add_task_tags(task, tags)?,
+            Change::Depends { task, on } => db.add_task_dependency(task, on)?,
+        }
+    }
+    db.set_setting(&key, &chrono::Local::now().timestamp().to_string())?;
+    let metadata = serde_json::json!({ "changes": changes.iter().map(|c| c.to_string()).collect::<Vec<_>>() }).to_string();
+    db.log_audit_meta(actor, "groom_applied", id, &format!("{} change(s)", changes.len()), &metadata)?;
+    Ok(())
+}
+
+/// Whether `task` reaches `on` through the dependency edges `deps`, directly or not.
+fn depends_on(deps: &[(String, String)], task: &str, on: &str) -> bool {
+    let mut seen = vec![task];
+    let mut next = vec![task];
+    while let Some(t) = next.pop() {
+        if t == on { return true; }
+        for (_, d) in deps.iter().filter(|(from, _)| from == t) {
+            if !seen.contains(&d.as_str()) {
+                seen.push(d);
+                next.push(d);
+            }
+        }
+    }
+    false
+}
@@ -0,0 +1,204 @@
+use crate::db::Db;
+use crate::error::{Result, ThinkTodoError};
+use crate::i18n::tr;
+use crate::period;
+use crate::server::{blocking, open_db};
+use crate::service;
+use rusqlite::params;
+use std::env;
+use std::net::SocketAddr;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::{Request, Response, Status};
+
+/// Types and service traits generated from `proto/think_todo.proto`.
+pub mod pb {
+    tonic::include_proto!("thinktodo.v1");
+}
+
+use pb::think_todo_server::{ThinkTodo, ThinkTodoServer};
+
+/// Serves `thinktodo.v1.ThinkTodo` on `port` alongside the HTTP server.
+pub async fn serve(port: u16) -> Result<()> {
+    let addr = SocketAddr::from(([0, 0, 0, 0], port));
+    println!("{}", tr!("🛰️  Think-Todo gRPC is listening on: {}", addr));
+    tonic::transport::Server::builder()
+        .add_service(ThinkTodoServer::new(Service))
+        .serve(addr).await
+        .map_err(|e| ThinkTodoError::Config(format!("gRPC server on port {} failed: {}", port, e)))
+}
+
+impl From<ThinkTodoError> for Status {
+    fn from(e: ThinkTodoError) -> Self {
+        let msg = e.to_string();
+        match e {
+            ThinkTodoError::TaskNotFound(_) | ThinkTodoError::SessionMissing(_) => Status::not_found(msg),
+            ThinkTodoError::TaskExists { .. } | ThinkTodoError::DuplicateTask { .. } | ThinkTodoError::SessionExists { .. } => Status::already_exists(msg),
+            ThinkTodoError::AgentBusy { .. } | ThinkTodoError::RigDirty(_) | ThinkTodoError::MergeConflict { .. }
+            | ThinkTodoError::EngineMissing { .. } | ThinkTodoError::Frozen(_)
+            | ThinkTodoError::EngineCrashed { .. } => Status::failed_precondition(msg),
+            ThinkTodoError::InvalidName { .. } | ThinkTodoError::InvalidArgument(_) => Status::invalid_argument(msg),
+            ThinkTodoError::DbLocked => Status::unavailable(msg),
+            ThinkTodoError::Locked { .. } => Status::aborted(msg),
+            ThinkTodoError::Session(_) | ThinkTodoError::Git(_) | ThinkTodoError::Config(_) | ThinkTodoError::Db(_) | ThinkTodoError::Io(_) | ThinkTodoError::Remote(_) => Status::internal(msg),
+        }
+    }
+}
+
+fn task(db: &Db, id: &str) -> Result<pb::Task> {
+    let mut task = db.conn.query_row(
+        "SELECT id, title, status, assignee, engine, due_at FROM tasks WHERE id = ?1", [id],
+        |row| Ok(pb::Task {
+            id: row.get(0)?, title: row.get(1)?, status: row.get(2)?,
+            assignee: row.get(3)?, engine: row.get(4)?, due_at: row.get(5)?, tags: Vec::new(),
+        }),
+    ).map_err(|_| ThinkTodoError::TaskNotFound(id.to_string()))?;
+    task.tags = db.task_tags(id)?;
+    Ok(task)
+}
+
+type RpcResult<T> = std::result::Result<Response<T>, Status>;
+
+/// Who a call that changes something acts as: the user whose token it bears in
+/// `authorization: Bearer <token>` (see `tt user add`), else "grpc". Once the workspace has users,
+/// such calls have to bear one, as on the HTTP API.
+async fn actor<T>(req: &Request<T>) -> std::result::Result<String, Status> {
+    let token = req.metadata().get("authorization").and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer ")).map(|t| t.trim().to_string());
+    let (user, has_users) = blocking(move || {
+        let db = open_db()?;
+        let user = match token { Some(t) => db.token_user(&t)?, None => None };
+        Ok((user, !db.users()?.is_empty()))
+    }).await?;
+    match user {
+        Some(user) => Ok(user),
+        None if has_users => Err(Status::unauthenticated("sign in: send authorization: Bearer <your user token> (tt user add)")),
+        None => Ok("grpc".to_string()),
+    }
+}
+
+struct Service;
+
+#[tonic::async_trait]
+impl ThinkTodo for Service {
+    async fn list_tasks(&self, req: Request<pb::ListTasksRequest>) -> RpcResult<pb::ListTasksResponse> {
+        let status = req.into_inner().status;
+        let tasks = blocking(move || {
+            let db = open_db()?;
+            let mut stmt = db.conn.prepare("SELECT id FROM tasks WHERE ?1 = '' OR status = ?1 ORDER BY created_at, id")?;
+            let ids = stmt.query_map([&status], |row| row.get::<_, String>(0))?.collect::<rusqlite::Result<Vec<_>>>()?;
+            ids.iter().map(|id| task(&db, id)).collect::<Result<Vec<_>>>()
+        }).await?;
+        Ok(Response::new(pb::ListTasksResponse { tasks }))
+    }
+
+    async fn add_task(&self, req: Request<pb::AddTaskRequest>) -> RpcResult<pb::Task> {
+        actor(&req).await?;
+        let req = req.into_inner();
+        let added = blocking(move || {
+            let db = open_db()?;
+            let due = req.due.as_deref().map(period::parse_due).transpose()?;
+            let estimate = req.estimate.as_deref().map(service::Estimate::parse).transpose()?;
+            let id = if req.id.is_empty() { service::new_task_id(&db, &env::current_dir()?)? } else { req.id };
+            service::add_task(&db, &service::NewTask { id: id.clone(), title: req.title, rig: req.rig, due, tags: req.tags, priority: None, force_title_update: false, force: req.force, estimate })?;
+            task(&db, &id)
+        }).await?;
+        Ok(Response::new(added))
+    }
+
+    async fn delete_task(&self, req: Request<pb::TaskRef>) -> RpcResult<pb::Ack> {
+        let actor = actor(&req).await?;
+        let id = req.into_inner().id;
+        blocking(move || service::delete_task(&open_db()?, &id, &actor)).await?;
+        Ok(Response::new(pb::Ack {}))
+    }
+
+    async fn sling(&self, req: Request<pb::SlingRequest>) -> RpcResult<pb::Task> {
+        let actor = actor(&req).await?;
+        let req = req.into_inner();
+        let started = blocking(move || {
+            let engine = if req.engine.is_empty() { "gemini".to_string() } else { req.engine };
+            let db = open_db()?;
+            let sling = service::Sling { task_id: req.task_id, agent: req.agent_name, engine, replace: req.replace, allow_dirty: req.allow_dirty, dry_run: false, brief: None };
+            service::sling(&db, &env::current_dir()?, &sling, &actor)?;
+            task(&db, &sling.task_id)
+        }).await?;
+        Ok(Response::new(started))
+    }
+
+    async fn done(&self, req: Request<pb::TaskRef>) -> RpcResult<pb::Task> {
+        let actor = actor(&req).await?;
+        let id = req.into_inner().id;
+        let closed = blocking(move || {
+            let db = open_db()?;
+            service::done(&db, &env::current_dir()?, &id, &actor)?;
+            task(&db, &id)
+        }).await?;
+        Ok(Response::new(closed))
+    }
+
+    async fn send_mail(&self, req: Request<pb::SendMailRequest>) -> RpcResult<pb::Ack> {
+        let actor = actor(&req).await?;
+        let req = req.into_inner();
+        // Mail goes out as whoever signed the call; nobody writes in someone else's name.
+        if !req.sender.is_empty() && req.sender != actor {
+            return Err(Status::permission_denied(format!("signed in as '{}', cannot send mail as '{}'", actor, req.sender)));
+        }
+        blocking(move || open_db()?.send_mail(&actor, &req.receiver, &req.subject, &req.body)).await?;
+        Ok(Response::new(pb::Ack {}))
+    }
+
+    async fn inbox(&self, req: Request<pb::InboxRequest>) -> RpcResult<pb::InboxResponse> {
+        let req = req.into_inner();
+        let messages = blocking(move || {
+            let db = open_db()?;
+            let mut stmt = db.conn.prepare(
+                "SELECT id, sender, receiver, subject, body, status, timestamp FROM messages
+                 WHERE (?1 = '' OR receiver = ?1) AND (?2 = 0 OR status = 'unread') ORDER BY id DESC")?;
+            let rows = stmt.query_map(params![req.receiver, req.unread_only], |row| Ok(pb::Mail {
+                id: row.get(0)?, sender: row.get(1)?, receiver: row.get(2)?, subject: row.get(3)?,
+                body: row.get(4)?, status: row.get(5)?, timestamp: row.get(6)?,
+            }))?.collect::<rusqlite::Result<Vec<_>>>()?;
+            Ok(rows)
+        }).await?;
+        Ok(Response::new(pb::InboxResponse { messages }))
+    }
+
+    async fn nudge(&self, req: Request<pb::NudgeRequest>) -> RpcResult<pb::NudgeResponse> {
+        let actor = actor(&req).await?;
+        let req = req.into_inner();
+        let shown = blocking(move || service::nudge(&open_db()?, &env::current_dir()?, &req.agent_name, &req.message, &actor)).await?;
+        Ok(Response::new(pb::NudgeResponse { shown }))
+    }
+
+    type WatchTrailStream = ReceiverStream<std::result::Result<pb::AuditEvent, Status>>;
+
+    async fn watch_trail(&self, req: Request<pb::WatchTrailRequest>) -> RpcResult<Self::WatchTrailStream> {
+        let mut after = req.into_inner().after_id;
+        let (tx, rx) = mpsc::channel(64);
+        tokio::spawn(async move {
+            // Poll until the client hangs up.
+            while !tx.is_closed() {
+                let batch = blocking(move || {
+                    let db = open_db()?;
+                    let mut stmt = db.conn.prepare("SELECT id, timestamp, actor, action, target, status, metadata FROM audit_logs WHERE id > ?1 ORDER BY id")?;
+                    let rows = stmt.query_map([after], |row| Ok(pb::AuditEvent {
+                        id: row.get(0)?, timestamp: row.get(1)?, actor: row.get(2)?,
+                        action: row.get(3)?, target: row.get(4)?, status: row.get(5)?, metadata: row.get(6)?,
+                    }))?.collect::<rusqlite::Result<Vec<_>>>()?;
+                    Ok(rows)
+                }).await;
+                match batch {
+                    Ok(events) => for event in events {
+                        after = event.id;
+                        if tx.send(Ok(event)).await.is_err() { return; }
+                    },
+                    Err(e) => { let _ = tx.send(Err(e.into())).await; return; }
+                }
+                tokio::time::sleep(Duration::from_secs(1)).await;
+            }
+        });
+        Ok(Response::new(ReceiverStream::new(rx)))
+    }
+}
@@ -0,0 +1,200 @@
+use crate::artifacts;
+use crate::config::Config;
+use crate::db::Db;
+use crate::error::Result;
+use crate::i18n;
+use crate::period;
+use crate::session;
+use crate::worker::Worker;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use std::fs;
+use std::path::Path;
+use std::time::UNIX_EPOCH;
+
+/// Settings key the monitor refreshes on every pass.
+pub const HEARTBEAT_KEY: &str = "monitor_heartbeat";
+/// Settings key of the last `DiskUsage` measured, as JSON; the monitor refreshes it on every pass.
+pub const DISK_USAGE_KEY: &str = "disk_usage";
+
+// Warning messages stay in English so `--json`, the API and the notifications table carry the
+// same text whatever `lang` is; `localize` translates them for people.
+const DB_CHECK_FAILED: &str = "integrity check failed: {}";
+const FROZEN: &str = "dispatch frozen: {} (tt unfreeze)";
+const MONITOR_LATE: &str = "monitor last seen {} ago (tt monitor start)";
+const MONITOR_NEVER_RAN: &str = "monitor has never run; finished tasks won't be detected (tt monitor start)";
+const TELEMETRY_FAILING: &str = "telemetry sink unreachable, shipments retried: {}";
+const EMAIL_FAILING: &str = "email bridge failing, retried each pass: {}";
+const NO_SESSION: &str = "'{}' has no live session (tt recover)";
+const SILENT: &str = "'{}' has been silent for {}";
+const OVER_BUDGET: &str = "spent ${:.2} of its ${:.2} cap";
+const ATTEMPT_FAILED: &str = "last attempt by '{}' was {}";
+const DISK_LOW: &str = "only {} free on the workspace's disk";
+const DISK_OVER_LIMIT: &str = "logs, workers and artifacts take {} (limit {} MB; tt gc, tt logs archive)";
+const URGENT_MAIL: &str = "unread: {} (tt mail read {})";
+const MESSAGES: &[&str] = &[DB_CHECK_FAILED, FROZEN, MONITOR_LATE, MONITOR_NEVER_RAN, TELEMETRY_FAILING, EMAIL_FAILING,
+    NO_SESSION, SILENT, OVER_BUDGET, ATTEMPT_FAILED, DISK_LOW, DISK_OVER_LIMIT, URGENT_MAIL];
+
+/// A warning's `message` in the current language, for the board, the bot and the notifications list.
+pub fn localize(message: &str) -> String {
+    if i18n::lang() == i18n::Lang::En { return message.to_string(); }
+    MESSAGES.iter().find_map(|t| i18n::refill(t, message)).unwrap_or_else(|| message.to_string())
+}
+
+/// Something on the board that needs a human.
+#[derive(Serialize, ToSchema)]
+pub struct Warning {
+    /// stalled | over_budget | urgent_mail | failed | frozen | daemon | db | disk | telemetry | email
+    pub kind: &'static str,
+    pub target: String,
+    pub message: String,
+}
+
+/// Space taken by what agents leave behind, and what the disk has left.
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct DiskUsage {
+    /// `.logs/` without the artifacts.
+    pub logs_bytes: u64,
+    pub workers_bytes: u64,
+    /// `.logs/artifacts/`: worker snapshots and stored attachments.
+    pub artifacts_bytes: u64,
+    /// Free space on the workspace's disk; None when it can't be read.
+    pub free_bytes: Option<u64>,
+    /// When the directories were walked, in unix seconds.
+    #[serde(default)]
+    pub measured_at: i64,
+}
+
+impl DiskUsage {
+    pub fn used_bytes(&self) -> u64 { self.logs_bytes + self.workers_bytes + self.artifacts_bytes }
+}
+
+/// Walks the logs, workers and artifacts and keeps the sizes for `disk_usage`.
+pub fn measure_disk_usage(db: &Db, work_dir: &Path) -> Result<DiskUsage> {
+    let artifacts_bytes = dir_size(&artifacts::dir(work_dir));
+    let usage = DiskUsage {
+        logs_bytes: dir_size(&work_dir.join(".logs")).saturating_sub(artifacts_bytes),
+        workers_bytes: dir_size(&work_dir.join("workers")),
+        artifacts_bytes,
+        free_bytes: fs4::available_space(work_dir).ok(),
+        measured_at: chrono::Local::now().timestamp(),
+    };
+    db.set_setting(DISK_USAGE_KEY, &serde_json::to_string(&usage).unwrap_or_default())?;
+    Ok(usage)
+}
+
+/// The sizes from the monitor's last pass, measured now only when it has never run, with the
+/// free space read fresh. Boards and dashboards poll this, and walking every worker tree isn't free.
+pub fn disk_usage(db: &Db, work_dir: &Path) -> Result<DiskUsage> {
+    match db.get_setting(DISK_USAGE_KEY)?.and_then(|v| serde_json::from_str::<DiskUsage>(&v).ok()) {
+        Some(usage) => Ok(DiskUsage { free_bytes: fs4::available_space(work_dir).ok(), ..usage }),
+        None => measure_disk_usage(db, work_dir),
+    }
+}
+
+/// Total size of the files under `path`. Symlinks aren't followed.
+fn dir_size(path: &Path) -> u64 {
+    let Ok(entries) = fs::read_dir(path) else { return 0 };
+    entries.flatten().map(|e| match e.file_type() {
+        Ok(t) if t.is_dir() => dir_size(&e.path()),
+        Ok(t) if t.is_file() => e.metadata().map(|m| m.len()).unwrap_or(0),
+        _ => 0,
+    }).sum()
+}
+
+/// Bytes as MB with one decimal, e.g. "12.5 MB".
+pub fn format_mb(bytes: u64) -> String { format!("{:.1} MB", bytes as f64 / 1024.0 / 1024.0) }
+
+/// When the monitor last completed a pass.
+pub fn heartbeat(db: &Db) -> Result<Option<i64>> {
+    Ok(db.get_setting(HEARTBEAT_KEY)?.and_then(|v| v.parse().ok()))
+}
+
+/// Everything that currently needs intervention, most structural problems first.
+pub fn warnings(db: &Db, work_dir: &Path) -> Result<Vec<Warning>> {
+    let config = Config::load(work_dir)?;
+    let now = chrono::Local::now().timestamp();
+    let mut out = Vec::new();
+    let mut warn = |kind, target: &str, message: String| out.push(Warning { kind, target: target.to_string(), message });
+
+    let check: String = db.conn.query_row("PRAGMA quick_check", [], |row| row.get(0))?;
+    if check != "ok" { warn("db", "think.db", i18n::fill(DB_CHECK_FAILED, &[&check])); }
+    if let Some(reason) = db.frozen()? { warn("frozen", "workspace", i18n::fill(FROZEN, &[&reason])); }
+
+    let active: i64 = db.conn.query_row("SELECT COUNT(*) FROM tasks WHERE status = 'in_progress'", [], |row| row.get(0))?;
+    match heartbeat(db)? {
+        Some(beat) if now - beat > config.health.daemon_timeout_secs =>
+            warn("daemon", "monitor", i18n::fill(MONITOR_LATE, &[&period::format_span(now - beat)])),
+        None if active > 0 => warn("daemon", "monitor", MONITOR_NEVER_RAN.to_string()),
+        _ => {}
+    }
+    if let Some(err) = db.get_setting(crate::telemetry::ERROR_KEY)? {
+        warn("telemetry", &config.telemetry.sink, i18n::fill(TELEMETRY_FAILING, &[&err]));
+    }
+    if let Some(err) = db.get_setting(crate::email::ERROR_KEY)? {
+        warn("email", &config.email.smtp_host, i18n::fill(EMAIL_FAILING, &[&err]));
+    }
+
+    // Stalled: the session is gone, or its log hasn't moved for a while.
+    let backend = session::backend(work_dir)?;
+    let stall = config.health.stall_minutes * 60;
+    let mut stmt = db.conn.prepare(
+        "SELECT t.id, t.assignee, (SELECT MAX(a.started_at) FROM attempts a WHERE a.task_id = t.id AND a.agent_name = t.assignee)
+         FROM tasks t WHERE t.status = 'in_progress' AND t.assignee IS NOT NULL ORDER BY t.id")?;
+    let running = stmt.query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?, row.get::<_, Option<i64>>(2)?)))?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    for (task_id, agent, started) in running {
+        if !backend.exists(&Worker::session_name(&agent)) {
+            warn("stalled", &task_id, i18n::fill(NO_SESSION, &[&agent]));
+            continue;
+        }
+        let last = mtime(&Worker::log_path(work_dir, &task_id, &agent)).or(started);
+        if let Some(last) = last.filter(|l| stall > 0 && now - l > stall) {
+            warn("stalled", &task_id, i18n::fill(SILENT, &[&agent, &period::format_span(now - last)]));
+        }
+    }
+
+    let cap = config.budget.task_usd_cap;
+    if cap > 0.0 {
+        let mut stmt = db.conn.prepare(
+            "SELECT c.task_id, SUM(c.cost_usd) FROM costs c JOIN tasks t ON t.id = c.task_id
+             WHERE t.status != 'closed' GROUP BY c.task_id HAVING SUM(c.cost_usd) > ?1 ORDER BY c.task_id")?;
+        let over = stmt.query_map([cap], |row| Ok((row.get::<_, String>(0)?, row.get::<_, f64>(1)?)))?.collect::<rusqlite::Result<Vec<_>>>()?;
+        for (task_id, spent) in over { warn("over_budget", &task_id, i18n::fill(OVER_BUDGET, &[&spent, &cap])); }
+    }
+
+    // Unfinished tasks whose last worker was lost or aborted and nobody picked them up again.
+    let mut stmt = db.conn.prepare(
+        "SELECT a.task_id, a.agent_name, a.outcome FROM attempts a JOIN tasks t ON t.id = a.task_id
+         WHERE t.status != 'closed' AND a.outcome IN ('lost', 'aborted', 'failed')
+           AND a.id = (SELECT MAX(b.id) FROM attempts b WHERE b.task_id = a.task_id) ORDER BY a.task_id")?;
+    let failed = stmt.query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?, row.get::<_, String>(2)?)))?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    for (task_id, agent, outcome) in failed { warn("failed", &task_id, i18n::fill(ATTEMPT_FAILED, &[&agent, &outcome])); }
+
+    let min_free = config.health.min_free_mb * 1024 * 1024;
+    if let Some(free) = fs4::available_space(work_dir).ok().filter(|f| *f < min_free) {
+        warn("disk", "workspace", i18n::fill(DISK_LOW, &[&format_mb(free)]));
+    }
+    let max_usage = config.health.max_usage_mb * 1024 * 1024;
+    if max_usage > 0 {
+        let used = disk_usage(db, work_dir)?.used_bytes();
+        if used > max_usage {
+            warn("disk", "workspace", i18n::fill(DISK_OVER_LIMIT, &[&format_mb(used), &config.health.max_usage_mb]));
+        }
+    }
+
+    // Escalations from the monitor and anything flagged URGENT, still unread.
+    let mut stmt = db.conn.prepare(
+        "SELECT id, subject FROM messages WHERE status = 'unread' AND (sender = 'monitor' OR subject LIKE '%URGENT%') ORDER BY id")?;
+    let urgent = stmt.query_map([], |row| Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?)))?.collect::<rusqlite::Result<Vec<_>>>()?;
+    for (id, subject) in urgent { warn("urgent_mail", &id.to_string(), i18n::fill(URGENT_MAIL, &[&subject, &id])); }
+
+    Ok(out)
+}
+
+/// Modification time of a file in unix seconds.
+pub fn mtime(path: &Path) -> Option<i64> {
+    let modified = fs::metadata(path).and_then(|m| m.modified()).ok()?;
+    Some(modified.duration_since(UNIX_EPOCH).ok()?.as_secs() as i64)
+}
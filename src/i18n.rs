@@ -0,0 +1,471 @@
+use crate::error::{Result, ThinkTodoError};
+use std::fmt::Display;
+use std::sync::OnceLock;
+
+/// The language of CLI output and worker prompts, from `lang` in think.toml or `$TT_LANG`.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Lang {
+    En,
+    Zh,
+}
+
+pub const LANG_VAR: &str = "TT_LANG";
+
+static LANG: OnceLock<Lang> = OnceLock::new();
+
+pub fn parse(code: &str) -> Result<Lang> {
+    match code.to_lowercase().as_str() {
+        "" | "en" => Ok(Lang::En),
+        "zh" | "zh-cn" | "zh_cn" => Ok(Lang::Zh),
+        other => Err(ThinkTodoError::Config(format!("unknown lang '{}' (expected en or zh)", other))),
+    }
+}
+
+/// Picks the language once per run; later calls are ignored.
+pub fn init(lang: Lang) { let _ = LANG.set(lang); }
+
+pub fn lang() -> Lang { *LANG.get().unwrap_or(&Lang::En) }
+
+/// `msg` in the current language. Messages are keyed by their English text, gettext style, so
+/// anything missing from the catalog falls back to English.
+pub fn text(msg: &'static str) -> &'static str {
+    match lang() {
+        Lang::En => msg,
+        Lang::Zh => ZH.iter().find(|(en, _)| *en == msg).map_or(msg, |(_, zh)| zh),
+    }
+}
+
+/// Fills the `{}` placeholders of a catalog message in order. Width, alignment and precision
+/// (`{:<12}`, `{:>8}`, `{:.4}`) work as in `format!`; `{{` and `}}` are literal braces.
+pub fn fill(template: &str, args: &[&dyn Display]) -> String {
+    let mut out = String::with_capacity(template.len());
+    let mut args = args.iter();
+    let mut rest = template;
+    while let Some(at) = rest.find(['{', '}']) {
+        out.push_str(&rest[..at]);
+        let tail = &rest[at..];
+        if tail.starts_with("{{") || tail.starts_with("}}") {
+            out.push_str(&tail[..1]);
+            rest = &tail[2..];
+            continue;
+        }
+        let Some(end) = tail.find('}').filter(|_| tail.starts_with('{')) else {
+            out.push_str(&tail[..1]);
+            rest = &tail[1..];
+            continue;
+        };
+        let spec = tail[1..end].trim_start_matches(':');
+        if let Some(arg) = args.next() { out.push_str(&apply(spec, *arg)); }
+        rest = &tail[end + 1..];
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Translates `message`, which `fill` made from the English `template`: the arguments are picked
+/// out from between the template's literal pieces and put into its translation as they are.
+/// None when `message` wasn't made from `template`.
+pub fn refill(template: &'static str, message: &str) -> Option<String> {
+    let english = pieces(template);
+    let mut rest = message.strip_prefix(english[0])?;
+    let mut args = Vec::new();
+    for (i, piece) in english.iter().enumerate().skip(1) {
+        let at = if i + 1 == english.len() { rest.strip_suffix(piece)?.len() } else { rest.find(piece)? };
+        args.push(&rest[..at]);
+        rest = &rest[at + piece.len()..];
+    }
+    let translated = pieces(text(template));
+    if translated.len() != english.len() { return None; }
+    let mut out = translated[0].to_string();
+    for (arg, piece) in args.iter().zip(&translated[1..]) {
+        out.push_str(arg);
+        out.push_str(piece);
+    }
+    Some(out)
+}
+
+/// The literal text around a template's placeholders.
+fn pieces(template: &str) -> Vec<&str> {
+    let mut out = Vec::new();
+    let mut rest = template;
+    while let Some((at, end)) = rest.find('{').and_then(|at| rest[at..].find('}').map(|end| (at, at + end))) {
+        out.push(&rest[..at]);
+        rest = &rest[end + 1..];
+    }
+    out.push(rest);
+    out
+}
+
+fn apply(spec: &str, arg: &dyn Display) -> String {
+    let (align, spec) = match spec.chars().next() {
+        Some(c @ ('<' | '>')) => (Some(c), &spec[1..]),
+        _ => (None, spec),
+    };
+    let (width, precision) = match spec.split_once('.') {
+        Some((w, p)) => (w.parse::<usize>().ok(), p.parse::<usize>().ok()),
+        None => (spec.parse::<usize>().ok(), None),
+    };
+    let value = match precision {
+        Some(p) => format!("{:.*}", p, arg),
+        None => arg.to_string(),
+    };
+    // Pad by columns, not chars, so Chinese table headers line up with ASCII rows.
+    let pad = " ".repeat(width.unwrap_or(0).saturating_sub(columns(&value)));
+    if align == Some('>') { pad + &value } else { value + &pad }
+}
+
+/// Terminal columns `text` takes: CJK characters and emoji are two wide.
+pub fn columns(text: &str) -> usize {
+    text.chars().map(|c| match c as u32 {
+        0x1100..=0x115F | 0x2E80..=0x303E | 0x3041..=0x33FF | 0x3400..=0x4DBF | 0x4E00..=0x9FFF
+        | 0xAC00..=0xD7A3 | 0xF900..=0xFAFF | 0xFE30..=0xFE4F | 0xFF00..=0xFF60 | 0xFFE0..=0xFFE6 | 0x1F300..=0x1FAFF => 2,
+        _ => 1,
+    }).sum()
+}
+
+/// `format!` through the message catalog: `tr!("Task [{}] deleted.", id)`.
+macro_rules! tr {
+    ($msg:literal $(, $arg:expr)* $(,)?) => {
+        $crate::i18n::fill($crate::i18n::text($msg), &[$(&$arg as &dyn std::fmt::Display),*])
+    };
+}
+pub(crate) use tr;
+
+/// English message -> Chinese.
+const ZH: &[(&str, &str)] = &[
+    ("Admin already running.", "Admin 已在运行。"),
+    ("🚀 Think Todo Admin is online!", "🚀 Think Todo Admin 已上线！"),
+    ("This chat ({}) may not control tt. Add it to [telegram] chats in think.toml.", "此会话（{}）无权控制 tt。请把它加入 think.toml 的 [telegram] chats。"),
+    ("🔥 Unfrozen. Dispatch is enabled again.", "🔥 已解冻，派发恢复。"),
+    ("Workspace is not frozen.", "工作区未冻结。"),
+    ("frozen by {}", "由 {} 冻结"),
+    ("🧊 Already frozen: {}", "🧊 已处于冻结状态：{}"),
+    ("🧊 Frozen: {}. No agent will be started until /approve.", "🧊 已冻结：{}。在 /approve 之前不会启动任何代理。"),
+    ("🔔 Nudged {}.", "🔔 已提醒 {}。"),
+    ("📬 {} has no live session; the nudge went to its inbox.", "📬 {} 没有活动会话，提醒已发到其收件箱。"),
+    ("✅ {} closed.", "✅ {} 已关闭。"),
+    (" ⚠️ {} file(s) conflict with the rig.", " ⚠️ {} 个文件与 rig 冲突。"),
+    ("🧊 Frozen: {}\n", "🧊 已冻结：{}\n"),
+    ("▶️ Dispatching\n", "▶️ 正常派发中\n"),
+    ("No warnings.", "没有警告。"),
+    ("No tasks.", "没有任务。"),
+    ("unassigned", "未指派"),
+    ("Approved.", "已批准。"),
+    ("Approved. {}", "已批准。{}"),
+    ("👍 Approved #{} ({}); {} was told.", "👍 已批准 #{}（{}），已通知 {}。"),
+    ("Run `tt agent checkin` within {}s of starting or the launch counts as failed.", "启动后 {} 秒内运行 `tt agent checkin`，否则视为启动失败。"),
+    ("Your task's log is cut back past {} MB; don't rely on old output staying in it.", "任务日志超过 {} MB 会被截断，不要依赖旧输出一直保留。"),
+    ("Stay inside your worker directory.", "只在你的 worker 目录内工作。"),
+    ("your agent name", "你的代理名"),
+    ("your task", "你的任务"),
+    ("the workspace; tt commands run from your worker directory find it through this", "工作区；在 worker 目录中运行的 tt 命令通过它找到工作区"),
+    ("## TT CONVENTIONS\n\n", "## TT 约定\n\n"),
+    ("### Identity\n{}\n", "### 身份\n{}\n"),
+    ("### Markers\nPrint these at the start of a line:\n{}\n", "### 标记\n在行首输出以下标记：\n{}\n"),
+    ("### Commands\n{}\n", "### 命令\n{}\n"),
+    ("### Limits\n", "### 限制\n"),
+    ("Since {}\n", "自 {} 起\n"),
+    ("Completed ({})", "已完成（{}）"),
+    ("Spend: ${:.2} over {} task(s)", "花费：${:.2}，涉及 {} 个任务"),
+    ("Failures ({})", "失败（{}）"),
+    ("  Dispatch is frozen: {} (tt unfreeze)", "  派发已冻结：{}（tt unfreeze）"),
+    ("  #{} from {}: {} (tt mail read {})", "  #{} 来自 {}：{}（tt mail read {}）"),
+    ("Awaiting you ({})", "待你处理（{}）"),
+    ("Due by tomorrow ({})", "明天前到期（{}）"),
+    ("Daily digest {}", "每日摘要 {}"),
+    ("🛰️  Think-Todo gRPC is listening on: {}", "🛰️  Think-Todo gRPC 正在监听：{}"),
+    ("integrity check failed: {}", "完整性检查失败：{}"),
+    ("dispatch frozen: {} (tt unfreeze)", "派发已冻结：{}（tt unfreeze）"),
+    ("monitor last seen {} ago (tt monitor start)", "monitor 上次活动在 {} 前（tt monitor start）"),
+    ("telemetry sink unreachable, shipments retried: {}", "遥测目标不可达，将重试推送：{}"),
+    ("email bridge failing, retried each pass: {}", "邮件桥接失败，每轮重试：{}"),
+    ("'{}' has no live session (tt recover)", "'{}' 没有活动会话（tt recover）"),
+    ("'{}' has been silent for {}", "'{}' 已沉默 {}"),
+    ("spent ${:.2} of its ${:.2} cap", "已花费 ${:.2}，上限 ${:.2}"),
+    ("last attempt by '{}' was {}", "'{}' 的上次尝试结果为 {}"),
+    ("only {} free on the workspace's disk", "工作区磁盘仅剩 {}"),
+    ("logs, workers and artifacts take {} (limit {} MB; tt gc, tt logs archive)", "日志、worker 与产物占用 {}（上限 {} MB；tt gc、tt logs archive）"),
+    ("unread: {} (tt mail read {})", "未读：{}（tt mail read {}）"),
+    ("🧪 DRY RUN: nothing was removed.", "🧪 演练：未删除任何内容。"),
+    ("Nothing to remove.", "没有需要删除的内容。"),
+    ("Session:   {}", "会话：     {}"),
+    ("Directory: {}", "目录：     {}"),
+    ("Record:    {}", "记录：     {}"),
+    ("🧪 SANDBOX: working on {} (tt sandbox reset starts over)", "🧪 沙盒：在 {} 中运行（tt sandbox reset 重新开始）"),
+    ("No named admins; hq-admin handles every task.", "没有具名 admin；hq-admin 负责所有任务。"),
+    ("{:<16} {:<8} scope {} (mail: admin-{})", "{:<16} {:<8} 范围 {}（邮件：admin-{}）"),
+    ("🧹 '{}' had no session or directory left.", "🧹 '{}' 已没有会话或目录。"),
+    ("🧹 Worker '{}' nuked.", "🧹 Worker '{}' 已清除。"),
+    ("♻️  '{}' restarted on {}.", "♻️  '{}' 已在 {} 上重启。"),
+    ("👋 {} checked in on [{}].", "👋 {} 已在 [{}] 上报到。"),
+    ("AGENTS:", "代理："),
+    ("tasks", "任务"),
+    ("agents", "代理"),
+    ("'{}' matches {} {}:", "'{}' 匹配到 {} 个{}："),
+    ("Task to start", "要开始的任务"),
+    ("Task to close", "要关闭的任务"),
+    ("Agent", "代理"),
+    ("+ new agent", "+ 新代理"),
+    ("New agent name", "新代理名"),
+    ("- {} [{}] {} · {} · spawned {} · {}", "- {} [{}] {} · {} · 启动于 {} · {}"),
+    ("✅ Task [{}] registered.", "✅ 任务 [{}] 已登记。"),
+    ("✏️  Task [{}] title updated.", "✏️  任务 [{}] 标题已更新。"),
+    ("💡 Re-run with --force-title-update to replace its title, or use the next free id: {}", "💡 加 --force-title-update 重新运行以替换标题，或使用下一个可用 id：{}"),
+    ("✏️  Task [{}] updated.", "✏️  任务 [{}] 已更新。"),
+    ("🏷️  Tagged {} task(s) with {}: {}", "🏷️  已为 {} 个任务加上标签 {}：{}"),
+    ("No unfinished task matches the filter.", "没有符合条件的未完成任务。"),
+    ("No agent is working on a task that matches the filter.", "没有代理在处理符合条件的任务。"),
+    ("THINK TODO BACKLOG:", "THINK TODO 待办："),
+    (" ⚠️ OVERDUE since {}", " ⚠️ 已于 {} 逾期"),
+    (" 📅 due {}", " 📅 {} 到期"),
+    ("🗑️  Task [{}] deleted.", "🗑️  任务 [{}] 已删除。"),
+    ("   assignee: {}  engine: {}  rig: {}", "   负责人：{}  引擎：{}  rig：{}"),
+    ("   delegated: {}", "   委派链：{}"),
+    ("   attempts: {} (instructions: --prompt)", "   尝试：{}（查看指令：--prompt）"),
+    ("No worker has been launched for '{}' yet.", "'{}' 还没有启动过 worker。"),
+    ("=== SPAWN #{} · {} · {} · {} ===\n{}\n", "=== 启动 #{} · {} · {} · {} ===\n{}\n"),
+    ("🐒 Chaos mode: workers will be killed and frozen at random ([chaos] in think.toml).", "🐒 混沌模式：worker 会被随机杀掉或冻结（think.toml 中的 [chaos]）。"),
+    ("✅ Monitor pass complete.", "✅ Monitor 检查完成。"),
+    ("📬 MAIL INBOX:", "📬 收件箱："),
+    ("{} [{}] From: {} | Subject: {}", "{} [{}] 发件人：{} | 主题：{}"),
+    ("🚀 Mail sent to {}.", "🚀 邮件已发送给 {}。"),
+    ("nobody (the team is empty)", "无人（团队为空）"),
+    ("--- MAIL MESSAGE ---", "--- 邮件 ---"),
+    ("From: {}", "发件人：{}"),
+    ("Subject: {}", "主题：{}"),
+    ("Date: {}", "日期：{}"),
+    ("📎 {} ({} bytes, artifact {})", "📎 {}（{} 字节，产物 {}）"),
+    ("💾 Saved {}", "💾 已保存 {}"),
+    ("❌ Message not found.", "❌ 找不到该邮件。"),
+    ("🏗️ REGISTERED RIGS:", "🏗️ 已登记的 RIG："),
+    ("✅ Rig '{}' added.", "✅ Rig '{}' 已添加。"),
+    ("🗑️  Rig '{}' unregistered; its files were left in place.", "🗑️  Rig '{}' 已注销，文件保留在原处。"),
+    ("RIG STATUS: {}", "RIG 状态：{}"),
+    ("Path: {}", "路径：{}"),
+    ("Repo: {}", "仓库：{}"),
+    ("Status: {}", "状态：{}"),
+    ("Last Sync: {}", "上次同步：{}"),
+    ("❌ Rig not found.", "❌ 找不到该 rig。"),
+    ("💠 THINK-TODO BOARD (SYSTEM PULSE)", "💠 THINK-TODO 看板（系统脉搏）"),
+    ("  [TASKS] Progress: [{:<20}] {:.1}%", "  [任务] 进度：[{:<20}] {:.1}%"),
+    ("          Total: {} | ⏳ Open: {} | 🚀 Active: {} | ✅ Done: {}", "          总计：{} | ⏳ 待办：{} | 🚀 进行中：{} | ✅ 完成：{}"),
+    ("  [WARNINGS] {} item(s) need attention:", "  [警告] {} 项需要处理："),
+    ("  [FRONTLINE] Active Workers:", "  [前线] 活跃 worker："),
+    ("  → Agent '{}' is working on '{}'", "  → 代理 '{}' 正在处理 '{}'"),
+    ("  (No active workers currently)", "  （当前没有活跃 worker）"),
+    ("  [OVERDUE]", "  [逾期]"),
+    ("  ⚠️  '{}' {} — due {} ({})", "  ⚠️  '{}' {} — {} 到期（{}）"),
+    ("  [RECENT TRAIL]", "  [最近动态]"),
+    ("  [ECONOMY] Total System Cost: ${:.4} | Today: ${:.4}", "  [花费] 系统总花费：${:.4} | 今日：${:.4}"),
+    ("  [DISK] Logs: {} | Workers: {} | Artifacts: {} | Free: {}", "  [磁盘] 日志：{} | Worker：{} | 产物：{} | 可用：{}"),
+    ("  🧊 FROZEN: {} (tt unfreeze)", "  🧊 已冻结：{}（tt unfreeze）"),
+    ("No logs for task '{}'{}.", "任务 '{}'{} 没有日志。"),
+    (" from '{}'", "（来自 '{}'）"),
+    ("Nothing to archive.", "没有需要归档的内容。"),
+    ("🗜️  Archived logs of '{}'", "🗜️  已归档 '{}' 的日志"),
+    ("📅 Calendar written to {}", "📅 日历已写入 {}"),
+    ("📈 ENGINE EFFICIENCY:", "📈 引擎效率："),
+    ("No attempts recorded yet.", "还没有尝试记录。"),
+    ("ENGINE", "引擎"),
+    ("MODEL", "模型"),
+    ("RUNS", "次数"),
+    ("DONE", "完成"),
+    ("SUCCESS", "成功率"),
+    ("AVG COST", "平均花费"),
+    ("TOTAL", "合计"),
+    ("🌊 FLOW:", "🌊 流量："),
+    ("DAY", "日期"),
+    ("CREATED", "新建"),
+    ("CLOSED", "关闭"),
+    ("WIP", "进行中"),
+    ("Total: {} created, {} closed", "总计：新建 {}，关闭 {}"),
+    ("Median wait (open → in_progress):    {}", "等待中位数（open → in_progress）：   {}"),
+    ("Median cycle (in_progress → closed): {}", "周期中位数（in_progress → closed）： {}"),
+    ("💸 DETAILED COSTS:", "💸 花费明细："),
+    ("TASK", "任务"),
+    ("AGENT", "代理"),
+    ("IN", "输入"),
+    ("OUT", "输出"),
+    ("COST($)", "花费($)"),
+    ("TOTAL ({} of {} rows shown)", "合计（显示 {}/{} 行）"),
+    ("TOTAL ({} rows)", "合计（{} 行）"),
+    ("📊 COST SUMMARY BY MODEL:", "📊 按模型汇总花费："),
+    ("- {}: {} in / {} out | Total: ${:.4}", "- {}：输入 {} / 输出 {} | 合计：${:.4}"),
+    ("✅ Cost entry added for task {}.", "✅ 已为任务 {} 添加花费记录。"),
+    ("🎯 START: Dispatching task '{}' to agent '{}' using engine '{}'...", "🎯 开始：把任务 '{}' 派给代理 '{}'，引擎 '{}'..."),
+    ("🚀 Agent '{}' is now on the hook for '{}'.", "🚀 代理 '{}' 现在负责 '{}'。"),
+    ("🤝 HANDOFF: Initiating session transfer...", "🤝 交接：开始转移会话..."),
+    ("[HINT] Current session context saved. Run 'tt sling' with a new agent name to resume.", "[提示] 当前会话上下文已保存。用新代理名运行 'tt sling' 继续。"),
+    ("🔍 HANDOFF STATUS: No pending transfers.", "🔍 交接状态：没有待处理的转移。"),
+    ("🏁 DONE: Closing task '{}'...", "🏁 完成：正在关闭任务 '{}'..."),
+    ("⚠️  {} will conflict with {} in: {} (see tt merge {} --resolve)", "⚠️  {} 与 {} 将在以下文件冲突：{}（见 tt merge {} --resolve）"),
+    ("🧹 Cleaned up worker '{}'.", "🧹 已清理 worker '{}'。"),
+    ("🗜️  Logs archived to {}", "🗜️  日志已归档到 {}"),
+    ("✅ Task '{}' is now marked as DONE and cleaned up.", "✅ 任务 '{}' 已标记为完成并清理。"),
+    ("↩️  Task '{}' reopened.", "↩️  任务 '{}' 已重新打开。"),
+    ("📦 Work restored from {}", "📦 已从 {} 恢复工作"),
+    ("💡 Resume with: tt start {} {} --engine {}", "💡 继续：tt start {} {} --engine {}"),
+    ("👀 PEEK: Viewing recent activity for agent '{}'...", "👀 查看：代理 '{}' 的最近活动..."),
+    ("--- LOG TAIL {} (last {} lines) ---", "--- 日志末尾 {}（最后 {} 行）---"),
+    ("--- SESSION SCREEN (no log file) ---", "--- 会话屏幕（无日志文件）---"),
+    ("❌ No log and no live session for agent '{}'.", "❌ 代理 '{}' 既没有日志也没有活动会话。"),
+    ("🎞️  REPLAY: {} decision(s) since {}", "🎞️  回放：自 {} 起的 {} 个决策"),
+    ("   because {}", "   原因：{}"),
+    ("📦 {:<11} {:>6} row(s) -> {}{}", "📦 {:<11} {:>6} 行 -> {}{}"),
+    (" (full)", "（全量）"),
+    ("📦 {:<11}      - nothing new", "📦 {:<11}      - 没有新数据"),
+    ("📨 Sent by {}.", "📨 已通过 {} 发送。"),
+    ("nothing ([daily] has no channel)", "无（[daily] 未配置渠道）"),
+    ("🏁 Bench run {} ({} x {}):", "🏁 基准测试 {}（{} x {}）："),
+    ("  {:<10} {:<8} dispatched as {}", "  {:<10} {:<8} 已派发为 {}"),
+    ("  {:<10} {:<8} not started: {}", "  {:<10} {:<8} 未启动：{}"),
+    ("The monitor scores each case when it finishes; see tt bench results.", "每个用例完成后由 monitor 评分；见 tt bench results。"),
+    ("No bench runs yet (tt bench run --suite basic).", "还没有基准测试（tt bench run --suite basic）。"),
+    ("🏁 BENCH RUN {} ({}):", "🏁 基准测试 {}（{}）："),
+    ("CASE", "用例"),
+    ("OUTCOME", "结果"),
+    ("TIME", "耗时"),
+    ("COST", "花费"),
+    ("DETAIL", "详情"),
+    ("\n📊 ALL RUNS OF {}:", "\n📊 {} 的全部测试："),
+    ("CASES", "用例数"),
+    ("PASSED", "通过"),
+    ("RATE", "通过率"),
+    ("AVG PASS", "平均通过耗时"),
+    ("Every {}s: tt {}    {}", "每 {} 秒：tt {}    {}"),
+    ("    took {} and ${:.2}", "    耗时 {}，花费 ${:.2}"),
+    ("    {} and ${:.2} in so far", "    至今已用 {}，花费 ${:.2}"),
+    ("   took {} and ${:.2}", "   耗时 {}，花费 ${:.2}"),
+    ("🔕 Nothing needs your attention.", "🔕 没有需要你处理的事项。"),
+    ("✅ {} notification(s) marked read.", "✅ 已将 {} 条通知标为已读。"),
+    ("🔍 Nothing matches '{}'.", "🔍 没有与 '{}' 匹配的内容。"),
+    ("📋 Tasks ({})", "📋 任务 ({})"),
+    ("📬 Mail ({})", "📬 邮件 ({})"),
+    ("🛤️ Audit ({})", "🛤️ 审计 ({})"),
+    ("👤 '{}' can sign in to the web console with this token (shown once):", "👤 '{}' 可用此 token 登录 Web 控制台（仅显示一次）："),
+    ("No web users: web actions are recorded as 'web'. Add one with: tt user add <name>", "尚无 Web 用户：Web 操作记为 'web'。添加：tt user add <名称>"),
+    ("added {}", "添加于 {}"),
+    ("🗑️  User '{}' removed; their token no longer works.", "🗑️  已删除用户 '{}'，其 token 随即失效。"),
+    ("🗄️  Task [{}] archived.", "🗄️  任务 [{}] 已归档。"),
+    ("📤 Task [{}] unarchived.", "📤 任务 [{}] 已取消归档。"),
+    ("{}, archived", "{}，已归档"),
+    ("⏳ OPEN TASKS BY AGE:", "⏳ 按等待时长的待办任务："),
+    ("🔇 IN PROGRESS, QUIET FOR:", "🔇 进行中任务的静默时长："),
+    ("Nothing in progress.", "没有进行中的任务。"),
+    ("⚠️  Not added: it looks like a duplicate of", "⚠️  未添加：它看起来与以下任务重复"),
+    ("💡 Re-run with --force to add it anyway.", "💡 加上 --force 重新运行即可强制添加。"),
+    ("🧹 Grooming the backlog as {} on {}.", "🧹 正在由 {}（{}）整理 backlog。"),
+    ("When it is done, review with tt groom show and apply with tt groom apply.", "完成后用 tt groom show 审阅，用 tt groom apply 应用。"),
+    ("🧹 GROOMING {} ({}):", "🧹 整理建议 {}（{}）："),
+    ("{} change(s); apply with tt groom apply --run {}", "共 {} 项修改；用 tt groom apply --run {} 应用"),
+    ("Would apply: {}", "将应用：{}"),
+    ("🧹 Applied {} change(s) from {}.", "🧹 已应用 {} 项修改（来自 {}）。"),
+    ("   depends on: {}", "   依赖：{}"),
+    ("🧠 Asking the planner about {}...", "🧠 正在请规划引擎拆解 {}……"),
+    ("🗂️  PROPOSED TASKS ({}):", "🗂️  拟添加的任务（{}）："),
+    ("    after: {}", "    在其后：{}"),
+    ("    ⚠️  like [{}] {}", "    ⚠️  与 [{}] {} 相似"),
+    ("Nothing added; re-run with --yes to add them.", "未添加任何任务；加上 --yes 重新运行即可添加。"),
+    ("Add these {} task(s)?", "添加这 {} 个任务？"),
+    ("✅ Task [{}] registered ({}).", "✅ 任务 [{}] 已登记（{}）。"),
+    ("   estimate: {}", "   估计：{}"),
+    ("⏱️  Took {} against an estimate of {} ({:.1}x).", "⏱️  实际用时 {}，估计 {}（{:.1} 倍）。"),
+    ("⏱️  Took {} and ${:.2} for {}.", "⏱️  实际用时 {}，花费 ${:.2}，估计 {}。"),
+    ("🎯 ESTIMATES VS ACTUALS:", "🎯 估计与实际："),
+    ("No estimated task closed yet (tt task add --estimate).", "还没有已完成且带估计的任务（tt task add --estimate）。"),
+    ("TASKS", "任务"),
+    ("MEDIAN", "中位数"),
+    ("ON TARGET", "达标"),
+    ("POINTS", "点数"),
+    ("TIME/PT", "每点用时"),
+    ("COST/PT", "每点花费"),
+    ("MEDIAN is actual time over estimated time; ON TARGET counts tasks within {}-{}% of their estimate.", "中位数为实际用时与估计用时之比；达标为实际用时在估计的 {}-{}% 之内的任务数。"),
+    ("📅 BY WEEK:", "📅 按周："),
+    ("{}  {} task(s)  {}", "{}  {} 个任务  {}"),
+    ("→ resolved {} to {}", "→ 已将 {} 解析为 {}"),
+    ("Go ahead with {}?", "确定对 {} 执行吗？"),
+    ("🛤️ TRAIL: Recent System Activity", "🛤️ 轨迹：最近的系统活动"),
+    ("[{}] {} -> {} on {} ({})", "[{}] {} -> {}，对象 {}（{}）"),
+    ("🔔 NUDGING agent '{}' with message: {}", "🔔 提醒代理 '{}'：{}"),
+    ("✅ Message displayed in agent's session.", "✅ 消息已显示在代理会话中。"),
+    ("❌ Agent '{}' has no session that can display messages. Sent to its inbox instead.", "❌ 代理 '{}' 没有可显示消息的会话，已改发到其收件箱。"),
+    ("🔍 DIFF: {} vs {} (rig '{}')", "🔍 差异：{} 对比 {}（rig '{}'）"),
+    ("No commits on {} yet.", "{} 上还没有提交。"),
+    ("\n📝 Uncommitted in worker tree:", "\n📝 worker 工作树中未提交的改动："),
+    ("   + {} (untracked)", "   + {}（未跟踪）"),
+    ("🔀 MERGE: {} → {} (rig '{}', {} commit(s) ahead)", "🔀 合并：{} → {}（rig '{}'，领先 {} 个提交）"),
+    ("❌ Trial merge conflicts in {} file(s):", "❌ 试合并有 {} 个文件冲突："),
+    ("💡 Re-run with --resolve to hand the conflicts to a resolver worker.", "💡 加 --resolve 重新运行，把冲突交给解决冲突的 worker。"),
+    ("✅ Merges cleanly.{}", "✅ 可以干净合并。{}"),
+    (" Nothing to merge.", "没有需要合并的内容。"),
+    ("✅ Merged {} into {}.", "✅ 已把 {} 合并到 {}。"),
+    ("🗓️  PLAN: {} task(s) left ({} open, {} in progress), {} until {}", "🗓️  计划：剩余 {} 个任务（{} 待办，{} 进行中），距 {} 还有 {}"),
+    ("   Budget: ${:.2}", "   预算：${:.2}"),
+    ("Not enough history: no attempt has finished with tt done yet.", "历史不足：还没有以 tt done 结束的尝试。"),
+    ("TASK TIME", "单任务耗时"),
+    ("COST/TASK", "单任务花费"),
+    ("WORKERS", "worker 数"),
+    ("TOTAL COST", "总花费"),
+    ("BUDGET", "预算"),
+    ("✅ Recommended: {}{} with max_workers = {}", "✅ 推荐：{}{}，max_workers = {}"),
+    ("❌ No engine clears the backlog within budget; move the date or raise the budget.", "❌ 没有引擎能在预算内清完待办；请推迟日期或提高预算。"),
+    ("🧊 Workspace frozen: {}. No agent will be started until 'tt unfreeze'.", "🧊 工作区已冻结：{}。在 'tt unfreeze' 之前不会启动任何代理。"),
+    ("🔥 Workspace unfrozen. Dispatch is enabled again.", "🔥 工作区已解冻，派发恢复。"),
+    ("📜 Conventions written to {}", "📜 约定已写入 {}"),
+    ("👥 Team '{}': {} member(s){}{}", "👥 团队 '{}'：{} 名成员{}{}"),
+    (", led by {}", "，负责人 {}"),
+    (", scope {}", "，范围 {}"),
+    ("No teams. Create one with: tt team create infra --members a,b --lead admin-infra", "没有团队。创建：tt team create infra --members a,b --lead admin-infra"),
+    ("👥 {} (lead: {}, scope: {})", "👥 {}（负责人：{}，范围：{}）"),
+    ("   members: {}", "   成员：{}"),
+    ("   {} in progress · {} closed · ${:.4}", "   进行中 {} · 已关闭 {} · ${:.4}"),
+    ("🗑️  Team '{}' deleted.", "🗑️  团队 '{}' 已删除。"),
+    ("🧹 Sandbox discarded.", "🧹 沙盒已丢弃。"),
+    ("No sandbox to discard.", "没有可丢弃的沙盒。"),
+    ("🔭 Scope '{}' = {}", "🔭 范围 '{}' = {}"),
+    ("No saved scopes. Add one with: tt scope add infra tag:infra", "没有保存的范围。添加：tt scope add infra tag:infra"),
+    ("🗑️  Scope '{}' removed.", "🗑️  范围 '{}' 已删除。"),
+    ("🩺 RECOVER: Scanning for orphaned tasks (mode: {})...", "🩺 恢复：扫描孤立任务（模式：{}）..."),
+    ("✅ All in_progress tasks have a live session.", "✅ 所有进行中的任务都有活动会话。"),
+    ("✅ Recovered {} task(s).", "✅ 已恢复 {} 个任务。"),
+    ("🧹 Removed {}", "🧹 已删除 {}"),
+    ("✅ {} idle worker(s) cleaned up.", "✅ 已清理 {} 个空闲 worker。"),
+    ("🧊 Dispatch is frozen ({}); reopening orphaned tasks instead of respawning them.", "🧊 派发已冻结（{}），孤立任务改为重新打开而不是重新启动。"),
+    ("🔁 Respawned '{}' for task '{}'.", "🔁 已重新启动 '{}'，任务 '{}'。"),
+    ("↩️  Task '{}' moved back to open.", "↩️  任务 '{}' 已退回待办。"),
+    ("🌐 Think-Todo WebUI is running at: http://localhost:{}{}/", "🌐 Think-Todo WebUI 运行于：http://localhost:{}{}/"),
+    ("🔒 Think-Todo WebUI is running at: https://localhost:{}{}/", "🔒 Think-Todo WebUI 运行于：https://localhost:{}{}/"),
+    ("🧪 SANDBOX: would start session '{}' in {}", "🧪 沙盒：将启动会话 '{}'，目录 {}"),
+    ("🧪 SANDBOX: would kill session '{}'", "🧪 沙盒：将杀掉会话 '{}'"),
+    ("🧪 SANDBOX: would show in '{}': {}", "🧪 沙盒：将在 '{}' 中显示：{}"),
+    ("🧪 SANDBOX: would attach to '{}'", "🧪 沙盒：将连接到 '{}'"),
+    ("🧪 SANDBOX: would {} session '{}'", "🧪 沙盒：将{}会话 '{}'"),
+    ("pause", "暂停"),
+    ("resume", "恢复"),
+    ("## RIG CONVENTIONS ({})\n\n{}", "## RIG 约定（{}）\n\n{}"),
+    ("You are a specialized agent.", "你是一名专职代理。"),
+    ("\n\nFIRST ACTION: run `tt agent checkin` so the orchestrator knows you are up.\n\nEXECUTE NOW.", "\n\n第一步：运行 `tt agent checkin`，让编排器知道你已就绪。\n\n立即执行。"),
+    ("🧪 DRY RUN: nothing was launched.", "🧪 演练：未启动任何内容。"),
+    ("Session:   {}{}", "会话：     {}{}"),
+    (" (already running)", "（已在运行）"),
+    ("Directory: {} (new worktree of rig '{}' on {})", "目录：     {}（rig '{}' 在 {} 上的新 worktree）"),
+    ("Log:       {}", "日志：     {}"),
+    ("Command:   {}", "命令：     {}"),
+    ("--- INSTRUCTION ---\n{}", "--- 指令 ---\n{}"),
+    ("♻️  Replacing existing session '{}'...", "♻️  正在替换已有会话 '{}'..."),
+    ("✅ Worker {} dispatched with engine {}!", "✅ Worker {} 已用引擎 {} 派出！"),
+    ("⚠️  '{}' is running but hasn't written anything yet; check it with tt peek {}", "⚠️  '{}' 正在运行但还没有任何输出；用 tt peek {} 查看"),
+    ("closes your task (same as `tt done`)", "关闭你的任务（等同 `tt done`）"),
+    ("mails the rest of the line to the admin as a question; keep working on what you can meanwhile", "把该行其余内容作为问题发给 admin；同时继续做你能做的部分"),
+    ("records the rest of the line as your task's result in the audit trail", "把该行其余内容作为任务结果记入审计轨迹"),
+    ("first thing after you start, and now and then as a heartbeat", "启动后立即运行，之后不时运行作为心跳"),
+    ("when the task is finished", "任务完成时"),
+    ("messages for you", "给你的消息"),
+    ("one message; --save-attachments copies its files", "读一封邮件；--save-attachments 复制其附件"),
+    ("reach the admin or another agent", "联系 admin 或其他代理"),
+    ("the whole backlog and what needs attention", "整个待办及需要关注的事项"),
+    ("another agent's latest output", "其他代理的最新输出"),
+    ("a task and its attempts", "任务及其尝试记录"),
+    ("/status - warnings and whether dispatch is frozen\n/beads - tasks by status, and who is on what\n/approve - lift the freeze so dispatch resumes\n/approve <mail id> [note] - answer an escalation with an approval\n/freeze [reason] - stop all new agent activity\n/nudge <agent> <message> - flash a message in the agent's session\n/done <task> - close a task and tear down its worker",
+     "/status - 警告与派发是否冻结\n/beads - 按状态统计任务，以及谁在做什么\n/approve - 解除冻结，恢复派发\n/approve <邮件 id> [备注] - 以批准答复一条升级\n/freeze [原因] - 停止所有新的代理活动\n/nudge <代理> <消息> - 在代理会话中闪现一条消息\n/done <任务> - 关闭任务并拆除其 worker"),
+];
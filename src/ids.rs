@@ -0,0 +1,36 @@
+use crate::error::{Result, ThinkTodoError};
+
+/// Task ids and agent names end up in file paths, session names and shell
+/// commands, so they are restricted to a charset that is safe in all three.
+pub const MAX_LEN: usize = 64;
+
+pub fn check(kind: &'static str, value: &str) -> Result<String> {
+    let ok = !value.is_empty()
+        && value.len() <= MAX_LEN
+        && value.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if ok { Ok(value.to_string()) } else { Err(ThinkTodoError::InvalidName { kind, value: value.to_string() }) }
+}
+
+/// clap value parser for task ids.
+pub fn task_id(value: &str) -> Result<String> { check("task id", value) }
+
+/// clap value parser for task tags.
+pub fn tag(value: &str) -> Result<String> { check("tag", value) }
+
+/// clap value parser for agent names.
+pub fn agent_name(value: &str) -> Result<String> { check("agent name", value) }
+
+/// clap value parser for mail receivers: an agent name, `@<team>` for every member of a team,
+/// or an email address (delivered by the `[email]` bridge).
+pub fn receiver(value: &str) -> Result<String> {
+    if is_address(value) { return Ok(value.to_string()); }
+    check("mail receiver", value.strip_prefix('@').unwrap_or(value))?;
+    Ok(value.to_string())
+}
+
+/// `user@example.com`: something before the `@` and a dotted domain after it.
+pub fn is_address(value: &str) -> bool {
+    let Some((user, domain)) = value.split_once('@') else { return false };
+    let safe = |s: &str, extra: &str| !s.is_empty() && s.chars().all(|c| c.is_ascii_alphanumeric() || extra.contains(c));
+    value.len() <= 254 && safe(user, "._%+-") && safe(domain, ".-") && domain.contains('.') && !domain.starts_with('.') && !domain.ends_with('.')
+}
@@ -0,0 +1,39 @@
+use crate::session;
+use std::path::{Path, PathBuf};
+use tracing_appender::rolling::{RollingFileAppender, Rotation};
+use tracing_subscriber::{fmt, layer::SubscriberExt, util::SubscriberInitExt, EnvFilter, Layer};
+
+/// Application log, one file per day: `.logs/tt/tt.<date>.log`.
+pub fn dir(work_dir: &Path) -> PathBuf { work_dir.join(".logs").join("tt") }
+
+/// Days of application logs kept before the oldest file is deleted.
+const KEEP_DAYS: usize = 14;
+
+/// Sends `tracing` events to the daily log file and, when `console` is set, to stderr as well.
+/// `TT_LOG` overrides the level (e.g. `TT_LOG=think_todo=trace`).
+pub fn init(work_dir: &Path, debug: bool, console: bool) {
+    let default = if debug { "think_todo=debug" } else { "think_todo=info" };
+    let filter = || EnvFilter::try_from_env("TT_LOG").unwrap_or_else(|_| EnvFilter::new(default));
+    // A workspace we can't write to still gets a working CLI, just without the log file.
+    // The directory must exist first: pruning old files prints an error when it doesn't.
+    let _ = std::fs::create_dir_all(dir(work_dir));
+    let file = RollingFileAppender::builder()
+        .rotation(Rotation::DAILY)
+        .filename_prefix("tt")
+        .filename_suffix("log")
+        .max_log_files(KEEP_DAYS)
+        .build(dir(work_dir))
+        .ok()
+        .map(|appender| fmt::layer().with_writer(appender).with_ansi(false).with_filter(filter()));
+    let console = console.then(|| fmt::layer().with_writer(std::io::stderr).with_target(false).with_filter(filter()));
+    let _ = tracing_subscriber::registry().with(file).with(console).try_init();
+}
+
+/// The engine command with the instruction (its last argument) elided: prompts can carry task
+/// details and pasted secrets that have no business in a long-lived log.
+pub fn sanitize_command(program: &str, args: &[String]) -> String {
+    match args.split_last() {
+        Some((prompt, flags)) => format!("{} \"<instruction: {} chars>\"", session::command_line(program, flags), prompt.chars().count()),
+        None => program.to_string(),
+    }
+}
@@ -0,0 +1,183 @@
+use crate::db::Db;
+use crate::error::Result;
+use crate::output::Progress;
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, UNIX_EPOCH};
+
+/// One agent's log for a task.
+pub struct AgentLog {
+    pub agent: String,
+    pub content: String,
+}
+
+/// Live logs, one directory per task: `.logs/tasks/<task_id>/<agent>.log`.
+pub fn tasks_dir(work_dir: &Path) -> PathBuf { work_dir.join(".logs").join("tasks") }
+/// Compressed logs of finished tasks: `.logs/archive/<task_id>.tar.gz`.
+pub fn archive_dir(work_dir: &Path) -> PathBuf { work_dir.join(".logs").join("archive") }
+
+/// Live `<agent>.log` files of a task, sorted by agent, optionally limited to one agent.
+fn live_logs(work_dir: &Path, task_id: &str, agent: Option<&str>) -> Vec<(String, PathBuf)> {
+    let Ok(entries) = fs::read_dir(tasks_dir(work_dir).join(task_id)) else { return Vec::new() };
+    let mut logs: Vec<(String, PathBuf)> = entries.flatten()
+        .filter_map(|e| {
+            let path = e.path();
+            let name = path.file_name()?.to_str()?.strip_suffix(".log")?.to_string();
+            Some((name, path))
+        })
+        .filter(|(name, _)| agent.is_none_or(|a| a == name))
+        .collect();
+    logs.sort();
+    logs
+}
+
+/// Every log of a task: the live ones, or the archived ones once the task has been archived.
+pub fn read_task_logs(work_dir: &Path, task_id: &str, agent: Option<&str>) -> Result<Vec<AgentLog>> {
+    let live = live_logs(work_dir, task_id, agent);
+    if !live.is_empty() {
+        return live.into_iter()
+            .map(|(agent, path)| Ok(AgentLog { agent, content: String::from_utf8_lossy(&fs::read(path)?).into_owned() }))
+            .collect();
+    }
+    let mut logs = Vec::new();
+    let dir = archive_dir(work_dir);
+    let mut archives: Vec<PathBuf> = fs::read_dir(&dir).map(|es| es.flatten().map(|e| e.path()).collect()).unwrap_or_default();
+    archives.retain(|p| p.file_name().and_then(|n| n.to_str()).is_some_and(|n| n == format!("{}.tar.gz", task_id) || n.starts_with(&format!("{}-", task_id))));
+    archives.sort();
+    for archive in archives {
+        let mut tar = tar::Archive::new(GzDecoder::new(File::open(&archive)?));
+        for entry in tar.entries()? {
+            let mut entry = entry?;
+            let path = entry.path()?.into_owned();
+            // Entries are "<task_id>/<agent>.log"; the prefix check keeps "T1-x" archives from matching T1.
+            if !path.starts_with(task_id) { continue; }
+            let Some(name) = path.file_name().and_then(|n| n.to_str()).and_then(|n| n.strip_suffix(".log")).map(str::to_string) else { continue };
+            if agent.is_some_and(|a| a != name) { continue; }
+            let mut bytes = Vec::new();
+            entry.read_to_end(&mut bytes)?;
+            logs.push(AgentLog { agent: name, content: String::from_utf8_lossy(&bytes).into_owned() });
+        }
+    }
+    Ok(logs)
+}
+
+/// Prints lines appended to a task's live logs (containing `grep`, if given) until interrupted.
+/// Logs created after the call starts are picked up too.
+pub fn follow(work_dir: &Path, task_id: &str, agent: Option<&str>, grep: Option<&str>, prefix: bool) -> Result<()> {
+    let mut offsets: HashMap<PathBuf, u64> = live_logs(work_dir, task_id, agent).into_iter()
+        .map(|(_, p)| { let len = fs::metadata(&p).map(|m| m.len()).unwrap_or(0); (p, len) })
+        .collect();
+    loop {
+        for (name, path) in live_logs(work_dir, task_id, agent) {
+            let offset = offsets.entry(path.clone()).or_insert(0);
+            let Ok(mut f) = File::open(&path) else { continue };
+            if f.metadata()?.len() < *offset { *offset = 0; } // truncated
+            f.seek(SeekFrom::Start(*offset))?;
+            let mut bytes = Vec::new();
+            f.read_to_end(&mut bytes)?;
+            // Only hand out complete lines; the rest is read again next round.
+            let Some(end) = bytes.iter().rposition(|&b| b == b'\n') else { continue };
+            *offset += end as u64 + 1;
+            for line in String::from_utf8_lossy(&bytes[..end]).lines() {
+                if grep.is_some_and(|g| !line.contains(g)) { continue; }
+                if prefix { println!("[{}] {}", name, line); } else { println!("{}", line); }
+            }
+        }
+        thread::sleep(Duration::from_millis(500));
+    }
+}
+
+/// Share of the quota kept when a task's logs are cut back, so a looping worker doesn't hit it
+/// again on the very next pass.
+const QUOTA_KEEP_DIVISOR: u64 = 4;
+
+/// The setting holding how far the monitor has read an agent's log for a task.
+pub fn read_offset_key(task_id: &str, agent: &str) -> String { format!("markers:{}:{}", task_id, agent) }
+
+/// Cuts a task's live logs back to their most recent lines once the directory grows past
+/// `max_bytes`. Writers append (`tee -a`), so they carry on at the new end, and the monitor's
+/// read offset moves with the kept lines so nothing it already acted on is read again. Returns
+/// the bytes dropped, or None while the task is within its quota.
+pub fn enforce_quota(db: &Db, work_dir: &Path, task_id: &str, max_bytes: u64) -> Result<Option<u64>> {
+    let logs = live_logs(work_dir, task_id, None);
+    let sizes: Vec<u64> = logs.iter().map(|(_, p)| fs::metadata(p).map(|m| m.len()).unwrap_or(0)).collect();
+    if max_bytes == 0 || sizes.iter().sum::<u64>() <= max_bytes { return Ok(None); }
+    let keep = max_bytes / QUOTA_KEEP_DIVISOR / logs.len() as u64;
+    let mut dropped = 0;
+    for ((agent, path), size) in logs.iter().zip(sizes) {
+        if size <= keep { continue; }
+        let mut f = File::open(path)?;
+        f.seek(SeekFrom::Start(size - keep))?;
+        let mut tail = Vec::new();
+        f.read_to_end(&mut tail)?;
+        // Start on a whole line.
+        let start = tail.iter().position(|&b| b == b'\n').map_or(tail.len(), |i| i + 1);
+        let tail = &tail[start..];
+        let cut = size - tail.len() as u64;
+        let header = format!("[tt] log over quota: {} earlier bytes dropped\n", cut);
+        let mut out = fs::OpenOptions::new().write(true).open(path)?;
+        out.set_len(0)?;
+        out.write_all(header.as_bytes())?;
+        out.write_all(tail)?;
+        dropped += cut;
+        // Lines cut before the monitor got to them are gone; it picks up after the header.
+        let key = read_offset_key(task_id, agent);
+        if let Some(offset) = db.get_setting(&key)?.and_then(|v| v.parse::<u64>().ok()) {
+            db.set_setting(&key, &(offset.saturating_sub(cut) + header.len() as u64).to_string())?;
+        }
+    }
+    Ok(Some(dropped))
+}
+
+/// Compresses a task's live log directory into the archive and removes it. Returns the archive path, or None without logs.
+pub fn archive_task(work_dir: &Path, task_id: &str) -> Result<Option<PathBuf>> {
+    let src = tasks_dir(work_dir).join(task_id);
+    if !src.is_dir() { return Ok(None); }
+    let dest_dir = archive_dir(work_dir);
+    fs::create_dir_all(&dest_dir)?;
+    let mut dest = dest_dir.join(format!("{}.tar.gz", task_id));
+    if dest.exists() {
+        // Reopened and finished again: keep the earlier archive too.
+        dest = dest_dir.join(format!("{}-{}.tar.gz", task_id, chrono::Local::now().format("%Y%m%d%H%M%S")));
+    }
+    let mut tar = tar::Builder::new(GzEncoder::new(File::create(&dest)?, Compression::default()));
+    tar.append_dir_all(task_id, &src)?;
+    tar.into_inner()?.finish()?;
+    fs::remove_dir_all(&src)?;
+    Ok(Some(dest))
+}
+
+/// Archives every task log directory untouched since `cutoff` (unix seconds), skipping tasks still in progress.
+pub fn archive_older_than(db: &Db, work_dir: &Path, cutoff: i64, progress: &Progress) -> Result<Vec<String>> {
+    let Ok(entries) = fs::read_dir(tasks_dir(work_dir)) else { return Ok(Vec::new()) };
+    let dirs: Vec<PathBuf> = entries.flatten().map(|e| e.path()).filter(|p| p.is_dir()).collect();
+    progress.set_len(dirs.len());
+    let mut archived = Vec::new();
+    for path in dirs {
+        let task_id = path.file_name().unwrap_or_default().to_string_lossy().to_string();
+        progress.start(&task_id);
+        let active = db.conn.query_row("SELECT COUNT(*) FROM tasks WHERE id = ?1 AND status = 'in_progress'", [&task_id], |row| row.get::<_, i64>(0))? > 0;
+        if !active && last_modified(&path) < cutoff && archive_task(work_dir, &task_id)?.is_some() { archived.push(task_id); }
+        progress.inc();
+    }
+    archived.sort();
+    Ok(archived)
+}
+
+/// When a task's live logs last changed; `None` before any worker has written one.
+pub fn last_activity(work_dir: &Path, task_id: &str) -> Option<i64> {
+    let dir = tasks_dir(work_dir).join(task_id);
+    if !dir.is_dir() { return None; }
+    Some(last_modified(&dir)).filter(|t| *t > 0)
+}
+
+/// Newest mtime (unix seconds) of the directory and the files directly inside it.
+fn last_modified(dir: &Path) -> i64 {
+    let mtime = |p: &Path| fs::metadata(p).and_then(|m| m.modified()).ok()
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok()).map(|d| d.as_secs() as i64).unwrap_or(0);
+    fs::read_dir(dir).map(|es| es.flatten().map(|e| mtime(&e.path())).max().unwrap_or(0)).unwrap_or(0).max(mtime(dir))
+}
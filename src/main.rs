@@ -4,11 +4,54 @@ mod worker;
 mod db;
 mod monitor;
 mod server;
+mod config;
+mod recover;
+mod session;
+mod error;
+mod ids;
+mod period;
+mod git;
+mod digest;
+mod merge;
+mod logs;
+mod calendar;
+mod stats;
+mod health;
+mod service;
+mod artifacts;
+mod usage;
+mod conventions;
+mod scope;
+mod sandbox;
+mod chaos;
+mod bench;
+mod groom;
+mod spec;
+mod export;
+mod telemetry;
+mod email;
+mod bot;
+mod daily;
+mod i18n;
+mod output;
+mod complete;
+mod resolve;
+mod watch;
+mod files;
+mod notify;
+mod search;
+mod status;
+mod replay;
+mod logging;
+mod remote;
+#[cfg(feature = "grpc")]
+mod grpc;
 
-use clap::{Parser, Subcommand};
+use clap::{CommandFactory, Parser, Subcommand};
+use crate::i18n::tr;
 use anyhow::Result;
 use std::env;
-use rusqlite::params;
+use rusqlite::{params, OptionalExtension};
 
 #[derive(Parser)]
 #[command(name = "tt")]
@@ -16,46 +59,449 @@ use rusqlite::params;
 struct Cli {
     #[command(subcommand)]
     command: Commands,
+    /// Log at debug level and echo the application log to stderr
     #[arg(long, global = true)]
     debug: bool,
+    /// Work on a copy of the workspace (.sandbox) and only print what would be launched, killed or shown
+    #[arg(long, global = true)]
+    sandbox: bool,
+    /// Narrow listings, dispatch and named admins to a saved scope (tt scope add) or an inline one like tag:infra
+    #[arg(long, global = true)]
+    scope: Option<String>,
+    /// Print timestamps in UTC instead of local time
+    #[arg(long, global = true, conflicts_with = "epoch")]
+    utc: bool,
+    /// Print timestamps as raw epoch seconds
+    #[arg(long, global = true)]
+    epoch: bool,
+    /// Don't draw progress bars
+    #[arg(long, short, global = true)]
+    quiet: bool,
+    /// Don't ask before acting: add planned tasks, or close, merge or remove what a shortened id stands for
+    #[arg(long, global = true)]
+    yes: bool,
 }
 
 #[derive(Subcommand)]
 enum Commands {
     Admin { #[command(subcommand)] action: AdminCommands },
     Worker { #[command(subcommand)] action: WorkerCommands },
+    Agent { #[command(subcommand)] action: AgentCommands },
     Task { #[command(subcommand)] action: TaskCommands },
     Monitor { #[command(subcommand)] action: MonitorCommands },
     Mail { #[command(subcommand)] action: MailCommands },
     Rig { #[command(subcommand)] action: RigCommands },
     Board { #[command(subcommand)] action: BoardCommands },
     Costs { #[command(subcommand)] action: CostsCommands },
-    Start { task_id: String, agent_name: String, #[arg(short, long, default_value = "gemini")] engine: String },
+    Stats { #[command(subcommand)] action: StatsCommands },
+    Schedule { #[command(subcommand)] action: ScheduleCommands },
+    /// Teams of agents: mail them with @<team>, list their tasks with --scope team:<team>
+    Team { #[command(subcommand)] action: TeamCommands },
+    /// Manage the --sandbox copy of the workspace
+    Sandbox { #[command(subcommand)] action: SandboxCommands },
+    /// Saved task filters for --scope and named admins
+    Scope { #[command(subcommand)] action: ScopeCommands },
+    /// People signing in to the web console, so what they do is audited under their name
+    User { #[command(subcommand)] action: UserCommands },
+    /// What needs your attention (escalations, failures, budget, stalls), kept until acknowledged
+    Notifications { #[command(subcommand)] action: NotificationsCommands },
+    /// Find tasks, mail and audit entries by words: tt search parser timeout
+    Search {
+        #[arg(required = true, num_args = 1..)] query: Vec<String>,
+        /// Most results shown per group
+        #[arg(long, default_value_t = 10)] limit: usize,
+    },
+    /// Read a task's logs (live or archived): tt logs <task_id> [--agent A] [--tail N] [--follow] [--grep P]
+    #[command(args_conflicts_with_subcommands = true)]
+    Logs {
+        #[command(subcommand)] action: Option<LogsCommands>,
+        #[arg(value_parser = ids::task_id, add = complete::tasks())] task_id: Option<String>,
+        /// Only this agent's log
+        #[arg(long, value_parser = ids::agent_name, add = complete::agents())] agent: Option<String>,
+        /// Only the last N (matching) lines of each log
+        #[arg(long)] tail: Option<usize>,
+        /// Keep printing new lines as they are written
+        #[arg(short, long)] follow: bool,
+        /// Only lines containing this text
+        #[arg(long)] grep: Option<String>,
+    },
+    /// Assign a task to an agent and spawn its worker session
+    #[command(alias = "sling")]
+    Start {
+        /// Picked from the open tasks when omitted in a terminal
+        #[arg(value_parser = ids::task_id, add = complete::tasks())] task_id: Option<String>,
+        /// Picked from the known agents (or typed) when omitted in a terminal
+        #[arg(value_parser = ids::agent_name, add = complete::agents())] agent_name: Option<String>,
+        #[arg(short, long, default_value = "gemini")] engine: String,
+        /// Kill and respawn the agent's session if one already exists
+        #[arg(long)] replace: bool,
+        /// Dispatch even if the task's rig has uncommitted changes or is behind upstream
+        #[arg(long)] allow_dirty: bool,
+        /// Print the composed instruction, command line, directory and session name without launching
+        #[arg(long)] dry_run: bool,
+        /// Append this mail's body (and its attachment list) to the worker's instruction
+        #[arg(long, conflicts_with = "brief_stdin")] brief_from_mail: Option<i64>,
+        /// Append text read from stdin to the worker's instruction
+        #[arg(long)] brief_stdin: bool,
+    },
     Handoff { #[command(subcommand)] action: HandoffCommands },
-    Done { task_id: String },
-    Peek { agent_name: String },
+    Done {
+        /// Picked from the unfinished tasks when omitted in a terminal
+        #[arg(value_parser = ids::task_id, add = complete::tasks())] task_id: Option<String>,
+        /// Close every unfinished task that matches, e.g. status=in_progress,tag=docs
+        #[arg(long, value_parser = scope::parse, conflicts_with = "task_id")] filter: Option<scope::Scope>,
+        /// List the session, directories and logs that would be removed, and change nothing
+        #[arg(long)] dry_run: bool,
+    },
+    /// Reopen a task closed by mistake, restoring its worker directory for the last assignee
+    Undone { #[arg(value_parser = ids::task_id, add = complete::tasks())] task_id: String },
+    /// List an agent's tasks and show the tail of one task's log (or its live screen)
+    Peek {
+        #[arg(value_parser = ids::agent_name, add = complete::agents())] agent_name: String,
+        /// Task to show when the agent has several
+        #[arg(long, value_parser = ids::task_id, add = complete::tasks())] task: Option<String>,
+        #[arg(short = 'n', long, default_value_t = 10)] lines: usize,
+    },
     Trail,
-    Nudge { agent_name: String, message: String },
-    Serve { #[arg(short, long, default_value_t = 3030)] port: u16 },
+    /// Rerun a read command on an interval and mark what changed: tt watch -- task list
+    Watch {
+        /// Time between runs, e.g. 5s or 1m
+        #[arg(short = 'n', long, default_value = "2s", value_parser = watch::interval)] interval: std::time::Duration,
+        /// Stop after this many runs
+        #[arg(long)] count: Option<u32>,
+        #[arg(required = true, trailing_var_arg = true, allow_hyphen_values = true)] command: Vec<String>,
+    },
+    /// Replay what the monitor and recovery decided, and why, from the inputs recorded with each decision
+    Replay {
+        /// e.g. 2h, 7d or 2024-07-01
+        #[arg(long, default_value = "24h", value_parser = period::parse_since)] since: i64,
+        /// Only decisions about this task, agent or session
+        #[arg(long)] target: Option<String>,
+    },
+    #[command(allow_missing_positional = true)]
+    Nudge {
+        #[arg(value_parser = ids::agent_name, add = complete::agents(), required_unless_present = "filter", conflicts_with = "filter")] agent_name: Option<String>,
+        message: String,
+        /// Nudge the agents working on every task that matches, e.g. rig=web or tag=docs,status=in_progress
+        #[arg(long, value_parser = scope::parse)] filter: Option<scope::Scope>,
+    },
+    /// Repeatable benchmark tasks per engine, scored by a witness check
+    Bench { #[command(subcommand)] action: BenchCommands },
+    /// Have a short-lived worker suggest merges, priorities, tags and dependencies for the backlog
+    #[command(args_conflicts_with_subcommands = true)]
+    Groom {
+        #[command(subcommand)] action: Option<GroomCommands>,
+        #[arg(short, long, default_value = "gemini")] engine: String,
+    },
+    /// Print the digest of what happened since the last one: closed tasks, spend, failures, what awaits you, what is due
+    Daily {
+        /// Also deliver it as [daily] says (tt mail, email, webhook)
+        #[arg(long)] send: bool,
+    },
+    /// Chat front-ends for checking on and steering tt away from the terminal
+    Bot { #[command(subcommand)] action: BotCommands },
+    Serve {
+        #[arg(short, long, default_value_t = 3030)] port: u16,
+        /// Serve under a path prefix (e.g. /tt) when sharing a host behind a reverse proxy
+        #[arg(long)] base_path: Option<String>,
+        /// PEM certificate chain; serves HTTPS together with --tls-key
+        #[arg(long, requires = "tls_key")] tls_cert: Option<std::path::PathBuf>,
+        /// PEM private key for --tls-cert
+        #[arg(long, requires = "tls_cert")] tls_key: Option<std::path::PathBuf>,
+        /// Plain HTTP port that redirects to HTTPS when TLS is on; 0 disables it
+        #[arg(long, default_value_t = 3080)] http_port: u16,
+        /// Also serve the gRPC interface on this port (needs a build with --features grpc)
+        #[arg(long)] grpc_port: Option<u16>,
+    },
+    /// Show what a rig task changed: its branch against the rig base plus uncommitted work
+    Diff {
+        #[arg(value_parser = ids::task_id, add = complete::tasks())] task_id: String,
+        /// Only print the diffstat
+        #[arg(long)] stat: bool,
+    },
+    /// Merge a rig task's branch into the rig after a conflict-free trial merge
+    Merge {
+        #[arg(value_parser = ids::task_id, add = complete::tasks())] task_id: String,
+        /// Only report what would happen
+        #[arg(long)] check: bool,
+        /// On conflicts, spawn a resolver worker with the conflict list
+        #[arg(long)] resolve: bool,
+    },
+    /// Estimate how many parallel workers, and on which engine, clear the backlog by a date within budget
+    #[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
+    Plan {
+        #[command(subcommand)] action: Option<PlanCommands>,
+        /// Target date, e.g. 2024-07-31 or 2w
+        #[arg(long, required = true, value_parser = period::parse_due)] by: Option<i64>,
+        /// Spend limit in USD (defaults to [budget] daily_usd_cap for each remaining day)
+        #[arg(long)] budget: Option<f64>,
+    },
+    /// Stop all new agent activity (start, spawn, restart, respawn, admin) until 'tt unfreeze'
+    Freeze {
+        /// Why, shown to whoever is refused
+        reason: Option<String>,
+    },
+    /// Lift a freeze (e.g. after the daily spend cap tripped) so tasks can be dispatched again
+    Unfreeze,
+    /// Print the script that completes commands, task ids, agents and rigs: eval "$(tt completions bash)"
+    Completions {
+        #[arg(value_parser = ["bash", "zsh", "fish", "elvish", "powershell"])] shell: String,
+    },
+    /// Print the markers and commands agents are told about, as embedded in worker prompts
+    Conventions {
+        /// md, or json for tooling
+        #[arg(long, default_value = "md", value_parser = ["md", "json"])] format: String,
+        /// Write to this file instead of stdout
+        #[arg(short, long)] output: Option<std::path::PathBuf>,
+    },
+    /// Write tables to JSONL or Parquet files for pandas/duckdb; --since last picks up where the previous export stopped
+    Export {
+        #[arg(long, value_delimiter = ',', default_values_t = export::TABLE_NAMES.iter().map(|t| t.to_string()), value_parser = export::TABLE_NAMES.to_vec())] tables: Vec<String>,
+        #[arg(long, default_value = "jsonl", value_parser = ["jsonl", "parquet"])] format: String,
+        /// last, or e.g. 7d or 2024-07-01; everything when omitted
+        #[arg(long, value_parser = export::parse_since)] since: Option<export::Since>,
+        /// Directory for the files (default: exports/ in the workspace)
+        #[arg(short, long)] out: Option<std::path::PathBuf>,
+    },
+    /// Re-attach or reopen in_progress tasks whose worker session is gone (e.g. after a reboot)
+    Recover { #[arg(short, long)] mode: Option<String> },
+    /// Remove worker directories and sessions that no in-progress task is using
+    Gc {
+        /// List what would be removed without removing it
+        #[arg(long)] dry_run: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum AdminCommands {
+    /// Start hq-admin, or with --name a further admin that only handles the tasks in --scope (default: all)
+    Start { #[arg(long, value_parser = ids::agent_name)] name: Option<String> },
+    Attach { #[arg(long, value_parser = ids::agent_name)] name: Option<String> },
+    Stop { #[arg(long, value_parser = ids::agent_name)] name: Option<String> },
+    /// Named admins, their scopes and whether their session is up
+    List,
 }
 
 #[derive(Subcommand)]
-enum AdminCommands { Start, Attach, Stop }
+enum TeamCommands {
+    /// Create or redefine a team; with --scope its members only take tasks in that scope
+    Create {
+        #[arg(value_parser = ids::tag)] name: String,
+        #[arg(long, value_delimiter = ',', value_parser = ids::agent_name)] members: Vec<String>,
+        /// Who answers for the team, e.g. a named admin's mailbox (admin-infra)
+        #[arg(long, value_parser = ids::agent_name)] lead: Option<String>,
+    },
+    /// Teams with their members and what they have done
+    List,
+    Delete { name: String },
+}
+
+#[derive(Subcommand)]
+enum BenchCommands {
+    /// Dispatch every case of a suite to each engine; the monitor scores them as they finish
+    Run {
+        #[arg(long, default_value = "basic")] suite: String,
+        /// Defaults to [bench] engines in think.toml
+        #[arg(long, value_delimiter = ',')] engines: Vec<String>,
+    },
+    /// Scores of a run (the latest by default) and of every run of its suite per engine
+    Results {
+        #[arg(long)] run: Option<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum PlanCommands {
+    /// Have [plan] command break a spec into tasks with dependencies and estimates, previewed before they are added
+    FromFile {
+        file: std::path::PathBuf,
+        /// Only show what would be added
+        #[arg(long)] dry_run: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum GroomCommands {
+    /// Print a run's suggestions (the latest by default); edit GROOM.txt in its worker directory to change them
+    Show {
+        #[arg(long)] run: Option<String>,
+    },
+    /// Apply a run's suggestions (the latest by default)
+    Apply {
+        #[arg(long)] run: Option<String>,
+        /// Check the suggestions and list them without changing anything
+        #[arg(long)] dry_run: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum BotCommands {
+    /// Answer /status, /beads, /approve, /freeze, /nudge and /done from the chats in [telegram] chats
+    Telegram {
+        /// Bot token from @BotFather (default: $TT_TELEGRAM_TOKEN, see [telegram] token_env)
+        #[arg(long)] token: Option<String>,
+        /// Handle the updates waiting now and exit
+        #[arg(long)] once: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum SandboxCommands {
+    /// Discard the sandbox; the next --sandbox run copies the workspace again
+    Reset,
+}
+
+#[derive(Subcommand)]
+enum NotificationsCommands {
+    /// Unread notifications, newest first
+    List {
+        /// Include the ones already read or resolved
+        #[arg(long)] all: bool,
+    },
+    /// Mark notifications read
+    Ack {
+        #[arg(required_unless_present = "all")] ids: Vec<i64>,
+        /// Every unread notification
+        #[arg(long, conflicts_with = "ids")] all: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum ScopeCommands {
+    /// Save a scope: comma-separated tag:<tag> and rig:<rig> terms that must all match
+    Add {
+        #[arg(value_parser = ids::tag)] name: String,
+        #[arg(value_parser = scope::parse)] filter: scope::Scope,
+    },
+    List,
+    Remove { name: String },
+}
+
+#[derive(Subcommand)]
+enum UserCommands {
+    /// Add a web user and print their token; for an existing user, replace it
+    Add { #[arg(value_parser = ids::agent_name)] name: String },
+    List,
+    Remove { name: String },
+}
 
 #[derive(Subcommand)]
 enum WorkerCommands {
-    Spawn { task_id: String, name: String },
-    Nuke { name: String },
+    Spawn {
+        #[arg(value_parser = ids::task_id, add = complete::tasks())] task_id: String,
+        #[arg(value_parser = ids::agent_name)] name: String,
+        #[arg(short, long, default_value = "gemini")] engine: String,
+        /// Kill and respawn the agent's session if one already exists
+        #[arg(long)] replace: bool,
+        /// Print what would be launched without launching it
+        #[arg(long)] dry_run: bool,
+    },
+    Nuke {
+        #[arg(value_parser = ids::agent_name)] name: String,
+        /// List the session and directory that would be removed without removing them
+        #[arg(long)] dry_run: bool,
+    },
+    /// Respawn the agent's session on its current task with the same engine
+    Restart { #[arg(value_parser = ids::agent_name, add = complete::agents())] name: String },
+    /// Run a command in the agent's worker directory and environment: tt worker exec bob -- git log
+    Exec {
+        #[arg(value_parser = ids::agent_name, add = complete::agents())] name: String,
+        #[arg(required = true, trailing_var_arg = true, allow_hyphen_values = true)] command: Vec<String>,
+    },
 }
 
 #[derive(Subcommand)]
-enum TaskCommands {
-    Add { id: String, title: String },
+enum AgentCommands {
+    /// Report that a spawned agent is up (its first action) and, when run again, still alive
+    Checkin {
+        /// Agent name (defaults to the agent whose session this is)
+        #[arg(long, value_parser = ids::agent_name)] name: Option<String>,
+    },
+    /// Spawned agents and whether they checked in
     List,
 }
 
 #[derive(Subcommand)]
-enum MonitorCommands { Start }
+enum TaskCommands {
+    /// tt task add [ID] TITLE; without an id one is made up from [tasks] id_prefix (TT-001, TT-002...)
+    Add {
+        /// The id, or the title when it is the only argument (one that looks like an id is refused)
+        #[arg(value_name = "ID")] first: String,
+        #[arg(value_name = "TITLE")] title: Option<String>,
+        /// Replace the title if the id already exists
+        #[arg(long)] force_title_update: bool,
+        /// Registered rig the task works on (its prompts/ overlay the workspace prompts)
+        #[arg(long, add = complete::rigs())] rig: Option<String>,
+        /// Due date: 2024-07-01, "2024-07-01 17:00" or a span from now like 3d
+        #[arg(long, value_parser = period::parse_due)] due: Option<i64>,
+        /// Label the task (repeatable); tags select `[[sla]]` policies
+        #[arg(long = "tag", value_parser = ids::tag)] tags: Vec<String>,
+        /// low, normal (the default), high or urgent; higher priorities list and dispatch first
+        #[arg(long, value_parser = service::parse_priority)] priority: Option<i64>,
+        /// Add it even when an unfinished task has a very similar title
+        #[arg(long)] force: bool,
+        /// Expected size: a span like 30m, 2h or 1d, or story points like 3pts (see tt stats estimates)
+        #[arg(long, value_parser = service::Estimate::parse)] estimate: Option<service::Estimate>,
+    },
+    /// Change a task's title, due date, tags, priority or estimate
+    Edit {
+        #[arg(value_parser = ids::task_id, add = complete::tasks())] id: String,
+        #[arg(long)] title: Option<String>,
+        #[arg(long, value_parser = period::parse_due, conflicts_with = "no_due")] due: Option<i64>,
+        /// Remove the due date
+        #[arg(long)] no_due: bool,
+        #[arg(long = "tag", value_parser = ids::tag)] tags: Vec<String>,
+        #[arg(long = "untag", value_parser = ids::tag)] untags: Vec<String>,
+        #[arg(long, value_parser = service::parse_priority)] priority: Option<i64>,
+        #[arg(long, value_parser = service::Estimate::parse, conflicts_with = "no_estimate")] estimate: Option<service::Estimate>,
+        /// Remove the estimate
+        #[arg(long)] no_estimate: bool,
+    },
+    List {
+        /// Only tasks with this tag (repeatable: tasks with all of them)
+        #[arg(long = "tag", value_parser = ids::tag)] tags: Vec<String>,
+        /// Also list archived tasks
+        #[arg(long)] include_archived: bool,
+    },
+    /// Hide closed tasks from the task list and the dashboard
+    Archive {
+        #[arg(required = true, value_parser = ids::task_id, add = complete::tasks())] ids: Vec<String>,
+    },
+    /// Bring archived tasks back into the task list
+    Unarchive {
+        #[arg(required = true, value_parser = ids::task_id, add = complete::tasks())] ids: Vec<String>,
+    },
+    /// Delete a task and its tags
+    Delete {
+        #[arg(value_parser = ids::task_id, add = complete::tasks())] id: String,
+        /// List what would be deleted without deleting it
+        #[arg(long)] dry_run: bool,
+    },
+    /// Task details and the instructions its workers were launched with
+    Show {
+        #[arg(value_parser = ids::task_id, add = complete::tasks())] id: String,
+        /// Print the full instruction of every spawn instead of the summary
+        #[arg(long)] prompt: bool,
+    },
+    /// Add tags to every task that matches a filter: tt task tag --filter assignee=bob cleanup
+    Tag {
+        #[arg(long, value_parser = scope::parse)] filter: scope::Scope,
+        #[arg(required = true, value_parser = ids::tag)] tags: Vec<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum MonitorCommands {
+    Start {
+        /// Randomly kill and freeze workers as [chaos] in think.toml says, to try out stall detection and recovery
+        #[arg(long)] chaos: bool,
+    },
+    /// Run a single detection pass and exit
+    Scan {
+        /// Inject [chaos] faults in this pass
+        #[arg(long)] chaos: bool,
+    },
+}
 
 #[derive(Subcommand)]
 enum HandoffCommands { New, Status }
@@ -63,15 +509,32 @@ enum HandoffCommands { New, Status }
 #[derive(Subcommand)]
 enum MailCommands {
     Inbox,
-    Send { receiver: String, #[arg(short, long)] subject: String, #[arg(short, long)] body: String },
-    Read { id: i32 },
+    Send {
+        /// Agent name, or @<team> for the team's lead and members
+        #[arg(value_parser = ids::receiver)] receiver: String,
+        #[arg(short, long)] subject: String,
+        #[arg(short, long)] body: String,
+        /// Attach a file, or a stored artifact by id (repeatable)
+        #[arg(long)] attach: Vec<String>,
+    },
+    Read {
+        id: i32,
+        /// Copy the attachments into this directory (default: the current one)
+        #[arg(long, num_args = 0..=1, default_missing_value = ".")] save_attachments: Option<std::path::PathBuf>,
+    },
 }
 
 #[derive(Subcommand)]
 enum RigCommands {
     List,
     Add { name: String, path: String, #[arg(short, long)] repo: Option<String> },
-    Status { name: String },
+    Status { #[arg(add = complete::rigs())] name: String },
+    /// Unregister a rig; its files are left in place
+    Remove {
+        #[arg(add = complete::rigs())] name: String,
+        /// Show what would be unregistered without doing it
+        #[arg(long)] dry_run: bool,
+    },
 }
 
 #[derive(Subcommand)]
@@ -79,84 +542,446 @@ enum BoardCommands {
     List,
 }
 
+#[derive(Subcommand)]
+enum LogsCommands {
+    /// Compress the live logs of tasks untouched for a while into .logs/archive
+    Archive {
+        /// e.g. 30d, 2w or 2024-07-01
+        #[arg(long, default_value = "30d", value_parser = period::parse_since)] older_than: i64,
+    },
+}
+
+#[derive(Subcommand)]
+enum ScheduleCommands {
+    /// Write the due dates of unfinished tasks as an iCalendar feed
+    Export {
+        /// File to write instead of stdout
+        #[arg(short, long)] output: Option<std::path::PathBuf>,
+    },
+}
+
+#[derive(Subcommand)]
+enum StatsCommands {
+    /// Average cost and success rate of attempts per engine/model
+    Engines {
+        /// Only attempts started after this (e.g. 7d, 2024-07-01)
+        #[arg(long, value_parser = period::parse_since)] since: Option<i64>,
+    },
+    /// Tasks created vs closed per day, WIP, and median wait/cycle times
+    Flow {
+        #[arg(long, default_value = "30d", value_parser = period::parse_since)] since: i64,
+    },
+    /// Open tasks by how long they've waited, in-progress ones by how long their logs have been quiet
+    Aging {
+        /// Flag open tasks waiting longer than this
+        #[arg(long, default_value = "7d", value_parser = period::parse_since)] stale: i64,
+    },
+    /// How closed tasks compared to their estimates, per engine and agent and per week
+    Estimates {
+        #[arg(long, default_value = "90d", value_parser = period::parse_since)] since: i64,
+    },
+}
+
 #[derive(Subcommand)]
 enum CostsCommands {
-    List,
+    List {
+        #[arg(long)] task: Option<String>,
+        #[arg(long)] agent: Option<String>,
+        #[arg(long)] model: Option<String>,
+        /// Only rows newer than this (e.g. 24h, 7d, 2024-07-01)
+        #[arg(long, value_parser = period::parse_since)] since: Option<i64>,
+        #[arg(long)] limit: Option<usize>,
+    },
     Summary,
     Add { task_id: String, agent: String, model: String, input: i32, output: i32, cost: f64 },
 }
 
-fn main() -> Result<()> {
-    let cli = Cli::parse();
-    if cli.debug { env::set_var("RUST_LOG", "debug"); } else { env::set_var("RUST_LOG", "info"); }
-    env_logger::init();
-    let work_dir = env::current_dir()?;
+fn main() {
+    clap_complete::CompleteEnv::with_factory(Cli::command).var(complete::VAR).complete();
+    if let Err(e) = run() {
+        tracing::error!("{:#}", e);
+        eprintln!("❌ Error: {:#}", e);
+        let code = e.downcast_ref::<error::ThinkTodoError>().map(|e| e.exit_code()).unwrap_or(1);
+        std::process::exit(code);
+    }
+}
+
+/// Lists what a destructive command would remove.
+fn print_dry_run(plan: &service::Teardown) {
+    println!("{}", tr!("🧪 DRY RUN: nothing was removed."));
+    if plan.is_empty() { println!("{}", tr!("Nothing to remove.")); }
+    for s in &plan.sessions { println!("{}", tr!("Session:   {}", s)); }
+    for p in &plan.paths { println!("{}", tr!("Directory: {}", p.display())); }
+    for r in &plan.records { println!("{}", tr!("Record:    {}", r)); }
+}
+
+/// Expands short or fuzzy task ids and agent names to the ones they stand for, and asks for the
+/// ones `start` and `done` were run without. Only arguments that name something existing are
+/// resolved; new ids and agent names are taken as typed. Commands that close, merge or remove
+/// something only take a whole id or a unique prefix of one, and say what it stood for.
+fn resolve_ids(db: &db::Db, command: &mut Commands, yes: bool) -> error::Result<()> {
+    match command {
+        Commands::Start { task_id, agent_name, .. } => {
+            if task_id.is_none() { *task_id = Some(resolve::pick_task(db, "status = 'open'", &tr!("Task to start"))?); }
+            if agent_name.is_none() { *agent_name = Some(resolve::pick_agent(db)?); }
+        }
+        Commands::Done { task_id, filter: None, .. } if task_id.is_none() => {
+            *task_id = Some(resolve::pick_task(db, "status != 'closed'", &tr!("Task to close"))?);
+        }
+        _ => {}
+    }
+    let destructive = matches!(command, Commands::Done { .. } | Commands::Undone { .. } | Commands::Merge { .. }
+        | Commands::Task { action: TaskCommands::Delete { .. } }
+        | Commands::Worker { action: WorkerCommands::Nuke { .. } | WorkerCommands::Restart { .. } | WorkerCommands::Exec { .. } });
+    let (tasks, agents): (Vec<&mut String>, Vec<&mut String>) = match command {
+        Commands::Logs { action: None, task_id, agent, .. } => (task_id.iter_mut().collect(), agent.iter_mut().collect()),
+        Commands::Start { task_id, .. } | Commands::Done { task_id, .. } => (task_id.iter_mut().collect(), vec![]),
+        Commands::Undone { task_id }
+        | Commands::Diff { task_id, .. } | Commands::Merge { task_id, .. }
+        | Commands::Worker { action: WorkerCommands::Spawn { task_id, .. } } => (vec![task_id], vec![]),
+        Commands::Peek { agent_name, task, .. } => (task.iter_mut().collect(), vec![agent_name]),
+        Commands::Nudge { agent_name, .. } => (vec![], agent_name.iter_mut().collect()),
+        Commands::Worker { action: WorkerCommands::Nuke { name, .. } | WorkerCommands::Restart { name } | WorkerCommands::Exec { name, .. } } => (vec![], vec![name]),
+        Commands::Task { action: TaskCommands::Edit { id, .. } | TaskCommands::Delete { id, .. } | TaskCommands::Show { id, .. } } => (vec![id], vec![]),
+        Commands::Task { action: TaskCommands::Archive { ids } | TaskCommands::Unarchive { ids } } => (ids.iter_mut().collect(), vec![]),
+        _ => (vec![], vec![]),
+    };
+    if destructive {
+        for id in tasks { *id = resolve::task_by_prefix(db, id, yes)?; }
+        for name in agents { *name = resolve::agent_by_prefix(db, name, yes)?; }
+        return Ok(());
+    }
+    for id in tasks { *id = resolve::task(db, id)?; }
+    for name in agents { *name = resolve::agent(db, name)?; }
+    Ok(())
+}
+
+/// Who is running a command.
+enum Actor { Verified(String), Unverified(String) }
+
+/// Agents run tt too. Their sessions carry a token issued at spawn, which names the actor; a
+/// `TT_AGENT` without a valid token is only a claim. With neither, it's the user at the terminal,
+/// unless this runs inside an agent's session that dropped its variables.
+fn resolve_actor(db: &db::Db) -> Result<Actor> {
+    let claimed = env::var("TT_AGENT").ok();
+    Ok(match env::var(session::TOKEN_VAR) {
+        Ok(token) => match db.token_actor(&token)? {
+            Some(actor) => Actor::Verified(actor),
+            None => Actor::Unverified(claimed.unwrap_or_else(|| "unknown".to_string())),
+        },
+        Err(_) => match claimed {
+            Some(agent) => Actor::Unverified(agent),
+            None if session::inside_agent_session() => Actor::Unverified("unknown".to_string()),
+            None => Actor::Verified("user".to_string()),
+        },
+    })
+}
+
+/// Whether the command changes anything; an unverified actor may only run the others.
+fn mutates(command: &Commands) -> bool {
+    !matches!(command,
+        Commands::Board { .. } | Commands::Trail | Commands::Watch { .. } | Commands::Replay { .. } | Commands::Peek { .. } | Commands::Diff { .. } | Commands::Plan { action: None, .. }
+        | Commands::Stats { .. } | Commands::Conventions { .. } | Commands::Schedule { .. } | Commands::Daily { send: false }
+        | Commands::Logs { action: None, .. }
+        | Commands::Task { action: TaskCommands::List { .. } | TaskCommands::Show { .. } }
+        | Commands::Mail { action: MailCommands::Inbox }
+        | Commands::Costs { action: CostsCommands::List { .. } | CostsCommands::Summary }
+        | Commands::Agent { action: AgentCommands::List }
+        | Commands::Admin { action: AdminCommands::List | AdminCommands::Attach { .. } }
+        | Commands::Team { action: TeamCommands::List }
+        | Commands::Scope { action: ScopeCommands::List }
+        | Commands::User { action: UserCommands::List }
+        | Commands::Search { .. }
+        | Commands::Bench { action: BenchCommands::Results { .. } }
+        | Commands::Groom { action: Some(GroomCommands::Show { .. }), .. }
+        | Commands::Rig { action: RigCommands::List | RigCommands::Status { .. } }
+        | Commands::Handoff { action: HandoffCommands::Status })
+}
+
+fn run() -> Result<()> {
+    let mut cli = Cli::parse();
+    // Completion scripts don't need, or create, a workspace.
+    if let Commands::Completions { shell } = &cli.command { return Ok(complete::registration(shell)?); }
+    // Agents run tt from their worker directory; their session points back at the workspace.
+    let work_dir = match env::var_os(session::WORKSPACE_VAR) {
+        Some(dir) => std::path::PathBuf::from(dir),
+        None => env::current_dir()?,
+    };
+    let settings = config::Config::load(&work_dir).ok();
+    let lang = match env::var(i18n::LANG_VAR) {
+        Ok(code) => code,
+        Err(_) => settings.as_ref().map(|c| c.lang.clone()).unwrap_or_default(),
+    };
+    i18n::init(i18n::parse(&lang)?);
+    period::init_time_style(match (cli.utc, cli.epoch) {
+        (_, true) => period::TimeStyle::Epoch,
+        (true, _) => period::TimeStyle::Utc,
+        _ => period::parse_time_style(settings.as_ref().map_or("", |c| c.time.as_str()))?,
+    });
+    output::init(&settings.map(|c| c.theme).unwrap_or_default(), cli.quiet);
+    let work_dir = match &cli.command {
+        _ if !cli.sandbox => work_dir,
+        Commands::Sandbox { .. } => work_dir,
+        Commands::Serve { .. } | Commands::Merge { .. } => anyhow::bail!("this command works on the real workspace and its rigs; it is not available with --sandbox"),
+        _ => {
+            let dir = sandbox::prepare(&work_dir)?;
+            // Every session this process would start goes through the printing backend.
+            env::set_var("TT_SESSION_BACKEND", "sandbox");
+            eprintln!("{}", tr!("🧪 SANDBOX: working on {} (tt sandbox reset starts over)", dir.display()));
+            dir
+        }
+    };
+    // Long-running commands echo their decisions; one-shot commands keep the terminal to their own output.
+    let daemon = matches!(cli.command, Commands::Monitor { action: MonitorCommands::Start { .. } } | Commands::Serve { .. }
+        | Commands::Bot { action: BotCommands::Telegram { once: false, .. } });
+    logging::init(&work_dir, cli.debug, cli.debug || daemon);
     let database = db::Db::new(work_dir.clone())?;
+    let actor = match resolve_actor(&database)? {
+        Actor::Verified(actor) => actor,
+        Actor::Unverified(claimed) if mutates(&cli.command) => return Err(error::ThinkTodoError::InvalidArgument(format!(
+            "'{}' could not be verified: agents change things with the {} their session was given", claimed, session::TOKEN_VAR)).into()),
+        Actor::Unverified(claimed) => claimed,
+    };
+    resolve_ids(&database, &mut cli.command, cli.yes)?;
+    let scope = cli.scope.as_deref().map(|s| scope::resolve(&database, s)).transpose()?;
 
     match cli.command {
         Commands::Admin { action } => {
-            let a = admin::Admin::new(work_dir);
             match action {
-                AdminCommands::Start => a.start()?,
-                AdminCommands::Attach => a.attach()?,
-                AdminCommands::Stop => tmux::Tmux::kill_session(&a.session_name)?,
+                AdminCommands::Start { name } => {
+                    if name.is_none() && scope.is_some() { anyhow::bail!("hq-admin handles every task; give a scoped admin a --name"); }
+                    admin::Admin::named(work_dir, name).start(scope.as_ref())?
+                }
+                AdminCommands::Attach { name } => admin::Admin::named(work_dir, name).attach()?,
+                AdminCommands::Stop { name } => admin::Admin::named(work_dir, name).stop()?,
+                AdminCommands::List => {
+                    let backend = session::backend(&work_dir)?;
+                    let admins = database.admins()?;
+                    if admins.is_empty() { println!("{}", tr!("No named admins; hq-admin handles every task.")); }
+                    for (name, scope) in admins {
+                        let state = if backend.exists(&format!("hq-admin-{}", name)) { "running" } else { "stopped" };
+                        println!("{}", tr!("{:<16} {:<8} scope {} (mail: admin-{})", name, state, scope, name));
+                    }
+                }
             }
         }
         Commands::Worker { action } => match action {
-            WorkerCommands::Spawn { task_id, name } => {
-                // Fix: Added default engine "gemini" for raw spawn
-                let w = worker::Worker::new(task_id, name.clone(), work_dir, "gemini".to_string());
-                w.spawn()?;
-                let _ = database.log_audit("user", "spawn", &name, "success");
+            WorkerCommands::Spawn { task_id, name, engine, replace, dry_run } => {
+                if let Some(s) = &scope { s.ensure(&database, &task_id)?; }
+                let req = service::Sling { task_id, agent: name, engine, replace, allow_dirty: false, dry_run, brief: None };
+                service::spawn(&database, &work_dir, &req, &actor)?;
+            }
+            WorkerCommands::Exec { name, command } => {
+                let status = service::exec(&database, &work_dir, &name, &command, &actor)?;
+                if !status.success() { std::process::exit(status.code().unwrap_or(1)); }
+            }
+            WorkerCommands::Nuke { name, dry_run: true } => print_dry_run(&service::plan_nuke(&work_dir, &name)?),
+            WorkerCommands::Nuke { name, dry_run: false } => {
+                let removed = service::nuke(&database, &work_dir, &name, &actor)?;
+                if removed.is_empty() { println!("{}", tr!("🧹 '{}' had no session or directory left.", name)); } else { println!("{}", tr!("🧹 Worker '{}' nuked.", name)); }
+            }
+            WorkerCommands::Restart { name } => {
+                let task_id = service::restart(&database, &work_dir, &name, &actor)?;
+                println!("{}", tr!("♻️  '{}' restarted on {}.", name, task_id));
+            }
+        },
+        Commands::Agent { action } => match action {
+            AgentCommands::Checkin { name } => {
+                let agent = (actor != "user").then_some(actor.as_str());
+                if let (Some(agent), Some(name)) = (agent, &name) {
+                    if agent != name { anyhow::bail!(error::ThinkTodoError::InvalidArgument(format!("{} cannot check in as {}", agent, name))); }
+                }
+                let name = name.or(agent.map(str::to_string))
+                    .ok_or_else(|| error::ThinkTodoError::InvalidArgument("no agent name: pass --name or run inside a worker session".to_string()))?;
+                let (task_id, first) = database.agent_checkin(&name)?;
+                if first {
+                    database.log_audit(&name, "agent_checkin", &task_id, "success")?;
+                    tracing::info!(agent = %name, task = %task_id, "checked in");
+                }
+                println!("{}", tr!("👋 {} checked in on [{}].", name, task_id));
+            }
+            AgentCommands::List => {
+                let mut stmt = database.conn.prepare("SELECT name, task_id, engine, status, spawned_at, checked_in_at FROM agents ORDER BY name")?;
+                let rows = stmt.query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?, row.get::<_, String>(2)?,
+                    row.get::<_, String>(3)?, row.get::<_, i64>(4)?, row.get::<_, Option<i64>>(5)?)))?;
+                println!("{}", tr!("AGENTS:"));
+                for r in rows {
+                    let (name, task_id, engine, status, spawned, checked_in) = r?;
+                    let checked_in = checked_in.map(|t| format!("last check-in {}", period::format_time(t))).unwrap_or_else(|| "never checked in".to_string());
+                    println!("{}", tr!("- {} [{}] {} · {} · spawned {} · {}", name, task_id, engine, status, period::format_time(spawned), checked_in));
+                }
             }
-            WorkerCommands::Nuke { name } => worker::Worker::nuke(&name, &work_dir)?,
         },
         Commands::Task { action } => match action {
-            TaskCommands::Add { id, title } => {
-                database.add_task(&id, &title)?;
-                println!("✅ Task [{}] registered.", id);
+            TaskCommands::Add { first, title, force_title_update, rig, due, tags, priority, force, estimate } => {
+                let (id, title) = match title {
+                    Some(title) => (ids::task_id(&first)?, title),
+                    // `tt task add T5` most likely lost its title rather than meant one.
+                    None if ids::task_id(&first).is_ok() && first.chars().any(|c| c.is_ascii_digit()) =>
+                        anyhow::bail!("'{}' looks like a task id, not a title; give both: tt task add {} \"<title>\"", first, first),
+                    None => (service::new_task_id(&database, &work_dir)?, first),
+                };
+                match service::add_task(&database, &service::NewTask { id: id.clone(), title, rig, due, tags, priority, force_title_update, force, estimate }) {
+                    Ok(true) => println!("{}", tr!("✅ Task [{}] registered.", id)),
+                    Ok(false) => println!("{}", tr!("✏️  Task [{}] title updated.", id)),
+                    Err(e @ error::ThinkTodoError::TaskExists { .. }) => {
+                        eprintln!("❌ {}", e);
+                        eprintln!("{}", tr!("💡 Re-run with --force-title-update to replace its title, or use the next free id: {}", database.next_free_id(&id)?));
+                        std::process::exit(e.exit_code());
+                    }
+                    Err(e @ error::ThinkTodoError::DuplicateTask { .. }) => {
+                        eprintln!("{}", tr!("⚠️  Not added: it looks like a duplicate of"));
+                        if let error::ThinkTodoError::DuplicateTask { similar, .. } = &e {
+                            for (id, title) in similar { eprintln!("  - [{}] {}", id, title); }
+                        }
+                        eprintln!("{}", tr!("💡 Re-run with --force to add it anyway."));
+                        std::process::exit(e.exit_code());
+                    }
+                    Err(e) => return Err(e.into()),
+                }
             }
-            TaskCommands::List => {
-                let mut stmt = database.conn.prepare("SELECT id, title, status FROM tasks")?;
-                let rows = stmt.query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?, row.get::<_, String>(2)?)))?;
-                println!("THINK TODO BACKLOG:");
-                for r in rows { let (id, title, status) = r?; println!("- [{}] {} ({})", id, title, status); }
+            TaskCommands::Edit { id, title, due, no_due, tags, untags, priority, estimate, no_estimate } => {
+                database.task_assignee(&id)?;
+                if let Some(title) = &title { database.update_task_title(&id, title)?; }
+                if let Some(p) = priority { database.set_task_priority(&id, p)?; }
+                if due.is_some() || no_due { database.set_task_due(&id, due)?; }
+                if estimate.is_some() || no_estimate { service::set_task_estimate(&database, &id, estimate)?; }
+                database.add_task_tags(&id, &tags)?;
+                database.remove_task_tags(&id, &untags)?;
+                println!("{}", tr!("✏️  Task [{}] updated.", id));
+            }
+            TaskCommands::List { tags, include_archived } => {
+                let (cond, params) = scope.unwrap_or_else(scope::Scope::all).and_tags(&tags).condition();
+                let archived = if include_archived { "" } else { " AND archived_at IS NULL" };
+                let mut stmt = database.conn.prepare(&format!(
+                    "SELECT id, title, status, due_at, COALESCE(priority, 1), archived_at IS NOT NULL, estimate FROM tasks WHERE ({}){} ORDER BY priority DESC, rowid", cond, archived))?;
+                let rows = stmt.query_map(rusqlite::params_from_iter(params), |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?, row.get::<_, String>(2)?, row.get::<_, Option<i64>>(3)?, row.get::<_, i64>(4)?, row.get::<_, bool>(5)?, row.get::<_, Option<String>>(6)?)))?;
+                let now = chrono::Local::now().timestamp();
+                println!("{}", output::heading(&tr!("THINK TODO BACKLOG:")));
+                for r in rows {
+                    let (id, title, status, due, priority, archived, estimate) = r?;
+                    let late = due.is_some_and(|d| status != "closed" && period::is_overdue(d, now));
+                    let due = match due {
+                        Some(d) if status != "closed" && period::is_overdue(d, now) => tr!(" ⚠️ OVERDUE since {}", period::format_due(d)),
+                        Some(d) => tr!(" 📅 due {}", period::format_due(d)),
+                        None => String::new(),
+                    };
+                    let tags: String = database.task_tags(&id)?.iter().map(|t| format!(" #{}", t)).collect();
+                    let priority = if priority == 1 { String::new() } else { format!(" !{}", service::priority_name(priority)) };
+                    let status = if archived { tr!("{}, archived", status) } else { status };
+                    let estimate = estimate.map(|e| format!(" ~{}", e)).unwrap_or_default();
+                    let line = output::show(&format!("- [{}] {} ({}){}{}{}{}", id, title, status, priority, tags, estimate, due));
+                    println!("{}", if late { output::alert(&line) } else { line });
+                }
+            }
+            TaskCommands::Tag { filter, tags } => {
+                let ids = filter.select(&database, "id", "1")?;
+                for id in &ids { database.add_task_tags(id, &tags)?; }
+                println!("{}", tr!("🏷️  Tagged {} task(s) with {}: {}", ids.len(), tags.join(", "), ids.join(", ")));
+            }
+            TaskCommands::Archive { ids } => {
+                for id in ids {
+                    service::archive(&database, &id, &actor)?;
+                    println!("{}", tr!("🗄️  Task [{}] archived.", id));
+                }
+            }
+            TaskCommands::Unarchive { ids } => {
+                for id in ids {
+                    service::unarchive(&database, &id, &actor)?;
+                    println!("{}", tr!("📤 Task [{}] unarchived.", id));
+                }
+            }
+            TaskCommands::Delete { id, dry_run: true } => print_dry_run(&service::plan_delete_task(&database, &id)?),
+            TaskCommands::Delete { id, dry_run: false } => {
+                service::delete_task(&database, &id, &actor)?;
+                println!("{}", tr!("🗑️  Task [{}] deleted.", id));
+            }
+            TaskCommands::Show { id, prompt } => {
+                let (title, status, assignee, engine, rig, priority) = database.conn.query_row(
+                    "SELECT title, status, assignee, engine, rig, COALESCE(priority, 1) FROM tasks WHERE id = ?1", params![id],
+                    |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?, row.get::<_, Option<String>>(2)?, row.get::<_, Option<String>>(3)?, row.get::<_, Option<String>>(4)?, row.get::<_, i64>(5)?))
+                ).optional()?.ok_or_else(|| error::ThinkTodoError::TaskNotFound(id.clone()))?;
+                let prompts = database.task_prompts(&id)?;
+                if !prompt {
+                    let priority = if priority == 1 { String::new() } else { format!(" !{}", service::priority_name(priority)) };
+                    println!("📌 [{}] {} ({}){}", id, title, status, priority);
+                    println!("{}", tr!("   assignee: {}  engine: {}  rig: {}", assignee.as_deref().unwrap_or("-"), engine.as_deref().unwrap_or("-"), rig.as_deref().unwrap_or("-")));
+                    let chain = database.task_delegation(&id)?;
+                    if !chain.is_empty() { println!("{}", tr!("   delegated: {}", chain.join(" → "))); }
+                    let deps = database.task_dependencies(&id)?;
+                    if !deps.is_empty() { println!("{}", tr!("   depends on: {}", deps.join(", "))); }
+                    if let Some(estimate) = database.task_estimate(&id)? { println!("{}", tr!("   estimate: {}", estimate)); }
+                    let attempts = database.task_attempts(&id)?;
+                    println!("{}", tr!("   attempts: {} (instructions: --prompt)", attempts.len()));
+                    for a in &attempts {
+                        println!("   #{} {} · {}{} · {} → {} · {} · ${:.4}", a.id, a.agent_name, a.engine,
+                            a.model.as_deref().map(|m| format!(" ({})", m)).unwrap_or_default(),
+                            period::format_time(a.started_at), a.ended_at.map(period::format_time).unwrap_or_else(|| "now".to_string()),
+                            a.outcome.as_deref().unwrap_or("running"), a.cost_usd);
+                    }
+                    if let (Some(first), Some(last)) = (attempts.iter().map(|a| a.started_at).min(), attempts.iter().map(|a| a.ended_at).max()) {
+                        let end = last.filter(|_| status == "closed").unwrap_or_else(|| chrono::Local::now().timestamp());
+                        println!("{}", tr!("   took {} and ${:.2}", period::format_span(end - first), database.task_lifecycle(&id)?.1));
+                    }
+                    return Ok(());
+                }
+                if prompts.is_empty() { println!("{}", tr!("No worker has been launched for '{}' yet.", id)); }
+                for (i, (agent, engine, ts, instruction)) in prompts.iter().enumerate() {
+                    println!("{}", tr!("=== SPAWN #{} · {} · {} · {} ===\n{}\n", i + 1, agent, engine, period::format_time(*ts), instruction));
+                }
             }
         },
         Commands::Monitor { action } => match action {
-            MonitorCommands::Start => {
-                let m = monitor::Monitor::new(work_dir);
+            MonitorCommands::Start { chaos } => {
+                let mut m = monitor::Monitor::new(work_dir);
+                m.chaos = chaos;
+                if chaos { println!("{}", tr!("🐒 Chaos mode: workers will be killed and frozen at random ([chaos] in think.toml).")); }
                 m.watch()?;
             }
+            MonitorCommands::Scan { chaos } => {
+                let mut m = monitor::Monitor::new(work_dir);
+                m.chaos = chaos;
+                m.pass(&database)?;
+                database.set_setting(health::HEARTBEAT_KEY, &chrono::Local::now().timestamp().to_string())?;
+                println!("{}", tr!("✅ Monitor pass complete."));
+            }
         },
         Commands::Mail { action } => match action {
             MailCommands::Inbox => {
                 let mut stmt = database.conn.prepare("SELECT id, sender, subject, status FROM messages ORDER BY timestamp DESC")?;
                 let rows = stmt.query_map([], |row| Ok((row.get::<_, i32>(0)?, row.get::<_, String>(1)?, row.get::<_, String>(2)?, row.get::<_, String>(3)?)))?;
-                println!("📬 MAIL INBOX:");
+                println!("{}", output::heading(&tr!("📬 MAIL INBOX:")));
                 for r in rows {
                     let (id, sender, subject, status) = r?;
                     let marker = if status == "unread" { "●" } else { " " };
-                    println!("{} [{}] From: {} | Subject: {}", marker, id, sender, subject);
+                    println!("{}", output::show(&tr!("{} [{}] From: {} | Subject: {}", marker, id, sender, subject)));
                 }
             }
-            MailCommands::Send { receiver, subject, body } => {
-                database.send_mail("user", &receiver, &subject, &body)?;
-                println!("🚀 Mail sent to {}.", receiver);
+            MailCommands::Send { receiver, subject, body, attach } => {
+                let sent = service::send_mail(&database, &work_dir, &actor, &receiver, &subject, &body, &attach)?;
+                let to: Vec<&str> = sent.iter().map(|(r, _)| r.as_str()).collect();
+                println!("{}", output::show(&tr!("🚀 Mail sent to {}.", if to.is_empty() { tr!("nobody (the team is empty)") } else { to.join(", ") })));
             }
-            MailCommands::Read { id } => {
+            MailCommands::Read { id, save_attachments } => {
                 let mut stmt = database.conn.prepare("SELECT sender, subject, body, timestamp FROM messages WHERE id = ?1")?;
                 let mut rows = stmt.query_map(params![id], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?, row.get::<_, String>(2)?, row.get::<_, i64>(3)?)))?;
                 if let Some(r) = rows.next() {
-                    let (sender, subject, body, _ts) = r?;
-                    println!("--- MAIL MESSAGE ---");
-                    println!("From: {}", sender);
-                    println!("Subject: {}", subject);
+                    let (sender, subject, body, ts) = r?;
+                    println!("{}", output::heading(&tr!("--- MAIL MESSAGE ---")));
+                    println!("{}", tr!("From: {}", sender));
+                    println!("{}", tr!("Date: {}", period::format_time(ts)));
+                    println!("{}", tr!("Subject: {}", subject));
                     println!("\n{}", body);
+                    let attachments = database.attachments(id.into())?;
+                    if !attachments.is_empty() { println!(); }
+                    for a in &attachments { println!("{}", output::show(&tr!("📎 {} ({} bytes, artifact {})", a.name, a.size, &a.artifact[..12]))); }
                     println!("--------------------");
+                    if let Some(dest) = &save_attachments {
+                        for p in service::save_attachments(&database, &work_dir, id.into(), dest)? { println!("{}", output::show(&tr!("💾 Saved {}", p.display()))); }
+                    }
                     database.conn.execute("UPDATE messages SET status = 'read' WHERE id = ?1", params![id])?;
                 } else {
-                    println!("❌ Message not found.");
+                    println!("{}", output::show(&tr!("❌ Message not found.")));
                 }
             }
         },
@@ -164,7 +989,7 @@ fn main() -> Result<()> {
             RigCommands::List => {
                 let mut stmt = database.conn.prepare("SELECT name, path, status FROM rigs")?;
                 let rows = stmt.query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?, row.get::<_, String>(2)?)))?;
-                println!("🏗️ REGISTERED RIGS:");
+                println!("{}", tr!("🏗️ REGISTERED RIGS:"));
                 for r in rows {
                     let (name, path, status) = r?;
                     println!("- {} [{}] ({})", name, path, status);
@@ -172,32 +997,38 @@ fn main() -> Result<()> {
             }
             RigCommands::Add { name, path, repo } => {
                 database.add_rig(&name, &path, &repo.unwrap_or_default())?;
-                println!("✅ Rig '{}' added.", name);
+                println!("{}", tr!("✅ Rig '{}' added.", name));
+            }
+            RigCommands::Remove { name, dry_run: true } => print_dry_run(&service::plan_remove_rig(&database, &name)?),
+            RigCommands::Remove { name, dry_run: false } => {
+                service::remove_rig(&database, &name, &actor)?;
+                println!("{}", tr!("🗑️  Rig '{}' unregistered; its files were left in place.", name));
             }
             RigCommands::Status { name } => {
                 let mut stmt = database.conn.prepare("SELECT path, repo, status, last_sync FROM rigs WHERE name = ?1")?;
                 let mut rows = stmt.query_map(params![name], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?, row.get::<_, String>(2)?, row.get::<_, i64>(3)?)))?;
                 if let Some(r) = rows.next() {
                     let (path, repo, status, ts) = r?;
-                    println!("RIG STATUS: {}", name);
-                    println!("Path: {}", path);
-                    println!("Repo: {}", repo);
-                    println!("Status: {}", status);
-                    println!("Last Sync: {}", ts);
+                    println!("{}", tr!("RIG STATUS: {}", name));
+                    println!("{}", tr!("Path: {}", path));
+                    println!("{}", tr!("Repo: {}", repo));
+                    println!("{}", tr!("Status: {}", status));
+                    println!("{}", tr!("Last Sync: {}", period::format_time(ts)));
                 } else {
-                    println!("❌ Rig not found.");
+                    println!("{}", tr!("❌ Rig not found."));
                 }
             }
         },
         Commands::Board { action } => match action {
             BoardCommands::List => {
-                println!("╔══════════════════════════════════════════════════════════════════════════╗");
-                println!("║ 💠 THINK-TODO BOARD (SYSTEM PULSE)                                       ║");
-                println!("╠══════════════════════════════════════════════════════════════════════════╣");
+                println!("{}", output::rule(output::Rule::Top));
+                println!("{}", output::boxed(&tr!("💠 THINK-TODO BOARD (SYSTEM PULSE)")));
+                println!("{}", output::rule(output::Rule::Split));
 
                 // 1. Task Progress Summary
-                let mut stmt = database.conn.prepare("SELECT status, COUNT(*) FROM tasks GROUP BY status")?;
-                let rows = stmt.query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?)))?;
+                let (cond, params) = scope.clone().unwrap_or_else(scope::Scope::all).condition();
+                let mut stmt = database.conn.prepare(&format!("SELECT status, COUNT(*) FROM tasks WHERE {} GROUP BY status", cond))?;
+                let rows = stmt.query_map(rusqlite::params_from_iter(&params), |row| Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?)))?;
                 let mut counts = std::collections::HashMap::new();
                 for r in rows { let (s, c) = r?; counts.insert(s, c); }
                 let open = counts.get("open").unwrap_or(&0);
@@ -206,58 +1037,170 @@ fn main() -> Result<()> {
                 let total = *open + *in_p + *closed;
                 let progress = if total > 0 { (*closed as f64 / total as f64) * 100.0 } else { 0.0 };
 
-                println!("  [TASKS] Progress: [{:<20}] {:.1}%", "=".repeat((progress/5.0) as usize), progress);
-                println!("          Total: {} | ⏳ Open: {} | 🚀 Active: {} | ✅ Done: {}", total, open, in_p, closed);
-                println!("╟──────────────────────────────────────────────────────────────────────────╢");
+                println!("{}", output::show(&tr!("  [TASKS] Progress: [{:<20}] {:.1}%", "=".repeat((progress/5.0) as usize), progress)));
+                println!("{}", output::show(&tr!("          Total: {} | ⏳ Open: {} | 🚀 Active: {} | ✅ Done: {}", total, open, in_p, closed)));
+                println!("{}", output::rule(output::Rule::Thin));
+
+                let warnings = health::warnings(&database, &work_dir)?;
+                if !warnings.is_empty() {
+                    println!("{}", output::heading(&tr!("  [WARNINGS] {} item(s) need attention:", warnings.len())));
+                    for w in &warnings { println!("{}", output::show(&format!("  ⚠️  {:<12} {:<10} {}", w.kind, w.target, health::localize(&w.message)))); }
+                    println!("{}", output::rule(output::Rule::Thin));
+                }
 
                 // 2. Active Workers (Frontline)
-                let mut stmt = database.conn.prepare("SELECT id, assignee FROM tasks WHERE status = 'in_progress'")?;
-                let rows = stmt.query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)))?;
-                println!("  [FRONTLINE] Active Workers:");
+                let mut stmt = database.conn.prepare(&format!("SELECT id, assignee FROM tasks WHERE status = 'in_progress' AND {}", cond))?;
+                let rows = stmt.query_map(rusqlite::params_from_iter(&params), |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)))?;
+                println!("{}", output::heading(&tr!("  [FRONTLINE] Active Workers:")));
                 let mut active_any = false;
                 for r in rows {
                     let (tid, agent) = r?;
-                    println!("  → Agent '{}' is working on '{}'", agent, tid);
+                    println!("{}", output::show(&tr!("  → Agent '{}' is working on '{}'", agent, tid)));
                     active_any = true;
                 }
-                if !active_any { println!("  (No active workers currently)"); }
-                println!("╟──────────────────────────────────────────────────────────────────────────╢");
+                if !active_any { println!("{}", output::show(&tr!("  (No active workers currently)"))); }
+                let mut overdue = database.overdue_tasks()?;
+                if let Some(s) = &scope { overdue.retain(|t| s.matches(&database, &t.id).unwrap_or(false)); }
+                if !overdue.is_empty() {
+                    println!("{}", output::heading(&tr!("  [OVERDUE]")));
+                    for t in &overdue {
+                        println!("{}", output::alert(&output::show(&tr!("  ⚠️  '{}' {} — due {} ({})", t.id, t.title, period::format_due(t.due_at), t.assignee.as_deref().unwrap_or("unassigned")))));
+                    }
+                }
+                println!("{}", output::rule(output::Rule::Thin));
 
                 // 3. Recent Activity (Trail)
                 let mut stmt = database.conn.prepare("SELECT actor, action, target, timestamp FROM audit_logs ORDER BY timestamp DESC LIMIT 3")?;
                 let rows = stmt.query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?, row.get::<_, String>(2)?, row.get::<_, i64>(3)?)))?;
-                println!("  [RECENT TRAIL]");
+                println!("{}", output::heading(&tr!("  [RECENT TRAIL]")));
                 for r in rows {
                     let (actor, action, target, _ts) = r?;
-                    println!("  • {} {} {}", actor, action, target);
+                    println!("{}", output::show(&format!("  • {} {} {}", actor, action, target)));
                 }
-                println!("╟──────────────────────────────────────────────────────────────────────────╢");
+                println!("{}", output::rule(output::Rule::Thin));
 
                 // 4. Financial Status (Costs)
                 let mut stmt = database.conn.prepare("SELECT SUM(cost_usd) FROM costs")?;
                 let total_cost: f64 = stmt.query_row([], |row| row.get(0)).unwrap_or(0.0);
-                println!("  [ECONOMY] Total System Cost: ${:.4}", total_cost);
-                println!("╚══════════════════════════════════════════════════════════════════════════╝");
+                println!("{}", output::show(&tr!("  [ECONOMY] Total System Cost: ${:.4} | Today: ${:.4}", total_cost, database.today_spend()?)));
+                let disk = health::disk_usage(&database, &work_dir)?;
+                println!("{}", output::show(&tr!("  [DISK] Logs: {} | Workers: {} | Artifacts: {} | Free: {}", health::format_mb(disk.logs_bytes), health::format_mb(disk.workers_bytes),
+                    health::format_mb(disk.artifacts_bytes), disk.free_bytes.map(health::format_mb).unwrap_or_else(|| "?".to_string()))));
+                if let Some(reason) = database.frozen()? { println!("{}", output::show(&tr!("  🧊 FROZEN: {} (tt unfreeze)", reason))); }
+                println!("{}", output::rule(output::Rule::Bottom));
+            }
+        },
+        Commands::Logs { action: None, task_id, agent, tail, follow, grep } => {
+            let Some(task_id) = task_id else { anyhow::bail!("Usage: tt logs <task_id> [--agent A] [--tail N] [--follow] [--grep P] | tt logs archive") };
+            let all = logs::read_task_logs(&work_dir, &task_id, agent.as_deref())?;
+            // Following a task that doesn't exist would wait forever.
+            if all.is_empty() { database.task_assignee(&task_id)?; }
+            if all.is_empty() && !follow {
+                println!("{}", tr!("No logs for task '{}'{}.", task_id, agent.map(|a| tr!(" from '{}'", a)).unwrap_or_default()));
+                return Ok(());
+            }
+            // With several agents, tag each line with where it came from.
+            let prefix = agent.is_none() && all.len() > 1;
+            for log in &all {
+                let lines: Vec<&str> = log.content.lines().filter(|l| grep.as_deref().is_none_or(|g| l.contains(g))).collect();
+                let n = tail.unwrap_or(if follow { 10 } else { lines.len() });
+                for line in &lines[lines.len().saturating_sub(n)..] {
+                    if prefix { println!("[{}] {}", log.agent, line); } else { println!("{}", line); }
+                }
+            }
+            if follow { logs::follow(&work_dir, &task_id, agent.as_deref(), grep.as_deref(), agent.is_none())?; }
+        }
+        Commands::Logs { action: Some(action), .. } => match action {
+            LogsCommands::Archive { older_than } => {
+                let archived = logs::archive_older_than(&database, &work_dir, older_than, &output::Progress::new(0))?;
+                if archived.is_empty() { println!("{}", tr!("Nothing to archive.")); }
+                for task_id in &archived {
+                    println!("{}", tr!("🗜️  Archived logs of '{}'", task_id));
+                    database.log_audit(&actor, "logs_archived", task_id, "success")?;
+                }
+            }
+        },
+        Commands::Schedule { action } => match action {
+            ScheduleCommands::Export { output } => {
+                let feed = calendar::ics(&database)?;
+                match output {
+                    Some(path) => { std::fs::write(&path, feed)?; println!("{}", tr!("📅 Calendar written to {}", path.display())); }
+                    None => print!("{}", feed),
+                }
+            }
+        },
+        Commands::Stats { action } => match action {
+            StatsCommands::Engines { since } => {
+                let stats = database.engine_stats(since)?;
+                println!("{}", tr!("📈 ENGINE EFFICIENCY:"));
+                if stats.is_empty() { println!("{}", tr!("No attempts recorded yet.")); }
+                println!("{}", tr!("{:<10} {:<16} {:>8} {:>8} {:>10} {:>12} {:>12}", tr!("ENGINE"), tr!("MODEL"), tr!("RUNS"), tr!("DONE"), tr!("SUCCESS"), tr!("AVG COST"), tr!("TOTAL")));
+                for s in &stats {
+                    let success = if s.finished > 0 { format!("{:.0}%", 100.0 * s.done as f64 / s.finished as f64) } else { "-".to_string() };
+                    println!("{:<10} {:<16} {:>8} {:>8} {:>10} {:>12} {:>12}", s.engine, s.model.as_deref().unwrap_or("-"), s.attempts, s.done, success,
+                        format!("${:.4}", s.cost_usd / s.attempts as f64), format!("${:.4}", s.cost_usd));
+                }
+            }
+            StatsCommands::Flow { since } => {
+                let report = stats::flow(&database, since)?;
+                println!("{}", tr!("🌊 FLOW:"));
+                println!("{}", tr!("{:<12} {:>8} {:>8} {:>6}", tr!("DAY"), tr!("CREATED"), tr!("CLOSED"), tr!("WIP")));
+                for d in &report.days { println!("{:<12} {:>8} {:>8} {:>6}", d.day, d.created, d.closed, d.wip); }
+                let (created, closed): (usize, usize) = report.days.iter().fold((0, 0), |(a, b), d| (a + d.created, b + d.closed));
+                println!("{}", tr!("Total: {} created, {} closed", created, closed));
+                let show = |m: Option<i64>| m.map(period::format_span).unwrap_or_else(|| "-".to_string());
+                println!("{}", tr!("Median wait (open → in_progress):    {}", show(report.median_wait)));
+                println!("{}", tr!("Median cycle (in_progress → closed): {}", show(report.median_cycle)));
+            }
+            StatsCommands::Aging { stale } => {
+                let report = stats::aging(&database, &work_dir)?;
+                let stale = chrono::Local::now().timestamp() - stale;
+                let stall = config::Config::load(&work_dir)?.health.stall_minutes * 60;
+                let line = |t: &stats::AgingTask, rotting: bool| {
+                    let who = t.assignee.as_deref().map(|a| format!(" @{}", a)).unwrap_or_default();
+                    let line = output::show(&format!("  - [{}] {}{} ({})", t.id, t.title, who, period::format_span(t.age)));
+                    if rotting { output::alert(&line) } else { line }
+                };
+                println!("{}", output::heading(&tr!("⏳ OPEN TASKS BY AGE:")));
+                for ((label, _), tasks) in stats::AGE_BUCKETS.iter().zip(&report.open) {
+                    println!("{} ({})", label, tasks.len());
+                    for t in tasks { println!("{}", line(t, t.age > stale)); }
+                }
+                println!("{}", output::heading(&tr!("🔇 IN PROGRESS, QUIET FOR:")));
+                if report.in_progress.is_empty() { println!("{}", tr!("Nothing in progress.")); }
+                for t in &report.in_progress { println!("{}", line(t, stall > 0 && t.age > stall)); }
+            }
+            StatsCommands::Estimates { since } => {
+                let report = stats::estimates(&database, since)?;
+                let ratio = |pct: i64| format!("{:.1}x", pct as f64 / 100.0);
+                println!("{}", output::heading(&tr!("🎯 ESTIMATES VS ACTUALS:")));
+                if report.rows.is_empty() { println!("{}", tr!("No estimated task closed yet (tt task add --estimate).")); return Ok(()); }
+                println!("{}", tr!("{:<10} {:<16} {:>6} {:>8} {:>10} {:>7} {:>10} {:>9}", tr!("ENGINE"), tr!("AGENT"), tr!("TASKS"), tr!("MEDIAN"), tr!("ON TARGET"), tr!("POINTS"), tr!("TIME/PT"), tr!("COST/PT")));
+                for r in &report.rows {
+                    let (time, cost) = if r.points > 0 {
+                        (period::format_span(r.point_secs / r.points), format!("${:.2}", r.point_cost / r.points as f64))
+                    } else { ("-".to_string(), "-".to_string()) };
+                    println!("{:<10} {:<16} {:>6} {:>8} {:>10} {:>7} {:>10} {:>9}", r.engine, r.agent, r.timed, r.median_ratio.map(ratio).unwrap_or_else(|| "-".to_string()),
+                        if r.timed > 0 { format!("{}/{}", r.on_target, r.timed) } else { "-".to_string() }, r.points, time, cost);
+                }
+                println!("{}", tr!("MEDIAN is actual time over estimated time; ON TARGET counts tasks within {}-{}% of their estimate.", stats::ON_TARGET.0, stats::ON_TARGET.1));
+                if !report.weeks.is_empty() {
+                    println!("{}", output::heading(&tr!("📅 BY WEEK:")));
+                    for w in &report.weeks { println!("{}", tr!("{}  {} task(s)  {}", w.week, w.tasks, ratio(w.median_ratio))); }
+                }
             }
         },
         Commands::Costs { action } => match action {
-            CostsCommands::List => {
-                let mut stmt = database.conn.prepare("SELECT task_id, agent_name, model, input_tokens, output_tokens, cost_usd, timestamp FROM costs ORDER BY timestamp DESC")?;
-                let rows = stmt.query_map([], |row| Ok((
-                    row.get::<_, String>(0)?,
-                    row.get::<_, String>(1)?,
-                    row.get::<_, String>(2)?,
-                    row.get::<_, i32>(3)?,
-                    row.get::<_, i32>(4)?,
-                    row.get::<_, f64>(5)?,
-                    row.get::<_, i64>(6)?
-                )))?;
-                println!("💸 DETAILED COSTS:");
-                println!("{:<10} {:<15} {:<15} {:<10} {:<10} {:<10}", "TASK", "AGENT", "MODEL", "IN", "OUT", "COST($)");
-                for r in rows {
-                    let (task, agent, model, input, output, cost, _ts) = r?;
-                    println!("{:<10} {:<15} {:<15} {:<10} {:<10} ${:<10.4}", task, agent, model, input, output, cost);
+            CostsCommands::List { task, agent, model, since, limit } => {
+                let (rows, totals) = database.list_costs(&db::CostFilter { task, agent, model, since, limit })?;
+                println!("{}", tr!("💸 DETAILED COSTS:"));
+                println!("{}", tr!("{:<10} {:<15} {:<15} {:<10} {:<10} {:<10}", tr!("TASK"), tr!("AGENT"), tr!("MODEL"), tr!("IN"), tr!("OUT"), tr!("COST($)")));
+                for r in &rows {
+                    println!("{:<10} {:<15} {:<15} {:<10} {:<10} ${:<10.4}", r.task_id, r.agent_name, r.model, r.input_tokens, r.output_tokens, r.cost_usd);
                 }
+                println!("{}", "─".repeat(74));
+                let label = if (rows.len() as i64) < totals.rows { tr!("TOTAL ({} of {} rows shown)", rows.len(), totals.rows) } else { tr!("TOTAL ({} rows)", totals.rows) };
+                println!("{:<41} {:<10} {:<10} ${:<10.4}", label, totals.input_tokens, totals.output_tokens, totals.cost_usd);
             }
             CostsCommands::Summary => {
                 let mut stmt = database.conn.prepare("SELECT model, SUM(input_tokens), SUM(output_tokens), SUM(cost_usd) FROM costs GROUP BY model")?;
@@ -267,103 +1210,524 @@ fn main() -> Result<()> {
                     row.get::<_, i64>(2)?,
                     row.get::<_, f64>(3)?
                 )))?;
-                println!("📊 COST SUMMARY BY MODEL:");
+                println!("{}", tr!("📊 COST SUMMARY BY MODEL:"));
                 for r in rows {
                     let (model, input, output, cost) = r?;
-                    println!("- {}: {} in / {} out | Total: ${:.4}", model, input, output, cost);
+                    println!("{}", tr!("- {}: {} in / {} out | Total: ${:.4}", model, input, output, cost));
                 }
             }
             CostsCommands::Add { task_id, agent, model, input, output, cost } => {
                 database.log_cost(&task_id, &agent, &model, input, output, cost)?;
-                println!("✅ Cost entry added for task {}.", task_id);
+                println!("{}", tr!("✅ Cost entry added for task {}.", task_id));
             }
         },
-        Commands::Start { task_id, agent_name, engine } => {
-            println!("🎯 START: Dispatching task '{}' to agent '{}' using engine '{}'...", task_id, agent_name, engine);
-            let w = worker::Worker::new(task_id.clone(), agent_name.clone(), work_dir, engine.clone());
-            w.spawn()?;
-            database.log_audit(&agent_name, "task_started", &task_id, "success")?;
-            database.conn.execute("UPDATE tasks SET assignee = ?1, status = 'in_progress', engine = ?2 WHERE id = ?3", params![agent_name, engine, task_id])?;
-            println!("🚀 Agent '{}' is now on the hook for '{}'.", agent_name, task_id);
+        Commands::Start { task_id: Some(task_id), agent_name: Some(agent_name), engine, replace, allow_dirty, dry_run, brief_from_mail, brief_stdin } => {
+            if let Some(s) = &scope { s.ensure(&database, &task_id)?; }
+            println!("{}", tr!("🎯 START: Dispatching task '{}' to agent '{}' using engine '{}'...", task_id, agent_name, engine));
+            let brief = match (brief_from_mail, brief_stdin) {
+                (Some(id), _) => Some(service::mail_brief(&database, id)?),
+                (None, true) => {
+                    let mut text = String::new();
+                    std::io::Read::read_to_string(&mut std::io::stdin(), &mut text)?;
+                    Some(format!("## BRIEF\n\n{}", text.trim_end()))
+                }
+                (None, false) => None,
+            };
+            let req = service::Sling { task_id: task_id.clone(), agent: agent_name.clone(), engine, replace, allow_dirty, dry_run, brief };
+            service::sling(&database, &work_dir, &req, &actor)?;
+            if let (Some(id), false) = (brief_from_mail, dry_run) {
+                database.conn.execute("UPDATE messages SET status = 'read' WHERE id = ?1", params![id])?;
+            }
+            if dry_run { return Ok(()); }
+            println!("{}", tr!("🚀 Agent '{}' is now on the hook for '{}'.", agent_name, task_id));
         },
         Commands::Handoff { action } => match action {
             HandoffCommands::New => {
-                println!("🤝 HANDOFF: Initiating session transfer...");
-                println!("[HINT] Current session context saved. Run 'tt sling' with a new agent name to resume.");
+                println!("{}", tr!("🤝 HANDOFF: Initiating session transfer..."));
+                println!("{}", tr!("[HINT] Current session context saved. Run 'tt sling' with a new agent name to resume."));
             }
             HandoffCommands::Status => {
-                println!("🔍 HANDOFF STATUS: No pending transfers.");
+                println!("{}", tr!("🔍 HANDOFF STATUS: No pending transfers."));
             }
         },
-        Commands::Done { task_id } => {
-            println!("🏁 DONE: Closing task '{}'...", task_id);
-            // Find the assignee to nuke their dir
-            let mut stmt = database.conn.prepare("SELECT assignee FROM tasks WHERE id = ?1")?;
-            let mut rows = stmt.query_map(params![task_id], |row| row.get::<_, Option<String>>(0))?;
-            if let Some(assignee) = rows.next() {
-                if let Some(name) = assignee? {
-                    println!("🧹 Cleaning up worker '{}'...", name);
-                    let _ = worker::Worker::nuke(&name, &work_dir);
-                }
-            }
-            database.conn.execute("UPDATE tasks SET status = 'closed' WHERE id = ?1", params![task_id])?;
-            database.log_audit("user", "task_closed", &task_id, "success")?;
-            println!("✅ Task '{}' is now marked as DONE and cleaned up.", task_id);
+        Commands::Start { .. } => unreachable!("picked in resolve_ids"),
+        Commands::Done { task_id, filter, dry_run } => {
+            let ids = match (task_id, filter) {
+                (Some(id), _) => vec![id],
+                (None, Some(filter)) => filter.select(&database, "id", "status != 'closed'")?,
+                (None, None) => unreachable!("picked in resolve_ids"),
+            };
+            if ids.is_empty() { println!("{}", tr!("No unfinished task matches the filter.")); }
+            for task_id in ids {
+                if dry_run {
+                    print_dry_run(&service::plan_done(&database, &work_dir, &task_id)?);
+                    continue;
+                }
+                println!("{}", tr!("🏁 DONE: Closing task '{}'...", task_id));
+                let closed = service::done(&database, &work_dir, &task_id, &actor)?;
+                if let Some(mc) = &closed.conflicts {
+                    println!("{}", tr!("⚠️  {} will conflict with {} in: {} (see tt merge {} --resolve)", mc.branch, mc.base, mc.conflicts.join(", "), task_id));
+                }
+                if let Some(name) = &closed.worker { println!("{}", tr!("🧹 Cleaned up worker '{}'.", name)); }
+                match closed.estimate {
+                    Some((estimate @ service::Estimate::Secs(estimated), secs, _)) => println!("{}", tr!("⏱️  Took {} against an estimate of {} ({:.1}x).",
+                        period::format_span(secs), estimate, secs as f64 / estimated as f64)),
+                    Some((estimate, secs, cost)) => println!("{}", tr!("⏱️  Took {} and ${:.2} for {}.", period::format_span(secs), cost, estimate)),
+                    None => {}
+                }
+                if let Some(archive) = &closed.archive {
+                    println!("{}", tr!("🗜️  Logs archived to {}", archive.display()));
+                }
+                println!("{}", tr!("✅ Task '{}' is now marked as DONE and cleaned up.", task_id));
+            }
         },
-        Commands::Peek { agent_name } => {
-            println!("👀 PEEK: Viewing recent activity for agent '{}'...", agent_name);
-            let mut stmt = database.conn.prepare("SELECT id FROM tasks WHERE assignee = ?1 AND status = 'in_progress'")?;
-            let mut rows = stmt.query_map(params![agent_name], |row| row.get::<_, String>(0))?;
-            if let Some(task_id) = rows.next() {
-                let task_id = task_id?;
-                let log_path = work_dir.join(".logs").join("tasks").join(&task_id).join(format!("{}.log", agent_name));
-                if log_path.exists() {
-                    let content = std::fs::read_to_string(&log_path)?;
-                    let lines: Vec<&str> = content.lines().collect();
-                    let last_lines = if lines.len() > 10 { &lines[lines.len()-10..] } else { &lines[..] };
-                    println!("--- LOG TAIL (last 10 lines) ---");
-                    for line in last_lines { println!("{}", line); }
-                    println!("--------------------------------");
-                } else {
-                    println!("❌ Log file not found at {:?}", log_path);
+        Commands::Undone { task_id } => {
+            let reopened = service::undone(&database, &work_dir, &task_id, &actor)?;
+            println!("{}", tr!("↩️  Task '{}' reopened.", task_id));
+            if let Some(from) = &reopened.restored { println!("{}", tr!("📦 Work restored from {}", from)); }
+            if let Some(agent) = &reopened.agent {
+                println!("{}", tr!("💡 Resume with: tt start {} {} --engine {}", task_id, agent, reopened.engine.as_deref().unwrap_or("gemini")));
+            }
+        },
+        Commands::Peek { agent_name, task, lines } => {
+            println!("{}", tr!("👀 PEEK: Viewing recent activity for agent '{}'...", agent_name));
+            // Current work first, then the most recent finished tasks.
+            let mut stmt = database.conn.prepare(
+                "SELECT id, title, status FROM tasks WHERE assignee = ?1
+                 ORDER BY status = 'in_progress' DESC, created_at DESC LIMIT 10")?;
+            let tasks = stmt.query_map(params![agent_name], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?, row.get::<_, String>(2)?)))?
+                .collect::<rusqlite::Result<Vec<_>>>()?;
+            for (id, title, status) in &tasks {
+                println!("{} {} [{}] {}", if task.as_deref() == Some(id.as_str()) { "▶" } else { "•" }, id, status, title);
+            }
+            let active: Vec<&String> = tasks.iter().filter(|t| t.2 == "in_progress").map(|t| &t.0).collect();
+            let task_id = match (task, active.as_slice()) {
+                (Some(t), _) => Some(t),
+                (None, [one]) => Some(one.to_string()),
+                (None, []) => tasks.first().map(|t| t.0.clone()),
+                (None, many) => return Err(error::ThinkTodoError::InvalidArgument(format!(
+                    "'{}' has {} in_progress tasks ({}); pick one with --task", agent_name, many.len(),
+                    many.iter().map(|s| s.as_str()).collect::<Vec<_>>().join(", "))).into()),
+            };
+            let log = match &task_id {
+                Some(t) => logs::read_task_logs(&work_dir, t, Some(&agent_name))?.into_iter().next(),
+                None => None,
+            };
+            if let Some(log) = log {
+                let all: Vec<&str> = log.content.lines().collect();
+                println!("{}", tr!("--- LOG TAIL {} (last {} lines) ---", task_id.unwrap_or_default(), lines));
+                for line in &all[all.len().saturating_sub(lines)..] { println!("{}", line); }
+                println!("--------------------------------");
+            } else {
+                match session::backend(&work_dir)?.capture(&worker::Worker::session_name(&agent_name), lines) {
+                    Ok(screen) => {
+                        println!("{}", tr!("--- SESSION SCREEN (no log file) ---"));
+                        println!("{}", screen);
+                        println!("--------------------------------");
+                    }
+                    Err(_) => println!("{}", tr!("❌ No log and no live session for agent '{}'.", agent_name)),
                 }
+            }
+        },
+        Commands::Replay { since, target } => {
+            let decisions = replay::decisions(&database, since, target.as_deref())?;
+            println!("{}", tr!("🎞️  REPLAY: {} decision(s) since {}", decisions.len(), period::format_time(since)));
+            for d in &decisions {
+                println!("[{}] {} {} {} ({})", period::format_time(d.timestamp), d.actor, d.action, d.target, d.status);
+                println!("{}", tr!("   because {}", d.reason()));
+            }
+        }
+        Commands::Export { tables, format, since, out } => {
+            let out = out.unwrap_or_else(|| work_dir.join("exports"));
+            let progress = output::Progress::new(tables.len());
+            let exported = export::export(&database, &tables, &format, &since.unwrap_or(export::Since::All), &out, &progress)?;
+            progress.finish();
+            for e in exported {
+                match &e.path {
+                    Some(path) => println!("{}", tr!("📦 {:<11} {:>6} row(s) -> {}{}", e.table, e.rows, path.display(), if e.full { tr!(" (full)") } else { String::new() })),
+                    None => println!("{}", tr!("📦 {:<11}      - nothing new", e.table)),
+                }
+            }
+        }
+        Commands::Daily { send } => {
+            let now = chrono::Local::now().timestamp();
+            let text = daily::compose(&database, daily::since(&database, now)?, now)?;
+            println!("{}", tr!("📰 {}\n{}", daily::subject(now), text));
+            if send {
+                let sent = daily::deliver(&database, &config::Config::load(&work_dir)?, &daily::subject(now), &text)?;
+                database.set_setting(daily::LAST_KEY, &now.to_string())?;
+                database.log_audit(&actor, "daily_digest", "workspace", &sent.join(","))?;
+                println!("{}", tr!("📨 Sent by {}.", if sent.is_empty() { tr!("nothing ([daily] has no channel)") } else { sent.join(", ") }));
+            }
+        }
+        Commands::Bot { action: BotCommands::Telegram { token, once } } => bot::telegram(&database, &work_dir, token, once)?,
+        Commands::Groom { action: None, engine } => {
+            let run = groom::start(&database, &work_dir, &engine, &actor)?;
+            println!("{}", tr!("🧹 Grooming the backlog as {} on {}.", run, engine));
+            println!("{}", tr!("When it is done, review with tt groom show and apply with tt groom apply."));
+        }
+        Commands::Groom { action: Some(GroomCommands::Show { run }), .. } => {
+            let run = groom::run_id(&database, run.as_deref())?;
+            let text = groom::read_patch(&database, &work_dir, &run)?;
+            println!("{}", output::heading(&tr!("🧹 GROOMING {} ({}):", run, groom::patch_path(&work_dir, &run).display())));
+            print!("{}", text);
+            let changes = groom::parse(&text)?;
+            println!("{}", tr!("{} change(s); apply with tt groom apply --run {}", changes.len(), run));
+        }
+        Commands::Groom { action: Some(GroomCommands::Apply { run, dry_run }), .. } => {
+            let run = groom::run_id(&database, run.as_deref())?;
+            let changes = groom::parse(&groom::read_patch(&database, &work_dir, &run)?)?;
+            if dry_run {
+                for c in &changes { println!("{}", tr!("Would apply: {}", c)); }
             } else {
-                println!("❌ No active task found for agent '{}'.", agent_name);
+                groom::apply(&database, &run, &changes, &actor)?;
+                for c in &changes { println!("✅ {}", c); }
+                println!("{}", tr!("🧹 Applied {} change(s) from {}.", changes.len(), run));
+            }
+        }
+        Commands::Bench { action } => match action {
+            BenchCommands::Run { suite, engines } => {
+                let engines = if engines.is_empty() { config::Config::load(&work_dir)?.bench.engines } else { engines };
+                if engines.is_empty() { anyhow::bail!("no engines to benchmark: pass --engines or set [bench] engines"); }
+                let (run_id, dispatched) = bench::run(&database, &work_dir, &suite, &engines, &actor)?;
+                println!("{}", tr!("🏁 Bench run {} ({} x {}):", run_id, suite, engines.join(", ")));
+                for d in &dispatched {
+                    match &d.error {
+                        None => println!("{}", tr!("  {:<10} {:<8} dispatched as {}", d.engine, d.case_id, d.task_id)),
+                        Some(e) => println!("{}", tr!("  {:<10} {:<8} not started: {}", d.engine, d.case_id, e)),
+                    }
+                }
+                println!("{}", tr!("The monitor scores each case when it finishes; see tt bench results."));
+            }
+            BenchCommands::Results { run } => {
+                let Some((run_id, results)) = bench::results(&database, run.as_deref())? else {
+                    println!("{}", tr!("No bench runs yet (tt bench run --suite basic)."));
+                    return Ok(());
+                };
+                let suite: String = database.conn.query_row("SELECT suite FROM bench_results WHERE run_id = ?1 LIMIT 1", [&run_id], |row| row.get(0))?;
+                println!("{}", tr!("🏁 BENCH RUN {} ({}):", run_id, suite));
+                println!("{}", tr!("{:<10} {:<8} {:<8} {:>8} {:>10}  {}", tr!("ENGINE"), tr!("CASE"), tr!("OUTCOME"), tr!("TIME"), tr!("COST"), tr!("DETAIL")));
+                for r in &results {
+                    let time = r.secs.filter(|_| r.outcome.is_some()).map(period::format_span).unwrap_or_else(|| "-".to_string());
+                    println!("{:<10} {:<8} {:<8} {:>8} {:>10}  {}", r.engine, r.case_id, r.outcome.as_deref().unwrap_or("running"), time,
+                        format!("${:.4}", r.cost_usd), r.detail.as_deref().unwrap_or(""));
+                }
+                println!("{}", tr!("\n📊 ALL RUNS OF {}:", suite));
+                println!("{}", tr!("{:<10} {:>6} {:>6} {:>8} {:>12} {:>10}", tr!("ENGINE"), tr!("CASES"), tr!("PASSED"), tr!("RATE"), tr!("AVG PASS"), tr!("AVG COST")));
+                for e in bench::summary(&database, &suite)? {
+                    println!("{:<10} {:>6} {:>6} {:>8} {:>12} {:>10}", e.engine, e.cases, e.passed, format!("{:.0}%", 100.0 * e.passed as f64 / e.cases as f64),
+                        e.avg_pass_secs.map(|s| period::format_span(s as i64)).unwrap_or_else(|| "-".to_string()), format!("${:.4}", e.avg_cost_usd));
+                }
             }
         },
+        Commands::Watch { interval, count, command } => {
+            let inner = Cli::try_parse_from(std::iter::once("tt".to_string()).chain(command.iter().cloned())).unwrap_or_else(|e| e.exit());
+            if matches!(inner.command, Commands::Watch { .. }) || mutates(&inner.command) {
+                anyhow::bail!("tt watch only reruns commands that change nothing; 'tt {}' isn't one", command.join(" "));
+            }
+            watch::run(&command, interval, count)?;
+        }
         Commands::Trail => {
-            println!("🛤️ TRAIL: Recent System Activity");
-            let mut stmt = database.conn.prepare("SELECT actor, action, target, status, timestamp FROM audit_logs ORDER BY timestamp DESC LIMIT 15")?;
+            println!("{}", output::heading(&tr!("🛤️ TRAIL: Recent System Activity")));
+            let mut stmt = database.conn.prepare("SELECT actor, action, target, status, timestamp, metadata FROM audit_logs ORDER BY timestamp DESC LIMIT 15")?;
             let rows = stmt.query_map([], |row| Ok((
                 row.get::<_, String>(0)?,
                 row.get::<_, String>(1)?,
                 row.get::<_, String>(2)?,
                 row.get::<_, String>(3)?,
-                row.get::<_, i64>(4)?
+                row.get::<_, i64>(4)?,
+                row.get::<_, Option<String>>(5)?
             )))?;
             for r in rows {
-                let (actor, action, target, status, ts) = r?;
-                println!("[{}] {} -> {} on {} ({})", ts, actor, action, target, status);
+                let (actor, action, target, status, ts, metadata) = r?;
+                println!("{}", output::show(&tr!("[{}] {} -> {} on {} ({})", period::format_time(ts), actor, action, target, status)));
+                let Some(m) = metadata else { continue };
+                let spent = serde_json::from_str::<serde_json::Value>(&m).ok()
+                    .and_then(|v| Some((v["duration_secs"].as_i64()?, v["cost_usd"].as_f64()?)));
+                match spent {
+                    Some((secs, cost)) if action == "task_closed" => println!("{}", tr!("    took {} and ${:.2}", period::format_span(secs), cost)),
+                    Some((secs, cost)) if secs > 0 || cost > 0.0 => println!("{}", tr!("    {} and ${:.2} in so far", period::format_span(secs), cost)),
+                    _ => {}
+                }
+                println!("    {}", m);
             }
         }
-        Commands::Nudge { agent_name, message } => {
-            println!("🔔 NUDGING agent '{}' with message: {}", agent_name, message);
-            if tmux::Tmux::has_session(&agent_name) {
-                tmux::Tmux::display_message(&agent_name, &format!("!!! NUDGE: {} !!!", message))?;
-                database.log_audit("user", "nudge_sent", &agent_name, "success")?;
-                println!("✅ Message displayed in agent's tmux session.");
+        Commands::Nudge { agent_name, message, filter } => {
+            let agents = match (agent_name, filter) {
+                (Some(agent), _) => vec![agent],
+                (None, Some(filter)) => filter.select(&database, "assignee", "status = 'in_progress' AND assignee IS NOT NULL")?,
+                (None, None) => unreachable!("clap requires an agent or a filter"),
+            };
+            if agents.is_empty() { println!("{}", tr!("No agent is working on a task that matches the filter.")); }
+            for agent_name in agents {
+                println!("{}", tr!("🔔 NUDGING agent '{}' with message: {}", agent_name, message));
+                if service::nudge(&database, &work_dir, &agent_name, &message, &actor)? {
+                    println!("{}", tr!("✅ Message displayed in agent's session."));
+                } else {
+                    println!("{}", tr!("❌ Agent '{}' has no session that can display messages. Sent to its inbox instead.", agent_name));
+                }
+            }
+        }
+        Commands::Diff { task_id, stat } => {
+            let d = merge::TaskDiff::run(&database, &work_dir, &task_id)?;
+            println!("{}", tr!("🔍 DIFF: {} vs {} (rig '{}')", d.branch, d.base, d.rig));
+            if d.stat.is_empty() { println!("{}", tr!("No commits on {} yet.", d.branch)); } else { println!("{}", d.stat); }
+            if !stat && !d.committed.is_empty() { println!("\n{}", d.committed); }
+            if !d.uncommitted.is_empty() || !d.untracked.is_empty() {
+                println!("{}", tr!("\n📝 Uncommitted in worker tree:"));
+                if !stat && !d.uncommitted.is_empty() { println!("{}", d.uncommitted); }
+                for f in &d.untracked { println!("{}", tr!("   + {} (untracked)", f)); }
+            }
+        }
+        Commands::Merge { task_id, check, resolve } => {
+            let mc = merge::MergeCheck::run(&database, &task_id)?;
+            println!("{}", tr!("🔀 MERGE: {} → {} (rig '{}', {} commit(s) ahead)", mc.branch, mc.base, mc.rig, mc.ahead));
+            if !mc.conflicts.is_empty() {
+                println!("{}", tr!("❌ Trial merge conflicts in {} file(s):", mc.conflicts.len()));
+                for f in &mc.conflicts { println!("   - {}", f); }
+                database.log_audit(&actor, "merge_conflict", &task_id, &mc.conflicts.join(","))?;
+                if resolve {
+                    let engine = database.conn.query_row("SELECT engine FROM tasks WHERE id = ?1", params![task_id], |row| row.get::<_, Option<String>>(0))?
+                        .unwrap_or_else(|| "gemini".to_string());
+                    let mut w = worker::Worker::new(task_id.clone(), format!("resolver-{}", task_id), work_dir, engine, "resolver".to_string());
+                    w.brief = Some(mc.resolver_brief());
+                    w.allow_dirty = true;
+                    w.spawn()?;
+                    database.log_audit(&actor, "resolver_spawned", &task_id, "success")?;
+                    return Ok(());
+                }
+                println!("{}", tr!("💡 Re-run with --resolve to hand the conflicts to a resolver worker."));
+                return Err(error::ThinkTodoError::MergeConflict { branch: mc.branch, base: mc.base, files: mc.conflicts }.into());
+            }
+            if check || mc.ahead == 0 {
+                println!("{}", tr!("✅ Merges cleanly.{}", if mc.ahead == 0 { tr!(" Nothing to merge.") } else { String::new() }));
+                return Ok(());
+            }
+            let _lock = database.lock(&format!("task {}", task_id), &actor, "merge")?;
+            let state = git::Git::state(&mc.rig_path, false)?;
+            if !state.dirty.is_empty() {
+                return Err(error::ThinkTodoError::RigDirty(format!("rig '{}' has {} uncommitted change(s)", mc.rig, state.dirty.len())).into());
+            }
+            let title: String = database.conn.query_row("SELECT title FROM tasks WHERE id = ?1", params![task_id], |row| row.get(0))?;
+            git::Git::run(&mc.rig_path, &["merge", "--no-ff", "-m", &format!("Merge {}: {}", mc.branch, title), &mc.branch])?;
+            database.log_audit(&actor, "task_merged", &task_id, "success")?;
+            println!("{}", tr!("✅ Merged {} into {}.", mc.branch, mc.base));
+        }
+        Commands::Plan { action: Some(PlanCommands::FromFile { file, dry_run }), .. } => {
+            let text = match std::fs::read_to_string(&file) {
+                Ok(text) => text,
+                Err(e) => anyhow::bail!("can't read {}: {}", file.display(), e),
+            };
+            eprintln!("{}", tr!("🧠 Asking the planner about {}...", file.display()));
+            let tasks = spec::parse(&spec::ask(&database, &work_dir, &text)?)?;
+            println!("{}", output::heading(&tr!("🗂️  PROPOSED TASKS ({}):", tasks.len())));
+            for t in &tasks {
+                let tags: String = t.tags.iter().map(|t| format!(" #{}", t)).collect();
+                let estimate = t.estimate.as_deref().map(|e| format!(" ~{}", e)).unwrap_or_default();
+                println!("- {}: {}{}{}", t.key, t.title, tags, estimate);
+                if !t.depends_on.is_empty() { println!("{}", tr!("    after: {}", t.depends_on.join(", "))); }
+                for (id, title) in service::similar_tasks(&database, &t.title)? { println!("{}", tr!("    ⚠️  like [{}] {}", id, title)); }
+            }
+            if dry_run { return Ok(()); }
+            if !cli.yes {
+                if !resolve::interactive() {
+                    println!("{}", tr!("Nothing added; re-run with --yes to add them."));
+                    return Ok(());
+                }
+                let add = dialoguer::Confirm::new().with_prompt(tr!("Add these {} task(s)?", tasks.len())).default(false).interact()
+                    .map_err(|dialoguer::Error::IO(e)| e)?;
+                if !add { return Ok(()); }
+            }
+            let created = spec::create(&database, &work_dir, &tasks, &file.display().to_string(), &actor)?;
+            for (key, id) in &created { println!("{}", tr!("✅ Task [{}] registered ({}).", id, key)); }
+        }
+        Commands::Plan { by, budget, .. } => {
+            let Some(by) = by else { anyhow::bail!("give --by") };
+            let budget = budget.or_else(|| {
+                let cap = config::Config::load(&work_dir).ok()?.budget.daily_usd_cap;
+                let days = ((by - chrono::Local::now().timestamp()) as f64 / 86400.0).ceil();
+                (cap > 0.0).then_some(cap * days)
+            });
+            let report = stats::plan(&database, by, budget)?;
+            println!("{}", tr!("🗓️  PLAN: {} task(s) left ({} open, {} in progress), {} until {}",
+                report.open + report.in_progress, report.open, report.in_progress, period::format_span(report.window_secs), period::format_due(by)));
+            if let Some(b) = report.budget { println!("{}", tr!("   Budget: ${:.2}", b)); }
+            if report.options.is_empty() {
+                println!("{}", tr!("Not enough history: no attempt has finished with tt done yet."));
+                return Ok(());
+            }
+            println!("{}", tr!("{:<10} {:<16} {:>10} {:>10} {:>8} {:>11} {:>7}", tr!("ENGINE"), tr!("MODEL"), tr!("TASK TIME"), tr!("COST/TASK"), tr!("WORKERS"), tr!("TOTAL COST"), tr!("BUDGET")));
+            for o in &report.options {
+                println!("{:<10} {:<16} {:>10} {:>10} {:>8} {:>11} {:>7}", o.engine, o.model.as_deref().unwrap_or("-"), period::format_span(o.secs_per_task),
+                    format!("${:.4}", o.cost_per_task), o.workers, format!("${:.2}", o.total_cost), if o.fits_budget { "ok" } else { "over" });
+            }
+            match report.recommended.map(|i| &report.options[i]) {
+                Some(o) => println!("{}", tr!("✅ Recommended: {}{} with max_workers = {}", o.engine, o.model.as_deref().map(|m| format!(" ({})", m)).unwrap_or_default(), o.workers.max(1))),
+                None => println!("{}", tr!("❌ No engine clears the backlog within budget; move the date or raise the budget.")),
+            }
+        }
+        Commands::Freeze { reason } => {
+            let reason = reason.unwrap_or_else(|| "frozen by user".to_string());
+            match service::freeze(&database, &reason, &actor)? {
+                Some(current) => println!("{}", tr!("🧊 Already frozen: {}", current)),
+                None => println!("{}", tr!("🧊 Workspace frozen: {}. No agent will be started until 'tt unfreeze'.", reason)),
+            }
+        }
+        Commands::Unfreeze => {
+            if service::unfreeze(&database, &work_dir, &actor)? {
+                println!("{}", tr!("🔥 Workspace unfrozen. Dispatch is enabled again."));
             } else {
-                println!("❌ Agent '{}' has no active tmux session. Logging to mail instead...", agent_name);
-                database.send_mail("user", &agent_name, "NUDGE: Action Required", &message)?;
-                database.log_audit("user", "nudge_mailed", &agent_name, "success")?;
-                println!("✅ Nudge sent to agent's inbox.");
+                println!("{}", tr!("Workspace is not frozen."));
+            }
+        }
+        Commands::Completions { .. } => unreachable!("handled before the workspace is opened"),
+        Commands::Conventions { format, output } => {
+            let conventions = conventions::collect(&config::Config::load(&work_dir)?, None);
+            let text = match format.as_str() {
+                "json" => serde_json::to_string_pretty(&conventions)? + "\n",
+                _ => conventions.markdown(),
+            };
+            match output {
+                Some(path) => {
+                    std::fs::write(&path, text)?;
+                    println!("{}", tr!("📜 Conventions written to {}", path.display()));
+                }
+                None => print!("{}", text),
+            }
+        }
+        Commands::Team { action } => match action {
+            TeamCommands::Create { name, members, lead } => {
+                database.save_team(&name, lead.as_deref(), scope.as_ref(), &members)?;
+                database.log_audit(&actor, "team_saved", &name, &members.join(","))?;
+                println!("{}", tr!("👥 Team '{}': {} member(s){}{}", name, members.len(),
+                    lead.map(|l| tr!(", led by {}", l)).unwrap_or_default(),
+                    scope.map(|s| tr!(", scope {}", s)).unwrap_or_default()));
             }
+            TeamCommands::List => {
+                let teams = database.teams(None)?;
+                if teams.is_empty() { println!("{}", tr!("No teams. Create one with: tt team create infra --members a,b --lead admin-infra")); }
+                for t in teams {
+                    let (active, closed, cost) = database.team_stats(&t.name)?;
+                    println!("{}", tr!("👥 {} (lead: {}, scope: {})", t.name, t.lead.as_deref().unwrap_or("-"), t.scope.map(|s| s.to_string()).unwrap_or_else(|| "all".to_string())));
+                    println!("{}", tr!("   members: {}", if t.members.is_empty() { "-".to_string() } else { t.members.join(", ") }));
+                    println!("{}", tr!("   {} in progress · {} closed · ${:.4}", active, closed, cost));
+                }
+            }
+            TeamCommands::Delete { name } => {
+                if !database.delete_team(&name)? { anyhow::bail!("No team named '{}'", name); }
+                database.log_audit(&actor, "team_deleted", &name, "success")?;
+                println!("{}", tr!("🗑️  Team '{}' deleted.", name));
+            }
+        },
+        Commands::Sandbox { action: SandboxCommands::Reset } => {
+            if sandbox::reset(&work_dir)? { println!("{}", tr!("🧹 Sandbox discarded.")); } else { println!("{}", tr!("No sandbox to discard.")); }
+        }
+        Commands::Scope { action } => match action {
+            ScopeCommands::Add { name, filter } => {
+                database.save_scope(&name, &filter)?;
+                println!("{}", tr!("🔭 Scope '{}' = {}", name, filter));
+            }
+            ScopeCommands::List => {
+                let saved = database.saved_scopes()?;
+                if saved.is_empty() { println!("{}", tr!("No saved scopes. Add one with: tt scope add infra tag:infra")); }
+                for (name, scope) in saved { println!("{:<16} {}", name, scope); }
+            }
+            ScopeCommands::Remove { name } => {
+                if !database.remove_scope(&name)? { anyhow::bail!("No saved scope named '{}'", name); }
+                println!("{}", tr!("🗑️  Scope '{}' removed.", name));
+            }
+        },
+        Commands::User { action } => match action {
+            // An agent's token would let it act as a person on the web.
+            UserCommands::Add { .. } | UserCommands::Remove { .. } if actor != "user" => anyhow::bail!("only the user at the terminal can manage web users, not '{}'", actor),
+            UserCommands::Add { name } => {
+                let token = database.add_user(&name)?;
+                database.log_audit(&actor, "user_added", &name, "success")?;
+                println!("{}", tr!("👤 '{}' can sign in to the web console with this token (shown once):", name));
+                println!("{}", token);
+            }
+            UserCommands::List => {
+                let users = database.users()?;
+                if users.is_empty() { println!("{}", tr!("No web users: web actions are recorded as 'web'. Add one with: tt user add <name>")); }
+                for (name, since) in users { println!("{:<16} {}", name, tr!("added {}", period::format_time(since))); }
+            }
+            UserCommands::Remove { name } => {
+                if !database.remove_user(&name)? { anyhow::bail!("No web user named '{}'", name); }
+                database.log_audit(&actor, "user_removed", &name, "success")?;
+                println!("{}", tr!("🗑️  User '{}' removed; their token no longer works.", name));
+            }
+        },
+        Commands::Notifications { action } => match action {
+            NotificationsCommands::List { all } => {
+                notify::sync(&database, &work_dir)?;
+                let list = notify::list(&database, all)?;
+                if list.is_empty() { println!("{}", tr!("🔕 Nothing needs your attention.")); }
+                for n in list {
+                    let state = match (n.resolved_at, n.read_at) {
+                        (Some(_), _) => "resolved",
+                        (None, Some(_)) => "read",
+                        (None, None) => "unread",
+                    };
+                    println!("{}", output::show(&format!("#{:<4} {} [{}] {}: {} ({})", n.id, period::format_time(n.created_at), n.kind, n.target, health::localize(&n.message), state)));
+                }
+            }
+            NotificationsCommands::Ack { ids, .. } => {
+                let n = notify::ack(&database, &ids, &actor)?;
+                println!("{}", tr!("✅ {} notification(s) marked read.", n));
+            }
+        },
+        Commands::Search { query, limit } => {
+            let query = query.join(" ");
+            let found = search::search(&database, &query, limit)?;
+            if found.is_empty() { println!("{}", tr!("🔍 Nothing matches '{}'.", query)); }
+            if !found.tasks.is_empty() {
+                println!("{}", output::heading(&tr!("📋 Tasks ({})", found.tasks.len())));
+                for t in &found.tasks { println!("{}", output::show(&format!("  [{}] {} ({})", t.id, t.title, t.status))); }
+            }
+            if !found.mail.is_empty() {
+                println!("{}", output::heading(&tr!("📬 Mail ({})", found.mail.len())));
+                for m in &found.mail {
+                    println!("{}", output::show(&format!("  #{} {} -> {}: {}", m.id, m.sender, m.receiver, m.subject)));
+                    println!("      {}", m.snippet.split_whitespace().collect::<Vec<_>>().join(" "));
+                }
+            }
+            if !found.audit.is_empty() {
+                println!("{}", output::heading(&tr!("🛤️ Audit ({})", found.audit.len())));
+                for a in &found.audit {
+                    println!("{}", output::show(&format!("  [{}] {} -> {} on {} ({})", period::format_time(a.timestamp), a.actor, a.action, a.target, a.status)));
+                }
+            }
+        }
+        Commands::Recover { mode } => {
+            let mode = match mode { Some(m) => m, None => config::Config::load(&work_dir)?.recover.mode };
+            if mode != "respawn" && mode != "reopen" { anyhow::bail!("Unknown recover mode '{}' (expected respawn|reopen)", mode); }
+            println!("{}", tr!("🩺 RECOVER: Scanning for orphaned tasks (mode: {})...", mode));
+            let n = recover::Recover::new(work_dir).run(&mode)?;
+            if n == 0 { println!("{}", tr!("✅ All in_progress tasks have a live session.")); } else { println!("{}", tr!("✅ Recovered {} task(s).", n)); }
         }
-        Commands::Serve { port } => {
+        Commands::Gc { dry_run: true } => print_dry_run(&service::plan_gc(&database, &work_dir)?),
+        Commands::Gc { dry_run: false } => {
+            let removed = service::gc(&database, &work_dir, &actor, &output::Progress::new(0))?;
+            for p in &removed.paths { println!("{}", tr!("🧹 Removed {}", p.display())); }
+            println!("{}", tr!("✅ {} idle worker(s) cleaned up.", removed.paths.len()));
+        }
+        Commands::Serve { port, base_path, tls_cert, tls_key, http_port, grpc_port } => {
+            let tls = tls_cert.zip(tls_key).map(|(cert, key)| server::Tls { cert, key, http_port });
             let rt = tokio::runtime::Builder::new_multi_thread()
                 .enable_all()
                 .build()?;
-            rt.block_on(server::start_server(port));
+            if let Some(grpc_port) = grpc_port {
+                // gRPC has no per-workspace routing, so it would only ever reach this one.
+                #[cfg(feature = "grpc")]
+                if !config::Config::load(&work_dir)?.server.tenants.is_empty() {
+                    anyhow::bail!("--grpc-port {}: gRPC serves a single workspace and can't be used with [[server.tenants]]", grpc_port);
+                }
+                #[cfg(feature = "grpc")]
+                rt.spawn(async move { if let Err(e) = grpc::serve(grpc_port).await { tracing::error!("{}", e); } });
+                #[cfg(not(feature = "grpc"))]
+                anyhow::bail!("--grpc-port {}: this tt was built without gRPC support (cargo build --features grpc)", grpc_port);
+            }
+            rt.block_on(server::start_server(port, base_path, tls))?;
         }
     }
     Ok(())
@@ -0,0 +1,123 @@
+use crate::artifacts;
+use crate::db::Db;
+use crate::error::{Result, ThinkTodoError};
+use crate::git::Git;
+use serde::Serialize;
+use std::path::{Component, Path, PathBuf};
+
+/// What a rig task changed: commits on its branch since the rig's base plus anything still uncommitted in the worktree.
+#[derive(Serialize)]
+pub struct TaskDiff {
+    pub rig: String,
+    pub base: String,
+    pub branch: String,
+    pub stat: String,
+    pub committed: String,
+    pub uncommitted: String,
+    pub untracked: Vec<String>,
+}
+
+impl TaskDiff {
+    pub fn run(db: &Db, work_dir: &Path, task_id: &str) -> Result<Self> {
+        let assignee = db.task_assignee(task_id)?;
+        let (rig, rig_path) = db.task_rig(task_id)?
+            .ok_or_else(|| ThinkTodoError::InvalidArgument(format!("task '{}' has no rig, nothing to diff", task_id)))?;
+        let branch = Git::task_branch(task_id);
+        let base = Git::current_branch(&rig_path)?;
+        let (stat, committed) = if Git::branch_exists(&rig_path, &branch) {
+            let range = format!("{}...{}", base, branch);
+            (Git::run(&rig_path, &["diff", "--stat", &range])?, Git::run(&rig_path, &["diff", &range])?)
+        } else {
+            (String::new(), String::new())
+        };
+        // The worker's tree only counts while it is still checked out on the task branch.
+        let worktree = assignee.map(|a| work_dir.join("workers").join(a))
+            .filter(|w| Git::current_branch(w).ok().as_deref() == Some(branch.as_str()));
+        let (uncommitted, untracked) = match worktree {
+            Some(w) => (
+                Git::run(&w, &["diff", "HEAD"])?,
+                Git::run(&w, &["ls-files", "--others", "--exclude-standard"])?.lines().map(|l| l.to_string()).collect(),
+            ),
+            None => (String::new(), Vec::new()),
+        };
+        Ok(Self { rig, base, branch, stat, committed, uncommitted, untracked })
+    }
+}
+
+/// A file an agent attached while working on a rig task, compared with the file at the same
+/// place in the rig, so a reviewer sees what it proposes before approving it.
+#[derive(Serialize)]
+pub struct ArtifactDiff {
+    pub artifact: String,
+    pub name: String,
+    pub task: String,
+    pub rig: String,
+    /// Path of the file in the rig.
+    pub file: String,
+    /// The rig has no such file yet.
+    pub new_file: bool,
+    /// Unified diff from the rig file to the artifact; empty when they are the same.
+    pub diff: String,
+}
+
+impl ArtifactDiff {
+    pub fn run(db: &Db, work_dir: &Path, artifact: &str) -> Result<Self> {
+        let artifact = artifacts::resolve(work_dir, artifact)?;
+        let att = db.task_attachment(&artifact)?
+            .ok_or_else(|| ThinkTodoError::InvalidArgument(format!("artifact {} wasn't attached while working on a task", &artifact[..12])))?;
+        let task = att.task_id.unwrap_or_default();
+        let (rig, rig_path) = db.task_rig(&task)?
+            .ok_or_else(|| ThinkTodoError::InvalidArgument(format!("task '{}' has no rig to compare with", task)))?;
+        let file = att.path.unwrap_or_else(|| att.name.clone());
+        if !Path::new(&file).components().all(|c| matches!(c, Component::Normal(_))) {
+            return Err(ThinkTodoError::InvalidArgument(format!("'{}' is not a path inside the rig", file)));
+        }
+        let new_file = !rig_path.join(&file).is_file();
+        let stored = artifacts::stored(work_dir, &artifact);
+        let old = if new_file { "/dev/null".to_string() } else { file.clone() };
+        // --no-index exits 1 when the files differ; only stderr tells a real failure.
+        let stored = stored.to_string_lossy();
+        let (_, diff, stderr) = Git::output(&rig_path, &["diff", "--no-index", "--no-color", "--", &old, &stored])?;
+        if diff.is_empty() && !stderr.trim().is_empty() { return Err(ThinkTodoError::Git(format!("git diff: {}", stderr.trim()))); }
+        // Name the artifact side after the file it proposes, not the store.
+        let diff = diff.replace(stored.trim_start_matches('/'), &file);
+        Ok(Self { artifact, name: att.name, task, rig, file, new_file, diff })
+    }
+}
+
+/// Outcome of merging a task branch into its rig's current branch, computed without touching any working tree.
+pub struct MergeCheck {
+    pub rig: String,
+    pub rig_path: PathBuf,
+    pub base: String,
+    pub branch: String,
+    pub ahead: u32,
+    pub conflicts: Vec<String>,
+}
+
+impl MergeCheck {
+    pub fn run(db: &Db, task_id: &str) -> Result<Self> {
+        db.task_assignee(task_id)?;
+        let (rig, rig_path) = db.task_rig(task_id)?
+            .ok_or_else(|| ThinkTodoError::InvalidArgument(format!("task '{}' has no rig, nothing to merge", task_id)))?;
+        let branch = Git::task_branch(task_id);
+        if !Git::branch_exists(&rig_path, &branch) {
+            return Err(ThinkTodoError::Git(format!("branch '{}' does not exist in rig '{}'", branch, rig)));
+        }
+        let base = Git::current_branch(&rig_path)?;
+        let ahead = Git::ahead(&rig_path, &base, &branch)?;
+        let conflicts = Git::trial_merge(&rig_path, &base, &branch)?;
+        Ok(Self { rig, rig_path, base, branch, ahead, conflicts })
+    }
+
+    /// Brief handed to a resolver worker.
+    pub fn resolver_brief(&self) -> String {
+        format!(
+            "MERGE CONFLICT RESOLUTION: Your worktree is on branch '{}'. Merging it into '{}' of rig '{}' conflicts in:\n{}\n\n\
+             Run `git merge {}`, resolve every conflict keeping the intent of both sides, run the tests, and commit the merge.",
+            self.branch, self.base, self.rig,
+            self.conflicts.iter().map(|f| format!("- {}", f)).collect::<Vec<_>>().join("\n"),
+            self.base
+        )
+    }
+}
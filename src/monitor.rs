@@ -1,38 +1,448 @@
+use crate::admin::Admin;
+use crate::bench;
+use crate::chaos;
+use crate::config::{Config, EscalationStep};
+use crate::conventions;
+use crate::daily;
 use crate::db::Db;
+use crate::email;
+use crate::git::Git;
+use crate::health;
+use crate::logs;
+use crate::notify;
+use crate::recover::Recover;
+use crate::scope::Scope;
+use crate::service;
+use crate::session;
+use crate::telemetry;
+use crate::usage;
+use crate::worker::Worker;
 use anyhow::Result;
+use rusqlite::params_from_iter;
+use std::collections::hash_map::DefaultHasher;
 use std::fs;
+use std::hash::{Hash, Hasher};
+use std::io::{Read, Seek, SeekFrom};
 use std::path::PathBuf;
 use std::thread;
 use std::time::Duration;
 
-pub struct Monitor { pub work_dir: PathBuf }
+/// Mail the `[[escalation]]` ladder is waiting on an answer to: questions, urgent mail and the
+/// monitor's own alerts that are still unread and not about a task that has since closed. Nudges
+/// mailed by tt nudge are reminders about these, not new ones.
+const UNANSWERED: &str = "status = 'unread'
+    AND (subject LIKE 'QUESTION from %' OR subject LIKE '%URGENT%' OR (sender = 'monitor' AND subject NOT LIKE 'NUDGE:%'))
+    AND NOT EXISTS (SELECT 1 FROM tasks WHERE tasks.status = 'closed' AND instr(messages.subject, '[' || tasks.id || ']') > 0)";
+
+pub struct Monitor {
+    pub work_dir: PathBuf,
+    /// Inject the faults configured in `[chaos]` (`--chaos`); for trying out recovery, never for real runs.
+    pub chaos: bool,
+}
 
 impl Monitor {
-    pub fn new(work_dir: PathBuf) -> Self { Self { work_dir } }
+    pub fn new(work_dir: PathBuf) -> Self { Self { work_dir, chaos: false } }
     pub fn watch(&self) -> Result<()> {
         let db = Db::new(self.work_dir.clone())?;
-        let logs_dir = self.work_dir.join(".logs").join("tasks");
-        println!("👀 Monitor started...");
+        tracing::info!(work_dir = %self.work_dir.display(), chaos = self.chaos, "monitor started");
+        // Startup pass: sessions don't survive a reboot, tasks do.
+        let mode = Config::load(&self.work_dir)?.recover.mode;
+        let recovered = Recover::new(self.work_dir.clone()).run(&mode)?;
+        if recovered > 0 { tracing::info!(recovered, mode = %mode, "recovered orphaned tasks on startup"); }
         loop {
-            if logs_dir.exists() {
-                if let Ok(entries) = fs::read_dir(&logs_dir) {
-                    for entry in entries.flatten() {
-                        let path = entry.path(); 
-                        if !path.is_dir() { continue; }
-                        let task_id = path.file_name().unwrap().to_string_lossy().to_string();
-                        // Fix: Iterate over &path so we don't move it
-                        if let Ok(log_files) = fs::read_dir(&path) {
-                            for log_file in log_files.flatten() {
-                                let content = fs::read_to_string(log_file.path()).unwrap_or_default();
-                                if content.contains("[TASK_DONE]") {
-                                    let _ = db.conn.execute("UPDATE tasks SET status = 'closed' WHERE id = ?1", [task_id.clone()]);
+            // A failed pass (a locked database, a bad edit to think.toml) is logged and the next
+            // one tried: the daemon only stops when it's told to.
+            if let Err(e) = db.set_setting(health::HEARTBEAT_KEY, &chrono::Local::now().timestamp().to_string()) {
+                tracing::error!("could not record the monitor heartbeat: {:#}", e);
+            }
+            if let Err(e) = self.pass(&db) {
+                tracing::error!("monitor pass failed: {:#}", e);
+            }
+            thread::sleep(Duration::from_secs(3));
+        }
+    }
+
+    /// Every check the daemon runs, in order; `tt monitor scan` runs the same pass once.
+    pub fn pass(&self, db: &Db) -> Result<()> {
+        self.chaos(db)?;
+        self.scan(db)?;
+        usage::collect(db, &self.work_dir)?;
+        bench::score(db, &self.work_dir)?;
+        telemetry::ship(db, &self.work_dir)?;
+        email::bridge(db, &self.work_dir)?;
+        daily::send_due(db, &self.work_dir)?;
+        self.check_budget(db)?;
+        self.checkpoint(db)?;
+        self.check_overdue(db)?;
+        self.check_sla(db)?;
+        self.check_escalations(db)?;
+        self.check_checkins(db)?;
+        self.reap(db)?;
+        self.check_supervisors(db)?;
+        self.check_log_quota(db)?;
+        self.auto_archive(db)?;
+        health::measure_disk_usage(db, &self.work_dir)?;
+        // Last, so it sees what this pass escalated.
+        notify::sync(db, &self.work_dir)?;
+        Ok(())
+    }
+
+    /// Resumes workers frozen by chaos once their stall is over and, with `--chaos`, injects new faults.
+    pub fn chaos(&self, db: &Db) -> Result<()> {
+        let backend = session::backend(&self.work_dir)?;
+        chaos::resume(db, backend.as_ref(), self.chaos)?;
+        if self.chaos { chaos::inject(db, &Config::load(&self.work_dir)?, backend.as_ref())?; }
+        Ok(())
+    }
+
+    /// Archives tasks closed longer than `[archive] after` ago, unless someone unarchived them since.
+    pub fn auto_archive(&self, db: &Db) -> Result<()> {
+        let after = Config::load(&self.work_dir)?.archive.after;
+        let Some(secs) = crate::period::span_secs(&after).filter(|_| !after.is_empty()) else { return Ok(()) };
+        let cutoff = chrono::Local::now().timestamp() - secs;
+        let mut stmt = db.conn.prepare(
+            "SELECT t.id FROM tasks t
+             JOIN (SELECT target, MAX(timestamp) AS closed_at FROM audit_logs WHERE action = 'task_closed' GROUP BY target) c ON c.target = t.id
+             WHERE t.status = 'closed' AND t.archived_at IS NULL AND c.closed_at <= ?1
+               AND NOT EXISTS (SELECT 1 FROM audit_logs u WHERE u.target = t.id AND u.action = 'task_unarchived' AND u.timestamp >= c.closed_at)")?;
+        let due = stmt.query_map([cutoff], |row| row.get::<_, String>(0))?.collect::<rusqlite::Result<Vec<_>>>()?;
+        for task_id in due {
+            service::archive(db, &task_id, "monitor")?;
+            tracing::info!(task = %task_id, after = %after, "closed task archived");
+        }
+        Ok(())
+    }
+
+    /// Cuts back task logs that outgrow `[logs] task_max_mb`; the admin is mailed the first time per task.
+    pub fn check_log_quota(&self, db: &Db) -> Result<()> {
+        let max_mb = Config::load(&self.work_dir)?.logs.task_max_mb;
+        if max_mb == 0 { return Ok(()); }
+        let Ok(entries) = fs::read_dir(logs::tasks_dir(&self.work_dir)) else { return Ok(()) };
+        for entry in entries.flatten() {
+            let task_id = entry.file_name().to_string_lossy().to_string();
+            let Some(dropped) = logs::enforce_quota(db, &self.work_dir, &task_id, max_mb * 1024 * 1024)? else { continue };
+            let what = format!("over {} MB, dropped {} bytes", max_mb, dropped);
+            db.log_decision("monitor", "log_truncated", &task_id, &what, serde_json::json!({ "task_max_mb": max_mb, "dropped_bytes": dropped }))?;
+            tracing::warn!(task = %task_id, "log quota: {}", what);
+            let key = format!("log_quota:{}", task_id);
+            if db.get_setting(&key)?.is_some() { continue; }
+            db.set_setting(&key, &chrono::Local::now().timestamp().to_string())?;
+            db.send_mail("monitor", &db.admin_for(&task_id)?, &format!("LOG QUOTA: [{}] logs truncated", task_id),
+                &format!("Task '{}' wrote more than {} MB of logs; older output was dropped. A worker may be stuck in an output loop: tt peek or tt logs {} --tail 50.", task_id, max_mb, task_id))?;
+        }
+        Ok(())
+    }
+
+    /// Kills sessions left behind by agents that are finished one way or another (their task is
+    /// closed, their launch or last attempt failed) and have gone quiet for `[session] reap_idle_secs`.
+    /// Worker directories stay for `tt done`/`tt gc`.
+    pub fn reap(&self, db: &Db) -> Result<()> {
+        let idle = Config::load(&self.work_dir)?.session.reap_idle_secs;
+        if idle <= 0 { return Ok(()); }
+        let now = chrono::Local::now().timestamp();
+        let backend = session::backend(&self.work_dir)?;
+        let mut stmt = db.conn.prepare(
+            "SELECT g.name, g.task_id, MAX(g.spawned_at, COALESCE(g.checked_in_at, 0)) FROM agents g LEFT JOIN tasks t ON t.id = g.task_id
+             WHERE g.status != 'reaped' AND (t.status IS NULL OR t.status = 'closed' OR g.status = 'failed_launch'
+                 OR (SELECT a.outcome FROM attempts a WHERE a.agent_name = g.name AND a.task_id = g.task_id ORDER BY a.id DESC LIMIT 1) = 'failed')")?;
+        let finished = stmt.query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?, row.get::<_, i64>(2)?)))?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        for (agent, task_id, seen) in finished {
+            let (task_status, agent_status): (Option<String>, String) = db.conn.query_row(
+                "SELECT (SELECT status FROM tasks WHERE id = ?2), status FROM agents WHERE name = ?1", [&agent, &task_id], |row| Ok((row.get(0)?, row.get(1)?)))?;
+            let session_name = Worker::session_name(&agent);
+            if !backend.exists(&session_name) { continue; }
+            let last = health::mtime(&Worker::log_path(&self.work_dir, &task_id, &agent)).map_or(seen, |m| m.max(seen));
+            if now - last < idle { continue; }
+            backend.kill(&session_name)?;
+            db.set_agent_status(&agent, "reaped")?;
+            db.log_decision("monitor", "session_reaped", &session_name, &format!("idle for {}", crate::period::format_span(now - last)), serde_json::json!({
+                "agent": agent, "task": task_id, "task_status": task_status, "agent_status": agent_status,
+                "last_activity": last, "now": now, "reap_idle_secs": idle,
+            }))?;
+            tracing::info!(agent = %agent, task = %task_id, "reaped idle session");
+        }
+        Ok(())
+    }
+
+    /// Stops admin sessions whose screen hasn't changed for `[session] supervisor_idle_secs` while
+    /// nothing in their scope is open or in progress, and starts them again once a task arrives.
+    pub fn check_supervisors(&self, db: &Db) -> Result<()> {
+        let idle = Config::load(&self.work_dir)?.session.supervisor_idle_secs;
+        if idle <= 0 { return Ok(()); }
+        let now = chrono::Local::now().timestamp();
+        let backend = session::backend(&self.work_dir)?;
+        let mut admins = vec![(None, Scope::all())];
+        admins.extend(db.admins()?.into_iter().map(|(name, scope)| (Some(name), scope)));
+        for (name, scope) in admins {
+            let admin = Admin::named(self.work_dir.clone(), name);
+            let (cond, params) = scope.condition();
+            let waiting: i64 = db.conn.query_row(&format!("SELECT COUNT(*) FROM tasks WHERE status IN ('open', 'in_progress') AND {}", cond),
+                params_from_iter(params), |row| row.get(0))?;
+            let seen_key = format!("idle_seen:{}", admin.session_name);
+            if !backend.exists(&admin.session_name) {
+                if waiting == 0 || db.get_setting(&admin.idle_key())?.is_none() { continue; }
+                // Fails while frozen or without an engine; tried again next pass.
+                match admin.start(Some(&scope)) {
+                    Ok(()) => {
+                        db.clear_setting(&admin.idle_key())?;
+                        db.log_decision("monitor", "supervisor_restarted", &admin.session_name, &format!("{} task(s) waiting", waiting),
+                            serde_json::json!({ "waiting": waiting, "now": now }))?;
+                        tracing::info!(session = %admin.session_name, waiting, "restarted idle-stopped admin");
+                    }
+                    Err(e) => tracing::warn!(session = %admin.session_name, "could not restart admin: {}", e),
+                }
+                continue;
+            }
+            let mut hasher = DefaultHasher::new();
+            backend.capture(&admin.session_name, 200).unwrap_or_default().hash(&mut hasher);
+            let screen = hasher.finish().to_string();
+            let seen = db.get_setting(&seen_key)?;
+            let since = match seen.as_deref().and_then(|v| v.split_once(':')) {
+                Some((hash, since)) if hash == screen => since.parse().unwrap_or(now),
+                _ => { db.set_setting(&seen_key, &format!("{}:{}", screen, now))?; continue; }
+            };
+            if waiting > 0 || now - since < idle { continue; }
+            backend.kill(&admin.session_name)?;
+            db.clear_setting(&seen_key)?;
+            db.set_setting(&admin.idle_key(), &now.to_string())?;
+            db.log_decision("monitor", "supervisor_idle_stopped", &admin.session_name, &format!("idle for {}", crate::period::format_span(now - since)),
+                serde_json::json!({ "last_output": since, "now": now, "supervisor_idle_secs": idle }))?;
+            tracing::info!(session = %admin.session_name, "stopped idle admin");
+        }
+        Ok(())
+    }
+
+    /// Flags agents that never ran `tt agent checkin` within `[session] checkin_secs` as failed
+    /// launches: their attempt ends as `failed` and the admin is mailed once.
+    pub fn check_checkins(&self, db: &Db) -> Result<()> {
+        let secs = Config::load(&self.work_dir)?.session.checkin_secs;
+        if secs <= 0 { return Ok(()); }
+        for (agent, task_id) in db.missed_checkins(secs)? {
+            let spawned_at: i64 = db.conn.query_row("SELECT spawned_at FROM agents WHERE name = ?1", [&agent], |row| row.get(0))?;
+            db.set_agent_status(&agent, "failed_launch")?;
+            db.end_attempts(Some(&task_id), Some(&agent), "failed")?;
+            let what = format!("no checkin within {}s", secs);
+            db.log_decision("monitor", "launch_failed", &agent, &what, serde_json::json!({ "task": task_id, "spawned_at": spawned_at, "checkin_secs": secs }))?;
+            db.send_mail("monitor", &db.admin_for(&task_id)?, &format!("FAILED LAUNCH: {} on [{}]", agent, task_id),
+                &format!("'{}' was spawned on '{}' but never ran tt agent checkin. Check it with tt peek {}, then tt worker restart {}.", agent, task_id, agent, agent))?;
+            tracing::warn!(agent = %agent, task = %task_id, "failed launch: {}", what);
+        }
+        Ok(())
+    }
+
+    /// Trips the freeze when today's spend passes `[budget] daily_usd_cap`, and tells active workers to wrap up.
+    /// After an unfreeze it waits for spend beyond what was spent when the user lifted it.
+    pub fn check_budget(&self, db: &Db) -> Result<()> {
+        let cap = Config::load(&self.work_dir)?.budget.daily_usd_cap;
+        if cap <= 0.0 || db.frozen()?.is_some() { return Ok(()); }
+        let spent = db.today_spend()?;
+        let today = chrono::Local::now().format("%Y-%m-%d").to_string();
+        let allowed = db.get_setting(crate::db::BUDGET_ALLOWED_KEY)?
+            .and_then(|v| v.strip_prefix(&today).and_then(|s| s.trim().parse::<f64>().ok()))
+            .unwrap_or(0.0);
+        if spent <= cap.max(allowed) { return Ok(()); }
+
+        let reason = format!("daily spend ${:.2} exceeded cap ${:.2}", spent, cap);
+        db.set_setting("freeze", &reason)?;
+        db.log_decision("monitor", "budget_freeze", "workspace", &reason, serde_json::json!({ "spent_usd": spent, "daily_usd_cap": cap }))?;
+        tracing::warn!(spent, cap, "budget freeze: {}", reason);
+
+        let message = "Daily budget exceeded. Commit your current work, write a short status note, and stop.";
+        let backend = session::backend(&self.work_dir)?;
+        let mut stmt = db.conn.prepare("SELECT assignee FROM tasks WHERE status = 'in_progress' AND assignee IS NOT NULL")?;
+        let agents = stmt.query_map([], |row| row.get::<_, String>(0))?.collect::<rusqlite::Result<Vec<_>>>()?;
+        for agent in agents {
+            let name = Worker::session_name(&agent);
+            if !(backend.exists(&name) && backend.notify(&name, &format!("!!! NUDGE: {} !!!", message)).is_ok()) {
+                db.send_mail("monitor", &agent, "NUDGE: Budget freeze", message)?;
+            }
+            db.log_audit("monitor", "nudge_sent", &agent, "budget_freeze")?;
+            tracing::info!(agent = %agent, "told to wrap up after budget freeze");
+        }
+        Ok(())
+    }
+
+    /// Mails the admin once per assigned task that has passed its due date (again if the due date changes).
+    pub fn check_overdue(&self, db: &Db) -> Result<()> {
+        for t in db.overdue_tasks()? {
+            let (task_id, title, due) = (t.id, t.title, t.due_at);
+            let Some(agent) = t.assignee else { continue };
+            let key = format!("overdue:{}", task_id);
+            if db.get_setting(&key)?.as_deref() == Some(due.to_string().as_str()) { continue; }
+            db.set_setting(&key, &due.to_string())?;
+            db.send_mail("monitor", &db.admin_for(&task_id)?, &format!("OVERDUE: [{}] {}", task_id, title),
+                &format!("Task '{}' assigned to '{}' was due {} and is not done. Check on it with tt peek {}.", task_id, agent, crate::period::format_due(due), agent))?;
+            db.log_decision("monitor", "task_overdue", &task_id, "escalated", serde_json::json!({ "assignee": agent, "due_at": due, "now": chrono::Local::now().timestamp() }))?;
+            tracing::warn!(task = %task_id, agent = %agent, due = %crate::period::format_due(due), "task overdue, admin mailed");
+        }
+        Ok(())
+    }
+
+    /// Records each `[[sla]]` breach once in the audit log and mails the admin about it.
+    pub fn check_sla(&self, db: &Db) -> Result<()> {
+        let now = chrono::Local::now().timestamp();
+        for policy in Config::load(&self.work_dir)?.sla {
+            let limits = [("start", &policy.start_within), ("finish", &policy.finish_within)];
+            for t in db.timelines(Some(&policy.tag))? {
+                let Some(created) = t.created_at else { continue };
+                for (kind, limit) in limits {
+                    let Some(limit) = limit else { continue };
+                    let Some(secs) = crate::period::span_secs(limit) else { continue };
+                    let reached = if kind == "start" { t.started_at } else { t.closed_at };
+                    // A task closed without a recorded close time can't be judged.
+                    if kind == "finish" && reached.is_none() && t.status == "closed" { continue; }
+                    if reached.unwrap_or(now) <= created + secs { continue; }
+                    let key = format!("sla:{}:{}", t.id, kind);
+                    if db.get_setting(&key)?.is_some() { continue; }
+                    db.set_setting(&key, &now.to_string())?;
+                    let what = format!("#{}: not {}ed within {}", policy.tag, kind, limit);
+                    db.log_decision("monitor", "sla_breach", &t.id, &what, serde_json::json!({
+                        "tag": policy.tag, "kind": kind, "limit": limit, "created_at": created, "reached_at": reached, "now": now,
+                    }))?;
+                    db.send_mail("monitor", &db.admin_for(&t.id)?, &format!("SLA BREACH: [{}] {}", t.id, what), &format!("Task '{}' ({}) breached its service level: {}.", t.id, t.status, what))?;
+                    tracing::warn!(task = %t.id, tag = %policy.tag, "SLA breach: {}", what);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Takes unanswered questions and escalations up the `[[escalation]]` ladder: every step whose
+    /// `after` a still-unread mail has reached fires once for it, and is recorded as a decision.
+    /// A mail's progress up the ladder is forgotten once it is read or its task closes.
+    pub fn check_escalations(&self, db: &Db) -> Result<()> {
+        let config = Config::load(&self.work_dir)?;
+        db.conn.execute(&format!(
+            "DELETE FROM settings WHERE key LIKE 'escalation:%' AND substr(key, 12) NOT IN (SELECT CAST(id AS TEXT) FROM messages WHERE {})",
+            UNANSWERED), [])?;
+        if config.escalation.is_empty() { return Ok(()); }
+        let mut ladder: Vec<(i64, &EscalationStep)> = config.escalation.iter()
+            .filter_map(|s| crate::period::span_secs(&s.after).map(|secs| (secs, s))).collect();
+        ladder.sort_by_key(|(secs, _)| *secs);
+        let now = chrono::Local::now().timestamp();
+        let mut stmt = db.conn.prepare(&format!("SELECT id, receiver, subject, timestamp FROM messages WHERE {} ORDER BY id", UNANSWERED))?;
+        let pending = stmt.query_map([], |row| Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?, row.get::<_, String>(2)?, row.get::<_, i64>(3)?)))?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        let backend = session::backend(&self.work_dir)?;
+        for (id, receiver, subject, sent) in pending {
+            let key = format!("escalation:{}", id);
+            let done: usize = db.get_setting(&key)?.and_then(|v| v.parse().ok()).unwrap_or(0);
+            let age = now - sent;
+            for (step, (_, rung)) in ladder.iter().enumerate().skip(done).take_while(|(_, (secs, _))| age >= *secs) {
+                db.set_setting(&key, &(step + 1).to_string())?;
+                let text = format!("Mail #{} to {} has waited {} for an answer: {} (tt mail read {})", id, receiver, crate::period::format_span(age), subject, id);
+                let mut via = Vec::new();
+                if rung.nudge {
+                    let name = if receiver == "admin" || receiver.starts_with("admin-") { format!("hq-{}", receiver) } else { Worker::session_name(&receiver) };
+                    if backend.exists(&name) && backend.notify(&name, &format!("!!! NUDGE: {} !!!", text)).is_ok() { via.push("nudge".to_string()); }
+                    else { via.push("nudge: no session".to_string()); }
+                }
+                if !rung.email.is_empty() {
+                    match email::send(&config.email, &rung.email, &format!("UNANSWERED: {}", subject), &text) {
+                        Ok(()) => via.push("email".to_string()),
+                        Err(e) => via.push(format!("email: {}", e)),
+                    }
+                }
+                if !rung.webhook.is_empty() {
+                    match crate::remote::post(&rung.webhook).send_json(serde_json::json!({ "text": text })) {
+                        Ok(_) => via.push("webhook".to_string()),
+                        Err(e) => via.push(format!("webhook: {}", crate::error::ThinkTodoError::remote_hiding(e, crate::error::url_secret(&rung.webhook)))),
+                    }
+                }
+                let what = format!("after {}: {}", rung.after, via.join(", "));
+                db.log_decision("monitor", "escalated", &id.to_string(), &what, serde_json::json!({
+                    "step": step + 1, "after": rung.after, "receiver": receiver, "subject": subject, "sent_at": sent, "now": now, "via": via,
+                }))?;
+                tracing::warn!(mail = id, receiver = %receiver, "unanswered mail escalated {}", what);
+            }
+        }
+        Ok(())
+    }
+
+    /// Commits each active worker's worktree to its task branch once `[checkpoint] interval_secs` has passed.
+    pub fn checkpoint(&self, db: &Db) -> Result<()> {
+        let interval = Config::load(&self.work_dir)?.checkpoint.interval_secs;
+        if interval <= 0 { return Ok(()); }
+        let now = chrono::Local::now();
+        let mut stmt = db.conn.prepare("SELECT id, assignee FROM tasks WHERE status = 'in_progress' AND assignee IS NOT NULL AND rig IS NOT NULL")?;
+        let active = stmt.query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)))?.collect::<rusqlite::Result<Vec<_>>>()?;
+        for (task_id, agent) in active {
+            let key = format!("checkpoint:{}", task_id);
+            let last: i64 = db.get_setting(&key)?.and_then(|v| v.parse().ok()).unwrap_or(0);
+            if now.timestamp() - last < interval { continue; }
+            db.set_setting(&key, &now.timestamp().to_string())?;
+
+            let worktree = self.work_dir.join("workers").join(&agent);
+            if Git::current_branch(&worktree).ok().as_deref() != Some(Git::task_branch(&task_id).as_str()) { continue; }
+            let message = format!("tt checkpoint: {} @ {}", task_id, now.format("%Y-%m-%d %H:%M:%S"));
+            match Git::checkpoint(&worktree, &message) {
+                Ok(true) => {
+                    tracing::info!(task = %task_id, agent = %agent, "checkpoint committed");
+                    db.log_audit("monitor", "checkpoint", &task_id, "success")?;
+                }
+                Ok(false) => tracing::debug!(task = %task_id, "checkpoint skipped: nothing changed"),
+                Err(e) => {
+                    tracing::warn!(task = %task_id, "checkpoint failed: {}", e);
+                    db.log_audit("monitor", "checkpoint", &task_id, &e.to_string())?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// One pass over what the task logs gained since the last: closes tasks that reported
+    /// `[TASK_DONE]`, passes on `[QUESTION]` lines to the admin and records `[RESULT]` lines. How far
+    /// every log has been read is kept in the settings, so each line is acted on once and a log
+    /// that didn't grow isn't opened.
+    pub fn scan(&self, db: &Db) -> Result<()> {
+        let Ok(tasks) = fs::read_dir(logs::tasks_dir(&self.work_dir)) else { return Ok(()) };
+        for task in tasks.flatten() {
+            let task_id = task.file_name().to_string_lossy().to_string();
+            let Ok(files) = fs::read_dir(task.path()) else { continue };
+            for file in files.flatten() {
+                let path = file.path();
+                let Some(agent) = path.file_name().and_then(|n| n.to_str()).and_then(|n| n.strip_suffix(".log")).map(str::to_string) else { continue };
+                let key = logs::read_offset_key(&task_id, &agent);
+                let Ok(len) = file.metadata().map(|m| m.len()) else { continue };
+                let mut offset: u64 = db.get_setting(&key)?.and_then(|v| v.parse().ok()).unwrap_or(0);
+                if offset == len { continue; }
+                if offset > len { offset = 0; } // rewritten behind the monitor's back
+                let mut bytes = Vec::new();
+                let mut log = fs::File::open(&path)?;
+                log.seek(SeekFrom::Start(offset))?;
+                log.read_to_end(&mut bytes)?;
+                // Complete lines only; a line still being written is read next pass.
+                let Some(end) = bytes.iter().rposition(|&b| b == b'\n').map(|i| i + 1) else { continue };
+                for line in String::from_utf8_lossy(&bytes[..end]).lines() {
+                    match conventions::marker(line) {
+                        Some((conventions::TASK_DONE, _)) => {
+                            match db.conn.execute("UPDATE tasks SET status = 'closed' WHERE id = ?1 AND status != 'closed'", [&task_id]) {
+                                Ok(1) => {
+                                    tracing::info!(task = %task_id, log = %path.display(), "closed: log reported [TASK_DONE]");
+                                    db.log_decision("monitor", "task_closed", &task_id, "success", serde_json::json!({
+                                        "log": path.display().to_string(), "marker": conventions::TASK_DONE,
+                                    }))?;
                                 }
+                                Ok(_) => {}
+                                Err(e) => tracing::error!(task = %task_id, "could not close task: {}", e),
                             }
                         }
+                        Some((conventions::QUESTION, text)) if !text.is_empty() => {
+                            db.send_mail(&agent, &db.admin_for(&task_id)?, &format!("QUESTION from {} [{}]", agent, task_id), text)?;
+                            db.log_audit(&agent, "agent_question", &task_id, "mailed")?;
+                            tracing::info!(agent = %agent, task = %task_id, "question relayed to admin");
+                        }
+                        Some((conventions::RESULT, text)) if !text.is_empty() => {
+                            db.log_audit_meta(&agent, "task_result", &task_id, "success", &serde_json::json!({ "result": text }).to_string())?;
+                            tracing::info!(agent = %agent, task = %task_id, "result recorded");
+                        }
+                        _ => {}
                     }
                 }
+                db.set_setting(&key, &(offset + end as u64).to_string())?;
             }
-            thread::sleep(Duration::from_secs(3));
         }
+        Ok(())
     }
 }
@@ -0,0 +1,79 @@
+use crate::db::Db;
+use crate::error::{Result, ThinkTodoError};
+use crate::health;
+use rusqlite::params;
+use serde::Serialize;
+use std::path::Path;
+
+/// Something that needed a human, kept until it is acknowledged: the board's warnings
+/// (escalations awaiting approval, failed attempts, budget, stalls, ...) turned into a list with
+/// read state, so the CLI and the web show the same thing.
+#[derive(Serialize)]
+pub struct Notification {
+    pub id: i64,
+    /// The warning kind, see `health::Warning`.
+    pub kind: String,
+    pub target: String,
+    pub message: String,
+    pub created_at: i64,
+    pub read_at: Option<i64>,
+    /// When the condition went away on its own.
+    pub resolved_at: Option<i64>,
+}
+
+/// Brings the table in line with the current warnings: a new condition adds an unread
+/// notification, one that persists keeps its notification (read or not), and one that went away
+/// is resolved, which also marks it read. Returns how many were added.
+pub fn sync(db: &Db, work_dir: &Path) -> Result<usize> {
+    let warnings = health::warnings(db, work_dir)?;
+    let mut added = 0;
+    for w in &warnings {
+        added += db.conn.execute(
+            "INSERT INTO notifications (kind, target, message, created_at) SELECT ?1, ?2, ?3, strftime('%s','now')
+             WHERE NOT EXISTS (SELECT 1 FROM notifications WHERE kind = ?1 AND target = ?2 AND resolved_at IS NULL)",
+            params![w.kind, w.target, w.message])?;
+    }
+    let mut stmt = db.conn.prepare("SELECT id, kind, target FROM notifications WHERE resolved_at IS NULL")?;
+    let open = stmt.query_map([], |row| Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?, row.get::<_, String>(2)?)))?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    for (id, kind, target) in open {
+        if !warnings.iter().any(|w| w.kind == kind && w.target == target) {
+            db.conn.execute("UPDATE notifications SET resolved_at = strftime('%s','now'), read_at = COALESCE(read_at, strftime('%s','now')) WHERE id = ?1", [id])?;
+        }
+    }
+    Ok(added)
+}
+
+/// Unread notifications, or every one with `all`, newest first.
+pub fn list(db: &Db, all: bool) -> Result<Vec<Notification>> {
+    let mut stmt = db.conn.prepare(
+        "SELECT id, kind, target, message, created_at, read_at, resolved_at FROM notifications WHERE ?1 OR read_at IS NULL ORDER BY id DESC")?;
+    let rows = stmt.query_map([all], |row| Ok(Notification {
+        id: row.get(0)?, kind: row.get(1)?, target: row.get(2)?, message: row.get(3)?,
+        created_at: row.get(4)?, read_at: row.get(5)?, resolved_at: row.get(6)?,
+    }))?;
+    Ok(rows.collect::<rusqlite::Result<Vec<_>>>()?)
+}
+
+pub fn unread(db: &Db) -> Result<i64> {
+    Ok(db.conn.query_row("SELECT COUNT(*) FROM notifications WHERE read_at IS NULL", [], |row| row.get(0))?)
+}
+
+/// Marks the notifications read, or all unread ones when `ids` is empty; returns how many changed.
+pub fn ack(db: &Db, ids: &[i64], actor: &str) -> Result<usize> {
+    let mut changed = 0;
+    if ids.is_empty() {
+        changed = db.conn.execute("UPDATE notifications SET read_at = strftime('%s','now') WHERE read_at IS NULL", [])?;
+    }
+    for id in ids {
+        match db.conn.execute("UPDATE notifications SET read_at = COALESCE(read_at, strftime('%s','now')) WHERE id = ?1", [id])? {
+            0 => return Err(ThinkTodoError::InvalidArgument(format!("no notification #{}", id))),
+            n => changed += n,
+        }
+    }
+    if changed > 0 {
+        let what = if ids.is_empty() { "all".to_string() } else { ids.iter().map(|i| i.to_string()).collect::<Vec<_>>().join(",") };
+        db.log_audit(actor, "notifications_acked", &what, "success")?;
+    }
+    Ok(changed)
+}
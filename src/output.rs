@@ -0,0 +1,168 @@
+use crate::config::ThemeConfig;
+use crate::i18n;
+use indicatif::{ProgressBar, ProgressDrawTarget, ProgressStyle};
+use std::env;
+use std::io::IsTerminal;
+use std::sync::OnceLock;
+
+/// How the CLI dresses its output, from `[theme]` in think.toml. Color is only used on a terminal
+/// and never when `NO_COLOR` is set (https://no-color.org).
+struct Theme {
+    emoji: bool,
+    ascii: bool,
+    /// SGR code of the heading color, None for plain headings.
+    accent: Option<u8>,
+    /// stdout is a terminal that takes color.
+    color: bool,
+    /// `--quiet`: no progress bars.
+    quiet: bool,
+}
+
+static THEME: OnceLock<Theme> = OnceLock::new();
+
+/// Columns between the side borders of the board.
+const WIDTH: usize = 74;
+
+/// Picks the theme once per run; later calls are ignored.
+pub fn init(cfg: &ThemeConfig, quiet: bool) {
+    let color = std::io::stdout().is_terminal() && env::var_os("NO_COLOR").is_none_or(|v| v.is_empty());
+    let accent = if color { accent_code(&cfg.accent).flatten() } else { None };
+    let _ = THEME.set(Theme { emoji: cfg.emoji, ascii: cfg.ascii, accent, color, quiet });
+}
+
+fn theme() -> &'static Theme {
+    THEME.get_or_init(|| Theme { emoji: true, ascii: false, accent: None, color: false, quiet: false })
+}
+
+/// The SGR code of an accent color: Some(None) for "none", None for a name it doesn't know.
+pub fn accent_code(name: &str) -> Option<Option<u8>> {
+    Some(Some(match name {
+        "" | "none" => return Some(None),
+        "red" => 31,
+        "green" => 32,
+        "yellow" => 33,
+        "blue" => 34,
+        "magenta" => 35,
+        "cyan" => 36,
+        _ => return None,
+    }))
+}
+
+/// `text` as the theme wants it: without emoji when they are off, with ASCII bullets and arrows
+/// when box-drawing is off.
+pub fn show(text: &str) -> String {
+    let theme = theme();
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if !theme.emoji && is_emoji(c) {
+            // An emoji opening a word takes its trailing space with it.
+            if out.is_empty() || out.ends_with(' ') {
+                while chars.next_if(|n| *n == ' ' || is_emoji(*n)).is_some() {}
+            }
+            continue;
+        }
+        match c {
+            '●' if theme.ascii => out.push('*'),
+            '•' | '·' if theme.ascii => out.push('-'),
+            '→' if theme.ascii => out.push_str("->"),
+            '—' if theme.ascii => out.push('-'),
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+/// A section heading in the accent color.
+pub fn heading(text: &str) -> String {
+    paint(show(text))
+}
+
+fn paint(text: String) -> String {
+    match theme().accent {
+        Some(code) => format!("\x1b[1;{}m{}\x1b[0m", code, text),
+        None => text,
+    }
+}
+
+/// Something late or broken, in red when color is on.
+pub fn alert(text: &str) -> String {
+    if theme().color { format!("\x1b[31m{}\x1b[0m", text) } else { text.to_string() }
+}
+
+/// A line that changed since the last render of `tt watch`, in reverse video when color is on.
+pub fn changed(text: &str) -> String {
+    if theme().color { format!("\x1b[7m{}\x1b[0m", text) } else { text.to_string() }
+}
+
+/// Whether output goes to a terminal that takes escape codes.
+pub fn color() -> bool { theme().color }
+
+fn is_emoji(c: char) -> bool {
+    matches!(c as u32, 0x1F000..=0x1FAFF | 0x2600..=0x27BF | 0x2B00..=0x2BFF | 0x231A..=0x23FF
+        | 0x21A9..=0x21AA | 0x25B6 | 0xFE0F | 0x200D)
+}
+
+/// Horizontal lines of the board.
+pub enum Rule {
+    Top,
+    Split,
+    Thin,
+    Bottom,
+}
+
+pub fn rule(rule: Rule) -> String {
+    let (left, fill, right) = match (rule, theme().ascii) {
+        (_, true) => ("+", "-", "+"),
+        (Rule::Top, false) => ("╔", "═", "╗"),
+        (Rule::Split, false) => ("╠", "═", "╣"),
+        (Rule::Thin, false) => ("╟", "─", "╢"),
+        (Rule::Bottom, false) => ("╚", "═", "╝"),
+    };
+    format!("{}{}{}", left, fill.repeat(WIDTH), right)
+}
+
+/// `text` between the board's side borders, padded to its width.
+pub fn boxed(text: &str) -> String {
+    let text = show(text);
+    let side = if theme().ascii { "|" } else { "║" };
+    let pad = " ".repeat(WIDTH.saturating_sub(i18n::columns(&text) + 1));
+    format!("{} {}{}{}", side, paint(text), pad, side)
+}
+
+/// A progress bar with an ETA on stderr for commands that go through many items. It draws nothing
+/// under `--quiet` or when stderr isn't a terminal, so piped output stays clean, unless
+/// `TT_PROGRESS=always` asks for it anyway (CI logs).
+pub struct Progress(ProgressBar);
+
+impl Progress {
+    pub fn new(len: usize) -> Self {
+        let target = if theme().quiet {
+            ProgressDrawTarget::hidden()
+        } else if env::var("TT_PROGRESS").is_ok_and(|v| v == "always") {
+            ProgressDrawTarget::term_like(Box::new(console::Term::stderr()))
+        } else {
+            ProgressDrawTarget::stderr()
+        };
+        let bar = ProgressBar::with_draw_target(Some(len as u64), target);
+        let chars = if theme().ascii { "#>-" } else { "█▉░" };
+        bar.set_style(ProgressStyle::with_template("{bar:30} {pos}/{len} ETA {eta} {wide_msg}")
+            .expect("valid progress template").progress_chars(chars));
+        Progress(bar)
+    }
+
+    /// Starts on the next item, named in the bar's message.
+    pub fn start(&self, item: &str) { self.0.set_message(item.to_string()); }
+
+    /// One more item done.
+    pub fn inc(&self) { self.0.inc(1); }
+
+    pub fn set_len(&self, len: usize) { self.0.set_length(len as u64); }
+
+    /// Clears the bar so the command's own report starts on a clean line.
+    pub fn finish(&self) { self.0.finish_and_clear(); }
+}
+
+impl Drop for Progress {
+    fn drop(&mut self) { self.0.finish_and_clear(); }
+}
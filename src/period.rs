@@ -0,0 +1,95 @@
+use crate::error::{Result, ThinkTodoError};
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime, TimeZone, Timelike};
+use std::sync::OnceLock;
+
+/// Parses a `--since`/`--older-than` value into a unix timestamp.
+/// Accepts relative spans ("30m", "12h", "7d", "2w"), dates ("2024-07-01", local midnight) and raw epoch seconds.
+pub fn parse_since(value: &str) -> Result<i64> {
+    let now = Local::now().timestamp();
+    if let Some(secs) = span_secs(value) { return Ok(now - secs); }
+    if let Ok(date) = NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+        let midnight = date.and_hms_opt(0, 0, 0).and_then(|dt| Local.from_local_datetime(&dt).earliest());
+        if let Some(dt) = midnight { return Ok(dt.timestamp()); }
+    }
+    if let Ok(epoch) = value.parse::<i64>() { return Ok(epoch); }
+    Err(ThinkTodoError::InvalidArgument(format!("can't read '{}' as a time (use e.g. 12h, 7d, 2w or 2024-07-01)", value)))
+}
+
+/// "90s", "30m", "12h", "7d", "2w" -> seconds.
+pub fn span_secs(value: &str) -> Option<i64> {
+    let unit = value.chars().last()?;
+    let n: i64 = value[..value.len() - unit.len_utf8()].parse().ok()?;
+    let mult = match unit { 's' => 1, 'm' => 60, 'h' => 3600, 'd' => 86400, 'w' => 604800, _ => return None };
+    Some(n * mult)
+}
+
+/// Parses a `--due` value into a unix timestamp. Relative spans count forward from now ("3d" = in three days);
+/// "2024-07-01" is that whole day (stored as local midnight, see `is_all_day`), "2024-07-01 17:00" a precise time.
+pub fn parse_due(value: &str) -> Result<i64> {
+    let now = Local::now().timestamp();
+    if let Some(secs) = span_secs(value.trim_start_matches('+')) { return Ok(now + secs); }
+    if let Ok(dt) = NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M") {
+        if let Some(dt) = Local.from_local_datetime(&dt).earliest() { return Ok(dt.timestamp()); }
+    }
+    if NaiveDate::parse_from_str(value, "%Y-%m-%d").is_ok() { return parse_since(value); }
+    Err(ThinkTodoError::InvalidArgument(format!("can't read '{}' as a due date (use e.g. 3d, 2024-07-01 or \"2024-07-01 17:00\")", value)))
+}
+
+/// A due date given as a plain day, i.e. stored at local midnight.
+pub fn is_all_day(due: i64) -> bool {
+    DateTime::from_timestamp(due, 0).is_some_and(|t| t.with_timezone(&Local).num_seconds_from_midnight() == 0)
+}
+
+/// Whether `due` has passed at `now`. A plain day is only overdue once the day is over.
+pub fn is_overdue(due: i64, now: i64) -> bool {
+    if is_all_day(due) { now >= due + 86400 } else { now > due }
+}
+
+/// "2024-07-01" for plain days, "2024-07-01 17:00" otherwise.
+pub fn format_due(due: i64) -> String {
+    let fmt = if is_all_day(due) { "%Y-%m-%d" } else { "%Y-%m-%d %H:%M" };
+    DateTime::from_timestamp(due, 0).map(|t| t.with_timezone(&Local).format(fmt).to_string()).unwrap_or_default()
+}
+
+/// How the CLI prints timestamps, from `time` in think.toml or `--utc`/`--epoch`.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum TimeStyle {
+    Local,
+    Utc,
+    Epoch,
+}
+
+static TIME_STYLE: OnceLock<TimeStyle> = OnceLock::new();
+
+pub fn parse_time_style(value: &str) -> Result<TimeStyle> {
+    match value {
+        "" | "local" => Ok(TimeStyle::Local),
+        "utc" => Ok(TimeStyle::Utc),
+        "epoch" => Ok(TimeStyle::Epoch),
+        other => Err(ThinkTodoError::Config(format!("unknown time style '{}' (expected local, utc or epoch)", other))),
+    }
+}
+
+/// Picks the style once per run; later calls are ignored.
+pub fn init_time_style(style: TimeStyle) { let _ = TIME_STYLE.set(style); }
+
+/// "2024-07-01 17:00:05" in local time, "2024-07-01 15:00:05Z" in UTC, or the raw epoch seconds.
+pub fn format_time(ts: i64) -> String {
+    let Some(t) = DateTime::from_timestamp(ts, 0) else { return ts.to_string() };
+    match TIME_STYLE.get().copied().unwrap_or(TimeStyle::Local) {
+        TimeStyle::Local => t.with_timezone(&Local).format("%Y-%m-%d %H:%M:%S").to_string(),
+        TimeStyle::Utc => t.format("%Y-%m-%d %H:%M:%SZ").to_string(),
+        TimeStyle::Epoch => ts.to_string(),
+    }
+}
+
+/// 5400 -> "1h 30m", 90 -> "1m 30s", 200000 -> "2d 7h".
+pub fn format_span(secs: i64) -> String {
+    let (d, h, m, s) = (secs / 86400, secs % 86400 / 3600, secs % 3600 / 60, secs % 60);
+    match (d, h, m) {
+        (0, 0, 0) => format!("{}s", s),
+        (0, 0, _) => format!("{}m {}s", m, s),
+        (0, _, _) => format!("{}h {}m", h, m),
+        _ => format!("{}d {}h", d, h),
+    }
+}
@@ -0,0 +1,89 @@
+use crate::config::Config;
+use crate::db::Db;
+use crate::i18n::tr;
+use crate::session::{self, SessionBackend};
+use crate::worker::Worker;
+use anyhow::Result;
+use std::fs;
+use std::path::PathBuf;
+
+/// Reconciles `in_progress` tasks with the sessions that actually exist,
+/// e.g. after a reboot wiped every session.
+pub struct Recover { pub work_dir: PathBuf }
+
+pub struct Orphan {
+    pub task_id: String,
+    pub assignee: Option<String>,
+    pub engine: Option<String>,
+}
+
+impl Recover {
+    pub fn new(work_dir: PathBuf) -> Self { Self { work_dir } }
+
+    /// In-progress tasks whose worker session is gone.
+    pub fn orphans(&self, db: &Db, backend: &dyn SessionBackend) -> Result<Vec<Orphan>> {
+        let mut stmt = db.conn.prepare("SELECT id, assignee, engine FROM tasks WHERE status = 'in_progress'")?;
+        let rows = stmt.query_map([], |row| Ok(Orphan { task_id: row.get(0)?, assignee: row.get(1)?, engine: row.get(2)? }))?;
+        let mut orphans = Vec::new();
+        for r in rows {
+            let o = r?;
+            let alive = o.assignee.as_deref().map(|a| backend.exists(&Worker::session_name(a))).unwrap_or(false);
+            if !alive { orphans.push(o); }
+        }
+        Ok(orphans)
+    }
+
+    /// Recovers every orphaned task using `mode` ("respawn" or "reopen"). Returns the number handled.
+    pub fn run(&self, mode: &str) -> Result<usize> {
+        let config = Config::load(&self.work_dir)?;
+        let db = Db::new(self.work_dir.clone())?;
+        let orphans = self.orphans(&db, session::backend(&self.work_dir)?.as_ref())?;
+        // A frozen workspace starts nothing; orphans go back to the backlog instead.
+        let frozen = db.frozen()?;
+        let requested = mode;
+        let mode = match &frozen {
+            Some(reason) if mode == "respawn" && !orphans.is_empty() => {
+                println!("{}", tr!("🧊 Dispatch is frozen ({}); reopening orphaned tasks instead of respawning them.", reason));
+                "reopen"
+            }
+            _ => mode,
+        };
+        for o in &orphans {
+            db.end_attempts(Some(&o.task_id), None, "lost")?;
+            let inputs = serde_json::json!({
+                "assignee": o.assignee, "session_alive": false, "requested_mode": requested, "frozen": frozen,
+            });
+            match (mode, o.assignee.as_deref()) {
+                ("respawn", Some(agent)) => {
+                    let engine = o.engine.clone().unwrap_or_else(|| "gemini".to_string());
+                    let mut w = Worker::new(o.task_id.clone(), agent.to_string(), self.work_dir.clone(), engine, "worker".to_string());
+                    w.allow_dirty = true; // resuming its own work
+                    w.brief = Some(self.resume_context(&o.task_id, agent, config.recover.context_lines));
+                    w.spawn()?;
+                    db.log_decision("recover", "task_respawned", &o.task_id, "success", inputs)?;
+                    tracing::info!(task = %o.task_id, agent, "orphan respawned");
+                    println!("{}", tr!("🔁 Respawned '{}' for task '{}'.", agent, o.task_id));
+                }
+                _ => {
+                    db.conn.execute("UPDATE tasks SET status = 'open', assignee = NULL WHERE id = ?1", [&o.task_id])?;
+                    db.log_decision("recover", "task_reopened", &o.task_id, "success", inputs)?;
+                    tracing::info!(task = %o.task_id, "orphan reopened");
+                    println!("{}", tr!("↩️  Task '{}' moved back to open.", o.task_id));
+                }
+            }
+        }
+        Ok(orphans.len())
+    }
+
+    fn resume_context(&self, task_id: &str, agent: &str, lines: usize) -> String {
+        let log = fs::read_to_string(Worker::log_path(&self.work_dir, task_id, agent)).unwrap_or_default();
+        let all: Vec<&str> = log.lines().collect();
+        let tail = &all[all.len().saturating_sub(lines)..];
+        format!(
+            "RESUME CONTEXT: Your previous session for this mission was interrupted (machine restart). \
+             Inspect your workspace for partial work and continue from where you left off.\n\
+             --- LAST {} LOG LINES ---\n{}",
+            tail.len(), tail.join("\n")
+        )
+    }
+}
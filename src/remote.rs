@@ -0,0 +1,11 @@
+use std::time::Duration;
+
+/// How long an outgoing request may take to connect, and to finish.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+/// A POST to a webhook or telemetry sink. These run inside monitor passes, so a host that
+/// doesn't answer fails the request instead of stalling the pass.
+pub fn post(url: &str) -> ureq::Request {
+    ureq::AgentBuilder::new().timeout_connect(CONNECT_TIMEOUT).timeout(TIMEOUT).build().post(url)
+}
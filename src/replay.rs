@@ -0,0 +1,76 @@
+use crate::db::Db;
+use crate::error::Result;
+use crate::period;
+use serde_json::{Map, Value};
+
+/// A decision recorded with `Db::log_decision`.
+pub struct Decision {
+    pub timestamp: i64,
+    pub actor: String,
+    pub action: String,
+    pub target: String,
+    pub status: String,
+    pub inputs: Map<String, Value>,
+}
+
+/// Decisions of the monitor daemon and of recovery since `since`, oldest first, optionally only
+/// those about `target` (a task id, or a session or agent name).
+pub fn decisions(db: &Db, since: i64, target: Option<&str>) -> Result<Vec<Decision>> {
+    let mut stmt = db.conn.prepare(
+        "SELECT timestamp, actor, action, target, status, json_extract(metadata, '$.inputs') FROM audit_logs
+         WHERE timestamp >= ?1 AND actor IN ('monitor', 'recover') AND json_valid(metadata) AND json_extract(metadata, '$.inputs') IS NOT NULL
+           AND (?2 IS NULL OR target = ?2 OR json_extract(metadata, '$.inputs.task') = ?2)
+         ORDER BY id")?;
+    let rows = stmt.query_map(rusqlite::params![since, target], |row| Ok(Decision {
+        timestamp: row.get(0)?, actor: row.get(1)?, action: row.get(2)?, target: row.get(3)?, status: row.get(4)?,
+        inputs: serde_json::from_str(&row.get::<_, String>(5)?).unwrap_or_default(),
+    }))?;
+    Ok(rows.collect::<rusqlite::Result<Vec<_>>>()?)
+}
+
+impl Decision {
+    fn num(&self, key: &str) -> Option<f64> { self.inputs.get(key).and_then(Value::as_f64) }
+    fn text(&self, key: &str) -> String {
+        match self.inputs.get(key) {
+            Some(Value::String(s)) => s.clone(),
+            Some(Value::Null) | None => "-".to_string(),
+            Some(v) => v.to_string(),
+        }
+    }
+    fn time(&self, key: &str) -> String {
+        self.inputs.get(key).and_then(Value::as_i64).map(period::format_due).unwrap_or_else(|| "never".to_string())
+    }
+    fn span(&self, from: &str, to: &str) -> String {
+        match (self.num(from), self.num(to)) {
+            (Some(a), Some(b)) => period::format_span((b - a) as i64),
+            _ => "-".to_string(),
+        }
+    }
+
+    /// Why the decision was taken, from the inputs recorded with it.
+    pub fn reason(&self) -> String {
+        match self.action.as_str() {
+            "task_closed" => format!("a line in {} starts with {}", self.text("log"), self.text("marker")),
+            "budget_freeze" => format!("today's spend ${:.2} passed the daily cap ${:.2}",
+                self.num("spent_usd").unwrap_or(0.0), self.num("daily_usd_cap").unwrap_or(0.0)),
+            "task_overdue" => format!("assigned to {} and due {}, still open at {}", self.text("assignee"), self.time("due_at"), self.time("now")),
+            "sla_breach" => format!("#{} requires {} within {}: created {}, {} {}",
+                self.text("tag"), self.text("kind"), self.text("limit"), self.time("created_at"), self.text("kind"), self.time("reached_at")),
+            "launch_failed" => format!("spawned on [{}] {} and no checkin within {}s", self.text("task"), self.time("spawned_at"), self.text("checkin_secs")),
+            "session_reaped" => format!("task [{}] is {}, agent is {}; quiet for {} (reap_idle_secs {})",
+                self.text("task"), self.text("task_status"), self.text("agent_status"), self.span("last_activity", "now"), self.text("reap_idle_secs")),
+            "log_truncated" => format!("logs passed {} MB; {} bytes dropped", self.text("task_max_mb"), self.text("dropped_bytes")),
+            "chaos_kill" => format!("--chaos rolled under kill_chance {} for the worker on [{}]", self.text("kill_chance"), self.text("task")),
+            "chaos_stall" => format!("--chaos rolled under stall_chance {} for the worker on [{}]; frozen for {}",
+                self.text("stall_chance"), self.text("task"), self.num("stall_secs").map_or("-".to_string(), |s| period::format_span(s as i64))),
+            "task_respawned" | "task_reopened" => {
+                let frozen = match self.inputs.get("frozen") {
+                    Some(Value::String(reason)) => format!(", dispatch frozen ({})", reason),
+                    _ => String::new(),
+                };
+                format!("session of {} was gone; mode {}{}", self.text("assignee"), self.text("requested_mode"), frozen)
+            }
+            _ => self.inputs.iter().map(|(k, v)| format!("{}={}", k, v)).collect::<Vec<_>>().join(", "),
+        }
+    }
+}
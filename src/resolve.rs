@@ -0,0 +1,135 @@
+use crate::db::Db;
+use crate::error::{Result, ThinkTodoError};
+use crate::i18n::tr;
+use dialoguer::{Confirm, FuzzySelect, Input};
+use std::io::{self, IsTerminal};
+
+/// A task or agent a short name could stand for, with a line to tell it apart from the others.
+pub struct Candidate {
+    pub id: String,
+    pub label: String,
+}
+
+/// The task `given` names: itself when it is an id, else the one task whose id starts with,
+/// contains, or spells out (in order) what was typed, e.g. `auth` -> `TT-auth-refactor-42`.
+pub fn task(db: &Db, given: &str) -> Result<String> {
+    let all = candidates(db, "SELECT id, title FROM tasks ORDER BY id")?;
+    resolve(given, &all, &tr!("tasks"))
+}
+
+/// The agent `given` names, matched like `task` against spawned agents.
+pub fn agent(db: &Db, given: &str) -> Result<String> {
+    let all = candidates(db, "SELECT name, task_id FROM agents ORDER BY name")?;
+    resolve(given, &all, &tr!("agents"))
+}
+
+/// The task `given` names for a command that closes, merges or removes it: itself, or the one
+/// task whose id starts with it. A shortened id is echoed and confirmed (or taken on `yes`).
+pub fn task_by_prefix(db: &Db, given: &str, yes: bool) -> Result<String> {
+    let all = candidates(db, "SELECT id, title FROM tasks ORDER BY id")?;
+    resolve_by_prefix(given, &all, &tr!("tasks"), yes)
+}
+
+/// The agent `given` names for a command that stops or drives it, matched like `task_by_prefix`.
+pub fn agent_by_prefix(db: &Db, given: &str, yes: bool) -> Result<String> {
+    let all = candidates(db, "SELECT name, task_id FROM agents ORDER BY name")?;
+    resolve_by_prefix(given, &all, &tr!("agents"), yes)
+}
+
+fn candidates(db: &Db, sql: &str) -> Result<Vec<Candidate>> {
+    let mut stmt = db.conn.prepare(sql)?;
+    let rows = stmt.query_map([], |row| Ok(Candidate { id: row.get(0)?, label: row.get(1)? }))?;
+    Ok(rows.collect::<rusqlite::Result<Vec<_>>>()?)
+}
+
+/// Unknown names are passed through so the command reports them as it always has.
+fn resolve(given: &str, all: &[Candidate], noun: &str) -> Result<String> {
+    if all.iter().any(|c| c.id == given) { return Ok(given.to_string()); }
+    let found = matches(given, all);
+    match found.as_slice() {
+        [] => Ok(given.to_string()),
+        [one] => Ok(one.id.clone()),
+        _ if interactive() => choose(&tr!("'{}' matches {} {}:", given, found.len(), noun), &found)?
+            .ok_or_else(|| ThinkTodoError::InvalidArgument("cancelled".to_string())),
+        _ => Err(ThinkTodoError::InvalidArgument(format!("'{}' is ambiguous, it matches {}; give more of it",
+            given, found.iter().map(|c| c.id.as_str()).collect::<Vec<_>>().join(", ")))),
+    }
+}
+
+fn resolve_by_prefix(given: &str, all: &[Candidate], noun: &str, yes: bool) -> Result<String> {
+    if all.iter().any(|c| c.id == given) { return Ok(given.to_string()); }
+    let lower = given.to_lowercase();
+    let found: Vec<&Candidate> = all.iter().filter(|c| c.id.to_lowercase().starts_with(&lower)).collect();
+    let id = match found.as_slice() {
+        [] => return Ok(given.to_string()),
+        [one] => one.id.clone(),
+        _ if interactive() => return choose(&tr!("'{}' matches {} {}:", given, found.len(), noun), &found)?
+            .ok_or_else(|| ThinkTodoError::InvalidArgument("cancelled".to_string())),
+        _ => return Err(ThinkTodoError::InvalidArgument(format!("'{}' is ambiguous, it matches {}; give more of it",
+            given, found.iter().map(|c| c.id.as_str()).collect::<Vec<_>>().join(", ")))),
+    };
+    eprintln!("{}", tr!("→ resolved {} to {}", given, id));
+    if yes { return Ok(id); }
+    if !interactive() {
+        return Err(ThinkTodoError::InvalidArgument(format!("'{}' is short for '{}'; give it in full or pass --yes", given, id)));
+    }
+    let go = Confirm::new().with_prompt(tr!("Go ahead with {}?", id)).default(false).interact()
+        .map_err(|dialoguer::Error::IO(e)| e)?;
+    if go { Ok(id) } else { Err(ThinkTodoError::InvalidArgument("cancelled".to_string())) }
+}
+
+/// The best tier of matches: prefix, then substring, then subsequence, all ignoring case.
+fn matches<'a>(given: &str, all: &'a [Candidate]) -> Vec<&'a Candidate> {
+    let given = given.to_lowercase();
+    let tiers: [&dyn Fn(&str) -> bool; 3] = [
+        &|id| id.starts_with(&given),
+        &|id| id.contains(&given),
+        &|id| {
+            let mut rest = id.chars();
+            given.chars().all(|g| rest.any(|c| c == g))
+        },
+    ];
+    tiers.iter()
+        .map(|tier| all.iter().filter(|c| tier(&c.id.to_lowercase())).collect::<Vec<_>>())
+        .find(|m| !m.is_empty())
+        .unwrap_or_default()
+}
+
+/// Whether there is someone at a terminal to ask.
+pub fn interactive() -> bool {
+    io::stdin().is_terminal() && io::stderr().is_terminal()
+}
+
+/// Lets the user pick one of `options` from a list they can narrow by typing; None on Esc.
+pub fn choose(prompt: &str, options: &[&Candidate]) -> Result<Option<String>> {
+    let items: Vec<String> = options.iter().map(|c| format!("{}  {}", c.id, c.label)).collect();
+    let picked = FuzzySelect::new().with_prompt(prompt).items(&items).default(0).max_length(15).interact_opt()
+        .map_err(|dialoguer::Error::IO(e)| e)?;
+    Ok(picked.map(|i| options[i].id.clone()))
+}
+
+/// A task picked from those matching `filter` (SQL over tasks), for commands run without one.
+pub fn pick_task(db: &Db, filter: &str, prompt: &str) -> Result<String> {
+    if !interactive() { return Err(ThinkTodoError::InvalidArgument("give a task id (run it in a terminal to pick one)".to_string())); }
+    let all = candidates(db, &format!("SELECT id, title || COALESCE(' (' || assignee || ')', '') FROM tasks WHERE {} ORDER BY priority DESC, id", filter))?;
+    if all.is_empty() { return Err(ThinkTodoError::InvalidArgument("no task to pick from".to_string())); }
+    choose(prompt, &all.iter().collect::<Vec<_>>())?.ok_or_else(|| ThinkTodoError::InvalidArgument("cancelled".to_string()))
+}
+
+/// An agent picked from the known ones, or a new name typed in.
+pub fn pick_agent(db: &Db) -> Result<String> {
+    if !interactive() { return Err(ThinkTodoError::InvalidArgument("give an agent name (run it in a terminal to pick one)".to_string())); }
+    let known = candidates(db, "SELECT name, task_id FROM agents ORDER BY name")?;
+    let new = Candidate { id: String::new(), label: tr!("+ new agent") };
+    let options: Vec<&Candidate> = known.iter().chain([&new]).collect();
+    match choose(&tr!("Agent"), &options)? {
+        Some(name) if !name.is_empty() => Ok(name),
+        Some(_) => {
+            let name: String = Input::new().with_prompt(tr!("New agent name"))
+                .validate_with(|v: &String| crate::ids::agent_name(v).map(|_| ()).map_err(|e| e.to_string()))
+                .interact_text().map_err(|dialoguer::Error::IO(e)| e)?;
+            Ok(name)
+        }
+        None => Err(ThinkTodoError::InvalidArgument("cancelled".to_string())),
+    }
+}
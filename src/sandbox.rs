@@ -0,0 +1,42 @@
+use crate::error::Result;
+use rusqlite::Connection;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Where `tt --sandbox` keeps its copy of the workspace.
+pub const DIR: &str = ".sandbox";
+
+/// The sandbox workspace for `work_dir`. Its database is a copy of the real one taken on first
+/// use and kept until `tt sandbox reset`; think.toml and prompts/ are refreshed on every run so
+/// edits to them can be tried straight away.
+pub fn prepare(work_dir: &Path) -> Result<PathBuf> {
+    let dir = work_dir.join(DIR);
+    fs::create_dir_all(&dir)?;
+    let db = dir.join("think.db");
+    let real = work_dir.join("think.db");
+    if !db.exists() && real.exists() {
+        Connection::open(&real)?.execute("VACUUM INTO ?1", [db.to_string_lossy()])?;
+    }
+    let toml = work_dir.join("think.toml");
+    if toml.exists() { fs::copy(&toml, dir.join("think.toml"))?; }
+    copy_dir(&work_dir.join("prompts"), &dir.join("prompts"))?;
+    Ok(dir)
+}
+
+/// Throws the sandbox away; the next `--sandbox` run starts from a fresh copy.
+pub fn reset(work_dir: &Path) -> Result<bool> {
+    let dir = work_dir.join(DIR);
+    if !dir.exists() { return Ok(false); }
+    fs::remove_dir_all(dir)?;
+    Ok(true)
+}
+
+fn copy_dir(from: &Path, to: &Path) -> Result<()> {
+    let Ok(entries) = fs::read_dir(from) else { return Ok(()) };
+    fs::create_dir_all(to)?;
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.is_dir() { copy_dir(&path, &to.join(entry.file_name()))?; } else { fs::copy(&path, to.join(entry.file_name()))?; }
+    }
+    Ok(())
+}
@@ -0,0 +1,110 @@
+use crate::db::Db;
+use crate::error::{Result, ThinkTodoError};
+use crate::ids;
+use rusqlite::params_from_iter;
+use std::fmt;
+
+/// A filter over tasks, e.g. `tag:infra`, `team:infra` or `rig:core,tag:urgent`. Every comma-separated term
+/// must match; `all` matches every task. Terms can also be written `key=value`, as in `--filter status=open`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Scope {
+    terms: Vec<Term>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Term {
+    Tag(String),
+    Rig(String),
+    /// Tasks assigned to a member of the team.
+    Team(String),
+    Status(String),
+    Assignee(String),
+}
+
+impl Scope {
+    pub fn all() -> Self { Self { terms: Vec::new() } }
+
+    /// The scope narrowed to tasks that also carry every one of `tags`.
+    pub fn and_tags(mut self, tags: &[String]) -> Self {
+        self.terms.extend(tags.iter().cloned().map(Term::Tag));
+        self
+    }
+
+    /// SQL condition on the `tasks` table, with its parameters in order.
+    pub fn condition(&self) -> (String, Vec<String>) {
+        if self.terms.is_empty() { return ("1".to_string(), Vec::new()); }
+        let mut sql = Vec::new();
+        let mut params = Vec::new();
+        for term in &self.terms {
+            match term {
+                Term::Tag(t) => { sql.push("EXISTS (SELECT 1 FROM task_tags g WHERE g.task_id = tasks.id AND g.tag = ?)"); params.push(t.clone()); }
+                Term::Rig(r) => { sql.push("tasks.rig = ?"); params.push(r.clone()); }
+                Term::Team(t) => { sql.push("tasks.assignee IN (SELECT m.agent FROM team_members m WHERE m.team = ?)"); params.push(t.clone()); }
+                Term::Status(v) => { sql.push("tasks.status = ?"); params.push(v.clone()); }
+                Term::Assignee(v) => { sql.push("tasks.assignee = ?"); params.push(v.clone()); }
+            }
+        }
+        (format!("({})", sql.join(" AND ")), params)
+    }
+
+    /// Distinct values of `column` over the tasks in the scope that also meet `extra` (SQL), in order.
+    pub fn select(&self, db: &Db, column: &str, extra: &str) -> Result<Vec<String>> {
+        let (cond, params) = self.condition();
+        let mut stmt = db.conn.prepare(&format!("SELECT DISTINCT {} FROM tasks WHERE {} AND {} ORDER BY 1", column, cond, extra))?;
+        let rows = stmt.query_map(params_from_iter(params), |row| row.get(0))?;
+        Ok(rows.collect::<rusqlite::Result<Vec<String>>>()?)
+    }
+
+    /// Fails with `InvalidArgument` unless `task_id` falls in the scope.
+    pub fn ensure(&self, db: &Db, task_id: &str) -> Result<()> {
+        if self.matches(db, task_id)? { return Ok(()); }
+        Err(ThinkTodoError::InvalidArgument(format!("task '{}' is outside the scope {}", task_id, self)))
+    }
+
+    /// Whether `task_id` exists and falls in the scope.
+    pub fn matches(&self, db: &Db, task_id: &str) -> Result<bool> {
+        let (cond, mut params) = self.condition();
+        params.insert(0, task_id.to_string());
+        let n: i64 = db.conn.query_row(&format!("SELECT COUNT(*) FROM tasks WHERE id = ? AND {}", cond), params_from_iter(params), |row| row.get(0))?;
+        Ok(n > 0)
+    }
+}
+
+impl fmt::Display for Scope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.terms.is_empty() { return f.write_str("all"); }
+        let terms: Vec<String> = self.terms.iter().map(|t| match t {
+            Term::Tag(v) => format!("tag:{}", v),
+            Term::Rig(v) => format!("rig:{}", v),
+            Term::Team(v) => format!("team:{}", v),
+            Term::Status(v) => format!("status:{}", v),
+            Term::Assignee(v) => format!("assignee:{}", v),
+        }).collect();
+        f.write_str(&terms.join(","))
+    }
+}
+
+/// A saved scope by name (see `tt scope add`), or else `text` read as a scope.
+pub fn resolve(db: &Db, text: &str) -> Result<Scope> {
+    match db.saved_scope(text)? {
+        Some(saved) => parse(&saved),
+        None if text != "all" && !text.contains([':', '=']) => Err(ThinkTodoError::InvalidArgument(format!("no saved scope named '{}' (see tt scope list)", text))),
+        None => parse(text),
+    }
+}
+
+/// clap value parser (and the reader for stored scopes).
+pub fn parse(text: &str) -> Result<Scope> {
+    let text = text.trim();
+    if text == "all" { return Ok(Scope::all()); }
+    let terms = text.split(',').map(|term| match term.trim().split_once([':', '=']) {
+        Some(("tag", v)) => Ok(Term::Tag(ids::tag(v)?)),
+        Some(("rig", v)) if !v.is_empty() => Ok(Term::Rig(v.to_string())),
+        Some(("team", v)) => Ok(Term::Team(ids::check("team", v)?)),
+        Some(("status", v)) if !v.is_empty() => Ok(Term::Status(v.to_string())),
+        Some(("assignee", v)) => Ok(Term::Assignee(ids::agent_name(v)?)),
+        _ => Err(ThinkTodoError::InvalidArgument(format!(
+            "bad scope term '{}' (expected tag:<tag>, rig:<rig>, team:<team>, status:<status>, assignee:<agent> or all)", term))),
+    }).collect::<Result<Vec<_>>>()?;
+    Ok(Scope { terms })
+}
@@ -0,0 +1,72 @@
+use crate::db::Db;
+use crate::error::{Result, ThinkTodoError};
+use serde::Serialize;
+
+/// Hits from one `tt search`, grouped by where they were found, best match first in each group.
+#[derive(Serialize)]
+pub struct Results {
+    pub tasks: Vec<TaskHit>,
+    pub mail: Vec<MailHit>,
+    pub audit: Vec<AuditHit>,
+}
+
+impl Results {
+    pub fn is_empty(&self) -> bool { self.tasks.is_empty() && self.mail.is_empty() && self.audit.is_empty() }
+}
+
+#[derive(Serialize)]
+pub struct TaskHit {
+    pub id: String,
+    pub title: String,
+    pub status: String,
+}
+
+#[derive(Serialize)]
+pub struct MailHit {
+    pub id: i64,
+    pub sender: String,
+    pub receiver: String,
+    pub subject: String,
+    /// The part of the body around the match, matches in `[...]`.
+    pub snippet: String,
+}
+
+#[derive(Serialize)]
+pub struct AuditHit {
+    pub timestamp: i64,
+    pub actor: String,
+    pub action: String,
+    pub target: String,
+    pub status: String,
+}
+
+/// Task ids and titles, mail subjects and bodies, and audit actions and targets matching every
+/// word of `query` (each as a prefix, so "pars" finds "parser"); at most `limit` per group.
+pub fn search(db: &Db, query: &str, limit: usize) -> Result<Results> {
+    let query = fts_query(query)?;
+    let mut stmt = db.conn.prepare(
+        "SELECT t.id, t.title, t.status FROM task_fts f JOIN tasks t ON t.rowid = f.rowid WHERE task_fts MATCH ?1 ORDER BY f.rank LIMIT ?2")?;
+    let tasks = stmt.query_map(rusqlite::params![query, limit], |row| Ok(TaskHit { id: row.get(0)?, title: row.get(1)?, status: row.get(2)? }))?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    let mut stmt = db.conn.prepare(
+        "SELECT m.id, m.sender, m.receiver, m.subject, snippet(mail_fts, 1, '[', ']', '…', 12)
+         FROM mail_fts JOIN messages m ON m.id = mail_fts.rowid WHERE mail_fts MATCH ?1 ORDER BY mail_fts.rank LIMIT ?2")?;
+    let mail = stmt.query_map(rusqlite::params![query, limit], |row| Ok(MailHit {
+        id: row.get(0)?, sender: row.get(1)?, receiver: row.get(2)?, subject: row.get(3)?, snippet: row.get(4)?,
+    }))?.collect::<rusqlite::Result<Vec<_>>>()?;
+    let mut stmt = db.conn.prepare(
+        "SELECT a.timestamp, a.actor, a.action, a.target, a.status FROM audit_fts f JOIN audit_logs a ON a.id = f.rowid
+         WHERE audit_fts MATCH ?1 ORDER BY a.id DESC LIMIT ?2")?;
+    let audit = stmt.query_map(rusqlite::params![query, limit], |row| Ok(AuditHit {
+        timestamp: row.get(0)?, actor: row.get(1)?, action: row.get(2)?, target: row.get(3)?, status: row.get(4)?,
+    }))?.collect::<rusqlite::Result<Vec<_>>>()?;
+    Ok(Results { tasks, mail, audit })
+}
+
+/// `query` as an FTS5 expression: every word quoted, so punctuation can't break the syntax, and
+/// matched as a prefix.
+fn fts_query(query: &str) -> Result<String> {
+    let words: Vec<String> = query.split_whitespace().map(|w| format!("\"{}\"*", w.replace('"', "\"\""))).collect();
+    if words.is_empty() { return Err(ThinkTodoError::InvalidArgument("nothing to search for".to_string())); }
+    Ok(words.join(" "))
+}
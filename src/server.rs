@@ -1,35 +1,64 @@
 use axum::{
-    extract::Path,
-    routing::{get, post},
+    extract::{ConnectInfo, Path, Query, Request, State},
+    http::{header, HeaderMap, HeaderValue, Method, StatusCode, Uri},
+    middleware::{self, Next},
+    response::{IntoResponse, Redirect, Response},
     Json, Router,
 };
 use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, OpenApi, ToSchema};
+use utoipa_axum::{router::OpenApiRouter, routes};
+use utoipa_swagger_ui::{Config as SwaggerConfig, SwaggerUi};
+use axum_server::tls_rustls::RustlsConfig;
 use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::Arc;
+use subtle::ConstantTimeEq;
+use tower_http::cors::{AllowOrigin, CorsLayer};
 use tower_http::services::ServeDir;
 use crate::db::Db;
+use crate::error::{Result as TtResult, ThinkTodoError};
+use crate::admin::Admin;
+use crate::calendar;
+use crate::config::Config;
+use crate::files;
+use crate::health;
+use crate::i18n::tr;
+use crate::ids;
+use crate::merge;
+use crate::notify;
+use crate::period;
+use crate::service;
+use crate::stats;
+use crate::status;
 use crate::worker::Worker;
-use crate::tmux::Tmux;
+use crate::session;
 use std::env;
 use std::fs;
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 struct DashboardData {
     tasks: Vec<TaskData>,
     agents: Vec<String>,
     recent_logs: Vec<LogData>,
     stats: StatsData,
+    health: HealthData,
 }
 
-#[derive(Serialize, Deserialize, Clone)]
+#[derive(Serialize, Deserialize, Clone, ToSchema)]
 struct TaskData {
     id: String,
     title: String,
     status: String,
     assignee: Option<String>,
     engine: Option<String>,
+    /// low, normal, high or urgent; tasks come highest first
+    priority: String,
+    tags: Vec<String>,
+    archived: bool,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 struct LogData {
     timestamp: i64,
     actor: String,
@@ -37,226 +66,1034 @@ struct LogData {
     target: String,
 }
 
-#[derive(Serialize)]
+/// Alert-banner material: the board's WARNINGS plus queue, budget and monitor state.
+#[derive(Serialize, ToSchema)]
+struct HealthData {
+    warnings: Vec<health::Warning>,
+    stalled: usize,
+    failed: usize,
+    /// Open tasks waiting for a worker.
+    queue_depth: i64,
+    budget: BudgetData,
+    daemon: DaemonData,
+    disk: health::DiskUsage,
+}
+
+#[derive(Serialize, ToSchema)]
+struct BudgetData {
+    today_usd: f64,
+    /// 0 when no cap is configured.
+    daily_cap_usd: f64,
+    frozen: Option<String>,
+}
+
+#[derive(Serialize, ToSchema)]
+struct DaemonData {
+    alive: bool,
+    last_seen: Option<i64>,
+}
+
+#[derive(Serialize, ToSchema)]
 struct StatsData {
     total_cost: f64,
     tasks_done: i64,
     tasks_total: i64,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 struct AgentLogResponse {
     content: String,
     path: String,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 struct AddTaskRequest {
-    id: String,
+    /// Made up from `[tasks] id_prefix` when omitted.
+    #[serde(default)]
+    id: Option<String>,
     title: String,
+    #[serde(default)]
+    rig: Option<String>,
+    /// Same formats as `tt task add --due`.
+    #[serde(default)]
+    due: Option<String>,
+    #[serde(default)]
+    tags: Vec<String>,
+    /// low, normal, high or urgent
+    #[serde(default)]
+    priority: Option<String>,
+    /// Same formats as `tt task add --estimate`.
+    #[serde(default)]
+    estimate: Option<String>,
+    /// Add it even when an unfinished task has a very similar title.
+    #[serde(default)]
+    force: bool,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, IntoParams)]
+struct ScreenQuery {
+    /// Lines to capture (1-500).
+    #[serde(default = "default_screen_lines")]
+    lines: usize,
+}
+
+fn default_screen_lines() -> usize { 40 }
+
+#[derive(Deserialize, ToSchema)]
 struct SlingRequest {
     task_id: String,
     agent_name: String,
     engine: String,
+    #[serde(default)]
+    replace: bool,
+    #[serde(default)]
+    allow_dirty: bool,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 struct NudgeRequest {
     agent_name: String,
     message: String,
 }
 
-pub async fn start_server(port: u16) {
-    let app = Router::new()
-        .route("/api/dashboard", get(get_dashboard))
-        .route("/api/logs/{task_id}/{agent_name}", get(get_agent_logs))
-        .route("/api/prompts/{role}", get(get_prompt))
-        .route("/api/agents/{agent_name}/files", get(list_agent_files))
-        .route("/api/tasks/{task_id}/history", get(get_task_history))
+/// Body of every error response.
+#[derive(Serialize, ToSchema)]
+struct ErrorBody {
+    /// Always "error".
+    status: &'static str,
+    message: String,
+}
+
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "Think Todo API", description = "Board, task and session control for a think-todo workspace."),
+    tags(
+        (name = "board", description = "Read-only views for the dashboard"),
+        (name = "tasks", description = "Task lifecycle"),
+        (name = "sessions", description = "Admin and worker sessions"),
+    ),
+)]
+struct ApiDoc;
+
+/// What the proxy-facing middleware needs to know.
+struct Edge {
+    base_path: String,
+    trust_forwarded: bool,
+    /// Names of the hosted workspaces, whose bare prefix ("/w/alpha") gets a trailing slash.
+    tenants: Vec<String>,
+}
+
+/// A workspace served under `/w/<name>/` from a `[[server.tenants]]` entry.
+struct Tenant {
+    name: String,
+    dir: PathBuf,
+    token: Option<String>,
+}
+
+tokio::task_local! {
+    /// The workspace of the current request when it is a hosted one.
+    static WORKSPACE: PathBuf;
+    /// The signed-in user of the current request.
+    static USER: String;
+}
+
+/// When `tt serve` started, for the status page's uptime.
+static STARTED: std::sync::OnceLock<std::time::Instant> = std::sync::OnceLock::new();
+
+/// Certificate and key for serving HTTPS, plus the plain port that redirects to it.
+pub struct Tls {
+    pub cert: PathBuf,
+    pub key: PathBuf,
+    pub http_port: u16,
+}
+
+pub async fn start_server(port: u16, base_path: Option<String>, tls: Option<Tls>) -> TtResult<()> {
+    let _ = STARTED.set(std::time::Instant::now());
+    let config = Config::load(&env::current_dir()?)?.server;
+    let tenants = tenants(&config.tenants)?;
+    let edge = Arc::new(Edge {
+        base_path: normalize_base(base_path.as_deref().unwrap_or(&config.base_path)),
+        trust_forwarded: config.trust_forwarded,
+        tenants: tenants.iter().map(|t| t.name.clone()).collect(),
+    });
+    let mut doc = ApiDoc::openapi();
+    doc.info.license = None; // the crate declares none
+    let (api, openapi) = OpenApiRouter::with_openapi(doc)
+        .routes(routes!(get_dashboard))
+        .routes(routes!(get_agent_logs))
+        .routes(routes!(get_prompt))
+        .routes(routes!(list_agent_files))
+        .routes(routes!(get_agent_file))
+        .routes(routes!(get_task_artifacts))
+        .routes(routes!(get_overdue_tasks))
+        .routes(routes!(get_notifications))
+        .routes(routes!(ack_notification))
+        .routes(routes!(get_artifact_diff))
+        .routes(routes!(get_task_history))
+        .routes(routes!(get_task_diff))
+        .routes(routes!(get_worker_screen))
+        .routes(routes!(get_calendar))
+        .routes(routes!(get_closed_history))
+        .routes(routes!(get_spend_history))
+        .routes(routes!(get_workers_history))
+        .routes(routes!(get_me))
         // Actions
-        .route("/api/tasks", post(add_task))
-        .route("/api/tasks/{task_id}", axum::routing::delete(delete_task))
-        .route("/api/start", post(start_task))
-        .route("/api/done/{task_id}", post(done_task))
-        .route("/api/nudge", post(nudge_agent))
-        .fallback_service(ServeDir::new("ui"));
+        .routes(routes!(add_task))
+        .routes(routes!(delete_task))
+        .routes(routes!(archive_task))
+        .routes(routes!(unarchive_task))
+        .routes(routes!(start_task))
+        .routes(routes!(done_task))
+        .routes(routes!(nudge_agent))
+        .routes(routes!(start_admin))
+        .routes(routes!(stop_admin))
+        .routes(routes!(nuke_worker))
+        .routes(routes!(restart_worker))
+        .split_for_parts();
+    // The docs page fetches the spec relative to itself so it keeps working under --base-path.
+    let docs = SwaggerUi::new("/api/docs").url("/api/openapi.json", openapi).config(SwaggerConfig::new(["../openapi.json"]));
+    let api = api.merge(docs).route("/status", axum::routing::get(get_status))
+        .fallback_service(ServeDir::new("ui")).layer(middleware::from_fn(who));
+    let mut hosted = api.clone();
+    for tenant in &tenants {
+        let scoped = api.clone().layer(middleware::from_fn_with_state(tenant.clone(), in_tenant));
+        hosted = hosted.nest_service(&format!("/w/{}", tenant.name), scoped);
+    }
+    let api = hosted.layer(middleware::from_fn_with_state(Arc::new(tenants), by_token));
+
+    let mut app = if edge.base_path.is_empty() { api } else { Router::new().nest_service(&edge.base_path, api) };
+    if let Some(cors) = cors_layer(&config.cors_origins)? { app = app.layer(cors); }
+    let app = app.layer(middleware::from_fn_with_state(edge.clone(), behind_proxy));
 
     let addr = SocketAddr::from(([0, 0, 0, 0], port));
-    println!("🌐 Think-Todo WebUI is running at: http://localhost:{}", port);
-    
-    let listener = tokio::net::TcpListener::bind(addr).await.unwrap();
-    axum::serve(listener, app).await.unwrap();
+    let service = app.into_make_service_with_connect_info::<SocketAddr>();
+    let Some(tls) = tls else {
+        println!("{}", tr!("🌐 Think-Todo WebUI is running at: http://localhost:{}{}/", port, edge.base_path));
+        let listener = tokio::net::TcpListener::bind(addr).await?;
+        axum::serve(listener, service).await?;
+        return Ok(());
+    };
+
+    let _ = rustls::crypto::ring::default_provider().install_default();
+    let certs = RustlsConfig::from_pem_file(&tls.cert, &tls.key).await
+        .map_err(|e| ThinkTodoError::Config(format!("cannot load TLS certificate {} / key {}: {}", tls.cert.display(), tls.key.display(), e)))?;
+    if tls.http_port != 0 { tokio::spawn(redirect_to_https(tls.http_port, port)); }
+    println!("{}", tr!("🔒 Think-Todo WebUI is running at: https://localhost:{}{}/", port, edge.base_path));
+    axum_server::bind_rustls(addr, certs).serve(service).await?;
+    Ok(())
 }
 
-async fn add_task(Json(req): Json<AddTaskRequest>) -> Json<serde_json::Value> {
-    let work_dir = env::current_dir().unwrap();
-    let db = Db::new(work_dir).unwrap();
-    match db.add_task(&req.id, &req.title) {
-        Ok(_) => Json(serde_json::json!({"status": "success"})),
-        Err(e) => Json(serde_json::json!({"status": "error", "message": e.to_string()})),
+/// Answers every plain HTTP request with a permanent redirect to the same URL over HTTPS.
+async fn redirect_to_https(http_port: u16, https_port: u16) {
+    let app = Router::new().fallback(move |headers: HeaderMap, uri: Uri| async move {
+        let host = forwarded(&headers, "host").unwrap_or("localhost");
+        // Drop the plain port, minding bracketed IPv6 literals.
+        let host = match host.rsplit_once(':') {
+            Some((h, p)) if !p.ends_with(']') => h,
+            _ => host,
+        };
+        let port = if https_port == 443 { String::new() } else { format!(":{}", https_port) };
+        Redirect::permanent(&format!("https://{}{}{}", host, port, uri.path_and_query().map_or("/", |p| p.as_str())))
+    });
+    match tokio::net::TcpListener::bind(SocketAddr::from(([0, 0, 0, 0], http_port))).await {
+        Ok(listener) => { let _ = axum::serve(listener, app).await; }
+        Err(e) => tracing::warn!("HTTP→HTTPS redirect on port {} is unavailable: {}", http_port, e),
     }
 }
 
-async fn delete_task(Path(task_id): Path<String>) -> Json<serde_json::Value> {
-    let work_dir = env::current_dir().unwrap();
-    let db = Db::new(work_dir).unwrap();
-    let _ = db.conn.execute("DELETE FROM tasks WHERE id = ?1", rusqlite::params![task_id]);
-    Json(serde_json::json!({"status": "success"}))
+/// The `[[server.tenants]]` entries, checked: unique names, existing directories and set tokens.
+fn tenants(configs: &[crate::config::TenantConfig]) -> TtResult<Vec<Arc<Tenant>>> {
+    let here = env::current_dir()?;
+    let mut out: Vec<Arc<Tenant>> = Vec::new();
+    for t in configs {
+        let name = ids::check("tenant", &t.name)?;
+        if out.iter().any(|o| o.name == name) { return Err(ThinkTodoError::Config(format!("tenant '{}' is listed twice", name))); }
+        let dir = here.join(&t.dir);
+        if t.dir.is_empty() || !dir.is_dir() {
+            return Err(ThinkTodoError::Config(format!("tenant '{}': workspace directory '{}' does not exist", name, dir.display())));
+        }
+        let token = match t.token_env.as_str() {
+            "" => None,
+            var => Some(env::var(var).ok().filter(|v| !v.is_empty())
+                .ok_or_else(|| ThinkTodoError::Config(format!("tenant '{}': ${} is not set", name, var)))?),
+        };
+        out.push(Arc::new(Tenant { name, dir, token }));
+    }
+    Ok(out)
 }
 
-async fn start_task(Json(req): Json<SlingRequest>) -> Json<serde_json::Value> {
-    let work_dir = env::current_dir().unwrap();
-    let db = Db::new(work_dir.clone()).unwrap();
-    
-    let w = Worker::new(req.task_id.clone(), req.agent_name.clone(), work_dir, req.engine.clone());
-    if let Ok(_) = w.spawn() {
-        let _ = db.log_audit(&req.agent_name, "task_started", &req.task_id, "success");
-        let _ = db.conn.execute("UPDATE tasks SET assignee = ?1, status = 'in_progress', engine = ?2 WHERE id = ?3", rusqlite::params![req.agent_name, req.engine, req.task_id]);
-        Json(serde_json::json!({"status": "success"}))
-    } else {
-        Json(serde_json::json!({"status": "error"}))
+/// Compares in time that doesn't depend on where the tokens differ.
+fn same_token(given: &str, token: &str) -> bool {
+    given.as_bytes().ct_eq(token.as_bytes()).into()
+}
+
+fn bearer(headers: &HeaderMap) -> Option<&str> {
+    forwarded(headers, header::AUTHORIZATION.as_str()).and_then(|v| v.strip_prefix("Bearer ")).map(str::trim)
+}
+
+/// Runs a request under `/w/<name>/` against that workspace, once its token (if it has one) or
+/// the token of one of its users checks out.
+async fn in_tenant(State(tenant): State<Arc<Tenant>>, req: Request, next: Next) -> Response {
+    if let Some(token) = &tenant.token {
+        let given = bearer(req.headers()).map(str::to_string);
+        let user = match given.clone().filter(|g| !same_token(g, token)) {
+            Some(g) => {
+                let dir = tenant.dir.clone();
+                blocking(move || Db::new(dir)?.token_user(&g)).await.ok().flatten()
+            }
+            None => None,
+        };
+        if !given.as_deref().is_some_and(|g| same_token(g, token)) && user.is_none() {
+            let body = ErrorBody { status: "error", message: format!("workspace '{}' needs its API token", tenant.name) };
+            return (StatusCode::UNAUTHORIZED, Json(body)).into_response();
+        }
     }
+    WORKSPACE.scope(tenant.dir.clone(), next.run(req)).await
 }
 
-async fn done_task(Path(task_id): Path<String>) -> Json<serde_json::Value> {
-    let work_dir = env::current_dir().unwrap();
-    let db = Db::new(work_dir.clone()).unwrap();
-    
-    let mut stmt = db.conn.prepare("SELECT assignee FROM tasks WHERE id = ?1").unwrap();
-    let assignee: Option<String> = stmt.query_row(rusqlite::params![task_id], |row| row.get(0)).unwrap_or(None);
-    
-    if let Some(name) = assignee {
-        let _ = Worker::nuke(&name, &work_dir);
+/// Sends a request bearing a hosted workspace's token to that workspace, whatever its path.
+async fn by_token(State(tenants): State<Arc<Vec<Arc<Tenant>>>>, req: Request, next: Next) -> Response {
+    let owner = bearer(req.headers()).and_then(|b| tenants.iter().find(|t| t.token.as_deref().is_some_and(|token| same_token(b, token))));
+    match owner {
+        Some(tenant) => WORKSPACE.scope(tenant.dir.clone(), next.run(req)).await,
+        None => next.run(req).await,
     }
-    let _ = db.conn.execute("UPDATE tasks SET status = 'closed' WHERE id = ?1", rusqlite::params![task_id]);
-    let _ = db.log_audit("web", "task_closed", &task_id, "success");
-    
-    Json(serde_json::json!({"status": "success"}))
 }
 
-async fn nudge_agent(Json(req): Json<NudgeRequest>) -> Json<serde_json::Value> {
-    let work_dir = env::current_dir().unwrap();
-    let db = Db::new(work_dir).unwrap();
-    
-    if Tmux::has_session(&req.agent_name) {
-        let _ = Tmux::display_message(&req.agent_name, &format!("!!! NUDGE: {} !!!", req.message));
-        let _ = db.log_audit("web", "nudge_sent", &req.agent_name, "success");
+/// Runs the request as the user whose token it bears (see `tt user add`). Once the workspace has
+/// users, requests that change anything have to bear one.
+async fn who(req: Request, next: Next) -> Response {
+    let reads = matches!(*req.method(), Method::GET | Method::HEAD | Method::OPTIONS);
+    let token = bearer(req.headers()).map(str::to_string);
+    if reads && token.is_none() { return next.run(req).await; }
+    let found = blocking(move || {
+        let db = open_db()?;
+        let user = match token { Some(t) => db.token_user(&t)?, None => None };
+        Ok((user, !db.users()?.is_empty()))
+    }).await;
+    match found {
+        Ok((Some(user), _)) => USER.scope(user, next.run(req)).await,
+        Ok((None, true)) if !reads => {
+            let body = ErrorBody { status: "error", message: "sign in: send Authorization: Bearer <your user token> (tt user add)".to_string() };
+            (StatusCode::UNAUTHORIZED, Json(body)).into_response()
+        }
+        Ok(_) => next.run(req).await,
+        Err(e) => e.into_response(),
+    }
+}
+
+/// Who a request acts as in the audit log: its signed-in user, else "web".
+pub fn web_actor() -> String {
+    USER.try_with(String::clone).unwrap_or_else(|_| "web".to_string())
+}
+
+/// "/tt/" and "tt" both become "/tt"; "" and "/" mean no prefix.
+fn normalize_base(path: &str) -> String {
+    let trimmed = path.trim().trim_matches('/');
+    if trimmed.is_empty() { String::new() } else { format!("/{}", trimmed) }
+}
+
+fn cors_layer(origins: &[String]) -> TtResult<Option<CorsLayer>> {
+    if origins.is_empty() { return Ok(None); }
+    let allow = if origins.iter().any(|o| o == "*") {
+        AllowOrigin::any()
     } else {
-        let _ = db.send_mail("web", &req.agent_name, "NUDGE: Web Action", &req.message);
+        let list = origins.iter()
+            .map(|o| HeaderValue::from_str(o.trim_end_matches('/')).map_err(|_| ThinkTodoError::Config(format!("invalid CORS origin '{}'", o))))
+            .collect::<TtResult<Vec<_>>>()?;
+        AllowOrigin::list(list)
+    };
+    Ok(Some(CorsLayer::new()
+        .allow_origin(allow)
+        .allow_methods([Method::GET, Method::POST, Method::DELETE])
+        .allow_headers([header::CONTENT_TYPE, header::AUTHORIZATION])))
+}
+
+fn forwarded<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+    headers.get(name).and_then(|v| v.to_str().ok()).map(str::trim).filter(|v| !v.is_empty())
+}
+
+/// Logs each request with the real client address and sends the bare prefix ("/tt") to "/tt/",
+/// so the dashboard's relative API paths resolve under it. Absolute redirects from the routes
+/// get the prefix too. A proxy that strips its own prefix announces it with X-Forwarded-Prefix.
+async fn behind_proxy(State(edge): State<Arc<Edge>>, ConnectInfo(peer): ConnectInfo<SocketAddr>, req: Request, next: Next) -> Response {
+    let headers = req.headers();
+    let trusted = |name| if edge.trust_forwarded { forwarded(headers, name) } else { None };
+    let client = trusted("x-forwarded-for").and_then(|v| v.split(',').next()).map(|v| v.trim().to_string())
+        .or_else(|| trusted("x-real-ip").map(str::to_string))
+        .unwrap_or_else(|| peer.ip().to_string());
+    let prefix = trusted("x-forwarded-prefix").unwrap_or("").trim_end_matches('/').to_string();
+    tracing::info!(method = %req.method(), path = req.uri().path(), %client, "request");
+
+    if !edge.base_path.is_empty() && req.uri().path() == edge.base_path {
+        return Redirect::permanent(&format!("{}{}/", prefix, edge.base_path)).into_response();
+    }
+    let hosted = req.uri().path().strip_prefix(&edge.base_path).and_then(|p| p.strip_prefix("/w/"));
+    if let Some(name) = hosted.filter(|n| edge.tenants.iter().any(|t| t == n)) {
+        return Redirect::permanent(&format!("{}{}/w/{}/", prefix, edge.base_path, name)).into_response();
     }
-    Json(serde_json::json!({"status": "success"}))
+    let mut res = next.run(req).await;
+    let outside = res.headers().get(header::LOCATION).and_then(|v| v.to_str().ok())
+        .filter(|l| l.starts_with('/') && !l.starts_with("//"))
+        .map(|l| format!("{}{}{}", prefix, edge.base_path, l));
+    if let Some(value) = outside.and_then(|l| HeaderValue::from_str(&l).ok()) {
+        res.headers_mut().insert(header::LOCATION, value);
+    }
+    res
 }
 
-async fn get_prompt(Path(role): Path<String>) -> Json<serde_json::Value> {
-    let work_dir = env::current_dir().unwrap();
-    let path = work_dir.join("prompts").join(format!("{}.md", role));
-    let content = fs::read_to_string(path).unwrap_or_else(|_| "Prompt not found.".to_string());
-    Json(serde_json::json!({"content": content}))
+impl IntoResponse for ThinkTodoError {
+    fn into_response(self) -> Response {
+        let status = match self {
+            ThinkTodoError::TaskNotFound(_) | ThinkTodoError::SessionMissing(_) => StatusCode::NOT_FOUND,
+            ThinkTodoError::AgentBusy { .. } | ThinkTodoError::TaskExists { .. } | ThinkTodoError::DuplicateTask { .. } | ThinkTodoError::SessionExists { .. } | ThinkTodoError::RigDirty(_)
+            | ThinkTodoError::MergeConflict { .. } | ThinkTodoError::Locked { .. } => StatusCode::CONFLICT,
+            ThinkTodoError::InvalidName { .. } | ThinkTodoError::InvalidArgument(_) => StatusCode::BAD_REQUEST,
+            ThinkTodoError::EngineMissing { .. } | ThinkTodoError::EngineCrashed { .. } => StatusCode::UNPROCESSABLE_ENTITY,
+            ThinkTodoError::DbLocked => StatusCode::SERVICE_UNAVAILABLE,
+            ThinkTodoError::Frozen(_) => StatusCode::LOCKED,
+            ThinkTodoError::Session(_) | ThinkTodoError::Git(_) | ThinkTodoError::Config(_) | ThinkTodoError::Db(_) | ThinkTodoError::Io(_) | ThinkTodoError::Remote(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        if status.is_server_error() { tracing::error!(%status, "{}", self); } else { tracing::debug!(%status, "{}", self); }
+        (status, Json(ErrorBody { status: "error", message: self.to_string() })).into_response()
+    }
 }
 
-async fn list_agent_files(Path(agent_name): Path<String>) -> Json<serde_json::Value> {
-    let work_dir = env::current_dir().unwrap();
-    let agent_path = work_dir.join("workers").join(&agent_name);
-    let mut files = Vec::new();
-    
-    if agent_path.exists() {
-        if let Ok(entries) = fs::read_dir(agent_path) {
-            for entry in entries.flatten() {
-                if let Ok(name) = entry.file_name().into_string() {
-                    if name != ".git" && name != ".DS_Store" {
-                        files.push(name);
-                    }
-                }
-            }
+type ApiResult = Result<Json<serde_json::Value>, ThinkTodoError>;
+
+/// Runs a handler's synchronous SQLite, git and session work on the blocking pool,
+/// so a slow query or the monitor's write lock can't stall other requests.
+/// The request's workspace and user go along.
+pub async fn blocking<T: Send + 'static>(f: impl FnOnce() -> TtResult<T> + Send + 'static) -> TtResult<T> {
+    let hosted = WORKSPACE.try_with(PathBuf::clone).ok();
+    let user = USER.try_with(String::clone).ok();
+    tokio::task::spawn_blocking(move || {
+        let f = move || match user {
+            Some(user) => USER.sync_scope(user, f),
+            None => f(),
+        };
+        match hosted {
+            Some(dir) => WORKSPACE.sync_scope(dir, f),
+            None => f(),
         }
+    }).await.map_err(|e| ThinkTodoError::Io(std::io::Error::other(e)))?
+}
+
+/// The workspace directory of the current request: a hosted one under `/w/<name>/`, else the one
+/// `tt serve` runs in.
+pub fn work_dir() -> TtResult<PathBuf> {
+    match WORKSPACE.try_with(PathBuf::clone) {
+        Ok(dir) => Ok(dir),
+        Err(_) => Ok(env::current_dir()?),
     }
-    Json(serde_json::json!({"files": files}))
 }
 
-async fn get_task_history(Path(task_id): Path<String>) -> Json<serde_json::Value> {
-    let work_dir = env::current_dir().unwrap();
-    let db = Db::new(work_dir).unwrap();
-    
-    // Search for logs where target is task_id OR actor is the task's assignee
-    let mut stmt = db.conn.prepare("SELECT timestamp, actor, action, target, status FROM audit_logs WHERE target = ?1 OR actor IN (SELECT assignee FROM tasks WHERE id = ?1) ORDER BY timestamp DESC").unwrap();
+/// The workspace database for one request.
+pub fn open_db() -> TtResult<Db> {
+    Db::new(work_dir()?)
+}
+
+/// Create a task.
+#[utoipa::path(
+    post, path = "/api/tasks", tag = "tasks",
+    request_body = AddTaskRequest,
+    responses((status = 200, description = "Success", body = serde_json::Value),
+        (status = "4XX", description = "Invalid request or state", body = ErrorBody)),
+)]
+async fn add_task(Json(req): Json<AddTaskRequest>) -> ApiResult {
+    blocking(move || {
+        let db = open_db()?;
+        let due = req.due.as_deref().map(period::parse_due).transpose()?;
+        let priority = req.priority.as_deref().map(service::parse_priority).transpose()?;
+        let estimate = req.estimate.as_deref().map(service::Estimate::parse).transpose()?;
+        let id = match req.id { Some(id) => id, None => service::new_task_id(&db, &work_dir()?)? };
+        service::add_task(&db, &service::NewTask { id: id.clone(), title: req.title, rig: req.rig, due, tags: req.tags, priority, force_title_update: false, force: req.force, estimate })?;
+        Ok(Json(serde_json::json!({"status": "success", "id": id})))
+    }).await
+}
+
+/// Delete a task.
+#[utoipa::path(
+    delete, path = "/api/tasks/{task_id}", tag = "tasks",
+    params(("task_id" = String, Path, description = "Task id")),
+    responses((status = 200, description = "Success", body = serde_json::Value),
+        (status = "4XX", description = "Invalid request or state", body = ErrorBody)),
+)]
+async fn delete_task(Path(task_id): Path<String>) -> ApiResult {
+    blocking(move || {
+        service::delete_task(&open_db()?, &task_id, &web_actor())?;
+        Ok(Json(serde_json::json!({"status": "success"})))
+    }).await
+}
+
+/// Hide a closed task from the dashboard.
+#[utoipa::path(
+    post, path = "/api/tasks/{task_id}/archive", tag = "tasks",
+    params(("task_id" = String, Path, description = "Task id")),
+    responses((status = 200, description = "Success", body = serde_json::Value),
+        (status = "4XX", description = "Invalid request or state", body = ErrorBody)),
+)]
+async fn archive_task(Path(task_id): Path<String>) -> ApiResult {
+    blocking(move || {
+        service::archive(&open_db()?, &task_id, &web_actor())?;
+        Ok(Json(serde_json::json!({"status": "success"})))
+    }).await
+}
+
+/// Bring an archived task back onto the dashboard.
+#[utoipa::path(
+    delete, path = "/api/tasks/{task_id}/archive", tag = "tasks",
+    params(("task_id" = String, Path, description = "Task id")),
+    responses((status = 200, description = "Success", body = serde_json::Value),
+        (status = "4XX", description = "Invalid request or state", body = ErrorBody)),
+)]
+async fn unarchive_task(Path(task_id): Path<String>) -> ApiResult {
+    blocking(move || {
+        service::unarchive(&open_db()?, &task_id, &web_actor())?;
+        Ok(Json(serde_json::json!({"status": "success"})))
+    }).await
+}
+
+/// Assign a task to an agent and spawn its worker session.
+#[utoipa::path(
+    post, path = "/api/start", tag = "tasks",
+    request_body = SlingRequest,
+    responses((status = 200, description = "Success", body = serde_json::Value),
+        (status = "4XX", description = "Invalid request or state", body = ErrorBody)),
+)]
+async fn start_task(Json(req): Json<SlingRequest>) -> ApiResult {
+    blocking(move || {
+        let work_dir = work_dir()?;
+        let db = open_db()?;
+        let sling = service::Sling { task_id: req.task_id, agent: req.agent_name, engine: req.engine, replace: req.replace, allow_dirty: req.allow_dirty, dry_run: false, brief: None };
+        service::sling(&db, &work_dir, &sling, &web_actor())?;
+        Ok(Json(serde_json::json!({"status": "success"})))
+    }).await
+}
+
+/// Close a task, tear down its worker and archive its logs.
+#[utoipa::path(
+    post, path = "/api/done/{task_id}", tag = "tasks",
+    params(("task_id" = String, Path, description = "Task id")),
+    responses((status = 200, description = "Success", body = serde_json::Value),
+        (status = "4XX", description = "Invalid request or state", body = ErrorBody)),
+)]
+async fn done_task(Path(task_id): Path<String>) -> ApiResult {
+    blocking(move || {
+        let work_dir = work_dir()?;
+        let db = open_db()?;
+        let closed = service::done(&db, &work_dir, &task_id, &web_actor())?;
+        Ok(Json(serde_json::json!({
+            "status": "success",
+            "conflicts": closed.conflicts.map(|mc| mc.conflicts).unwrap_or_default(),
+            "archive": closed.archive.map(|p| p.display().to_string()),
+        })))
+    }).await
+}
+
+/// Flash a message in the agent's session, or mail it when the session is gone.
+#[utoipa::path(
+    post, path = "/api/nudge", tag = "sessions",
+    request_body = NudgeRequest,
+    responses((status = 200, description = "Success", body = serde_json::Value),
+        (status = "4XX", description = "Invalid request or state", body = ErrorBody)),
+)]
+async fn nudge_agent(Json(req): Json<NudgeRequest>) -> ApiResult {
+    blocking(move || {
+        let work_dir = work_dir()?;
+        let db = open_db()?;
+        let shown = service::nudge(&db, &work_dir, &req.agent_name, &req.message, &web_actor())?;
+        Ok(Json(serde_json::json!({"status": "success", "shown": shown})))
+    }).await
+}
+
+/// Start the admin session.
+#[utoipa::path(
+    post, path = "/api/admin/start", tag = "sessions",
+    responses((status = 200, description = "Success", body = serde_json::Value),
+        (status = "4XX", description = "Invalid request or state", body = ErrorBody)),
+)]
+async fn start_admin() -> ApiResult {
+    blocking(move || {
+        let work_dir = work_dir()?;
+        Admin::new(work_dir.clone()).start(None)?;
+        open_db()?.log_audit(&web_actor(), "admin_started", "hq-admin", "success")?;
+        Ok(Json(serde_json::json!({"status": "success"})))
+    }).await
+}
+
+/// Stop the admin session.
+#[utoipa::path(
+    post, path = "/api/admin/stop", tag = "sessions",
+    responses((status = 200, description = "Success", body = serde_json::Value),
+        (status = "4XX", description = "Invalid request or state", body = ErrorBody)),
+)]
+async fn stop_admin() -> ApiResult {
+    blocking(move || {
+        let work_dir = work_dir()?;
+        Admin::new(work_dir.clone()).stop()?;
+        open_db()?.log_audit(&web_actor(), "admin_stopped", "hq-admin", "success")?;
+        Ok(Json(serde_json::json!({"status": "success"})))
+    }).await
+}
+
+/// Kill the agent's session and remove its worktree.
+#[utoipa::path(
+    post, path = "/api/workers/{name}/nuke", tag = "sessions",
+    params(("name" = String, Path, description = "Agent name")),
+    responses((status = 200, description = "Success", body = serde_json::Value),
+        (status = "4XX", description = "Invalid request or state", body = ErrorBody)),
+)]
+async fn nuke_worker(Path(name): Path<String>) -> ApiResult {
+    blocking(move || {
+        let work_dir = work_dir()?;
+        service::nuke(&open_db()?, &work_dir, &name, &web_actor())?;
+        Ok(Json(serde_json::json!({"status": "success"})))
+    }).await
+}
+
+/// Respawn the agent's session on its in-progress task.
+#[utoipa::path(
+    post, path = "/api/workers/{name}/restart", tag = "sessions",
+    params(("name" = String, Path, description = "Agent name")),
+    responses((status = 200, description = "Success", body = serde_json::Value),
+        (status = "4XX", description = "Invalid request or state", body = ErrorBody)),
+)]
+async fn restart_worker(Path(name): Path<String>) -> ApiResult {
+    blocking(move || {
+        let work_dir = work_dir()?;
+        let task_id = service::restart(&open_db()?, &work_dir, &name, &web_actor())?;
+        Ok(Json(serde_json::json!({"status": "success", "task_id": task_id})))
+    }).await
+}
+
+/// Read a role prompt.
+#[utoipa::path(
+    get, path = "/api/prompts/{role}", tag = "board",
+    params(("role" = String, Path, description = "Role name, e.g. worker")),
+    responses((status = 200, description = "Success", body = serde_json::Value),
+        (status = "4XX", description = "Invalid request or state", body = ErrorBody)),
+)]
+async fn get_prompt(Path(role): Path<String>) -> ApiResult {
+    blocking(move || {
+        ids::check("role", &role)?;
+        let work_dir = work_dir()?;
+        let path = work_dir.join("prompts").join(format!("{}.md", role));
+        let content = fs::read_to_string(path).unwrap_or_else(|_| "Prompt not found.".to_string());
+        Ok(Json(serde_json::json!({"content": content})))
+    }).await
+}
+
+/// List an agent's worktree: its top level as `files`, and the whole tree with sizes and
+/// modification times as `tree` (cut off after a couple thousand entries, see `truncated`).
+#[utoipa::path(
+    get, path = "/api/agents/{agent_name}/files", tag = "board",
+    params(("agent_name" = String, Path, description = "Agent name")),
+    responses((status = 200, description = "Success", body = serde_json::Value),
+        (status = "4XX", description = "Invalid request or state", body = ErrorBody)),
+)]
+async fn list_agent_files(Path(agent_name): Path<String>) -> ApiResult {
+    blocking(move || {
+        ids::agent_name(&agent_name)?;
+        let work_dir = work_dir()?;
+        let (tree, truncated) = files::tree(&work_dir.join("workers").join(&agent_name));
+        let names: Vec<&str> = tree.iter().map(|n| n.name.as_str()).collect();
+        Ok(Json(serde_json::json!({"files": names, "tree": tree, "truncated": truncated})))
+    }).await
+}
+
+#[derive(Deserialize, IntoParams)]
+struct FileQuery {
+    /// Path relative to the worker directory, e.g. src/main.rs.
+    path: String,
+}
+
+/// The start of one file in an agent's worktree: text files with a syntax hint, binary files
+/// as metadata only.
+#[utoipa::path(
+    get, path = "/api/agents/{agent_name}/file", tag = "board",
+    params(("agent_name" = String, Path, description = "Agent name"), FileQuery),
+    responses((status = 200, description = "Success", body = serde_json::Value),
+        (status = "4XX", description = "Invalid request or state", body = ErrorBody)),
+)]
+async fn get_agent_file(Path(agent_name): Path<String>, Query(q): Query<FileQuery>) -> ApiResult {
+    blocking(move || {
+        ids::agent_name(&agent_name)?;
+        let work_dir = work_dir()?;
+        let preview = files::preview(&work_dir.join("workers").join(&agent_name), &q.path)?;
+        Ok(Json(serde_json::to_value(preview).unwrap_or_default()))
+    }).await
+}
+
+/// Audit trail of a task and its assignee.
+#[utoipa::path(
+    get, path = "/api/tasks/{task_id}/history", tag = "board",
+    params(("task_id" = String, Path, description = "Task id")),
+    responses((status = 200, description = "Success", body = serde_json::Value),
+        (status = "4XX", description = "Invalid request or state", body = ErrorBody)),
+)]
+async fn get_task_history(Path(task_id): Path<String>) -> ApiResult {
+    blocking(move || {
+        let db = open_db()?;
+
+        // Search for logs where target is task_id OR actor is the task's assignee
+        let mut stmt = db.conn.prepare("SELECT timestamp, actor, action, target, status, metadata FROM audit_logs WHERE target = ?1 OR actor IN (SELECT assignee FROM tasks WHERE id = ?1) ORDER BY timestamp DESC")?;
+
+        let history = stmt.query_map([&task_id], |row| {
+            Ok(serde_json::json!({
+                "timestamp": row.get::<_, i64>(0)?,
+                "actor": row.get::<_, String>(1)?,
+                "action": row.get::<_, String>(2)?,
+                "target": row.get::<_, String>(3)?,
+                "status": row.get::<_, String>(4)?,
+                "metadata": row.get::<_, Option<String>>(5)?.and_then(|m| serde_json::from_str::<serde_json::Value>(&m).ok()),
+            }))
+        })?.collect::<rusqlite::Result<Vec<_>>>()?;
+
+        Ok(Json(serde_json::json!({"history": history})))
+    }).await
+}
+
+/// What a rig task changed against the rig base.
+#[utoipa::path(
+    get, path = "/api/tasks/{task_id}/diff", tag = "board",
+    params(("task_id" = String, Path, description = "Task id")),
+    responses((status = 200, description = "Success", body = serde_json::Value),
+        (status = "4XX", description = "Invalid request or state", body = ErrorBody)),
+)]
+async fn get_task_diff(Path(task_id): Path<String>) -> ApiResult {
+    blocking(move || {
+        ids::task_id(&task_id)?;
+        let work_dir = work_dir()?;
+        let db = open_db()?;
+        let diff = merge::TaskDiff::run(&db, &work_dir, &task_id)?;
+        Ok(Json(serde_json::to_value(diff).unwrap_or_default()))
+    }).await
+}
+
+#[derive(Deserialize, IntoParams)]
+struct NotificationsQuery {
+    /// Include notifications already read or resolved.
+    #[serde(default)]
+    all: bool,
+}
+
+/// What needs attention, with the unread count for a badge.
+#[utoipa::path(
+    get, path = "/api/notifications", tag = "board",
+    params(NotificationsQuery),
+    responses((status = 200, description = "Success", body = serde_json::Value),
+        (status = "4XX", description = "Invalid request or state", body = ErrorBody)),
+)]
+async fn get_notifications(Query(q): Query<NotificationsQuery>) -> ApiResult {
+    blocking(move || {
+        let work_dir = work_dir()?;
+        let db = open_db()?;
+        notify::sync(&db, &work_dir)?;
+        Ok(Json(serde_json::json!({"unread": notify::unread(&db)?, "notifications": notify::list(&db, q.all)?})))
+    }).await
+}
+
+/// Mark a notification read.
+#[utoipa::path(
+    post, path = "/api/notifications/{id}/ack", tag = "board",
+    params(("id" = i64, Path, description = "Notification id")),
+    responses((status = 200, description = "Success", body = serde_json::Value),
+        (status = "4XX", description = "Invalid request or state", body = ErrorBody)),
+)]
+async fn ack_notification(Path(id): Path<i64>) -> ApiResult {
+    blocking(move || {
+        let db = open_db()?;
+        notify::ack(&db, &[id], &web_actor())?;
+        Ok(Json(serde_json::json!({"status": "success", "unread": notify::unread(&db)?})))
+    }).await
+}
+
+/// Unfinished tasks past their due date, most overdue first.
+#[utoipa::path(
+    get, path = "/api/tasks/overdue", tag = "board",
+    responses((status = 200, description = "Success", body = serde_json::Value),
+        (status = "4XX", description = "Invalid request or state", body = ErrorBody)),
+)]
+async fn get_overdue_tasks() -> ApiResult {
+    blocking(move || {
+        let now = chrono::Local::now().timestamp();
+        let tasks: Vec<serde_json::Value> = open_db()?.overdue_tasks()?.into_iter().map(|t| serde_json::json!({
+            "id": t.id, "title": t.title, "assignee": t.assignee, "due_at": t.due_at, "overdue_secs": now - t.due_at,
+        })).collect();
+        Ok(Json(serde_json::json!({"tasks": tasks})))
+    }).await
+}
+
+/// Files the task's agents attached to their mail while working on it.
+#[utoipa::path(
+    get, path = "/api/tasks/{task_id}/artifacts", tag = "board",
+    params(("task_id" = String, Path, description = "Task id")),
+    responses((status = 200, description = "Success", body = serde_json::Value),
+        (status = "4XX", description = "Invalid request or state", body = ErrorBody)),
+)]
+async fn get_task_artifacts(Path(task_id): Path<String>) -> ApiResult {
+    blocking(move || {
+        ids::task_id(&task_id)?;
+        let db = open_db()?;
+        db.task_assignee(&task_id)?;
+        let artifacts: Vec<serde_json::Value> = db.task_artifacts(&task_id)?.into_iter().map(|(message, sender, timestamp, a)| serde_json::json!({
+            "artifact": a.artifact, "name": a.name, "path": a.path, "size": a.size,
+            "message_id": message, "sender": sender, "timestamp": timestamp,
+        })).collect();
+        Ok(Json(serde_json::json!({"task_id": task_id, "artifacts": artifacts})))
+    }).await
+}
+
+/// What an artifact changes against the file at the same path in its task's rig.
+#[utoipa::path(
+    get, path = "/api/artifacts/{artifact}/diff", tag = "board",
+    params(("artifact" = String, Path, description = "Artifact id, or a prefix of 8+ characters")),
+    responses((status = 200, description = "Success", body = serde_json::Value),
+        (status = "4XX", description = "Invalid request or state", body = ErrorBody)),
+)]
+async fn get_artifact_diff(Path(artifact): Path<String>) -> ApiResult {
+    blocking(move || {
+        let work_dir = work_dir()?;
+        let diff = merge::ArtifactDiff::run(&open_db()?, &work_dir, &artifact)?;
+        Ok(Json(serde_json::to_value(diff).unwrap_or_default()))
+    }).await
+}
+
+/// Last lines of the agent's live pane, for a terminal preview without attaching.
+#[utoipa::path(
+    get, path = "/api/workers/{name}/screen", tag = "board",
+    params(("name" = String, Path, description = "Agent name"), ScreenQuery),
+    responses((status = 200, description = "Success", body = serde_json::Value),
+        (status = "4XX", description = "Invalid request or state", body = ErrorBody)),
+)]
+async fn get_worker_screen(Path(name): Path<String>, Query(q): Query<ScreenQuery>) -> ApiResult {
+    blocking(move || {
+        ids::agent_name(&name)?;
+        let work_dir = work_dir()?;
+        let session_name = Worker::session_name(&name);
+        let screen = session::backend(&work_dir)?.capture(&session_name, q.lines.clamp(1, 500))?;
+        Ok(Json(serde_json::json!({"agent": name, "session": session_name, "screen": screen})))
+    }).await
+}
+
+/// The public status page, when the workspace turns it on with `[server] status_page`.
+async fn get_status() -> Result<Response, ThinkTodoError> {
+    blocking(move || {
+        let work_dir = work_dir()?;
+        if !Config::load(&work_dir)?.server.status_page { return Ok(StatusCode::NOT_FOUND.into_response()); }
+        let up = STARTED.get().map_or(0, |t| t.elapsed().as_secs() as i64);
+        let page = status::html(&status::collect(&open_db()?, &work_dir, up)?);
+        Ok(([(header::CONTENT_TYPE, "text/html; charset=utf-8")], page).into_response())
+    }).await
+}
+
+/// Who the request is signed in as, and whether changes need a sign-in.
+#[utoipa::path(
+    get, path = "/api/me", tag = "board",
+    responses((status = 200, description = "Success", body = serde_json::Value),
+        (status = "4XX", description = "Invalid request or state", body = ErrorBody)),
+)]
+async fn get_me() -> ApiResult {
+    blocking(move || {
+        let user = USER.try_with(String::clone).ok();
+        Ok(Json(serde_json::json!({"user": user, "login_required": !open_db()?.users()?.is_empty()})))
+    }).await
+}
+
+#[derive(Deserialize, IntoParams)]
+struct HistoryQuery {
+    /// How many days back, today included (1-366, default 30).
+    #[serde(default = "default_history_days")]
+    days: i64,
+}
+
+fn default_history_days() -> i64 { 30 }
+
+/// One day of a history chart.
+#[derive(Serialize, ToSchema)]
+struct HistoryPoint {
+    /// Local date, YYYY-MM-DD.
+    day: String,
+    value: f64,
+}
+
+/// One point per day over the last `days` days, valued by `value`.
+async fn history(days: i64, value: fn(&stats::HistoryDay) -> f64) -> Result<Json<Vec<HistoryPoint>>, ThinkTodoError> {
+    if !(1..=366).contains(&days) {
+        return Err(ThinkTodoError::InvalidArgument(format!("days must be between 1 and 366, not {}", days)));
+    }
+    blocking(move || {
+        let since = chrono::Local::now().timestamp() - (days - 1) * 86400;
+        let days = stats::history(&open_db()?, since)?;
+        Ok(Json(days.iter().map(|d| HistoryPoint { day: d.day.to_string(), value: value(d) }).collect()))
+    }).await
+}
+
+/// Tasks closed per day.
+#[utoipa::path(
+    get, path = "/api/history/closed", tag = "board",
+    params(HistoryQuery),
+    responses((status = 200, description = "Tasks closed each day, oldest first", body = Vec<HistoryPoint>),
+        (status = "4XX", description = "Invalid request or state", body = ErrorBody)),
+)]
+async fn get_closed_history(Query(q): Query<HistoryQuery>) -> Result<Json<Vec<HistoryPoint>>, ThinkTodoError> {
+    history(q.days, |d| d.closed as f64).await
+}
+
+/// Spend per day in USD.
+#[utoipa::path(
+    get, path = "/api/history/spend", tag = "board",
+    params(HistoryQuery),
+    responses((status = 200, description = "USD spent each day, oldest first", body = Vec<HistoryPoint>),
+        (status = "4XX", description = "Invalid request or state", body = ErrorBody)),
+)]
+async fn get_spend_history(Query(q): Query<HistoryQuery>) -> Result<Json<Vec<HistoryPoint>>, ThinkTodoError> {
+    history(q.days, |d| d.spend_usd).await
+}
+
+/// Agents with a worker running per day.
+#[utoipa::path(
+    get, path = "/api/history/workers", tag = "board",
+    params(HistoryQuery),
+    responses((status = 200, description = "Agents that had a worker running each day, oldest first", body = Vec<HistoryPoint>),
+        (status = "4XX", description = "Invalid request or state", body = ErrorBody)),
+)]
+async fn get_workers_history(Query(q): Query<HistoryQuery>) -> Result<Json<Vec<HistoryPoint>>, ThinkTodoError> {
+    history(q.days, |d| d.workers as f64).await
+}
+
+/// iCalendar feed of due dates.
+#[utoipa::path(
+    get, path = "/api/calendar.ics", tag = "board",
+    responses((status = 200, description = "iCalendar feed", content_type = "text/calendar", body = String),
+        (status = "4XX", description = "Invalid request or state", body = ErrorBody)),
+)]
+async fn get_calendar() -> Result<Response, ThinkTodoError> {
+    blocking(move || {
+        let db = open_db()?;
+        let feed = calendar::ics(&db)?;
+        Ok(([(axum::http::header::CONTENT_TYPE, "text/calendar; charset=utf-8")], feed).into_response())
+    }).await
+}
+
+/// Full log of one agent on one task.
+#[utoipa::path(
+    get, path = "/api/logs/{task_id}/{agent_name}", tag = "board",
+    params(("task_id" = String, Path, description = "Task id"), ("agent_name" = String, Path, description = "Agent name")),
+    responses((status = 200, description = "Log content", body = AgentLogResponse),
+        (status = "4XX", description = "Invalid request or state", body = ErrorBody)),
+)]
+async fn get_agent_logs(Path((task_id, agent_name)): Path<(String, String)>) -> Result<Json<AgentLogResponse>, ThinkTodoError> {
+    blocking(move || {
+        ids::task_id(&task_id)?;
+        ids::agent_name(&agent_name)?;
+        let work_dir = work_dir()?;
+        // Path: .logs/tasks/<task_id>/<agent_name>.log
+        let log_path = work_dir.join(".logs").join("tasks").join(&task_id).join(format!("{}.log", agent_name));
     
-    let history = stmt.query_map([&task_id], |row| {
-        Ok(serde_json::json!({
-            "timestamp": row.get::<_, i64>(0)?,
-            "actor": row.get::<_, String>(1)?,
-            "action": row.get::<_, String>(2)?,
-            "target": row.get::<_, String>(3)?,
-            "status": row.get::<_, String>(4)?,
+        let content = if log_path.exists() {
+            fs::read_to_string(&log_path).unwrap_or_else(|_| "Error reading log file.".to_string())
+        } else {
+            format!("Log file not found at: {:?}", log_path)
+        };
+
+        Ok(Json(AgentLogResponse {
+            content,
+            path: log_path.to_string_lossy().to_string(),
         }))
-    }).unwrap().map(|r| r.unwrap()).collect::<Vec<_>>();
+    }).await
+}
 
-    Json(serde_json::json!({"history": history}))
+#[derive(Deserialize, IntoParams)]
+struct DashboardQuery {
+    /// Include archived tasks.
+    #[serde(default)]
+    include_archived: bool,
 }
 
-async fn get_agent_logs(Path((task_id, agent_name)): Path<(String, String)>) -> Json<AgentLogResponse> {
-    let work_dir = env::current_dir().unwrap();
-    // Path: .logs/tasks/<task_id>/<agent_name>.log
-    let log_path = work_dir.join(".logs").join("tasks").join(&task_id).join(format!("{}.log", agent_name));
-    
-    let content = if log_path.exists() {
-        fs::read_to_string(&log_path).unwrap_or_else(|_| "Error reading log file.".to_string())
-    } else {
-        format!("Log file not found at: {:?}", log_path)
-    };
+/// Tasks, activity, cost and health for the dashboard.
+#[utoipa::path(
+    get, path = "/api/dashboard", tag = "board",
+    params(DashboardQuery),
+    responses((status = 200, description = "Dashboard snapshot", body = DashboardData),
+        (status = "4XX", description = "Invalid request or state", body = ErrorBody)),
+)]
+async fn get_dashboard(Query(q): Query<DashboardQuery>) -> Result<Json<DashboardData>, ThinkTodoError> {
+    blocking(move || {
+        let work_dir = work_dir()?;
+        let db = open_db()?;
 
-    Json(AgentLogResponse {
-        content,
-        path: log_path.to_string_lossy().to_string(),
-    })
-}
-
-async fn get_dashboard() -> Json<DashboardData> {
-    let work_dir = env::current_dir().unwrap();
-    let db = Db::new(work_dir).unwrap();
-
-    // 1. Get Tasks (Make engine field optional to handle legacy data)
-    let mut stmt = db.conn.prepare("SELECT id, title, status, assignee, engine FROM tasks").unwrap();
-    let tasks = stmt.query_map([], |row| {
-        Ok(TaskData {
-            id: row.get(0)?,
-            title: row.get(1)?,
-            status: row.get(2)?,
-            assignee: row.get(3)?,
-            engine: row.get(4).ok(),
-        })
-    }).unwrap().map(|r| r.unwrap()).collect::<Vec<_>>();
-
-    // 2. Get Recent Logs
-    let mut stmt = db.conn.prepare("SELECT timestamp, actor, action, target FROM audit_logs ORDER BY timestamp DESC LIMIT 20").unwrap();
-    let logs = stmt.query_map([], |row| {
-        Ok(LogData {
-            timestamp: row.get(0)?,
-            actor: row.get(1)?,
-            action: row.get(2)?,
-            target: row.get(3)?,
-        })
-    }).unwrap().map(|r| r.unwrap()).collect::<Vec<_>>();
-
-    // 3. Get Active Agents (from tasks in progress)
-    let agents = tasks.iter()
-        .filter(|t| t.status == "in_progress")
-        .filter_map(|t| t.assignee.clone())
-        .collect::<Vec<_>>();
-
-    // 4. Get Stats
-    let mut stmt = db.conn.prepare("SELECT SUM(cost_usd) FROM costs").unwrap();
-    let total_cost: f64 = stmt.query_row([], |row| row.get(0)).unwrap_or(0.0);
-    
-    let tasks_total = tasks.len() as i64;
-    let tasks_done = tasks.iter().filter(|t| t.status == "closed").count() as i64;
-
-    Json(DashboardData {
-        tasks,
-        agents,
-        recent_logs: logs,
-        stats: StatsData { total_cost, tasks_done, tasks_total },
-    })
+        // 1. Get Tasks (Make engine field optional to handle legacy data)
+        let mut stmt = db.conn.prepare(
+            "SELECT id, title, status, assignee, engine, COALESCE(priority, 1), archived_at IS NOT NULL FROM tasks
+             WHERE ?1 OR archived_at IS NULL ORDER BY priority DESC, rowid")?;
+        let mut tasks = stmt.query_map([q.include_archived], |row| {
+            Ok(TaskData {
+                id: row.get(0)?,
+                title: row.get(1)?,
+                status: row.get(2)?,
+                assignee: row.get(3)?,
+                engine: row.get(4).ok(),
+                priority: service::priority_name(row.get(5)?).to_string(),
+                tags: Vec::new(),
+                archived: row.get(6)?,
+            })
+        })?.collect::<rusqlite::Result<Vec<_>>>()?;
+        for t in &mut tasks { t.tags = db.task_tags(&t.id)?; }
+
+        // 2. Get Recent Logs
+        let mut stmt = db.conn.prepare("SELECT timestamp, actor, action, target FROM audit_logs ORDER BY timestamp DESC LIMIT 20")?;
+        let logs = stmt.query_map([], |row| {
+            Ok(LogData {
+                timestamp: row.get(0)?,
+                actor: row.get(1)?,
+                action: row.get(2)?,
+                target: row.get(3)?,
+            })
+        })?.collect::<rusqlite::Result<Vec<_>>>()?;
+
+        // 3. Get Active Agents (from tasks in progress)
+        let agents = tasks.iter()
+            .filter(|t| t.status == "in_progress")
+            .filter_map(|t| t.assignee.clone())
+            .collect::<Vec<_>>();
+
+        // 4. Get Stats
+        let mut stmt = db.conn.prepare("SELECT SUM(cost_usd) FROM costs")?;
+        let total_cost: f64 = stmt.query_row([], |row| row.get(0)).unwrap_or(0.0);
+
+        // Archived tasks still count towards progress.
+        let (tasks_total, tasks_done): (i64, i64) = db.conn.query_row(
+            "SELECT COUNT(*), COUNT(*) FILTER (WHERE status = 'closed') FROM tasks", [], |row| Ok((row.get(0)?, row.get(1)?)))?;
+
+        // 5. Health
+        let config = Config::load(&work_dir).unwrap_or_default();
+        let warnings = health::warnings(&db, &work_dir).unwrap_or_default();
+        let last_seen = health::heartbeat(&db).unwrap_or(None);
+        let health = HealthData {
+            stalled: warnings.iter().filter(|w| w.kind == "stalled").count(),
+            failed: warnings.iter().filter(|w| w.kind == "failed").count(),
+            queue_depth: tasks.iter().filter(|t| t.status == "open").count() as i64,
+            budget: BudgetData {
+                today_usd: db.today_spend().unwrap_or(0.0),
+                daily_cap_usd: config.budget.daily_usd_cap,
+                frozen: db.frozen().unwrap_or(None),
+            },
+            daemon: DaemonData {
+                alive: last_seen.is_some_and(|t| chrono::Local::now().timestamp() - t <= config.health.daemon_timeout_secs),
+                last_seen,
+            },
+            disk: health::disk_usage(&db, &work_dir)?,
+            warnings,
+        };
+
+        Ok(Json(DashboardData {
+            tasks,
+            agents,
+            recent_logs: logs,
+            stats: StatsData { total_cost, tasks_done, tasks_total },
+            health,
+        }))
+    }).await
 }
@@ -0,0 +1,613 @@
+use crate::artifacts;
+use crate::config::Config;
+use crate::db::{Attachment, Db};
+use crate::error::{Result, ThinkTodoError};
+use crate::ids;
+use crate::logs;
+use crate::output::Progress;
+use crate::period;
+use crate::merge::MergeCheck;
+use crate::session;
+use crate::usage;
+use crate::worker::Worker;
+use rusqlite::{params, OptionalExtension};
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, ExitStatus};
+
+// Task and session operations shared by the CLI, the HTTP API and the gRPC interface.
+// Front ends only parse input and render the outcome, so their behavior can't drift apart.
+
+pub struct NewTask {
+    pub id: String,
+    pub title: String,
+    pub rig: Option<String>,
+    pub due: Option<i64>,
+    pub tags: Vec<String>,
+    /// See `parse_priority`; None leaves it at normal (or as it was).
+    pub priority: Option<i64>,
+    /// Replace the title (and apply rig/due/tags) when the id already exists instead of failing.
+    pub force_title_update: bool,
+    /// Add it even when an unfinished task has a very similar title.
+    pub force: bool,
+    pub estimate: Option<Estimate>,
+}
+
+/// Task priorities from lowest to highest; a task stores its index, so higher sorts first.
+pub const PRIORITIES: [&str; 4] = ["low", "normal", "high", "urgent"];
+
+/// clap value parser for `--priority`.
+pub fn parse_priority(value: &str) -> Result<i64> {
+    PRIORITIES.iter().position(|p| *p == value).map(|i| i as i64)
+        .ok_or_else(|| ThinkTodoError::InvalidArgument(format!("unknown priority '{}' (expected {})", value, PRIORITIES.join(", "))))
+}
+
+pub fn priority_name(priority: i64) -> &'static str {
+    PRIORITIES.get(priority as usize).copied().unwrap_or("normal")
+}
+
+/// How big a task was expected to be: a span of time or story points.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Estimate {
+    Secs(i64),
+    Points(i64),
+}
+
+impl Estimate {
+    /// clap value parser for `--estimate`: a span like 30m, 2h or 1d, or points like 3pts.
+    pub fn parse(value: &str) -> Result<Estimate> {
+        let value = value.trim();
+        let points = ["points", "point", "pts", "pt"].iter().find_map(|unit| value.strip_suffix(unit)).map(|n| n.trim().parse::<i64>());
+        let estimate = match points {
+            Some(n) => n.ok().map(Estimate::Points),
+            None => period::span_secs(value).map(Estimate::Secs),
+        };
+        estimate.filter(|e| matches!(e, Estimate::Secs(n) | Estimate::Points(n) if *n > 0))
+            .ok_or_else(|| ThinkTodoError::InvalidArgument(format!("can't read '{}' as an estimate (use e.g. 30m, 2h, 1d or 3pts)", value)))
+    }
+}
+
+impl std::fmt::Display for Estimate {
+    /// The largest unit that fits exactly: 7200 -> "2h", 5400 -> "90m"; 1 point -> "1pt".
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match *self {
+            Estimate::Secs(secs) => {
+                let (n, unit) = [(604800, 'w'), (86400, 'd'), (3600, 'h'), (60, 'm'), (1, 's')].into_iter()
+                    .find(|(size, _)| secs % size == 0).unwrap_or((1, 's'));
+                write!(f, "{}{}", secs / n, unit)
+            }
+            Estimate::Points(1) => write!(f, "1pt"),
+            Estimate::Points(n) => write!(f, "{}pts", n),
+        }
+    }
+}
+
+/// Sets or, with None, removes a task's estimate.
+pub fn set_task_estimate(db: &Db, id: &str, estimate: Option<Estimate>) -> Result<()> {
+    db.set_task_estimate(id, estimate.map(|e| e.to_string()).as_deref())
+}
+
+/// A task's estimate; one that can't be read (written by hand into the database) is an error.
+pub fn task_estimate(db: &Db, id: &str) -> Result<Option<Estimate>> {
+    db.task_estimate(id)?.as_deref().map(Estimate::parse).transpose()
+}
+
+/// An unused id for a task added without one, from `[tasks] id_prefix` and `id_digits`.
+pub fn new_task_id(db: &Db, work_dir: &Path) -> Result<String> {
+    let config = Config::load(work_dir)?.tasks;
+    db.next_task_id(&config.id_prefix, config.id_digits)
+}
+
+/// Registers a task; returns false when an existing task was updated instead.
+pub fn add_task(db: &Db, task: &NewTask) -> Result<bool> {
+    ids::task_id(&task.id)?;
+    for tag in &task.tags { ids::tag(tag)?; }
+    if let Some(rig) = &task.rig { db.rig_path(rig)?; }
+    if !task.force {
+        let similar = similar_tasks(db, &task.title)?.into_iter().filter(|(id, _)| *id != task.id).collect::<Vec<_>>();
+        if !similar.is_empty() { return Err(ThinkTodoError::DuplicateTask { title: task.title.clone(), similar }); }
+    }
+    let created = match db.add_task(&task.id, &task.title) {
+        Ok(()) => true,
+        Err(ThinkTodoError::TaskExists { .. }) if task.force_title_update => {
+            db.update_task_title(&task.id, &task.title)?;
+            false
+        }
+        Err(e) => return Err(e),
+    };
+    if let Some(rig) = &task.rig { db.set_task_rig(&task.id, rig)?; }
+    if task.due.is_some() { db.set_task_due(&task.id, task.due)?; }
+    if let Some(p) = task.priority { db.set_task_priority(&task.id, p)?; }
+    if task.estimate.is_some() { set_task_estimate(db, &task.id, task.estimate)?; }
+    db.add_task_tags(&task.id, &task.tags)?;
+    Ok(created)
+}
+
+/// How alike two titles must be, 0 to 1, for a new task to count as a duplicate.
+const DUPLICATE_SIMILARITY: f64 = 0.8;
+
+/// Unfinished tasks whose title is nearly the same as `title`, most alike first.
+pub fn similar_tasks(db: &Db, title: &str) -> Result<Vec<(String, String)>> {
+    let mut stmt = db.conn.prepare("SELECT id, title FROM tasks WHERE status != 'closed'")?;
+    let rows = stmt.query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)))?.collect::<rusqlite::Result<Vec<_>>>()?;
+    let mut similar: Vec<(f64, String, String)> = rows.into_iter()
+        .map(|(id, other)| (similarity(title, &other), id, other))
+        .filter(|(score, ..)| *score >= DUPLICATE_SIMILARITY).collect();
+    similar.sort_by(|a, b| b.0.total_cmp(&a.0));
+    Ok(similar.into_iter().map(|(_, id, title)| (id, title)).collect())
+}
+
+/// Dice coefficient of the character pairs of both titles, compared lowercase with punctuation
+/// and extra whitespace dropped, so "Fix the parser!" and "fix  the parser" are the same.
+/// Titles that differ in a number ("Fix bug 12", "Fix bug 13") are never alike.
+fn similarity(a: &str, b: &str) -> f64 {
+    let normalize = |s: &str| s.to_lowercase().split(|c: char| !c.is_alphanumeric()).filter(|w| !w.is_empty()).collect::<Vec<_>>().join(" ");
+    let (a, b) = (normalize(a), normalize(b));
+    if a == b { return 1.0; }
+    let numbered = |s: &str| s.split(' ').filter(|w| w.chars().any(|c| c.is_ascii_digit())).map(str::to_string).collect::<Vec<_>>();
+    if numbered(&a) != numbered(&b) { return 0.0; }
+    let pairs = |s: &str| { let c: Vec<char> = s.chars().collect(); c.windows(2).map(|w| (w[0], w[1])).collect::<Vec<_>>() };
+    let (a, mut b) = (pairs(&a), pairs(&b));
+    if a.is_empty() || b.is_empty() { return 0.0; }
+    let total = a.len() + b.len();
+    let mut shared = 0;
+    for pair in a {
+        if let Some(i) = b.iter().position(|p| *p == pair) { b.swap_remove(i); shared += 1; }
+    }
+    2.0 * shared as f64 / total as f64
+}
+
+/// What a destructive operation removes. Dry runs print it; real runs record it as the audit entry's metadata.
+#[derive(Default, Serialize)]
+pub struct Teardown {
+    pub sessions: Vec<String>,
+    pub paths: Vec<PathBuf>,
+    /// Database rows, e.g. "task T1" or "tag T1#backend".
+    pub records: Vec<String>,
+}
+
+impl Teardown {
+    pub fn is_empty(&self) -> bool { self.sessions.is_empty() && self.paths.is_empty() && self.records.is_empty() }
+
+    fn add_worker(&mut self, work_dir: &Path, agent: &str) -> Result<()> {
+        let session_name = Worker::session_name(agent);
+        if session::backend(work_dir)?.exists(&session_name) { self.sessions.push(session_name); }
+        let dir = work_dir.join("workers").join(agent);
+        if dir.exists() { self.paths.push(dir); }
+        Ok(())
+    }
+
+    fn metadata(&self) -> String { serde_json::to_string(self).unwrap_or_default() }
+}
+
+/// What `delete_task` removes: the task row, its tags and the dependencies from and on it.
+pub fn plan_delete_task(db: &Db, task_id: &str) -> Result<Teardown> {
+    ids::task_id(task_id)?;
+    db.task_assignee(task_id)?;
+    let mut plan = Teardown { records: vec![format!("task {}", task_id)], ..Default::default() };
+    plan.records.extend(db.task_tags(task_id)?.into_iter().map(|t| format!("tag {}#{}", task_id, t)));
+    let mut stmt = db.conn.prepare("SELECT task_id, depends_on FROM task_deps WHERE task_id = ?1 OR depends_on = ?1 ORDER BY task_id, depends_on")?;
+    let deps = stmt.query_map(params![task_id], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)))?.collect::<rusqlite::Result<Vec<_>>>()?;
+    plan.records.extend(deps.into_iter().map(|(from, on)| format!("dependency {} -> {}", from, on)));
+    Ok(plan)
+}
+
+pub fn delete_task(db: &Db, task_id: &str, actor: &str) -> Result<Teardown> {
+    let plan = plan_delete_task(db, task_id)?;
+    let _lock = db.lock(&format!("task {}", task_id), actor, "delete")?;
+    db.conn.execute("DELETE FROM task_tags WHERE task_id = ?1", params![task_id])?;
+    db.conn.execute("DELETE FROM task_deps WHERE task_id = ?1 OR depends_on = ?1", params![task_id])?;
+    db.conn.execute("DELETE FROM tasks WHERE id = ?1", params![task_id])?;
+    db.log_audit_meta(actor, "task_deleted", task_id, "success", &plan.metadata())?;
+    Ok(plan)
+}
+
+/// Who works on what, and how the session is launched.
+pub struct Sling {
+    pub task_id: String,
+    pub agent: String,
+    pub engine: String,
+    pub replace: bool,
+    pub allow_dirty: bool,
+    /// Print what would be launched instead of launching it.
+    pub dry_run: bool,
+    /// Extra instructions appended to the prompt (see `mail_brief`).
+    pub brief: Option<String>,
+}
+
+impl Sling {
+    fn worker(&self, work_dir: &Path) -> Worker {
+        let mut w = Worker::new(self.task_id.clone(), self.agent.clone(), work_dir.to_path_buf(), self.engine.clone(), "worker".to_string());
+        w.replace = self.replace;
+        w.allow_dirty = self.allow_dirty;
+        w.dry_run = self.dry_run;
+        w.brief = self.brief.clone();
+        w
+    }
+}
+
+/// Assigns the task to the agent and spawns its worker session.
+/// `actor` is who asked; the task records the delegation chain from the human through `actor`
+/// to the agent (see `Db::delegation_chain`).
+pub fn sling(db: &Db, work_dir: &Path, req: &Sling, actor: &str) -> Result<()> {
+    ids::task_id(&req.task_id)?;
+    ids::agent_name(&req.agent)?;
+    db.task_assignee(&req.task_id)?;
+    let w = req.worker(work_dir);
+    if req.dry_run { return w.spawn(); }
+    db.ensure_not_frozen()?;
+    db.ensure_agent_free(&req.agent, &req.task_id)?;
+    ensure_team_scope(db, &req.agent, &req.task_id)?;
+    w.spawn()?;
+    db.log_audit_meta(&req.agent, "task_started", &req.task_id, "success", &lifecycle(db, &req.task_id)?.to_string())?;
+    db.conn.execute("UPDATE tasks SET assignee = ?1, status = 'in_progress', engine = ?2 WHERE id = ?3",
+        params![req.agent, req.engine, req.task_id])?;
+    let mut chain = db.delegation_chain(actor)?;
+    chain.push(req.agent.clone());
+    db.set_task_delegation(&req.task_id, &chain)?;
+    db.log_audit_meta(actor, "delegated", &req.task_id, &chain.join(" → "), &serde_json::json!({"chain": chain}).to_string())?;
+    Ok(())
+}
+
+/// How long the task has been worked on since its first spawn and what it has cost so far, for the
+/// audit entries of spawns and `done` (shown by `tt trail` as "took 3h 0m and $4.20").
+fn lifecycle(db: &Db, task_id: &str) -> Result<serde_json::Value> {
+    let (secs, cost) = db.task_lifecycle(task_id)?;
+    Ok(serde_json::json!({ "duration_secs": secs.unwrap_or(0), "cost_usd": cost }))
+}
+
+/// Agents on a team with a scope only take tasks in it.
+fn ensure_team_scope(db: &Db, agent: &str, task_id: &str) -> Result<()> {
+    for team in db.teams(Some(agent))? {
+        let Some(scope) = team.scope else { continue };
+        if !scope.matches(db, task_id)? {
+            return Err(ThinkTodoError::InvalidArgument(format!("{} is on team {}, which only takes tasks in {}", agent, team.name, scope)));
+        }
+    }
+    Ok(())
+}
+
+/// Spawns a worker session for the task without assigning it (`tt worker spawn`).
+pub fn spawn(db: &Db, work_dir: &Path, req: &Sling, actor: &str) -> Result<()> {
+    ids::task_id(&req.task_id)?;
+    ids::agent_name(&req.agent)?;
+    ensure_team_scope(db, &req.agent, &req.task_id)?;
+    req.worker(work_dir).spawn()?;
+    if !req.dry_run {
+        let mut meta = lifecycle(db, &req.task_id)?;
+        meta["task"] = req.task_id.clone().into();
+        db.log_audit_meta(actor, "spawn", &req.agent, "success", &meta.to_string())?;
+    }
+    Ok(())
+}
+
+pub struct Closed {
+    /// Set when the task branch will conflict with its rig (see `tt merge --resolve`).
+    pub conflicts: Option<MergeCheck>,
+    /// The worker that was torn down.
+    pub worker: Option<String>,
+    pub archive: Option<PathBuf>,
+    /// The task's estimate with the time (since its first attempt) and money it actually took.
+    pub estimate: Option<(Estimate, i64, f64)>,
+}
+
+/// What `done` removes: the assignee's session and worker directory, and the live log directory
+/// (which is compressed into `.logs/archive` first).
+pub fn plan_done(db: &Db, work_dir: &Path, task_id: &str) -> Result<Teardown> {
+    ids::task_id(task_id)?;
+    let mut plan = Teardown::default();
+    if let Some(agent) = db.task_assignee(task_id)? { plan.add_worker(work_dir, &agent)?; }
+    let live = logs::tasks_dir(work_dir).join(task_id);
+    if live.is_dir() { plan.paths.push(live); }
+    Ok(plan)
+}
+
+/// Closes the task: ends its attempts, tears down its worker and archives its logs.
+pub fn done(db: &Db, work_dir: &Path, task_id: &str, actor: &str) -> Result<Closed> {
+    let plan = plan_done(db, work_dir, task_id)?;
+    let _lock = db.lock(&format!("task {}", task_id), actor, "done")?;
+    let assignee = db.task_assignee(task_id)?;
+    let _worker_lock = assignee.as_ref().map(|name| db.lock(&format!("worker {}", name), actor, "done")).transpose()?;
+    let conflicts = MergeCheck::run(db, task_id).ok().filter(|mc| !mc.conflicts.is_empty());
+    // Usage footers written since the monitor's last pass would leave with the logs.
+    if let Err(e) = usage::collect(db, work_dir) { tracing::warn!(task = %task_id, "usage not collected: {}", e); }
+    let mut metadata = serde_json::to_value(&plan).unwrap_or_default();
+    if let (Some(meta), serde_json::Value::Object(spent)) = (metadata.as_object_mut(), lifecycle(db, task_id)?) { meta.extend(spent); }
+    let estimate = task_estimate(db, task_id).unwrap_or_else(|e| {
+        tracing::warn!(task = %task_id, "estimate left out of the close: {}", e);
+        None
+    });
+    if let (Some(meta), Some(e)) = (metadata.as_object_mut(), estimate) { meta.insert("estimate".to_string(), e.to_string().into()); }
+    let (secs, cost) = db.task_lifecycle(task_id)?;
+    db.end_attempts(Some(task_id), None, "done")?;
+    if let Some(name) = &assignee {
+        artifacts::collect(work_dir, task_id, &work_dir.join("workers").join(name))?;
+        let _ = Worker::nuke(name, work_dir);
+    }
+    db.conn.execute("UPDATE tasks SET status = 'closed' WHERE id = ?1", params![task_id])?;
+    db.log_audit_meta(actor, "task_closed", task_id, "success", &metadata.to_string())?;
+    let archive = logs::archive_task(work_dir, task_id)?;
+    Ok(Closed { conflicts, worker: assignee, archive, estimate: estimate.map(|e| (e, secs.unwrap_or(0), cost)) })
+}
+
+pub struct Reopened {
+    /// The last assignee, who gets the task back.
+    pub agent: Option<String>,
+    pub engine: Option<String>,
+    /// Where the work was restored from (the task branch or a snapshot); None when there was nothing saved.
+    pub restored: Option<String>,
+}
+
+/// Undoes `done` within `[undo] window`: reopens the task, brings its work back into the last
+/// assignee's worker directory and leaves the task linked to that assignee.
+pub fn undone(db: &Db, work_dir: &Path, task_id: &str, actor: &str) -> Result<Reopened> {
+    ids::task_id(task_id)?;
+    let _lock = db.lock(&format!("task {}", task_id), actor, "undone")?;
+    let (status, agent, engine): (String, Option<String>, Option<String>) = db.conn.query_row(
+        "SELECT status, assignee, engine FROM tasks WHERE id = ?1", params![task_id], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+    ).optional()?.ok_or_else(|| ThinkTodoError::TaskNotFound(task_id.to_string()))?;
+    if status != "closed" { return Err(ThinkTodoError::InvalidArgument(format!("task '{}' is {}, not closed", task_id, status))); }
+
+    let window = Config::load(work_dir)?.undo.window;
+    let window_secs = period::span_secs(&window).unwrap_or(0);
+    if window_secs <= 0 { return Err(ThinkTodoError::InvalidArgument("undo is disabled ([undo] window)".to_string())); }
+    let closed_at: Option<i64> = db.conn.query_row(
+        "SELECT MAX(timestamp) FROM audit_logs WHERE target = ?1 AND action = 'task_closed'", params![task_id], |row| row.get(0))?;
+    if let Some(age) = closed_at.map(|c| chrono::Local::now().timestamp() - c).filter(|age| *age > window_secs) {
+        return Err(ThinkTodoError::InvalidArgument(format!(
+            "task '{}' was closed {} ago, outside the undo window of {}", task_id, period::format_span(age), window)));
+    }
+
+    let mut restored = None;
+    if let Some(agent) = &agent {
+        db.ensure_agent_free(agent, task_id)?;
+        let worker_path = work_dir.join("workers").join(agent);
+        // Closed by the monitor rather than `tt done`: the directory was never torn down.
+        if !worker_path.exists() {
+            let rig = db.task_rig(task_id)?;
+            restored = artifacts::restore(work_dir, task_id, rig.as_ref().map(|(_, p)| p.as_path()), &worker_path)?;
+        }
+    }
+    db.conn.execute("UPDATE tasks SET status = 'open', archived_at = NULL WHERE id = ?1", params![task_id])?;
+    let metadata = serde_json::json!({ "agent": agent, "restored": restored }).to_string();
+    db.log_audit_meta(actor, "task_reopened", task_id, "success", &metadata)?;
+    Ok(Reopened { agent, engine, restored })
+}
+
+/// Hides a closed task from `tt task list` and the dashboard unless archived tasks are asked for.
+pub fn archive(db: &Db, task_id: &str, actor: &str) -> Result<()> {
+    ids::task_id(task_id)?;
+    let (status, archived_at): (String, Option<i64>) = db.conn.query_row(
+        "SELECT status, archived_at FROM tasks WHERE id = ?1", params![task_id], |row| Ok((row.get(0)?, row.get(1)?))
+    ).optional()?.ok_or_else(|| ThinkTodoError::TaskNotFound(task_id.to_string()))?;
+    if status != "closed" { return Err(ThinkTodoError::InvalidArgument(format!("task '{}' is {}, not closed", task_id, status))); }
+    if archived_at.is_some() { return Err(ThinkTodoError::InvalidArgument(format!("task '{}' is already archived", task_id))); }
+    db.conn.execute("UPDATE tasks SET archived_at = ?2 WHERE id = ?1", params![task_id, chrono::Local::now().timestamp()])?;
+    db.log_audit(actor, "task_archived", task_id, "success")?;
+    Ok(())
+}
+
+/// Brings an archived task back into the default listings.
+pub fn unarchive(db: &Db, task_id: &str, actor: &str) -> Result<()> {
+    ids::task_id(task_id)?;
+    let archived_at: Option<i64> = db.conn.query_row(
+        "SELECT archived_at FROM tasks WHERE id = ?1", params![task_id], |row| row.get(0)
+    ).optional()?.ok_or_else(|| ThinkTodoError::TaskNotFound(task_id.to_string()))?;
+    if archived_at.is_none() { return Err(ThinkTodoError::InvalidArgument(format!("task '{}' is not archived", task_id))); }
+    db.conn.execute("UPDATE tasks SET archived_at = NULL WHERE id = ?1", params![task_id])?;
+    db.log_audit(actor, "task_unarchived", task_id, "success")?;
+    Ok(())
+}
+
+/// Sends mail with attachments. Each one is a file, copied into the artifact store, or the id
+/// (or an 8+ character prefix) of something already stored. Returns the message id.
+/// Sends one message per receiver: `@<team>` reaches the team's lead and members.
+pub fn send_mail(db: &Db, work_dir: &Path, sender: &str, receiver: &str, subject: &str, body: &str, attach: &[String]) -> Result<Vec<(String, i64)>> {
+    ids::receiver(receiver)?;
+    let receivers = match receiver.strip_prefix('@') {
+        Some(team) => {
+            let team = db.teams(None)?.into_iter().find(|t| t.name == team)
+                .ok_or_else(|| ThinkTodoError::InvalidArgument(format!("no team named '{}'", team)))?;
+            let mut all: Vec<String> = Vec::new();
+            for r in team.lead.into_iter().chain(team.members) {
+                if r != sender && !all.contains(&r) { all.push(r); }
+            }
+            all
+        }
+        None => vec![receiver.to_string()],
+    };
+    let mut attachments = Vec::new();
+    // An agent attaching from its worker directory proposes a file of its task (see `ArtifactDiff`).
+    let task_id: Option<String> = db.conn.query_row(
+        "SELECT id FROM tasks WHERE assignee = ?1 AND status = 'in_progress'", params![sender], |row| row.get(0)).optional()?;
+    let worker = work_dir.join("workers").join(sender).canonicalize().ok();
+    for a in attach {
+        let path = Path::new(a);
+        let att = if path.exists() {
+            let (artifact, size) = artifacts::store(work_dir, path)?;
+            let name = path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_else(|| artifact.clone());
+            let rel = worker.as_ref().zip(path.canonicalize().ok())
+                .and_then(|(w, p)| p.strip_prefix(w).ok().map(|r| r.to_string_lossy().replace('\\', "/")));
+            Attachment { name, artifact, size, task_id: task_id.clone().filter(|_| rel.is_some()), path: rel }
+        } else {
+            let artifact = artifacts::resolve(work_dir, a)?;
+            let size = fs::metadata(artifacts::stored(work_dir, &artifact))?.len();
+            let name = db.artifact_name(&artifact)?.unwrap_or_else(|| artifact[..12].to_string());
+            Attachment { name, artifact, size, path: None, task_id: None }
+        };
+        attachments.push(att);
+    }
+    let mut sent = Vec::new();
+    for r in receivers {
+        let id = db.send_mail(sender, &r, subject, body)?;
+        for att in &attachments { db.add_attachment(id, att)?; }
+        sent.push((r, id));
+    }
+    Ok(sent)
+}
+
+/// A mail message rendered as a worker brief, so instructions written as mail reach the worker's prompt.
+pub fn mail_brief(db: &Db, message_id: i64) -> Result<String> {
+    let (sender, subject, body): (String, String, String) = db.conn.query_row(
+        "SELECT sender, subject, body FROM messages WHERE id = ?1", params![message_id], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+    ).optional()?.ok_or_else(|| ThinkTodoError::InvalidArgument(format!("mail #{} not found", message_id)))?;
+    let mut brief = format!("## BRIEF (mail #{} from {}: {})\n\n{}", message_id, sender, subject, body.trim_end());
+    let attachments = db.attachments(message_id)?;
+    if !attachments.is_empty() {
+        brief.push_str(&format!("\n\nAttachments (fetch them with `tt mail read {} --save-attachments`):", message_id));
+        for a in attachments { brief.push_str(&format!("\n- {}", a.name)); }
+    }
+    Ok(brief)
+}
+
+/// Copies a message's attachments into `dest` under their names; returns the written paths.
+/// Names are reduced to their last component so a sender can't write outside `dest`, and
+/// existing files are never overwritten.
+pub fn save_attachments(db: &Db, work_dir: &Path, message_id: i64, dest: &Path) -> Result<Vec<PathBuf>> {
+    let mut saved = Vec::new();
+    for att in db.attachments(message_id)? {
+        let name = Path::new(&att.name).file_name().map(|n| n.to_os_string()).unwrap_or_else(|| att.artifact.clone().into());
+        let target = dest.join(name);
+        if target.exists() {
+            return Err(ThinkTodoError::InvalidArgument(format!("{} already exists; pick another directory", target.display())));
+        }
+        fs::create_dir_all(dest)?;
+        fs::copy(artifacts::stored(work_dir, &att.artifact), &target)?;
+        saved.push(target);
+    }
+    Ok(saved)
+}
+
+/// Flashes the message in the agent's session, or mails it when no session can show it.
+/// Returns whether it was shown.
+pub fn nudge(db: &Db, work_dir: &Path, agent: &str, message: &str, actor: &str) -> Result<bool> {
+    ids::agent_name(agent)?;
+    let session_name = Worker::session_name(agent);
+    let shown = session::backend(work_dir)
+        .map(|b| b.exists(&session_name) && b.notify(&session_name, &format!("!!! NUDGE: {} !!!", message)).is_ok())
+        .unwrap_or(false);
+    if shown {
+        db.log_audit(actor, "nudge_sent", agent, "success")?;
+    } else {
+        db.send_mail(actor, agent, "NUDGE: Action Required", message)?;
+        db.log_audit(actor, "nudge_mailed", agent, "success")?;
+    }
+    Ok(shown)
+}
+
+/// Stops all new agent activity. Returns the reason it was already frozen for, if it was.
+pub fn freeze(db: &Db, reason: &str, actor: &str) -> Result<Option<String>> {
+    if let Some(current) = db.frozen()? { return Ok(Some(current)); }
+    db.set_setting("freeze", reason)?;
+    db.log_audit(actor, "freeze", "workspace", reason)?;
+    tracing::warn!("workspace frozen: {}", reason);
+    Ok(None)
+}
+
+/// Lifts a freeze from `tt freeze`, the budget cap or the freeze file. Returns whether there was one.
+pub fn unfreeze(db: &Db, work_dir: &Path, actor: &str) -> Result<bool> {
+    let file = work_dir.join(crate::db::FREEZE_FILE);
+    let removed_file = file.exists() && fs::remove_file(&file).is_ok();
+    let cleared = db.clear_setting("freeze")?;
+    if cleared || removed_file {
+        let today = chrono::Local::now().format("%Y-%m-%d");
+        db.set_setting(crate::db::BUDGET_ALLOWED_KEY, &format!("{} {}", today, db.today_spend()?))?;
+        db.log_audit(actor, "unfreeze", "workspace", "success")?;
+    }
+    Ok(cleared || removed_file)
+}
+
+/// What `nuke` removes: the agent's session and worker directory.
+pub fn plan_nuke(work_dir: &Path, agent: &str) -> Result<Teardown> {
+    ids::agent_name(agent)?;
+    let mut plan = Teardown::default();
+    plan.add_worker(work_dir, agent)?;
+    Ok(plan)
+}
+
+/// Kills the agent's session and removes its worktree.
+pub fn nuke(db: &Db, work_dir: &Path, agent: &str, actor: &str) -> Result<Teardown> {
+    let plan = plan_nuke(work_dir, agent)?;
+    let _lock = db.lock(&format!("worker {}", agent), actor, "nuke")?;
+    Worker::nuke(agent, work_dir)?;
+    db.log_audit_meta(actor, "worker_nuked", agent, "success", &plan.metadata())?;
+    Ok(plan)
+}
+
+/// Worker directories (and their sessions) that no in-progress task is assigned to.
+pub fn plan_gc(db: &Db, work_dir: &Path) -> Result<Teardown> {
+    let mut plan = Teardown::default();
+    let Ok(entries) = fs::read_dir(work_dir.join("workers")) else { return Ok(plan) };
+    let mut agents: Vec<String> = entries.flatten().filter(|e| e.path().is_dir()).map(|e| e.file_name().to_string_lossy().to_string()).collect();
+    agents.sort();
+    for agent in agents {
+        let busy: bool = db.conn.query_row(
+            "SELECT EXISTS(SELECT 1 FROM tasks WHERE assignee = ?1 AND status = 'in_progress')", params![agent], |row| row.get(0))?;
+        if !busy { plan.add_worker(work_dir, &agent)?; }
+    }
+    Ok(plan)
+}
+
+pub fn gc(db: &Db, work_dir: &Path, actor: &str, progress: &Progress) -> Result<Teardown> {
+    let plan = plan_gc(db, work_dir)?;
+    if plan.is_empty() { return Ok(plan); }
+    progress.set_len(plan.paths.len());
+    for dir in &plan.paths {
+        if let Some(agent) = dir.file_name().and_then(|n| n.to_str()) {
+            progress.start(agent);
+            Worker::nuke(agent, work_dir)?;
+        }
+        progress.inc();
+    }
+    db.log_audit_meta(actor, "gc", "workers", "success", &plan.metadata())?;
+    Ok(plan)
+}
+
+/// What `remove_rig` removes: only the registration; the rig's files stay where they are.
+pub fn plan_remove_rig(db: &Db, name: &str) -> Result<Teardown> {
+    db.rig_path(name)?;
+    let open: Vec<String> = db.conn.prepare("SELECT id FROM tasks WHERE rig = ?1 AND status != 'closed' ORDER BY id")?
+        .query_map(params![name], |row| row.get(0))?.collect::<rusqlite::Result<_>>()?;
+    if !open.is_empty() {
+        return Err(ThinkTodoError::InvalidArgument(format!("rig '{}' still has unfinished tasks: {}", name, open.join(", "))));
+    }
+    Ok(Teardown { records: vec![format!("rig {}", name)], ..Default::default() })
+}
+
+pub fn remove_rig(db: &Db, name: &str, actor: &str) -> Result<Teardown> {
+    let plan = plan_remove_rig(db, name)?;
+    db.conn.execute("DELETE FROM rigs WHERE name = ?1", params![name])?;
+    db.log_audit_meta(actor, "rig_removed", name, "success", &plan.metadata())?;
+    Ok(plan)
+}
+
+/// Respawns the agent on its in-progress task; returns that task's id.
+pub fn restart(db: &Db, work_dir: &Path, agent: &str, actor: &str) -> Result<String> {
+    ids::agent_name(agent)?;
+    let task_id = Worker::restart(agent, work_dir)?;
+    db.log_audit(actor, "worker_restarted", agent, &task_id)?;
+    Ok(task_id)
+}
+
+/// Runs `command` in the agent's worker directory with the environment its session has, minus the
+/// session token, so a `tt` run this way only claims to be the agent and is refused anything that
+/// changes state. Output goes straight to the terminal; returns the command's exit status.
+pub fn exec(db: &Db, work_dir: &Path, agent: &str, command: &[String], actor: &str) -> Result<ExitStatus> {
+    ids::agent_name(agent)?;
+    let dir = work_dir.join("workers").join(agent);
+    if !dir.is_dir() {
+        return Err(ThinkTodoError::InvalidArgument(format!("'{}' has no worker directory ({})", agent, dir.display())));
+    }
+    let (program, args) = command.split_first()
+        .ok_or_else(|| ThinkTodoError::InvalidArgument("give a command to run".to_string()))?;
+    let task: Option<String> = db.conn.query_row("SELECT task_id FROM agents WHERE name = ?1", params![agent], |row| row.get(0)).optional()?;
+    let mut cmd = Command::new(program);
+    cmd.args(args).current_dir(&dir)
+        .env("TT_AGENT", agent)
+        .env(session::WORKSPACE_VAR, work_dir)
+        .env_remove(session::TOKEN_VAR);
+    if let Some(task) = &task { cmd.env("TT_TASK", task); }
+    db.log_audit(actor, "worker_exec", agent, &command.join(" "))?;
+    cmd.status().map_err(|e| ThinkTodoError::InvalidArgument(format!("can't run '{}': {}", program, e)))
+}
@@ -0,0 +1,463 @@
+use crate::config::Config;
+use crate::i18n::tr;
+use crate::tmux::Tmux;
+use crate::error::{Result, ThinkTodoError};
+use std::env;
+use std::fs::{self, OpenOptions};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// What to run in a new agent session.
+pub struct SessionSpec<'a> {
+    pub name: &'a str,
+    pub dir: &'a Path,
+    /// The program and its arguments, passed on word for word: prompts carry mail bodies and
+    /// other text nobody vetted, so no backend may let a shell read them.
+    pub program: &'a str,
+    pub args: &'a [String],
+    /// Output is appended here when set.
+    pub log: Option<&'a Path>,
+    /// Environment variables set for the command.
+    pub env: &'a [(&'a str, String)],
+}
+
+impl SessionSpec<'_> {
+    pub fn command_line(&self) -> String { command_line(self.program, self.args) }
+}
+
+/// `program` and `args` as one `sh` line with every word quoted, for tmux and for showing it.
+pub fn command_line(program: &str, args: &[String]) -> String {
+    std::iter::once(program).chain(args.iter().map(String::as_str)).map(shell_quote).collect::<Vec<_>>().join(" ")
+}
+
+/// `word` as a single literal `sh` word.
+pub fn shell_quote(word: &str) -> String {
+    let plain = !word.is_empty() && word.chars().all(|c| c.is_ascii_alphanumeric() || "-_./=:@%+,".contains(c));
+    if plain { word.to_string() } else { format!("'{}'", word.replace('\'', "'\\''")) }
+}
+
+/// Where agent sessions live. Tmux on unix, background processes on Windows.
+pub trait SessionBackend {
+    fn start(&self, spec: &SessionSpec) -> Result<()>;
+    fn kill(&self, name: &str) -> Result<()>;
+    fn exists(&self, name: &str) -> bool;
+    /// Show a message inside the session. Backends without a UI return an error so callers can fall back to mail.
+    fn notify(&self, name: &str, msg: &str) -> Result<()>;
+    fn attach(&self, name: &str) -> Result<()>;
+    /// Recent on-screen output of the session, for when there is no log to read.
+    fn capture(&self, name: &str, lines: usize) -> Result<String>;
+    /// Checks that `program` is installed and answers `--version`, so a session isn't started
+    /// with an engine that would exit right away.
+    fn probe(&self, program: &str) -> Result<()> { probe_program(program, None) }
+    /// Whether sessions really run; the sandbox backend only says what it would do.
+    fn launches(&self) -> bool { true }
+    /// Freezes (or resumes) the session's processes, so it stops writing output without exiting.
+    /// Only `tt monitor --chaos` uses this.
+    fn pause(&self, name: &str, _paused: bool) -> Result<()> {
+        Err(ThinkTodoError::Session(format!("'{}' cannot be paused by this session backend", name)))
+    }
+}
+
+/// Picks the backend from `TT_SESSION_BACKEND`, then `[session] backend` in think.toml.
+pub fn backend(work_dir: &Path) -> Result<Box<dyn SessionBackend>> {
+    let name = match env::var("TT_SESSION_BACKEND") {
+        Ok(b) => b,
+        Err(_) => Config::load(work_dir)?.session.backend,
+    };
+    match name.as_str() {
+        "tmux" => Ok(Box::new(TmuxBackend::new(work_dir)?)),
+        "process" => Ok(Box::new(ProcessBackend::new(work_dir))),
+        "mock" => Ok(Box::new(MockBackend::new(work_dir))),
+        "sandbox" => Ok(Box::new(SandboxBackend(MockBackend::new(work_dir)))),
+        other => Err(ThinkTodoError::Config(format!("Unknown session backend '{}' (expected tmux|process|mock|sandbox)", other))),
+    }
+}
+
+/// Tmux sessions are shared by every workspace on the machine, so names get `[session] prefix`
+/// and each session records the workspace that started it. Sessions of other workspaces are
+/// invisible to `exists` and are never killed or taken over.
+pub struct TmuxBackend { prefix: String, work_dir: PathBuf, extra_path: String }
+
+/// The workspace a session belongs to. Set in every agent's environment so the `tt` commands
+/// it runs from its worker directory find the workspace database.
+pub const WORKSPACE_VAR: &str = "TT_WORKSPACE";
+/// Token a session was issued at spawn; tt takes the actor from it (see `Db::issue_token`).
+pub const TOKEN_VAR: &str = "TT_TOKEN";
+
+/// Whether this process runs inside an agent's session, even after dropping the session's variables:
+/// some process it descends from was started with them. Only the environment is checked where /proc
+/// isn't available. A process running as the same user can still write think.db directly; this keeps
+/// agents from acting as the user through tt.
+pub fn inside_agent_session() -> bool {
+    if env::var_os(WORKSPACE_VAR).is_some() { return true; }
+    let marks = [format!("{}=", TOKEN_VAR), format!("{}=", WORKSPACE_VAR)];
+    let mut pid = std::process::id();
+    // Bounded in case processes exit and their ids are reused while we walk.
+    for _ in 0..64 {
+        let Ok(stat) = fs::read_to_string(format!("/proc/{}/stat", pid)) else { return false };
+        // The command name is in parentheses and may hold spaces; the parent's pid is the second field after it.
+        let Some(parent) = stat.rsplit_once(')').and_then(|(_, rest)| rest.split_whitespace().nth(1)?.parse::<u32>().ok()) else { return false };
+        if parent <= 1 { return false; }
+        if let Ok(environ) = fs::read(format!("/proc/{}/environ", parent)) {
+            if environ.split(|b| *b == 0).any(|var| marks.iter().any(|m| var.starts_with(m.as_bytes()))) { return true; }
+        }
+        pid = parent;
+    }
+    false
+}
+
+impl TmuxBackend {
+    pub fn new(work_dir: &Path) -> Result<Self> {
+        let cfg = Config::load(work_dir)?.session;
+        Ok(Self { extra_path: cfg.extra_path, ..Self::with_prefix(&cfg.prefix, work_dir) })
+    }
+    /// `template` is `[session] prefix`; `{workspace}` in it becomes the workspace directory's name.
+    fn with_prefix(template: &str, work_dir: &Path) -> Self {
+        let workspace = work_dir.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+        let prefix = tmux_safe(&template.replace("{workspace}", &workspace));
+        let work_dir = work_dir.canonicalize().unwrap_or_else(|_| work_dir.to_path_buf());
+        Self { prefix, work_dir, extra_path: String::new() }
+    }
+    fn full(&self, name: &str) -> String { format!("{}{}", self.prefix, name) }
+    fn ensure_ours(&self, session: &str) -> Result<()> {
+        self.check_owner(session, Tmux::show_environment(session, WORKSPACE_VAR).as_deref())
+    }
+    /// Sessions from before ownership was recorded count as ours.
+    fn check_owner(&self, session: &str, owner: Option<&str>) -> Result<()> {
+        match owner {
+            Some(owner) if Path::new(owner) != self.work_dir => Err(ThinkTodoError::Session(format!(
+                "tmux session '{}' belongs to workspace {}; set [session] prefix (e.g. \"tt-{{workspace}}-\") to keep workspaces apart",
+                session, owner))),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// tmux reads ':' and '.' in a target as window and pane separators.
+fn tmux_safe(name: &str) -> String { name.replace([':', '.'], "_") }
+
+impl SessionBackend for TmuxBackend {
+    fn start(&self, spec: &SessionSpec) -> Result<()> {
+        let session = self.full(spec.name);
+        if Tmux::has_session(&session) { self.ensure_ours(&session)?; }
+        let mut cmd = String::new();
+        if !self.extra_path.is_empty() {
+            cmd.push_str(&format!("export PATH=\"$PATH\":{} && ", shell_quote(&self.extra_path)));
+        }
+        cmd.push_str(&format!("cd {} && ", shell_quote(&spec.dir.to_string_lossy())));
+        for (key, value) in spec.env {
+            cmd.push_str(&format!("export {}={} && ", key, shell_quote(value)));
+        }
+        match spec.log {
+            Some(log) => cmd.push_str(&format!("({} 2>&1 | tee -a {})", spec.command_line(), shell_quote(&log.to_string_lossy()))),
+            None => cmd.push_str(&spec.command_line()),
+        }
+        Tmux::new_session(&session, &cmd)?;
+        Tmux::set_environment(&session, WORKSPACE_VAR, &self.work_dir.to_string_lossy())
+    }
+    fn kill(&self, name: &str) -> Result<()> {
+        let session = self.full(name);
+        self.ensure_ours(&session)?;
+        Tmux::kill_session(&session)
+    }
+    fn exists(&self, name: &str) -> bool {
+        let session = self.full(name);
+        Tmux::has_session(&session) && self.ensure_ours(&session).is_ok()
+    }
+    fn notify(&self, name: &str, msg: &str) -> Result<()> {
+        let session = self.full(name);
+        self.ensure_ours(&session)?;
+        Tmux::display_message(&session, msg)
+    }
+    fn attach(&self, name: &str) -> Result<()> {
+        let session = self.full(name);
+        self.ensure_ours(&session)?;
+        let status = Command::new("tmux").args(["attach-session", "-t", &Tmux::target(&session)]).status()?;
+        if !status.success() { return Err(ThinkTodoError::SessionMissing(session)); }
+        Ok(())
+    }
+    fn capture(&self, name: &str, lines: usize) -> Result<String> {
+        let session = self.full(name);
+        self.ensure_ours(&session)?;
+        Tmux::capture_pane(&session, lines)
+    }
+    fn probe(&self, program: &str) -> Result<()> {
+        probe_program(program, Some(self.extra_path.as_str()).filter(|p| !p.is_empty()))
+    }
+    fn pause(&self, name: &str, paused: bool) -> Result<()> {
+        let session = self.full(name);
+        self.ensure_ours(&session)?;
+        let pid = Tmux::pane_pid(&session)?;
+        signal_tree(pid, paused)
+    }
+}
+
+/// How long `<engine> --version` may take before the engine counts as hung.
+const PROBE_TIMEOUT: Duration = Duration::from_secs(10);
+
+fn probe_program(program: &str, extra_path: Option<&str>) -> Result<()> {
+    let missing = |reason: String| ThinkTodoError::EngineMissing { engine: program.to_string(), reason };
+    let mut cmd = Command::new(program);
+    if let Some(extra) = extra_path {
+        cmd.env("PATH", format!("{}:{}", env::var("PATH").unwrap_or_default(), extra));
+    }
+    let mut child = cmd.arg("--version").stdin(Stdio::null()).stdout(Stdio::null()).stderr(Stdio::piped()).spawn()
+        .map_err(|e| missing(format!("'{}' could not be run ({}); install it or pick another engine with --engine", program, e)))?;
+    let started = Instant::now();
+    let status = loop {
+        if let Some(status) = child.try_wait()? { break status; }
+        if started.elapsed() > PROBE_TIMEOUT {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(missing(format!("'{} --version' did not answer within {}s", program, PROBE_TIMEOUT.as_secs())));
+        }
+        thread::sleep(Duration::from_millis(50));
+    };
+    if status.success() { return Ok(()); }
+    let mut stderr = String::new();
+    if let Some(mut err) = child.stderr.take() { let _ = err.read_to_string(&mut stderr); }
+    let first = stderr.lines().find(|l| !l.trim().is_empty()).unwrap_or("no output");
+    Err(missing(format!("'{} --version' failed ({}): {}", program, status, first.trim())))
+}
+
+/// Runs each session as a detached child process with output redirected to its log.
+/// PIDs are tracked under `.sessions/` in the workspace.
+pub struct ProcessBackend { pub state_dir: PathBuf }
+
+impl ProcessBackend {
+    pub fn new(work_dir: &Path) -> Self { Self { state_dir: work_dir.join(".sessions") } }
+    fn pid_file(&self, name: &str) -> PathBuf { self.state_dir.join(format!("{}.pid", name)) }
+    fn pid(&self, name: &str) -> Option<u32> {
+        fs::read_to_string(self.pid_file(name)).ok()?.trim().parse().ok()
+    }
+}
+
+impl SessionBackend for ProcessBackend {
+    fn start(&self, spec: &SessionSpec) -> Result<()> {
+        if self.exists(spec.name) { return Err(ThinkTodoError::SessionExists { session: spec.name.to_string(), task: None }); }
+        fs::create_dir_all(&self.state_dir)?;
+        let log_path = spec.log.map(Path::to_path_buf).unwrap_or_else(|| self.state_dir.join(format!("{}.out", spec.name)));
+        let log = OpenOptions::new().create(true).append(true).open(&log_path)?;
+        let child = Command::new(spec.program).args(spec.args).current_dir(spec.dir)
+            .envs(spec.env.iter().map(|(k, v)| (k, v)))
+            .stdin(Stdio::null())
+            .stdout(log.try_clone()?)
+            .stderr(log)
+            .spawn()
+            .map_err(|e| ThinkTodoError::Session(format!("Failed to start process session '{}': {}", spec.name, e)))?;
+        fs::write(self.pid_file(spec.name), child.id().to_string())?;
+        Ok(())
+    }
+    fn kill(&self, name: &str) -> Result<()> {
+        if let Some(pid) = self.pid(name) { kill_pid(pid); }
+        let _ = fs::remove_file(self.pid_file(name));
+        Ok(())
+    }
+    fn exists(&self, name: &str) -> bool {
+        self.pid(name).map(pid_alive).unwrap_or(false)
+    }
+    fn notify(&self, name: &str, _msg: &str) -> Result<()> {
+        Err(ThinkTodoError::Session(format!("'{}' runs as a background process and cannot display messages", name)))
+    }
+    fn attach(&self, name: &str) -> Result<()> {
+        Err(ThinkTodoError::Session(format!("'{}' runs as a background process; use 'tt peek' to follow its output", name)))
+    }
+    fn capture(&self, name: &str, lines: usize) -> Result<String> {
+        // Sessions started without a log write to `<name>.out`.
+        let out = fs::read_to_string(self.state_dir.join(format!("{}.out", name))).map_err(|_| ThinkTodoError::SessionMissing(name.to_string()))?;
+        let all: Vec<&str> = out.lines().collect();
+        Ok(all[all.len().saturating_sub(lines)..].join("\n"))
+    }
+    fn pause(&self, name: &str, paused: bool) -> Result<()> {
+        let pid = self.pid(name).ok_or_else(|| ThinkTodoError::SessionMissing(name.to_string()))?;
+        signal_tree(pid, paused)
+    }
+}
+
+/// Records sessions as marker files (directory, command, then `KEY=value` lines) without
+/// launching anything. Used by the integration tests.
+pub struct MockBackend { pub state_dir: PathBuf }
+
+impl MockBackend {
+    pub fn new(work_dir: &Path) -> Self { Self { state_dir: work_dir.join(".sessions").join("mock") } }
+    fn marker(&self, name: &str) -> PathBuf { self.state_dir.join(name) }
+}
+
+impl SessionBackend for MockBackend {
+    fn start(&self, spec: &SessionSpec) -> Result<()> {
+        if self.exists(spec.name) { return Err(ThinkTodoError::SessionExists { session: spec.name.to_string(), task: None }); }
+        use std::io::Write;
+        fs::create_dir_all(&self.state_dir)?;
+        // Tests make an engine die on startup with `<program>.crash`, holding what it prints.
+        let crash = fs::read_to_string(self.state_dir.join(format!("{}.crash", spec.program))).ok();
+        if let Some(log) = spec.log {
+            let mut f = OpenOptions::new().create(true).append(true).open(log)?;
+            write!(f, "{}", crash.as_deref().unwrap_or("mock session started\n"))?;
+        }
+        if crash.is_none() {
+            let env: String = spec.env.iter().map(|(k, v)| format!("{}={}\n", k, v)).collect();
+            fs::write(self.marker(spec.name), format!("{}\n{}\n{}", spec.dir.display(), spec.command_line(), env))?;
+        }
+        Ok(())
+    }
+    fn kill(&self, name: &str) -> Result<()> { let _ = fs::remove_file(self.marker(name)); Ok(()) }
+    fn exists(&self, name: &str) -> bool { self.marker(name).exists() }
+    fn notify(&self, name: &str, msg: &str) -> Result<()> {
+        use std::io::Write;
+        let mut f = OpenOptions::new().create(true).append(true).open(self.state_dir.join(format!("{}.notify", name)))?;
+        writeln!(f, "{}", msg)?;
+        Ok(())
+    }
+    fn attach(&self, _name: &str) -> Result<()> { Ok(()) }
+    /// Tests mark an engine as not installed with `<program>.missing`.
+    fn probe(&self, program: &str) -> Result<()> {
+        if !self.state_dir.join(format!("{}.missing", program)).exists() { return Ok(()); }
+        Err(ThinkTodoError::EngineMissing { engine: program.to_string(), reason: format!("'{}' is not installed (mock)", program) })
+    }
+    /// Tests put the fake screen contents in `<name>.screen`.
+    fn capture(&self, name: &str, _lines: usize) -> Result<String> {
+        if !self.exists(name) { return Err(ThinkTodoError::SessionMissing(name.to_string())); }
+        Ok(fs::read_to_string(self.state_dir.join(format!("{}.screen", name))).unwrap_or_default())
+    }
+    /// A paused session has a `<name>.paused` file.
+    fn pause(&self, name: &str, paused: bool) -> Result<()> {
+        if !self.exists(name) { return Err(ThinkTodoError::SessionMissing(name.to_string())); }
+        let flag = self.state_dir.join(format!("{}.paused", name));
+        if paused { fs::write(flag, "")?; } else { let _ = fs::remove_file(flag); }
+        Ok(())
+    }
+}
+
+/// Used by `tt --sandbox`: prints what would happen to each session and keeps the same
+/// bookkeeping as the mock backend, so later commands see the sessions it "started".
+pub struct SandboxBackend(MockBackend);
+
+impl SessionBackend for SandboxBackend {
+    fn start(&self, spec: &SessionSpec) -> Result<()> {
+        println!("{}", tr!("🧪 SANDBOX: would start session '{}' in {}", spec.name, spec.dir.display()));
+        let command = spec.command_line().split_whitespace().collect::<Vec<_>>().join(" ");
+        match command.char_indices().nth(160) {
+            Some((cut, _)) => println!("   $ {}…", &command[..cut]),
+            None => println!("   $ {}", command),
+        }
+        if let Some(log) = spec.log {
+            use std::io::Write;
+            let mut f = OpenOptions::new().create(true).append(true).open(log)?;
+            writeln!(f, "[sandbox] session not launched")?;
+        }
+        let spec = SessionSpec { log: None, ..*spec };
+        self.0.start(&spec)
+    }
+    fn kill(&self, name: &str) -> Result<()> {
+        if self.exists(name) { println!("{}", tr!("🧪 SANDBOX: would kill session '{}'", name)); }
+        self.0.kill(name)
+    }
+    fn exists(&self, name: &str) -> bool { self.0.exists(name) }
+    fn notify(&self, name: &str, msg: &str) -> Result<()> {
+        if !self.exists(name) { return Err(ThinkTodoError::SessionMissing(name.to_string())); }
+        println!("{}", tr!("🧪 SANDBOX: would show in '{}': {}", name, msg));
+        Ok(())
+    }
+    fn attach(&self, name: &str) -> Result<()> {
+        println!("{}", tr!("🧪 SANDBOX: would attach to '{}'", name));
+        Ok(())
+    }
+    fn capture(&self, name: &str, lines: usize) -> Result<String> { self.0.capture(name, lines) }
+    fn probe(&self, _program: &str) -> Result<()> { Ok(()) }
+    fn launches(&self) -> bool { false }
+    fn pause(&self, name: &str, paused: bool) -> Result<()> {
+        println!("{}", tr!("🧪 SANDBOX: would {} session '{}'", if paused { tr!("pause") } else { tr!("resume") }, name));
+        self.0.pause(name, paused)
+    }
+}
+
+#[cfg(windows)]
+fn pid_alive(pid: u32) -> bool {
+    Command::new("tasklist").args(["/FI", &format!("PID eq {}", pid), "/NH"]).output()
+        .map(|o| String::from_utf8_lossy(&o.stdout).contains(&pid.to_string()))
+        .unwrap_or(false)
+}
+
+#[cfg(not(windows))]
+fn pid_alive(pid: u32) -> bool {
+    Command::new("kill").args(["-0", &pid.to_string()]).stderr(Stdio::null()).status().map(|s| s.success()).unwrap_or(false)
+}
+
+#[cfg(windows)]
+fn kill_pid(pid: u32) {
+    let _ = Command::new("taskkill").args(["/PID", &pid.to_string(), "/T", "/F"]).stdout(Stdio::null()).stderr(Stdio::null()).status();
+}
+
+#[cfg(not(windows))]
+fn kill_pid(pid: u32) {
+    let _ = Command::new("kill").arg(pid.to_string()).stderr(Stdio::null()).status();
+}
+
+#[cfg(windows)]
+fn signal_tree(pid: u32, _paused: bool) -> Result<()> {
+    Err(ThinkTodoError::Session(format!("pausing process {} is not supported on Windows", pid)))
+}
+
+/// SIGSTOP/SIGCONT for `pid` and its children (the engine runs under a shell).
+#[cfg(not(windows))]
+fn signal_tree(pid: u32, paused: bool) -> Result<()> {
+    let signal = if paused { "-STOP" } else { "-CONT" };
+    let _ = Command::new("pkill").args([signal, "-P", &pid.to_string()]).stderr(Stdio::null()).status();
+    let status = Command::new("kill").args([signal, &pid.to_string()]).stderr(Stdio::null()).status()?;
+    if !status.success() { return Err(ThinkTodoError::Session(format!("could not signal process {}", pid))); }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ids;
+
+    #[test]
+    fn sessions_are_targeted_by_their_whole_name() {
+        let tmux = TmuxBackend::with_prefix("tt-", Path::new("/srv/proj"));
+        let (bob, bobby) = (tmux.full("worker-bob"), tmux.full("worker-bobby"));
+        assert_eq!(bob, "tt-worker-bob");
+        assert_eq!(Tmux::target(&bob), "=tt-worker-bob:");
+        assert_ne!(Tmux::target(&bob), Tmux::target(&bobby));
+    }
+
+    #[test]
+    fn colliding_prefixes_are_caught_by_the_owner() {
+        let one = TmuxBackend::with_prefix("tt-{workspace}-", Path::new("/srv/one/proj"));
+        let two = TmuxBackend::with_prefix("tt-{workspace}-", Path::new("/srv/two/proj"));
+        let session = one.full("worker-bob");
+        assert_eq!(session, two.full("worker-bob"));
+        assert!(one.check_owner(&session, Some("/srv/one/proj")).is_ok());
+        assert!(one.check_owner(&session, None).is_ok());
+        let err = two.check_owner(&session, Some("/srv/one/proj")).unwrap_err().to_string();
+        assert!(err.contains("belongs to workspace /srv/one/proj"), "{}", err);
+
+        let short = TmuxBackend::with_prefix("a-", Path::new("/srv/a"));
+        let long = TmuxBackend::with_prefix("a-b-", Path::new("/srv/b"));
+        assert_eq!(short.full("b-x"), long.full("x"));
+        assert!(long.check_owner(&short.full("b-x"), Some("/srv/a")).is_err());
+    }
+
+    #[test]
+    fn case_is_kept_in_prefixes_and_names() {
+        let upper = TmuxBackend::with_prefix("tt-{workspace}-", Path::new("/srv/Proj"));
+        let lower = TmuxBackend::with_prefix("tt-{workspace}-", Path::new("/srv/proj"));
+        assert_eq!(upper.full("worker-Bob"), "tt-Proj-worker-Bob");
+        assert_ne!(upper.full("worker-bob"), lower.full("worker-bob"));
+        assert_ne!(upper.full("worker-Bob"), upper.full("worker-bob"));
+    }
+
+    #[test]
+    fn names_at_max_len_come_through_whole() {
+        let name = ids::agent_name(&"b".repeat(ids::MAX_LEN)).unwrap();
+        let tmux = TmuxBackend::with_prefix("tt-{workspace}-", Path::new("/srv/my.proj:2"));
+        let session = tmux.full(&name);
+        assert_eq!(session, format!("tt-my_proj_2-{}", name));
+        assert_eq!(Tmux::target(&session), format!("={}:", session));
+        assert!(ids::agent_name(&format!("{}b", name)).is_err());
+    }
+}
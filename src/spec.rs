@@ -0,0 +1,136 @@
+use crate::config::Config;
+use crate::db::Db;
+use crate::error::{Result, ThinkTodoError};
+use crate::ids;
+use crate::service::{self, Estimate, NewTask};
+use serde::Deserialize;
+use std::io::{ErrorKind, Read, Write};
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// A task the planning engine proposes. `key` names it within the plan, so others can depend on it
+/// before it has an id.
+#[derive(Deserialize, Debug)]
+pub struct Proposed {
+    pub key: String,
+    pub title: String,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub depends_on: Vec<String>,
+    /// As the engine wrote it, e.g. "2h" or "3pts".
+    #[serde(default)]
+    pub estimate: Option<String>,
+}
+
+/// Hands the spec, and what the backlog already holds, to `[plan] command` and returns what it printed.
+pub fn ask(db: &Db, work_dir: &Path, spec: &str) -> Result<String> {
+    let plan = Config::load(work_dir)?.plan;
+    let command = plan.command;
+    let mut stmt = db.conn.prepare("SELECT id, title FROM tasks WHERE status != 'closed' ORDER BY rowid")?;
+    let backlog: String = stmt.query_map([], |row| Ok(format!("- [{}] {}\n", row.get::<_, String>(0)?, row.get::<_, String>(1)?)))?
+        .collect::<rusqlite::Result<Vec<_>>>()?.concat();
+    let prompt = format!("You are planning work for a team of coding agents. Break the specification below into tasks \
+that one agent can finish in a single sitting.
+Answer with only a JSON array, one object per task:
+  {{\"key\": \"short-name\", \"title\": \"...\", \"tags\": [\"...\"], \"depends_on\": [\"key of another task\"], \"estimate\": \"2h\"}}
+Keys and tags use only letters, digits, '-' and '_'. depends_on names keys from your own list. estimate is a time \
+span like 30m, 2h or 1d, or story points like 3pts.
+These tasks are already in the backlog; don't repeat them:
+{}
+--- SPECIFICATION ---
+{}", if backlog.is_empty() { "(none)\n" } else { &backlog }, spec);
+
+    let mut child = Command::new("sh").args(["-c", &command]).current_dir(work_dir)
+        .stdin(Stdio::piped()).stdout(Stdio::piped()).stderr(Stdio::piped()).spawn()
+        .map_err(|e| ThinkTodoError::Remote(format!("can't run [plan] command '{}': {}", command, e)))?;
+    // Feed and drain the pipes on their own threads: a command that prints before it has read
+    // the whole prompt would otherwise block on its output while we block on its input.
+    let stdin = child.stdin.take();
+    let writer = thread::spawn(move || match stdin.map(|mut s| s.write_all(prompt.as_bytes())) {
+        // A command that doesn't read its input isn't an error.
+        Some(Err(e)) if e.kind() != ErrorKind::BrokenPipe => Err(e),
+        _ => Ok(()),
+    });
+    let (stdout, stderr) = (drain(child.stdout.take()), drain(child.stderr.take()));
+
+    let started = Instant::now();
+    let status = loop {
+        if let Some(status) = child.try_wait()? { break status; }
+        if plan.timeout_secs > 0 && started.elapsed() >= Duration::from_secs(plan.timeout_secs) {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(ThinkTodoError::Remote(format!("[plan] command '{}' gave no answer within {}s ([plan] timeout_secs)", command, plan.timeout_secs)));
+        }
+        thread::sleep(Duration::from_millis(100));
+    };
+    writer.join().unwrap_or(Ok(()))?;
+    let (stdout, stderr) = (stdout.join().unwrap_or_default(), stderr.join().unwrap_or_default());
+    if !status.success() {
+        let said = String::from_utf8_lossy(&stderr).trim().to_string();
+        return Err(ThinkTodoError::Remote(format!("[plan] command '{}' failed ({}){}", command, status,
+            if said.is_empty() { String::new() } else { format!(": {}", said) })));
+    }
+    Ok(String::from_utf8_lossy(&stdout).into_owned())
+}
+
+/// Reads all of `pipe` on a thread of its own.
+fn drain<R: Read + Send + 'static>(pipe: Option<R>) -> thread::JoinHandle<Vec<u8>> {
+    thread::spawn(move || {
+        let mut out = Vec::new();
+        if let Some(mut pipe) = pipe { let _ = pipe.read_to_end(&mut out); }
+        out
+    })
+}
+
+/// The tasks in the engine's answer: the outermost JSON array in it, so code fences and chatter
+/// around it don't matter. Keys must be unique and dependencies must name keys of the plan.
+pub fn parse(answer: &str) -> Result<Vec<Proposed>> {
+    let json = answer.find('[').zip(answer.rfind(']')).filter(|(a, b)| a < b).map(|(a, b)| &answer[a..=b])
+        .ok_or_else(|| ThinkTodoError::InvalidArgument("the planner's answer holds no JSON array".to_string()))?;
+    let tasks: Vec<Proposed> = serde_json::from_str(json)
+        .map_err(|e| ThinkTodoError::InvalidArgument(format!("the planner's answer isn't a task list: {}", e)))?;
+    if tasks.is_empty() { return Err(ThinkTodoError::InvalidArgument("the planner proposed no tasks".to_string())); }
+    for (i, t) in tasks.iter().enumerate() {
+        ids::task_id(&t.key)?;
+        for tag in &t.tags { ids::tag(tag)?; }
+        if let Some(e) = &t.estimate { Estimate::parse(e)?; }
+        if t.title.trim().is_empty() { return Err(ThinkTodoError::InvalidArgument(format!("'{}' has no title", t.key))); }
+        if tasks[..i].iter().any(|o| o.key == t.key) { return Err(ThinkTodoError::InvalidArgument(format!("'{}' is proposed twice", t.key))); }
+        if let Some(dep) = t.depends_on.iter().find(|d| *d == &t.key || !tasks.iter().any(|o| &o.key == *d)) {
+            return Err(ThinkTodoError::InvalidArgument(format!("'{}' depends on '{}', which isn't another task of the plan", t.key, dep)));
+        }
+    }
+    // Peel off tasks whose dependencies are all peeled; whatever is left waits on itself.
+    let mut left: Vec<&Proposed> = tasks.iter().collect();
+    while let Some(i) = left.iter().position(|t| t.depends_on.iter().all(|d| !left.iter().any(|o| &o.key == d))) { left.remove(i); }
+    if !left.is_empty() {
+        return Err(ThinkTodoError::InvalidArgument(format!("the plan's dependencies go in a circle: {}",
+            left.iter().map(|t| t.key.as_str()).collect::<Vec<_>>().join(", "))));
+    }
+    Ok(tasks)
+}
+
+/// Adds the plan to the backlog with ids from `[tasks] id_prefix`, in order, and links the
+/// dependencies. Returns each key with the id it got.
+pub fn create(db: &Db, work_dir: &Path, tasks: &[Proposed], source: &str, actor: &str) -> Result<Vec<(String, String)>> {
+    let mut created: Vec<(String, String)> = Vec::new();
+    for t in tasks {
+        let id = service::new_task_id(db, work_dir)?;
+        // The user has seen any near-duplicates in the preview.
+        service::add_task(db, &NewTask {
+            id: id.clone(), title: t.title.trim().to_string(), rig: None, due: None, tags: t.tags.clone(), priority: None, force_title_update: false, force: true,
+            estimate: t.estimate.as_deref().map(Estimate::parse).transpose()?,
+        })?;
+        created.push((t.key.clone(), id));
+    }
+    let id_of = |key: &str| created.iter().find(|(k, _)| k == key).map(|(_, id)| id.as_str()).unwrap_or_default();
+    for t in tasks {
+        for dep in &t.depends_on { db.add_task_dependency(id_of(&t.key), id_of(dep))?; }
+    }
+    let metadata = serde_json::json!({ "tasks": created.iter().map(|(_, id)| id).collect::<Vec<_>>() }).to_string();
+    db.log_audit_meta(actor, "plan_imported", source, &format!("{} task(s)", created.len()), &metadata)?;
+    Ok(created)
+}
@@ -0,0 +1,269 @@
+use crate::db::{Db, TaskTimeline};
+use crate::error::{Result, ThinkTodoError};
+use crate::logs;
+use crate::service::Estimate;
+use chrono::{Datelike, Duration, Local, NaiveDate, TimeZone};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// One calendar day of `tt stats flow`.
+pub struct FlowDay {
+    pub day: NaiveDate,
+    pub created: usize,
+    pub closed: usize,
+    /// Tasks started but not closed at the end of the day.
+    pub wip: usize,
+}
+
+pub struct FlowReport {
+    pub days: Vec<FlowDay>,
+    /// Median open -> in_progress over tasks started in the window.
+    pub median_wait: Option<i64>,
+    /// Median in_progress -> closed over tasks closed in the window.
+    pub median_cycle: Option<i64>,
+}
+
+/// Throughput and cycle times for every local day from `since` up to today.
+pub fn flow(db: &Db, since: i64) -> Result<FlowReport> {
+    let timelines = db.timelines(None)?;
+    let days = days_since(since).into_iter().map(|(day, start, end)| {
+        let within = |t: Option<i64>| t.is_some_and(|t| t >= start && t < end);
+        FlowDay {
+            day,
+            created: timelines.iter().filter(|t| within(t.created_at)).count(),
+            closed: timelines.iter().filter(|t| within(t.closed_at)).count(),
+            wip: timelines.iter().filter(|t| t.started_at.is_some_and(|s| s < end) && t.closed_at.is_none_or(|c| c >= end)).count(),
+        }
+    }).collect();
+    let waits = spans(&timelines, |t| Some((t.created_at?, t.started_at?)), since);
+    let cycles = spans(&timelines, |t| Some((t.started_at?, t.closed_at?)), since);
+    Ok(FlowReport { days, median_wait: median(waits), median_cycle: median(cycles) })
+}
+
+/// One calendar day of the dashboard's history charts.
+pub struct HistoryDay {
+    pub day: NaiveDate,
+    pub closed: usize,
+    pub spend_usd: f64,
+    /// Agents that had a worker running at some point of the day.
+    pub workers: usize,
+}
+
+/// Tasks closed, money spent and workers running on every local day from `since` up to today.
+pub fn history(db: &Db, since: i64) -> Result<Vec<HistoryDay>> {
+    let timelines = db.timelines(None)?;
+    let mut days = Vec::new();
+    for (day, start, end) in days_since(since) {
+        let spend_usd: f64 = db.conn.query_row(
+            "SELECT COALESCE(SUM(cost_usd), 0) FROM costs WHERE timestamp >= ?1 AND timestamp < ?2", [start, end], |row| row.get(0))?;
+        let workers: i64 = db.conn.query_row(
+            "SELECT COUNT(DISTINCT agent_name) FROM attempts WHERE started_at < ?2 AND (ended_at IS NULL OR ended_at >= ?1)", [start, end], |row| row.get(0))?;
+        days.push(HistoryDay {
+            day,
+            closed: timelines.iter().filter(|t| t.closed_at.is_some_and(|c| c >= start && c < end)).count(),
+            spend_usd,
+            workers: workers as usize,
+        });
+    }
+    Ok(days)
+}
+
+/// Age brackets of `tt stats aging`, youngest first, each with the age it runs up to.
+pub const AGE_BUCKETS: [(&str, i64); 4] = [("< 1d", 86400), ("1-7d", 7 * 86400), ("7-30d", 30 * 86400), ("30d+", i64::MAX)];
+
+pub struct AgingTask {
+    pub id: String,
+    pub title: String,
+    pub assignee: Option<String>,
+    /// Seconds open for an open task, seconds since its logs last changed for one in progress.
+    pub age: i64,
+}
+
+pub struct AgingReport {
+    /// Open tasks per `AGE_BUCKETS` entry, oldest first within each.
+    pub open: Vec<Vec<AgingTask>>,
+    /// In-progress tasks, quietest first.
+    pub in_progress: Vec<AgingTask>,
+}
+
+/// How long open tasks have waited, and how long in-progress ones have been quiet: since their
+/// logs last changed, or since they started if no worker has logged anything yet.
+pub fn aging(db: &Db, work_dir: &Path) -> Result<AgingReport> {
+    let now = Local::now().timestamp();
+    let timelines = db.timelines(None)?;
+    let mut stmt = db.conn.prepare("SELECT id, title, status, assignee FROM tasks WHERE status IN ('open', 'in_progress')")?;
+    let rows = stmt.query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?, row.get::<_, String>(2)?, row.get::<_, Option<String>>(3)?)))?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    let mut open: Vec<Vec<AgingTask>> = AGE_BUCKETS.iter().map(|_| Vec::new()).collect();
+    let mut in_progress = Vec::new();
+    for (id, title, status, assignee) in rows {
+        let timeline = timelines.iter().find(|t| t.id == id);
+        let created = timeline.and_then(|t| t.created_at).unwrap_or(now);
+        if status == "open" {
+            let age = (now - created).max(0);
+            let bucket = AGE_BUCKETS.iter().position(|(_, upto)| age < *upto).unwrap_or(AGE_BUCKETS.len() - 1);
+            open[bucket].push(AgingTask { id, title, assignee, age });
+        } else {
+            let since = logs::last_activity(work_dir, &id).or(timeline.and_then(|t| t.started_at)).unwrap_or(created);
+            in_progress.push(AgingTask { age: (now - since).max(0), id, title, assignee });
+        }
+    }
+    for bucket in &mut open { bucket.sort_by_key(|t| std::cmp::Reverse(t.age)); }
+    in_progress.sort_by_key(|t| std::cmp::Reverse(t.age));
+    Ok(AgingReport { open, in_progress })
+}
+
+/// Every local day from `since` up to today, with the timestamps it starts and ends at.
+fn days_since(since: i64) -> Vec<(NaiveDate, i64, i64)> {
+    let now = Local::now();
+    let mut day = Local.timestamp_opt(since, 0).single().unwrap_or(now).date_naive();
+    let mut days = Vec::new();
+    while day <= now.date_naive() {
+        days.push((day, local_midnight(day), local_midnight(day + Duration::days(1))));
+        day += Duration::days(1);
+    }
+    days
+}
+
+/// Durations between two timeline points for tasks whose later point falls after `since`.
+fn spans(timelines: &[TaskTimeline], points: impl Fn(&TaskTimeline) -> Option<(i64, i64)>, since: i64) -> Vec<i64> {
+    timelines.iter().filter_map(&points).filter(|(_, to)| *to >= since).map(|(from, to)| (to - from).max(0)).collect()
+}
+
+fn median(mut values: Vec<i64>) -> Option<i64> {
+    if values.is_empty() { return None; }
+    values.sort_unstable();
+    let mid = values.len() / 2;
+    Some(if values.len().is_multiple_of(2) { (values[mid - 1] + values[mid]) / 2 } else { values[mid] })
+}
+
+fn local_midnight(day: NaiveDate) -> i64 {
+    day.and_hms_opt(0, 0, 0).and_then(|dt| Local.from_local_datetime(&dt).earliest()).map(|dt| dt.timestamp()).unwrap_or(0)
+}
+
+/// Actual time within this range of the estimate, in percent, counts as on target.
+pub const ON_TARGET: (i64, i64) = (50, 150);
+
+/// How the tasks one agent finished on one engine compared to their estimates.
+#[derive(Default)]
+pub struct EstimateRow {
+    pub engine: String,
+    pub agent: String,
+    /// Tasks estimated in time.
+    pub timed: usize,
+    /// Median of actual time over estimate, in percent.
+    pub median_ratio: Option<i64>,
+    pub on_target: usize,
+    /// Story points of the tasks estimated in points, and the time and money they took.
+    pub points: i64,
+    pub point_secs: i64,
+    pub point_cost: f64,
+}
+
+/// Tasks estimated in time that closed in one week (starting Monday), and how far off they were.
+pub struct EstimateWeek {
+    pub week: NaiveDate,
+    pub tasks: usize,
+    pub median_ratio: i64,
+}
+
+pub struct EstimateReport {
+    pub rows: Vec<EstimateRow>,
+    pub weeks: Vec<EstimateWeek>,
+}
+
+/// Estimates against actuals for the estimated tasks closed since `since`, per engine and agent of
+/// their last attempt and per week. Actual time runs from the first attempt to the close; tasks never
+/// worked on by an agent are left out.
+pub fn estimates(db: &Db, since: i64) -> Result<EstimateReport> {
+    let mut stmt = db.conn.prepare(
+        "SELECT t.estimate, c.closed_at, (SELECT MIN(started_at) FROM attempts WHERE task_id = t.id),
+             (SELECT COALESCE(SUM(cost_usd), 0) FROM costs WHERE task_id = t.id), a.engine, a.agent_name
+         FROM tasks t
+         JOIN (SELECT target, MAX(timestamp) AS closed_at FROM audit_logs WHERE action = 'task_closed' GROUP BY target) c ON c.target = t.id
+         JOIN attempts a ON a.id = (SELECT MAX(id) FROM attempts WHERE task_id = t.id)
+         WHERE t.status = 'closed' AND t.estimate IS NOT NULL AND c.closed_at >= ?1")?;
+    let tasks = stmt.query_map([since], |row| Ok((
+        row.get::<_, String>(0)?, row.get::<_, i64>(1)?, row.get::<_, i64>(2)?, row.get::<_, f64>(3)?, row.get::<_, String>(4)?, row.get::<_, String>(5)?,
+    )))?.collect::<rusqlite::Result<Vec<_>>>()?;
+
+    let mut rows: BTreeMap<(String, String), (EstimateRow, Vec<i64>)> = BTreeMap::new();
+    let mut weeks: BTreeMap<NaiveDate, Vec<i64>> = BTreeMap::new();
+    for (estimate, closed_at, started_at, cost, engine, agent) in tasks {
+        let estimate = match Estimate::parse(&estimate) {
+            Ok(estimate) => estimate,
+            Err(e) => {
+                tracing::warn!("a closed task's estimate is left out of the report: {}", e);
+                continue;
+            }
+        };
+        let secs = (closed_at - started_at).max(0);
+        let (row, ratios) = rows.entry((engine.clone(), agent.clone()))
+            .or_insert_with(|| (EstimateRow { engine, agent, ..Default::default() }, Vec::new()));
+        match estimate {
+            Estimate::Secs(estimated) => {
+                let ratio = secs * 100 / estimated;
+                row.timed += 1;
+                if (ON_TARGET.0..=ON_TARGET.1).contains(&ratio) { row.on_target += 1; }
+                ratios.push(ratio);
+                let day = Local.timestamp_opt(closed_at, 0).single().unwrap_or_else(Local::now).date_naive();
+                weeks.entry(day - Duration::days(day.weekday().num_days_from_monday() as i64)).or_default().push(ratio);
+            }
+            Estimate::Points(points) => {
+                row.points += points;
+                row.point_secs += secs;
+                row.point_cost += cost;
+            }
+        }
+    }
+    Ok(EstimateReport {
+        rows: rows.into_values().map(|(row, ratios)| EstimateRow { median_ratio: median(ratios), ..row }).collect(),
+        weeks: weeks.into_iter().map(|(week, ratios)| EstimateWeek { week, tasks: ratios.len(), median_ratio: median(ratios).unwrap_or(0) }).collect(),
+    })
+}
+
+/// What one engine/model would need to clear the backlog by the target date.
+pub struct PlanOption {
+    pub engine: String,
+    pub model: Option<String>,
+    pub secs_per_task: i64,
+    pub cost_per_task: f64,
+    pub workers: i64,
+    pub total_cost: f64,
+    pub fits_budget: bool,
+}
+
+pub struct PlanReport {
+    pub open: i64,
+    pub in_progress: i64,
+    pub window_secs: i64,
+    pub budget: Option<f64>,
+    pub options: Vec<PlanOption>,
+    /// Cheapest option within budget (fewest workers on a tie).
+    pub recommended: Option<usize>,
+}
+
+/// Sizes the worker pool needed to finish every open and in-progress task before `by`, per engine/model,
+/// from the time and spend its past successful attempts took. Failed attempts count towards cost per task.
+pub fn plan(db: &Db, by: i64, budget: Option<f64>) -> Result<PlanReport> {
+    let now = Local::now().timestamp();
+    if by <= now { return Err(ThinkTodoError::InvalidArgument("the target date must be in the future".to_string())); }
+    let (open, in_progress): (i64, i64) = db.conn.query_row(
+        "SELECT COALESCE(SUM(status = 'open'), 0), COALESCE(SUM(status = 'in_progress'), 0) FROM tasks", [], |row| Ok((row.get(0)?, row.get(1)?)))?;
+    let backlog = open + in_progress;
+    let window_secs = by - now;
+    let mut options: Vec<PlanOption> = db.engine_stats(None)?.into_iter().filter(|s| s.done > 0).map(|s| {
+        let secs_per_task = (s.done_secs / s.done).max(1);
+        let cost_per_task = s.cost_usd / s.done as f64;
+        let total_cost = cost_per_task * backlog as f64;
+        PlanOption {
+            engine: s.engine, model: s.model, secs_per_task, cost_per_task,
+            workers: (backlog * secs_per_task + window_secs - 1) / window_secs,
+            total_cost,
+            fits_budget: budget.is_none_or(|b| total_cost <= b),
+        }
+    }).collect();
+    options.sort_by(|a, b| a.total_cost.total_cmp(&b.total_cost).then(a.workers.cmp(&b.workers)));
+    let recommended = options.iter().position(|o| o.fits_budget);
+    Ok(PlanReport { open, in_progress, window_secs, budget, options, recommended })
+}
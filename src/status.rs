@@ -0,0 +1,92 @@
+use crate::config::Config;
+use crate::db::Db;
+use crate::error::Result;
+use crate::health;
+use crate::period;
+use std::path::Path;
+
+/// The numbers on the public status page: counts only, no titles, names, logs or spend.
+pub struct Status {
+    pub project: String,
+    pub total: i64,
+    pub closed: i64,
+    pub in_progress: i64,
+    pub open: i64,
+    /// Closed in the last seven days.
+    pub done_this_week: usize,
+    /// Seconds `tt serve` has been up.
+    pub uptime_secs: i64,
+    pub monitor_alive: bool,
+}
+
+impl Status {
+    pub fn progress(&self) -> i64 { if self.total == 0 { 0 } else { self.closed * 100 / self.total } }
+}
+
+pub fn collect(db: &Db, work_dir: &Path, uptime_secs: i64) -> Result<Status> {
+    let now = chrono::Local::now().timestamp();
+    let count = |status: &str| db.conn.query_row("SELECT COUNT(*) FROM tasks WHERE status = ?1", [status], |row| row.get::<_, i64>(0));
+    let total: i64 = db.conn.query_row("SELECT COUNT(*) FROM tasks", [], |row| row.get(0))?;
+    let done_this_week = db.timelines(None)?.iter()
+        .filter(|t| t.status == "closed" && t.closed_at.is_some_and(|c| now - c <= 7 * 86400)).count();
+    let timeout = Config::load(work_dir)?.health.daemon_timeout_secs;
+    Ok(Status {
+        project: work_dir.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default(),
+        total,
+        closed: count("closed")?,
+        in_progress: count("in_progress")?,
+        open: count("open")?,
+        done_this_week,
+        uptime_secs,
+        monitor_alive: health::heartbeat(db)?.is_some_and(|t| now - t <= timeout),
+    })
+}
+
+/// A self-contained page that refreshes itself every minute.
+pub fn html(s: &Status) -> String {
+    let project = escape(&s.project);
+    let monitor = if s.monitor_alive { ("ok", "running") } else { ("down", "not running") };
+    format!(r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+<meta charset="utf-8">
+<meta name="viewport" content="width=device-width, initial-scale=1">
+<meta http-equiv="refresh" content="60">
+<title>{project} · status</title>
+<style>
+body {{ font-family: system-ui, sans-serif; background: #111827; color: #e5e7eb; max-width: 40rem; margin: 3rem auto; padding: 0 1rem; }}
+h1 {{ font-size: 1.4rem; margin-bottom: 0.2rem; }}
+.sub {{ color: #9ca3af; font-size: 0.85rem; margin-bottom: 2rem; }}
+.bar {{ background: #374151; border-radius: 6px; height: 14px; overflow: hidden; }}
+.fill {{ background: #3b82f6; height: 100%; }}
+.grid {{ display: grid; grid-template-columns: repeat(3, 1fr); gap: 1rem; margin-top: 2rem; }}
+.card {{ background: #1f2937; border-radius: 8px; padding: 1rem; }}
+.num {{ font-size: 1.6rem; font-weight: 700; }}
+.label {{ color: #9ca3af; font-size: 0.75rem; text-transform: uppercase; }}
+.ok {{ color: #22c55e; }} .down {{ color: #ef4444; }}
+</style>
+</head>
+<body>
+<h1>{project}</h1>
+<div class="sub">Updated {updated}</div>
+<div class="label">Progress: {progress}% ({closed} of {total} tasks done)</div>
+<div class="bar"><div class="fill" style="width: {progress}%"></div></div>
+<div class="grid">
+<div class="card"><div class="num">{week}</div><div class="label">Done this week</div></div>
+<div class="card"><div class="num">{in_progress}</div><div class="label">In progress</div></div>
+<div class="card"><div class="num">{open}</div><div class="label">Waiting</div></div>
+<div class="card"><div class="num">{uptime}</div><div class="label">Uptime</div></div>
+<div class="card"><div class="num {monitor_class}">{monitor}</div><div class="label">Orchestrator</div></div>
+</div>
+</body>
+</html>
+"#,
+        updated = period::format_time(chrono::Local::now().timestamp()),
+        progress = s.progress(), closed = s.closed, total = s.total, week = s.done_this_week,
+        in_progress = s.in_progress, open = s.open, uptime = period::format_span(s.uptime_secs),
+        monitor_class = monitor.0, monitor = monitor.1)
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
@@ -0,0 +1,111 @@
+use crate::config::{Config, TelemetryConfig};
+use crate::db::Db;
+use crate::error::{Result, ThinkTodoError};
+use crate::export;
+use crate::period;
+use serde_json::{json, Map, Value};
+use std::env;
+use std::path::Path;
+
+/// Rows shipped per stream and pass; a backlog drains over the following passes.
+const BATCH: i64 = 500;
+
+/// A stream of rows the sink receives, named after the table it lands in.
+struct Stream {
+    name: &'static str,
+    /// Selects rows after the cursor (`?1`, and `?2` for attempts), oldest first.
+    sql: &'static str,
+}
+
+const STREAMS: &[Stream] = &[
+    Stream { name: "events", sql: "SELECT * FROM audit_logs WHERE id > ?1 ORDER BY id LIMIT ?3" },
+    Stream { name: "costs", sql: "SELECT * FROM costs WHERE id > ?1 ORDER BY id LIMIT ?3" },
+    // Attempts change until they end, so they are shipped once they have.
+    Stream {
+        name: "attempts",
+        sql: "SELECT * FROM attempts WHERE ended_at IS NOT NULL AND (ended_at > ?1 OR (ended_at = ?1 AND id > ?2)) ORDER BY ended_at, id LIMIT ?3",
+    },
+];
+
+/// Settings key of a stream's cursor: the last shipped id, or `ended_at:id` for attempts.
+fn cursor_key(stream: &str) -> String { format!("telemetry:{}", stream) }
+pub const ERROR_KEY: &str = "telemetry:error";
+const LAST_KEY: &str = "telemetry:last";
+
+/// Ships what was added since the last shipment to `[telemetry]`, at most every `interval_secs`,
+/// then prunes shipped events and costs past `prune_after`. A sink that is down is retried on the
+/// next round; its error is kept under `ERROR_KEY` until a shipment goes through.
+pub fn ship(db: &Db, work_dir: &Path) -> Result<()> {
+    let cfg = Config::load(work_dir)?.telemetry;
+    if cfg.sink.is_empty() { return Ok(()); }
+    let now = chrono::Local::now().timestamp();
+    let last: i64 = db.get_setting(LAST_KEY)?.and_then(|v| v.parse().ok()).unwrap_or(0);
+    if now - last < cfg.interval_secs { return Ok(()); }
+    db.set_setting(LAST_KEY, &now.to_string())?;
+
+    for stream in STREAMS {
+        let cursor = db.get_setting(&cursor_key(stream.name))?.unwrap_or_default();
+        let (a, b) = match cursor.split_once(':') {
+            Some((a, b)) => (a.parse().unwrap_or(0), b.parse().unwrap_or(0)),
+            None => (cursor.parse().unwrap_or(0), 0),
+        };
+        let rows = export::select_json(db, stream.sql, &[a, b, BATCH])?;
+        let Some(tail) = rows.last() else { continue };
+        let id = tail.get("id").and_then(Value::as_i64).unwrap_or(0);
+        let next = match tail.get("ended_at").and_then(Value::as_i64) {
+            Some(ended) if stream.name == "attempts" => format!("{}:{}", ended, id),
+            _ => id.to_string(),
+        };
+        if let Err(e) = send(&cfg, stream.name, &rows) {
+            tracing::warn!(sink = %cfg.sink, stream = stream.name, "telemetry not shipped: {}", e);
+            db.set_setting(ERROR_KEY, &e.to_string())?;
+            return Ok(());
+        }
+        db.set_setting(&cursor_key(stream.name), &next)?;
+        tracing::debug!(sink = %cfg.sink, stream = stream.name, rows = rows.len(), "telemetry shipped");
+    }
+    db.clear_setting(ERROR_KEY)?;
+    if let Some(keep) = period::span_secs(&cfg.prune_after) { prune(db, now - keep)?; }
+    Ok(())
+}
+
+/// Deletes events and costs that were shipped and are older than `before`.
+fn prune(db: &Db, before: i64) -> Result<()> {
+    for (table, stream) in [("audit_logs", "events"), ("costs", "costs")] {
+        let Some(shipped) = db.get_setting(&cursor_key(stream))?.and_then(|v| v.parse::<i64>().ok()) else { continue };
+        let n = db.conn.execute(&format!("DELETE FROM {} WHERE id <= ?1 AND timestamp < ?2", table), [shipped, before])?;
+        if n > 0 { tracing::info!(table, rows = n, "pruned shipped rows"); }
+    }
+    Ok(())
+}
+
+fn send(cfg: &TelemetryConfig, table: &str, rows: &[Map<String, Value>]) -> Result<()> {
+    let secret = env::var(&cfg.secret_env).ok();
+    match cfg.sink.as_str() {
+        "clickhouse" => {
+            let body: String = rows.iter().map(|r| format!("{}\n", Value::Object(r.clone()))).collect();
+            let mut req = crate::remote::post(&cfg.url)
+                .query("query", &format!("INSERT INTO {}.{} FORMAT JSONEachRow", cfg.database, table))
+                .set("X-ClickHouse-User", &cfg.user);
+            if let Some(secret) = &secret { req = req.set("X-ClickHouse-Key", secret); }
+            req.send_string(&body)?;
+        }
+        "bigquery" => {
+            let root = if cfg.url.is_empty() { "https://bigquery.googleapis.com/bigquery/v2" } else { cfg.url.trim_end_matches('/') };
+            let url = format!("{}/projects/{}/datasets/{}/tables/{}/insertAll", root, cfg.project, cfg.database, table);
+            let token = secret.ok_or_else(|| ThinkTodoError::Config(format!("[telemetry] bigquery needs an access token in ${}", cfg.secret_env)))?;
+            // insertId lets BigQuery drop duplicates when a shipment is retried.
+            let rows: Vec<Value> = rows.iter().map(|r| json!({
+                "insertId": format!("{}-{}", table, r.get("id").and_then(Value::as_i64).unwrap_or_default()),
+                "json": r,
+            })).collect();
+            let resp: Value = crate::remote::post(&url).set("Authorization", &format!("Bearer {}", token))
+                .send_json(json!({ "rows": rows }))?.into_json()?;
+            if let Some(errors) = resp.get("insertErrors") {
+                return Err(ThinkTodoError::Remote(format!("bigquery rejected rows: {}", errors)));
+            }
+        }
+        other => return Err(ThinkTodoError::Config(format!("unknown telemetry sink '{}'", other))),
+    }
+    Ok(())
+}
@@ -1,20 +1,44 @@
-use std::process::Command;
-use anyhow::{Result, Context};
+use std::process::{Command, Stdio};
+use crate::error::{Result, ThinkTodoError};
 
 pub struct Tmux;
 
 impl Tmux {
     fn run(args: &[&str]) -> Result<String> {
-        let output = Command::new("tmux").args(args).output().with_context(|| format!("Tmux failed: {:?}", args))?;
+        let output = Command::new("tmux").args(args).output()
+            .map_err(|e| ThinkTodoError::Session(format!("Tmux failed: {:?}: {}", args, e)))?;
         if !output.status.success() {
             let err = String::from_utf8_lossy(&output.stderr);
-            if err.contains("duplicate") { return Ok(String::new()); }
-            anyhow::bail!("Tmux error: {}", err.trim());
+            if err.contains("duplicate session") {
+                let target = args.iter().skip_while(|a| **a != "-s").nth(1).unwrap_or(&"");
+                return Err(ThinkTodoError::SessionExists { session: target.to_string(), task: None });
+            }
+            if err.contains("can't find session") {
+                let target = args.iter().skip_while(|a| **a != "-t").nth(1).unwrap_or(&"");
+                return Err(ThinkTodoError::SessionMissing(target.trim_start_matches('=').trim_end_matches(':').to_string()));
+            }
+            return Err(ThinkTodoError::Session(format!("Tmux error: {}", err.trim())));
         }
         Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
     }
+    /// Targets exactly `session`: tmux otherwise takes any session whose name starts with it, so
+    /// worker-bob would find worker-bobby.
+    pub fn target(session: &str) -> String { format!("={}:", session) }
     pub fn new_session(name: &str, cmd: &str) -> Result<()> { Self::run(&["new-session", "-d", "-s", name, cmd])?; Ok(()) }
-    pub fn kill_session(name: &str) -> Result<()> { let _ = Command::new("tmux").args(&["kill-session", "-t", name]).status(); Ok(()) }
-    pub fn has_session(name: &str) -> bool { Command::new("tmux").args(&["has-session", "-t", name]).status().map(|s| s.success()).unwrap_or(false) }
-    pub fn display_message(session: &str, msg: &str) -> Result<()> { Self::run(&["display-message", "-t", session, msg])?; Ok(()) }
+    pub fn kill_session(name: &str) -> Result<()> { let _ = Command::new("tmux").args(["kill-session", "-t", &Self::target(name)]).stderr(Stdio::null()).status(); Ok(()) }
+    pub fn has_session(name: &str) -> bool { Command::new("tmux").args(["has-session", "-t", &Self::target(name)]).stderr(Stdio::null()).status().map(|s| s.success()).unwrap_or(false) }
+    /// The last `lines` lines of the session's visible pane and scrollback.
+    pub fn capture_pane(session: &str, lines: usize) -> Result<String> { Self::run(&["capture-pane", "-p", "-t", &Self::target(session), "-S", &format!("-{}", lines)]) }
+    pub fn set_environment(session: &str, key: &str, value: &str) -> Result<()> { Self::run(&["set-environment", "-t", &Self::target(session), key, value])?; Ok(()) }
+    /// A variable from the session's environment; None when the session or the variable is missing.
+    pub fn show_environment(session: &str, key: &str) -> Option<String> {
+        let line = Self::run(&["show-environment", "-t", &Self::target(session), key]).ok()?;
+        line.strip_prefix(&format!("{}=", key)).map(str::to_string)
+    }
+    /// The process id of the shell in the session's pane.
+    pub fn pane_pid(session: &str) -> Result<u32> {
+        let pid = Self::run(&["display-message", "-p", "-t", &Self::target(session), "#{pane_pid}"])?;
+        pid.parse().map_err(|_| ThinkTodoError::Session(format!("no pane pid for '{}': {}", session, pid)))
+    }
+    pub fn display_message(session: &str, msg: &str) -> Result<()> { Self::run(&["display-message", "-t", &Self::target(session), msg])?; Ok(()) }
 }
@@ -0,0 +1,107 @@
+use crate::config::{Config, UsagePattern};
+use crate::db::Db;
+use crate::error::{Result, ThinkTodoError};
+use crate::logs;
+use regex::Regex;
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
+
+/// One usage report found in a worker's log.
+#[derive(Debug, PartialEq)]
+pub struct Usage {
+    pub model: String,
+    pub input: i32,
+    pub output: i32,
+    pub cost: f64,
+}
+
+/// Footers the engines print out of the box. `[usage.<engine>]` in think.toml replaces one.
+fn builtin() -> HashMap<String, UsagePattern> {
+    let pattern = |p: &str| UsagePattern { pattern: p.to_string(), ..Default::default() };
+    HashMap::from([
+        // Claude's summary on exit: "Total cost: $0.0421 ... Usage: 12,034 input, 1,820 output, ..."
+        ("claude".to_string(), pattern(r"(?s)Total cost:\s*\$(?P<cost>[0-9.]+).*?Usage:\s*(?P<input>[0-9,]+) input, (?P<output>[0-9,]+) output")),
+        // Gemini's usage metadata JSON; it carries tokens only, so the cost comes from the prices.
+        ("gemini".to_string(), UsagePattern {
+            pattern: r#"(?s)"promptTokenCount":\s*(?P<input>[0-9]+),\s*"candidatesTokenCount":\s*(?P<output>[0-9]+)"#.to_string(),
+            input_usd_per_mtok: 1.25,
+            output_usd_per_mtok: 10.0,
+            ..Default::default()
+        }),
+    ])
+}
+
+/// The usage patterns in effect: the built-in ones overlaid with the configured ones.
+pub fn patterns(config: &Config) -> HashMap<String, UsagePattern> {
+    let mut all = builtin();
+    all.extend(config.usage.clone());
+    all
+}
+
+/// Usage reports in `text`, with the byte offset just past the last one.
+pub fn parse(engine: &str, spec: &UsagePattern, text: &str) -> Result<(Vec<Usage>, usize)> {
+    let re = Regex::new(&spec.pattern)
+        .map_err(|e| ThinkTodoError::Config(format!("[usage.{}] pattern: {}", engine, e)))?;
+    let num = |c: &regex::Captures, name: &str| c.name(name).map(|m| m.as_str().replace(',', ""));
+    let mut found = Vec::new();
+    let mut end = 0;
+    for c in re.captures_iter(text) {
+        let input: i32 = num(&c, "input").and_then(|n| n.parse().ok()).unwrap_or(0);
+        let output: i32 = num(&c, "output").and_then(|n| n.parse().ok()).unwrap_or(0);
+        let cost = num(&c, "cost").and_then(|n| n.parse().ok()).unwrap_or_else(|| {
+            (input as f64 * spec.input_usd_per_mtok + output as f64 * spec.output_usd_per_mtok) / 1_000_000.0
+        });
+        let model = c.name("model").map(|m| m.as_str().to_string())
+            .or_else(|| spec.model.clone())
+            .unwrap_or_else(|| engine.to_string());
+        found.push(Usage { model, input, output, cost });
+        end = c.get(0).map_or(end, |m| m.end());
+    }
+    Ok((found, end))
+}
+
+/// Unread output kept back for the next pass when nothing matched, in case a footer is
+/// still being written.
+const CARRY_BYTES: u64 = 4096;
+
+/// Reads what the workers logged since the last pass and records every usage footer as a cost
+/// on the agent's attempt. Returns the number of entries recorded.
+pub fn collect(db: &Db, work_dir: &Path) -> Result<usize> {
+    let patterns = patterns(&Config::load(work_dir)?);
+    let Ok(tasks) = fs::read_dir(logs::tasks_dir(work_dir)) else { return Ok(0) };
+    let mut recorded = 0;
+    for task in tasks.flatten() {
+        let task_id = task.file_name().to_string_lossy().to_string();
+        let Ok(files) = fs::read_dir(task.path()) else { continue };
+        for file in files.flatten() {
+            let path = file.path();
+            let Some(agent) = path.file_name().and_then(|n| n.to_str()).and_then(|n| n.strip_suffix(".log")).map(str::to_string) else { continue };
+            let engine: Option<String> = db.conn.query_row(
+                "SELECT engine FROM attempts WHERE task_id = ?1 AND agent_name = ?2 ORDER BY id DESC LIMIT 1",
+                [&task_id, &agent], |row| row.get(0)).ok();
+            let Some((engine, spec)) = engine.and_then(|e| patterns.get(&e).map(|s| (e, s))) else { continue };
+
+            let key = format!("usage:{}:{}", task_id, agent);
+            let mut offset: u64 = db.get_setting(&key)?.and_then(|v| v.parse().ok()).unwrap_or(0);
+            let len = file.metadata()?.len();
+            // Cut back by the log quota: what's left was read before.
+            if len < offset { offset = len; }
+            let mut f = File::open(&path)?;
+            f.seek(SeekFrom::Start(offset))?;
+            let mut bytes = Vec::new();
+            f.read_to_end(&mut bytes)?;
+            let text = String::from_utf8_lossy(&bytes);
+            let (found, end) = parse(&engine, spec, &text)?;
+            for u in &found {
+                db.log_cost(&task_id, &agent, &u.model, u.input, u.output, u.cost)?;
+                tracing::info!(task = %task_id, agent = %agent, model = %u.model, cost = u.cost, "usage recorded from log");
+            }
+            recorded += found.len();
+            let next = (offset + end as u64).max(offset + (bytes.len() as u64).saturating_sub(CARRY_BYTES));
+            if next != offset { db.set_setting(&key, &next.to_string())?; }
+        }
+    }
+    Ok(recorded)
+}
@@ -0,0 +1,44 @@
+use crate::error::{Result, ThinkTodoError};
+use crate::i18n::tr;
+use crate::output;
+use crate::period;
+use std::env;
+use std::process::Command;
+use std::thread;
+use std::time::Duration;
+
+/// clap value parser for `--interval`: a span like "2s" or "1m", or plain seconds.
+pub fn interval(value: &str) -> Result<Duration> {
+    let secs = value.parse::<i64>().ok().or_else(|| period::span_secs(value));
+    match secs {
+        Some(secs) if secs > 0 => Ok(Duration::from_secs(secs as u64)),
+        _ => Err(ThinkTodoError::InvalidArgument(format!("can't read '{}' as an interval (use e.g. 2s or 1m)", value))),
+    }
+}
+
+/// Reruns `tt <args>` every `every` and redraws its output, marking the lines that changed since
+/// the previous run. Stops after `count` renders, or when interrupted.
+pub fn run(args: &[String], every: Duration, count: Option<u32>) -> Result<()> {
+    let exe = env::current_exe()?;
+    let mut previous: Vec<String> = Vec::new();
+    let mut renders = 0;
+    loop {
+        let out = Command::new(&exe).args(args).output()?;
+        let mut text = String::from_utf8_lossy(&out.stdout).into_owned();
+        text.push_str(&String::from_utf8_lossy(&out.stderr));
+        let lines: Vec<String> = text.lines().map(str::to_string).collect();
+
+        // On a terminal each render replaces the last; piped, they follow one another.
+        if output::color() { print!("\x1b[H\x1b[2J"); }
+        println!("{}", output::heading(&tr!("Every {}s: tt {}    {}", every.as_secs(), args.join(" "), period::format_time(chrono::Local::now().timestamp()))));
+        println!();
+        for (i, line) in lines.iter().enumerate() {
+            let moved = renders > 0 && previous.get(i) != Some(line);
+            println!("{}", if moved { output::changed(line) } else { line.clone() });
+        }
+        previous = lines;
+        renders += 1;
+        if count.is_some_and(|c| renders >= c) { return Ok(()); }
+        thread::sleep(every);
+    }
+}
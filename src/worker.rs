@@ -1,7 +1,17 @@
-use crate::tmux::Tmux;
-use anyhow::Result;
+use crate::config::Config;
+use crate::conventions;
+use crate::db::Db;
+use crate::digest;
+use crate::git::Git;
+use crate::i18n::tr;
+use crate::logging;
+use crate::session::{self, SessionBackend, SessionSpec};
+use crate::error::{Result, ThinkTodoError};
+use rusqlite::OptionalExtension;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant};
 
 pub struct Worker { 
     pub id: String, 
@@ -9,49 +19,216 @@ pub struct Worker {
     pub work_dir: PathBuf,
     pub engine: String,
     pub role: String, // mayor, worker, witness
+    pub brief: Option<String>, // extra context appended to the instruction (e.g. resume notes)
+    pub replace: bool, // kill an existing session for this agent instead of refusing
+    pub allow_dirty: bool, // skip the rig dirty-state guard
+    pub dry_run: bool, // print what would be launched instead of launching it
 }
 
 impl Worker {
     pub fn new(id: String, name: String, work_dir: PathBuf, engine: String, role: String) -> Self { 
-        Self { id, name, work_dir, engine, role } 
+        Self { id, name, work_dir, engine, role, brief: None, replace: false, allow_dirty: false, dry_run: false } 
     }
+    pub fn session_name(name: &str) -> String { format!("worker-{}", name) }
+    pub fn log_path(work_dir: &Path, task_id: &str, name: &str) -> PathBuf {
+        work_dir.join(".logs").join("tasks").join(task_id).join(format!("{}.log", name))
+    }
+    /// Reads `prompts/<rel>` from the workspace and, when the task belongs to a rig,
+    /// layers the rig's own `prompts/<rel>` on top of it.
+    fn layered_prompt(&self, rig: Option<&(String, PathBuf)>, rel: &Path) -> Option<String> {
+        let base = fs::read_to_string(self.work_dir.join("prompts").join(rel)).ok();
+        let overlay = rig.and_then(|(name, path)| {
+            fs::read_to_string(path.join("prompts").join(rel)).ok().map(|p| tr!("## RIG CONVENTIONS ({})\n\n{}", name, p))
+        });
+        match (base, overlay) {
+            (Some(b), Some(o)) => Some(format!("{}\n\n{}", b, o)),
+            (b, o) => b.or(o),
+        }
+    }
+
+    /// The full instruction handed to the engine.
+    pub fn compose_instruction(&self, db: &Db) -> Result<String> {
+        let rig = db.task_rig(&self.id)?;
+        let base_prompt = self.layered_prompt(rig.as_ref(), Path::new("base.md")).unwrap_or_default();
+        let role_prompt = self.layered_prompt(rig.as_ref(), &Path::new("roles").join(format!("{}.md", self.role)))
+            .unwrap_or_else(|| tr!("You are a specialized agent."));
+        
+        let mut final_instruction = format!("{}\n\n{}", base_prompt, role_prompt);
+        let config = Config::load(&self.work_dir)?;
+        if let (true, Some((name, path))) = (config.digest.enabled, rig.as_ref()) {
+            final_instruction.push_str(&format!("\n\n{}", digest::rig_digest(name, path, &config.digest)));
+        }
+        if config.prompt.preamble {
+            let identity = conventions::Identity { agent: &self.name, task: &self.id };
+            final_instruction.push_str(&format!("\n\n{}", conventions::render(&config, Some(&identity))));
+        }
+        final_instruction.push_str(&format!("\n\nMISSION ID: {}\nMISSIONS: {}", self.id, self.id));
+        if let Some(brief) = &self.brief {
+            final_instruction.push_str(&format!("\n\n{}", brief));
+        }
+        final_instruction.push_str(&tr!("\n\nFIRST ACTION: run `tt agent checkin` so the orchestrator knows you are up.\n\nEXECUTE NOW."));
+        Ok(final_instruction)
+    }
+
+    /// Applies `[rig] dirty_policy` when the task's rig has uncommitted changes or is behind upstream.
+    fn check_rig(&self, db: &Db) -> Result<()> {
+        let cfg = Config::load(&self.work_dir)?.rig;
+        let Some((name, path)) = db.task_rig(&self.id)? else { return Ok(()) };
+        if self.allow_dirty || cfg.dirty_policy == "ignore" { return Ok(()); }
+        let Ok(state) = Git::state(&path, cfg.fetch) else { return Ok(()) }; // not a git repo
+        let mut problems = Vec::new();
+        if !state.dirty.is_empty() { problems.push(format!("{} uncommitted change(s)", state.dirty.len())); }
+        if state.behind > 0 { problems.push(format!("{} commit(s) behind upstream", state.behind)); }
+        if problems.is_empty() { return Ok(()); }
+        let msg = format!("rig '{}' has {}", name, problems.join(" and "));
+        if cfg.dirty_policy == "refuse" { return Err(ThinkTodoError::RigDirty(msg)); }
+        tracing::warn!(task = %self.id, "{}", msg);
+        println!("⚠️  {}", msg);
+        Ok(())
+    }
+
+    /// The engine's executable and the flags it runs with.
+    fn engine_program(&self) -> Result<(&'static str, &'static [&'static str])> {
+        match self.engine.as_str() {
+            "opencode" => Ok(("opencode", &[])),
+            "claude" => Ok(("claude", &[])),
+            "gemini" => Ok(("gemini", &["--approval-mode", "yolo"])),
+            other => Err(ThinkTodoError::EngineMissing { engine: other.to_string(), reason: "expected claude, gemini or opencode".to_string() }),
+        }
+    }
+
+    /// The engine's executable and the arguments that run it on `instruction`.
+    fn engine_command(&self, instruction: &str) -> Result<(&'static str, Vec<String>)> {
+        let (program, flags) = self.engine_program()?;
+        let mut args: Vec<String> = flags.iter().map(|f| f.to_string()).collect();
+        args.push(instruction.to_string());
+        Ok((program, args))
+    }
+
     pub fn spawn(&self) -> Result<()> {
-        let session_name = format!("worker-{}", self.name);
+        let db = Db::new(self.work_dir.clone())?;
+        self.check_rig(&db)?;
+        let session_name = Self::session_name(&self.name);
+        let backend = session::backend(&self.work_dir)?;
         let worker_path = self.work_dir.join("workers").join(&self.name);
-        let _ = fs::create_dir_all(&worker_path);
-        
-        let base_prompt = fs::read_to_string(self.work_dir.join("prompts").join("base.md")).unwrap_or_default();
-        let role_prompt = fs::read_to_string(self.work_dir.join("prompts").join("roles").join(format!("{}.md", self.role)))
-            .unwrap_or_else(|_| "You are a specialized agent.".to_string());
-        
-        let final_instruction = format!("{}\n\n{}\n\nMISSION ID: {}\nMISSIONS: {}\n\nEXECUTE NOW.", 
-            base_prompt, role_prompt, self.id, self.id);
-        
-        let log_dir = self.work_dir.join(".logs").join("tasks").join(&self.id);
-        let _ = fs::create_dir_all(&log_dir);
-        let log_file = log_dir.join(format!("{}.log", self.name));
-
-        // Choose CLI tool based on engine
-        let engine_cmd = match self.engine.as_str() {
-            "opencode" => format!("opencode \"{}\"", final_instruction.replace("\"", "\\\"")),
-            "claude" => format!("claude \"{}\"", final_instruction.replace("\"", "\\\"")),
-            _ => format!("gemini --approval-mode yolo \"{}\"", final_instruction.replace("\"", "\\\"")),
-        };
-
-        let cmd = format!("export PATH=$PATH:/Users/xucongyong/.bun/bin && cd {} && ({} 2>&1 | tee {})", 
-            worker_path.display(), 
-            engine_cmd,
-            log_file.display()
-        );
-        
-        Tmux::new_session(&session_name, &cmd)?;
-        println!("✅ Worker {} dispatched with engine {}!", self.name, self.engine);
+        let log_file = Self::log_path(&self.work_dir, &self.id, &self.name);
+        let final_instruction = self.compose_instruction(&db)?;
+        let (program, args) = self.engine_command(&final_instruction)?;
+
+        if self.dry_run {
+            let rig = db.task_rig(&self.id)?;
+            println!("{}", tr!("🧪 DRY RUN: nothing was launched."));
+            println!("{}", tr!("Session:   {}{}", session_name, if backend.exists(&session_name) { tr!(" (already running)") } else { String::new() }));
+            match rig {
+                Some((name, _)) if !worker_path.exists() => println!("{}", tr!("Directory: {} (new worktree of rig '{}' on {})", worker_path.display(), name, Git::task_branch(&self.id))),
+                _ => println!("{}", tr!("Directory: {}", worker_path.display())),
+            }
+            println!("{}", tr!("Log:       {}", log_file.display()));
+            println!("{}", tr!("Command:   {}", session::command_line(program, &args)));
+            println!("{}", tr!("--- INSTRUCTION ---\n{}", final_instruction));
+            return Ok(());
+        }
+
+        db.ensure_not_frozen()?;
+        backend.probe(self.engine_program()?.0)?;
+        if backend.exists(&session_name) {
+            if !self.replace {
+                let task = db.conn.query_row(
+                    "SELECT id FROM tasks WHERE assignee = ?1 AND status = 'in_progress'", [&self.name], |row| row.get(0)
+                ).ok();
+                return Err(ThinkTodoError::SessionExists { session: session_name, task });
+            }
+            tracing::info!(session = %session_name, "replacing existing session");
+            println!("{}", tr!("♻️  Replacing existing session '{}'...", session_name));
+            backend.kill(&session_name)?;
+        }
+        match db.task_rig(&self.id)? {
+            // Rig tasks work in a worktree on their own branch so the result can be reviewed and merged.
+            Some((_, rig_path)) if backend.launches() && !worker_path.exists() && Git::has_commits(&rig_path) => {
+                if let Some(parent) = worker_path.parent() { fs::create_dir_all(parent)?; }
+                Git::add_worktree(&rig_path, &worker_path, &Git::task_branch(&self.id))?;
+            }
+            _ => { let _ = fs::create_dir_all(&worker_path); }
+        }
+        if let Some(log_dir) = log_file.parent() { let _ = fs::create_dir_all(log_dir); }
+        let log_start = fs::metadata(&log_file).map(|m| m.len()).unwrap_or(0);
+        // What `tt agent checkin` (and every other tt command the agent runs) needs to find its way back.
+        let env = [
+            ("TT_AGENT", self.name.clone()),
+            ("TT_TASK", self.id.clone()),
+            (session::WORKSPACE_VAR, self.work_dir.display().to_string()),
+            (session::TOKEN_VAR, db.issue_token(&self.name)?),
+        ];
+
+        db.register_agent(&self.name, &self.id, &self.engine)?;
+        backend.start(&SessionSpec {
+            name: &session_name,
+            dir: &worker_path,
+            program,
+            args: &args,
+            log: Some(&log_file),
+            env: &env,
+        })?;
+        tracing::info!(task = %self.id, session = %session_name, dir = %worker_path.display(), command = %logging::sanitize_command(program, &args), "worker spawned");
+        let attempt = db.start_attempt(&self.id, &self.name, &self.engine)?;
+        db.record_prompt(attempt, &self.id, &self.name, &self.engine, &final_instruction)?;
+        if let Err(e) = self.confirm_started(&*backend, &session_name, &log_file, log_start) {
+            db.end_attempts(Some(&self.id), Some(&self.name), "failed")?;
+            db.set_agent_status(&self.name, "failed_launch")?;
+            let _ = backend.kill(&session_name);
+            tracing::error!(task = %self.id, session = %session_name, "{}", e);
+            return Err(e);
+        }
+        println!("{}", tr!("✅ Worker {} dispatched with engine {}!", self.name, self.engine));
         Ok(())
     }
-    pub fn nuke(name: &str, work_dir: &PathBuf) -> Result<()> {
-        let _ = Tmux::kill_session(&format!("worker-{}", name));
+    /// Waits up to `[session] startup_secs` for the new session to write to its log. A session
+    /// that is gone by then failed to start; the engine's output (its error) is in the log.
+    /// A live but silent one only gets a warning, since some engines take a while to print.
+    fn confirm_started(&self, backend: &dyn SessionBackend, session_name: &str, log_file: &Path, log_start: u64) -> Result<()> {
+        let grace = Duration::from_secs(Config::load(&self.work_dir)?.session.startup_secs);
+        if grace.is_zero() { return Ok(()); }
+        let started = Instant::now();
+        loop {
+            let grown = fs::metadata(log_file).is_ok_and(|m| m.len() > log_start);
+            if !backend.exists(session_name) {
+                let log = fs::read(log_file).unwrap_or_default();
+                let new = String::from_utf8_lossy(log.get(log_start as usize..).unwrap_or_default());
+                let lines: Vec<&str> = new.lines().filter(|l| !l.trim().is_empty()).collect();
+                let output = match lines.len() {
+                    0 => backend.capture(session_name, 20).unwrap_or_else(|_| "(no output)".to_string()),
+                    n => lines[n.saturating_sub(20)..].join("\n"),
+                };
+                return Err(ThinkTodoError::EngineCrashed { engine: self.engine.clone(), output });
+            }
+            if grown { return Ok(()); }
+            if started.elapsed() >= grace {
+                tracing::warn!(session = session_name, "no output after {}s", grace.as_secs());
+                println!("{}", tr!("⚠️  '{}' is running but hasn't written anything yet; check it with tt peek {}", session_name, self.name));
+                return Ok(());
+            }
+            thread::sleep(Duration::from_millis(200));
+        }
+    }
+    /// Respawns the agent's session on its in-progress task with the same engine, keeping its worktree.
+    pub fn restart(name: &str, work_dir: &Path) -> Result<String> {
+        let db = Db::new(work_dir.to_path_buf())?;
+        db.ensure_not_frozen()?;
+        let (task_id, engine): (String, Option<String>) = db.conn.query_row(
+            "SELECT id, engine FROM tasks WHERE assignee = ?1 AND status = 'in_progress'", [name], |row| Ok((row.get(0)?, row.get(1)?))
+        ).optional()?.ok_or_else(|| ThinkTodoError::InvalidArgument(format!("'{}' has no task in progress", name)))?;
+        let mut w = Self::new(task_id.clone(), name.to_string(), work_dir.to_path_buf(), engine.unwrap_or_else(|| "gemini".to_string()), "worker".to_string());
+        w.replace = true;
+        w.spawn()?;
+        Ok(task_id)
+    }
+    pub fn nuke(name: &str, work_dir: &Path) -> Result<()> {
+        Db::new(work_dir.to_path_buf())?.end_attempts(None, Some(name), "aborted")?;
+        let _ = session::backend(work_dir)?.kill(&Self::session_name(name));
         let worker_path = work_dir.join("workers").join(name);
-        let _ = fs::remove_dir_all(worker_path);
+        let _ = Git::remove_worktree(&worker_path);
+        let _ = fs::remove_dir_all(&worker_path);
+        tracing::info!(agent = name, dir = %worker_path.display(), "worker nuked");
         Ok(())
     }
 }
\ No newline at end of file
@@ -0,0 +1,3033 @@
+//! End-to-end runs of the `tt` binary against throwaway workspaces.
+//! Sessions use the mock backend, except where `live` launches stand-in engines through real ones.
+
+use rusqlite::Connection;
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Output};
+use tempfile::TempDir;
+
+fn tt(dir: &Path, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_think-todo"))
+        .args(args)
+        .current_dir(dir)
+        .env("TT_SESSION_BACKEND", "mock")
+        .output()
+        .expect("failed to run tt")
+}
+
+fn ok(dir: &Path, args: &[&str]) -> String {
+    let out = tt(dir, args);
+    assert!(out.status.success(), "tt {:?} failed: {}", args, String::from_utf8_lossy(&out.stderr));
+    String::from_utf8_lossy(&out.stdout).to_string()
+}
+
+fn task_row(dir: &Path, id: &str) -> (String, Option<String>) {
+    let conn = Connection::open(dir.join("think.db")).unwrap();
+    conn.query_row("SELECT status, assignee FROM tasks WHERE id = ?1", [id], |r| Ok((r.get(0)?, r.get(1)?))).unwrap()
+}
+
+fn session_exists(dir: &Path, name: &str) -> bool {
+    dir.join(".sessions").join("mock").join(name).exists()
+}
+
+/// A variable the mock session was started with.
+fn session_env(dir: &Path, name: &str, key: &str) -> String {
+    let marker = fs::read_to_string(dir.join(".sessions").join("mock").join(name)).unwrap();
+    marker.lines().find_map(|l| l.strip_prefix(&format!("{}=", key))).unwrap_or_default().to_string()
+}
+
+/// `tt` with a real session backend, and stand-ins for the engines and for `tt` itself first on
+/// `PATH`: an engine saves the prompt it was given to `prompt.txt` in the workspace, prints a
+/// line and stays up a moment, and `tt` leaves a `tt-ran` file there before running the real
+/// one. tmux gets a server of its own under `dir`. For checking what a shell does with prompts,
+/// which the mock backend never shows.
+fn live(dir: &Path, backend: &str, args: &[&str]) -> Output {
+    use std::os::unix::fs::PermissionsExt;
+    let bin = dir.join(".bin");
+    fs::create_dir_all(&bin).unwrap();
+    let engine = "#!/bin/sh\n[ \"$1\" = --version ] && exit 0\nfor a in \"$@\"; do last=\"$a\"; done\n\
+        printf '%s' \"$last\" > \"$TT_WORKSPACE/prompt.txt\"\necho engine up\nsleep 3\n";
+    let tt = format!("#!/bin/sh\ntouch \"$TT_WORKSPACE/tt-ran\"\nexec '{}' \"$@\"\n", env!("CARGO_BIN_EXE_think-todo"));
+    for (name, script) in [("claude", engine), ("gemini", engine), ("tt", &tt)] {
+        fs::write(bin.join(name), script).unwrap();
+        fs::set_permissions(bin.join(name), fs::Permissions::from_mode(0o755)).unwrap();
+    }
+    let path = format!("{}:{}", bin.display(), std::env::var("PATH").unwrap_or_default());
+    Command::new(env!("CARGO_BIN_EXE_think-todo"))
+        .args(args)
+        .current_dir(dir)
+        .env("TT_SESSION_BACKEND", backend)
+        .env("TMUX_TMPDIR", dir)
+        .env("PATH", path)
+        .output()
+        .expect("failed to run tt")
+}
+
+/// The prompt a `live` engine saved, once it has.
+fn live_prompt(dir: &Path) -> String {
+    for _ in 0..50 {
+        if let Ok(prompt) = fs::read_to_string(dir.join("prompt.txt")) { return prompt; }
+        std::thread::sleep(std::time::Duration::from_millis(100));
+    }
+    panic!("the engine never started");
+}
+
+fn has_tmux() -> bool {
+    Command::new("tmux").arg("-V").output().is_ok_and(|o| o.status.success())
+}
+
+/// Request lines and bodies received by `http_server`.
+type Requests = std::sync::Arc<std::sync::Mutex<Vec<(String, String)>>>;
+
+/// A local HTTP endpoint that answers every request with `response` (a JSON body) and keeps
+/// each request's line and body, for sinks and bots that talk to outside services.
+fn http_server(response: &'static str) -> (String, Requests) {
+    use std::io::{BufRead, BufReader, Read};
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let seen = Requests::default();
+    let log = seen.clone();
+    std::thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let log = log.clone();
+            std::thread::spawn(move || {
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut stream = stream;
+                loop {
+                    let mut line = String::new();
+                    if reader.read_line(&mut line).unwrap_or(0) == 0 { return; }
+                    let mut len = 0;
+                    loop {
+                        let mut header = String::new();
+                        reader.read_line(&mut header).unwrap();
+                        if header.trim().is_empty() { break; }
+                        if let Some((k, v)) = header.split_once(':') {
+                            if k.eq_ignore_ascii_case("content-length") { len = v.trim().parse().unwrap(); }
+                        }
+                    }
+                    let mut body = vec![0; len];
+                    reader.read_exact(&mut body).unwrap();
+                    log.lock().unwrap().push((line.trim().to_string(), String::from_utf8_lossy(&body).to_string()));
+                    write!(stream, "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}", response.len(), response).unwrap();
+                }
+            });
+        }
+    });
+    (url, seen)
+}
+
+/// `tt serve` on a free port, stopped when dropped.
+struct Served { child: std::process::Child, url: String }
+
+impl Served {
+    fn start(dir: &Path, args: &[&str]) -> Served { Served::start_with(dir, args, &[]) }
+
+    fn start_with(dir: &Path, args: &[&str], env: &[(&str, &str)]) -> Served {
+        let port = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+        let child = Command::new(env!("CARGO_BIN_EXE_think-todo"))
+            .args(["serve", "-p", &port.to_string()]).args(args)
+            .current_dir(dir)
+            .env("TT_SESSION_BACKEND", "mock")
+            .envs(env.iter().copied())
+            .stdout(std::process::Stdio::null())
+            .stderr(std::process::Stdio::null())
+            .spawn()
+            .unwrap();
+        let served = Served { child, url: format!("http://127.0.0.1:{}", port) };
+        for _ in 0..100 {
+            if std::net::TcpStream::connect(("127.0.0.1", port)).is_ok() { return served; }
+            std::thread::sleep(std::time::Duration::from_millis(50));
+        }
+        panic!("tt serve did not come up");
+    }
+
+    /// The response to a request, redirects not followed; a body is sent as JSON.
+    fn send(&self, method: &str, path: &str, headers: &[(&str, &str)], body: Option<&str>) -> ureq::Response {
+        let mut req = ureq::AgentBuilder::new().redirects(0).build().request(method, &format!("{}{}", self.url, path));
+        for (name, value) in headers { req = req.set(name, value); }
+        let res = match body {
+            Some(body) => req.set("Content-Type", "application/json").send_string(body),
+            None => req.call(),
+        };
+        match res {
+            Ok(r) | Err(ureq::Error::Status(_, r)) => r,
+            Err(e) => panic!("{} {}: {}", method, path, e),
+        }
+    }
+
+    /// Status and body of a request.
+    fn call(&self, method: &str, path: &str, headers: &[(&str, &str)], body: Option<&str>) -> (u16, String) {
+        let r = self.send(method, path, headers, body);
+        (r.status(), r.into_string().unwrap_or_default())
+    }
+
+    fn get(&self, path: &str) -> (u16, String) { self.call("GET", path, &[], None) }
+}
+
+impl Drop for Served {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+#[test]
+fn task_add_then_list() {
+    let ws = TempDir::new().unwrap();
+    ok(ws.path(), &["task", "add", "T1", "Write the parser"]);
+    let list = ok(ws.path(), &["task", "list"]);
+    assert!(list.contains("[T1] Write the parser (open)"));
+}
+
+#[test]
+fn full_flow_start_monitor_done() {
+    let ws = TempDir::new().unwrap();
+    let dir = ws.path();
+    ok(dir, &["task", "add", "T1", "Write the parser"]);
+
+    ok(dir, &["start", "T1", "bob", "--engine", "claude"]);
+    assert_eq!(task_row(dir, "T1"), ("in_progress".to_string(), Some("bob".to_string())));
+    assert!(session_exists(dir, "worker-bob"));
+    assert!(dir.join("workers").join("bob").is_dir());
+
+    let log = dir.join(".logs/tasks/T1/bob.log");
+    fs::write(&log, "working...\n[TASK_DONE]\n").unwrap();
+    ok(dir, &["monitor", "scan"]);
+    assert_eq!(task_row(dir, "T1").0, "closed");
+
+    // Lines already read aren't acted on again; only what the log gains is.
+    let db = Connection::open(dir.join("think.db")).unwrap();
+    db.execute("UPDATE tasks SET status = 'in_progress' WHERE id = 'T1'", []).unwrap();
+    ok(dir, &["monitor", "scan"]);
+    assert_eq!(task_row(dir, "T1").0, "in_progress");
+    fs::File::options().append(true).open(&log).unwrap().write_all(b"fixed it\n[TASK_DONE]\n").unwrap();
+    ok(dir, &["monitor", "scan"]);
+    assert_eq!(task_row(dir, "T1").0, "closed");
+
+    ok(dir, &["done", "T1"]);
+    assert!(!session_exists(dir, "worker-bob"));
+    assert!(!dir.join("workers").join("bob").exists());
+    assert!(ok(dir, &["trail"]).contains("task_closed on T1"));
+}
+
+#[test]
+fn recover_reopens_orphaned_task() {
+    let ws = TempDir::new().unwrap();
+    let dir = ws.path();
+    ok(dir, &["task", "add", "T1", "Write the parser"]);
+    ok(dir, &["start", "T1", "bob"]);
+
+    // Simulate a reboot: the session disappears, the task row does not.
+    fs::remove_file(dir.join(".sessions/mock/worker-bob")).unwrap();
+    ok(dir, &["recover"]);
+    assert_eq!(task_row(dir, "T1"), ("open".to_string(), None));
+}
+
+#[test]
+fn recover_respawns_with_resume_context() {
+    let ws = TempDir::new().unwrap();
+    let dir = ws.path();
+    ok(dir, &["task", "add", "T1", "Write the parser"]);
+    ok(dir, &["start", "T1", "bob"]);
+    fs::write(dir.join(".logs/tasks/T1/bob.log"), "halfway through the lexer\n").unwrap();
+    fs::remove_file(dir.join(".sessions/mock/worker-bob")).unwrap();
+
+    ok(dir, &["recover", "--mode", "respawn"]);
+    assert_eq!(task_row(dir, "T1").0, "in_progress");
+    let cmd = fs::read_to_string(dir.join(".sessions/mock/worker-bob")).unwrap();
+    assert!(cmd.contains("RESUME CONTEXT"));
+    assert!(cmd.contains("halfway through the lexer"));
+}
+
+#[test]
+fn worker_restart_respawns_on_current_task() {
+    let ws = TempDir::new().unwrap();
+    let dir = ws.path();
+    ok(dir, &["task", "add", "T1", "Write the parser"]);
+    ok(dir, &["start", "T1", "bob", "--engine", "claude"]);
+    fs::remove_file(dir.join(".sessions/mock/worker-bob")).unwrap();
+
+    ok(dir, &["worker", "restart", "bob"]);
+    let cmd = fs::read_to_string(dir.join(".sessions/mock/worker-bob")).unwrap();
+    assert!(cmd.contains("\nclaude '"), "{}", cmd);
+    assert!(ok(dir, &["task", "show", "T1"]).contains("replaced"));
+    assert!(!tt(dir, &["worker", "restart", "alice"]).status.success());
+}
+
+#[test]
+fn worker_spawn_uses_the_requested_engine() {
+    let ws = TempDir::new().unwrap();
+    let dir = ws.path();
+    ok(dir, &["task", "add", "T1", "Write the parser"]);
+    ok(dir, &["worker", "spawn", "T1", "bob", "--engine", "claude"]);
+    let cmd = fs::read_to_string(dir.join(".sessions/mock/worker-bob")).unwrap();
+    assert!(cmd.contains("\nclaude '"), "{}", cmd);
+    assert!(ok(dir, &["trail"]).contains("spawn on bob"));
+}
+
+#[test]
+fn real_sessions_start_in_a_workspace_whose_path_needs_quoting() {
+    let backends: &[&str] = if has_tmux() { &["process", "tmux"] } else { &["process"] };
+    for backend in backends {
+        let ws = TempDir::new().unwrap();
+        let dir = ws.path().join("it's a $HOME & co");
+        fs::create_dir(&dir).unwrap();
+        let dir = dir.as_path();
+        fs::write(dir.join("think.toml"), "[session]\nstartup_secs = 0\n").unwrap();
+        ok(dir, &["task", "add", "T1", "Write the parser"]);
+
+        let out = live(dir, backend, &["start", "T1", "bob", "--engine", "claude"]);
+        assert!(out.status.success(), "{}: {}", backend, String::from_utf8_lossy(&out.stderr));
+        assert!(live_prompt(dir).contains("MISSION ID: T1"), "{}", backend);
+        let log = dir.join(".logs/tasks/T1/bob.log");
+        for _ in 0..50 {
+            if fs::read_to_string(&log).is_ok_and(|l| l.contains("engine up")) { break; }
+            std::thread::sleep(std::time::Duration::from_millis(100));
+        }
+        assert!(fs::read_to_string(&log).unwrap_or_default().contains("engine up"), "{}", backend);
+        if *backend == "tmux" { let _ = Command::new("tmux").arg("kill-server").env("TMUX_TMPDIR", dir).output(); }
+    }
+}
+
+#[test]
+fn conventions_reach_the_engine_through_tmux_word_for_word() {
+    if !has_tmux() { return; }
+    let ws = TempDir::new().unwrap();
+    let dir = ws.path();
+    fs::write(dir.join("think.toml"), "[session]\nstartup_secs = 0\n").unwrap();
+    ok(dir, &["task", "add", "T1", "Write the parser"]);
+
+    let out = live(dir, "tmux", &["start", "T1", "bob", "--engine", "claude"]);
+    assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+    let prompt = live_prompt(dir);
+    let _ = Command::new("tmux").arg("kill-server").env("TMUX_TMPDIR", dir).output();
+    assert!(prompt.contains("## TT CONVENTIONS"), "{}", prompt);
+    assert!(prompt.contains("- `tt agent checkin`: first thing"), "{}", prompt);
+    assert!(prompt.contains("- `TT_WORKSPACE`: the workspace"), "{}", prompt);
+    // The backticks were text for the engine, not commands for the shell that launched it.
+    assert!(!dir.join("tt-ran").exists());
+}
+
+#[test]
+fn app_log_records_decisions_without_prompts() {
+    let ws = TempDir::new().unwrap();
+    let dir = ws.path();
+    ok(dir, &["task", "add", "T1", "Write the parser"]);
+    ok(dir, &["start", "T1", "bob", "--engine", "claude"]);
+    fs::write(dir.join(".logs/tasks/T1/bob.log"), "[TASK_DONE]\n").unwrap();
+    ok(dir, &["monitor", "scan"]);
+    assert!(!tt(dir, &["done", "nope"]).status.success());
+
+    let log: String = fs::read_dir(dir.join(".logs/tt")).unwrap().flatten()
+        .map(|e| fs::read_to_string(e.path()).unwrap()).collect();
+    assert!(log.contains("worker spawned"), "{}", log);
+    assert!(log.contains("claude \"<instruction: "));
+    assert!(!log.contains("MISSION ID"));
+    assert!(log.contains("closed: log reported [TASK_DONE]"));
+    assert!(log.contains("ERROR") && log.contains("nope"));
+}
+
+#[test]
+fn destructive_commands_dry_run_and_record_what_they_removed() {
+    let ws = TempDir::new().unwrap();
+    let dir = ws.path();
+    ok(dir, &["task", "add", "T1", "Write the parser", "--tag", "backend"]);
+    ok(dir, &["start", "T1", "bob", "--engine", "claude"]);
+
+    let plan = ok(dir, &["done", "T1", "--dry-run"]);
+    assert!(plan.contains("Session:   worker-bob"), "{}", plan);
+    assert!(plan.contains("workers/bob") && plan.contains(".logs/tasks/T1"));
+    assert_eq!(task_row(dir, "T1").0, "in_progress");
+    assert!(session_exists(dir, "worker-bob"));
+
+    ok(dir, &["worker", "nuke", "bob", "--dry-run"]);
+    assert!(dir.join("workers/bob").is_dir());
+    ok(dir, &["done", "T1"]);
+    let trail = ok(dir, &["trail"]);
+    assert!(trail.contains("\"sessions\":[\"worker-bob\"]"), "{}", trail);
+
+    fs::create_dir_all(dir.join("workers/idle")).unwrap();
+    assert!(ok(dir, &["gc", "--dry-run"]).contains("workers/idle"));
+    assert!(dir.join("workers/idle").exists());
+    ok(dir, &["gc"]);
+    assert!(!dir.join("workers/idle").exists());
+
+    assert!(ok(dir, &["task", "delete", "T1", "--dry-run"]).contains("Record:    tag T1#backend"));
+    ok(dir, &["task", "delete", "T1"]);
+    assert!(!ok(dir, &["task", "list"]).contains("T1"));
+    assert!(ok(dir, &["trail"]).contains("task_deleted on T1"));
+}
+
+#[test]
+fn start_fails_fast_when_the_engine_is_not_installed() {
+    let ws = TempDir::new().unwrap();
+    let dir = ws.path();
+    ok(dir, &["task", "add", "T1", "Write the parser"]);
+    fs::create_dir_all(dir.join(".sessions/mock")).unwrap();
+    fs::write(dir.join(".sessions/mock/claude.missing"), "").unwrap();
+    let out = tt(dir, &["start", "T1", "bob", "--engine", "claude"]);
+    assert_eq!(out.status.code(), Some(7));
+    assert!(String::from_utf8_lossy(&out.stderr).contains("engine 'claude' is not available"));
+    assert_eq!(task_row(dir, "T1"), ("open".to_string(), None));
+    assert!(!session_exists(dir, "worker-bob"));
+}
+
+#[test]
+fn start_reports_an_engine_that_dies_on_startup() {
+    let ws = TempDir::new().unwrap();
+    let dir = ws.path();
+    ok(dir, &["task", "add", "T1", "Write the parser"]);
+    fs::create_dir_all(dir.join(".sessions/mock")).unwrap();
+    fs::write(dir.join(".sessions/mock/claude.crash"), "Error: ANTHROPIC_API_KEY is not set\n").unwrap();
+    let out = tt(dir, &["start", "T1", "bob", "--engine", "claude"]);
+    assert_eq!(out.status.code(), Some(13));
+    assert!(String::from_utf8_lossy(&out.stderr).contains("ANTHROPIC_API_KEY is not set"));
+    assert_eq!(task_row(dir, "T1"), ("open".to_string(), None));
+    let conn = Connection::open(dir.join("think.db")).unwrap();
+    let outcome: String = conn.query_row("SELECT outcome FROM attempts WHERE task_id = 'T1'", [], |r| r.get(0)).unwrap();
+    assert_eq!(outcome, "failed");
+}
+
+#[test]
+fn mail_attachments_go_through_the_artifact_store() {
+    let ws = TempDir::new().unwrap();
+    let dir = ws.path();
+    fs::write(dir.join("spec.md"), "# Parser spec\n").unwrap();
+    ok(dir, &["mail", "send", "bob", "-s", "spec", "-b", "see attached", "--attach", "spec.md"]);
+    fs::remove_file(dir.join("spec.md")).unwrap();
+
+    let read = ok(dir, &["mail", "read", "1"]);
+    assert!(read.contains("📎 spec.md (14 bytes, artifact "), "{}", read);
+    let id = read.split("artifact ").nth(1).unwrap()[..12].to_string();
+    ok(dir, &["mail", "send", "amy", "-s", "fwd", "-b", "same spec", "--attach", &id]);
+    ok(dir, &["mail", "read", "2", "--save-attachments", "inbox"]);
+    assert_eq!(fs::read_to_string(dir.join("inbox/spec.md")).unwrap(), "# Parser spec\n");
+    assert!(!tt(dir, &["mail", "read", "2", "--save-attachments", "inbox"]).status.success());
+    assert!(!tt(dir, &["mail", "send", "amy", "-s", "x", "-b", "y", "--attach", "nope.txt"]).status.success());
+}
+
+#[test]
+fn sling_can_brief_the_worker_from_mail() {
+    let ws = TempDir::new().unwrap();
+    let dir = ws.path();
+    ok(dir, &["task", "add", "T1", "Write the parser"]);
+    ok(dir, &["mail", "send", "bob", "-s", "parser notes", "-b", "Use a Pratt parser for expressions."]);
+    ok(dir, &["sling", "T1", "bob", "--brief-from-mail", "1"]);
+    let cmd = fs::read_to_string(dir.join(".sessions/mock/worker-bob")).unwrap();
+    assert!(cmd.contains("## BRIEF (mail #1 from user: parser notes)"), "{}", cmd);
+    assert!(cmd.contains("Use a Pratt parser for expressions."));
+    assert!(!ok(dir, &["mail", "inbox"]).contains("●"));
+    assert!(!tt(dir, &["sling", "T1", "amy", "--brief-from-mail", "9", "--dry-run"]).status.success());
+}
+
+#[test]
+fn mail_briefs_reach_the_engine_without_a_shell_reading_them() {
+    if !has_tmux() { return; }
+    let ws = TempDir::new().unwrap();
+    let dir = ws.path();
+    fs::write(dir.join("think.toml"), "[session]\nstartup_secs = 0\n").unwrap();
+    fs::write(dir.join("spec.md"), "# Spec\n").unwrap();
+    ok(dir, &["task", "add", "T1", "Write the parser"]);
+    let body = "Start with $(touch pwned) then `touch pwned`; don't \"stop\"";
+    ok(dir, &["mail", "send", "bob", "-s", "notes", "-b", body, "--attach", "spec.md"]);
+
+    let out = live(dir, "tmux", &["start", "T1", "bob", "--engine", "claude", "--brief-from-mail", "1"]);
+    assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+    let prompt = live_prompt(dir);
+    let _ = Command::new("tmux").arg("kill-server").env("TMUX_TMPDIR", dir).output();
+    assert!(prompt.contains(body), "{}", prompt);
+    assert!(prompt.contains("(fetch them with `tt mail read 1 --save-attachments`):\n- spec.md"), "{}", prompt);
+    assert!(!dir.join("tt-ran").exists());
+    assert!(!dir.join("workers/bob/pwned").exists() && !dir.join("pwned").exists());
+}
+
+#[test]
+fn admin_start_delivers_mail_sent_while_offline() {
+    let ws = TempDir::new().unwrap();
+    let dir = ws.path();
+    ok(dir, &["mail", "send", "admin", "-s", "Hire a reviewer", "-b", "We need someone\non the auth PRs."]);
+    ok(dir, &["mail", "send", "bob", "-s", "not for admin", "-b", "x"]);
+    ok(dir, &["admin", "start"]);
+    let cmd = fs::read_to_string(dir.join(".sessions/mock/hq-admin")).unwrap();
+    assert!(cmd.contains("Unread Mail:\n- #1 from user: Hire a reviewer\n  We need someone on the auth PRs."), "{}", cmd);
+    assert!(!cmd.contains("not for admin"));
+    let conn = Connection::open(dir.join("think.db")).unwrap();
+    let status: String = conn.query_row("SELECT status FROM messages WHERE id = 1", [], |r| r.get(0)).unwrap();
+    assert_eq!(status, "delivered");
+}
+
+#[test]
+fn admin_start_hands_mail_to_the_engine_without_a_shell_reading_it() {
+    if !has_tmux() { return; }
+    let ws = TempDir::new().unwrap();
+    let dir = ws.path();
+    fs::write(dir.join("think.toml"), "[session]\nstartup_secs = 0\n").unwrap();
+    let body = "Please $(touch pwned) and `touch pwned` \"now\", it's urgent";
+    ok(dir, &["mail", "send", "admin", "-s", "Hi", "-b", body]);
+
+    let out = live(dir, "tmux", &["admin", "start"]);
+    assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+    let prompt = live_prompt(dir);
+    let _ = Command::new("tmux").arg("kill-server").env("TMUX_TMPDIR", dir).output();
+    assert!(prompt.contains(body), "{}", prompt);
+    assert!(!dir.join("admin").join("pwned").exists() && !dir.join("pwned").exists());
+}
+
+#[test]
+fn named_admins_see_only_their_scope_and_get_its_alerts() {
+    let ws = TempDir::new().unwrap();
+    let dir = ws.path();
+    ok(dir, &["task", "add", "I1", "Rotate certs", "--tag", "infra"]);
+    ok(dir, &["task", "add", "P1", "Fix the parser"]);
+    ok(dir, &["mail", "send", "admin-infra", "-s", "Renew the wildcard", "-b", "expires friday"]);
+    assert!(!tt(dir, &["admin", "start", "--scope", "tag:infra"]).status.success());
+    assert!(!tt(dir, &["admin", "start", "--name", "infra", "--scope", "owner:infra"]).status.success());
+    ok(dir, &["admin", "start", "--name", "infra", "--scope", "tag:infra"]);
+    let cmd = fs::read_to_string(dir.join(".sessions/mock/hq-admin-infra")).unwrap();
+    assert!(cmd.contains("[I1] Rotate certs") && !cmd.contains("[P1]"), "{}", cmd);
+    assert!(cmd.contains("Your scope is `tag:infra`") && cmd.contains("Renew the wildcard"), "{}", cmd);
+    assert!(ok(dir, &["admin", "list"]).contains("infra            running  scope tag:infra (mail: admin-infra)"));
+
+    // Failed launches are mailed to whoever owns the task.
+    ok(dir, &["start", "I1", "bob"]);
+    ok(dir, &["start", "P1", "amy"]);
+    let db = Connection::open(dir.join("think.db")).unwrap();
+    db.execute("UPDATE agents SET spawned_at = spawned_at - 600", []).unwrap();
+    ok(dir, &["monitor", "scan"]);
+    let to = |subject: &str| -> String { db.query_row("SELECT receiver FROM messages WHERE subject = ?1", [subject], |r| r.get(0)).unwrap() };
+    assert_eq!(to("FAILED LAUNCH: bob on [I1]"), "admin-infra");
+    assert_eq!(to("FAILED LAUNCH: amy on [P1]"), "admin");
+
+    ok(dir, &["admin", "stop", "--name", "infra"]);
+    assert!(!session_exists(dir, "hq-admin-infra"));
+    assert!(ok(dir, &["admin", "list"]).contains("No named admins"));
+}
+
+#[test]
+fn saved_scopes_narrow_listings_dispatch_and_admins() {
+    let ws = TempDir::new().unwrap();
+    let dir = ws.path();
+    ok(dir, &["task", "add", "I1", "Rotate certs", "--tag", "infra"]);
+    ok(dir, &["task", "add", "P1", "Fix the parser"]);
+    ok(dir, &["scope", "add", "infra", "tag:infra"]);
+    assert!(ok(dir, &["scope", "list"]).contains("infra            tag:infra"));
+    assert!(!tt(dir, &["scope", "add", "bad", "owner:me"]).status.success());
+
+    let list = ok(dir, &["--scope", "infra", "task", "list"]);
+    assert!(list.contains("[I1]") && !list.contains("[P1]"), "{}", list);
+    assert!(ok(dir, &["task", "list", "--scope", "tag:infra"]).contains("[I1]"));
+    assert!(ok(dir, &["board", "list", "--scope", "infra"]).contains("Total: 1"));
+    assert!(!tt(dir, &["--scope", "nope", "task", "list"]).status.success());
+
+    let out = tt(dir, &["--scope", "infra", "start", "P1", "amy"]);
+    assert!(String::from_utf8_lossy(&out.stderr).contains("outside the scope tag:infra"));
+    assert_eq!(task_row(dir, "P1").0, "open");
+    ok(dir, &["--scope", "infra", "start", "I1", "bob"]);
+
+    ok(dir, &["admin", "start", "--name", "infra", "--scope", "infra"]);
+    assert!(ok(dir, &["admin", "list"]).contains("scope tag:infra"));
+    ok(dir, &["scope", "remove", "infra"]);
+    assert!(!tt(dir, &["scope", "remove", "infra"]).status.success());
+}
+
+#[test]
+fn teams_receive_broadcasts_and_keep_members_in_scope() {
+    let ws = TempDir::new().unwrap();
+    let dir = ws.path();
+    ok(dir, &["task", "add", "I1", "Rotate certs", "--tag", "infra"]);
+    ok(dir, &["task", "add", "P1", "Fix the parser"]);
+    ok(dir, &["--scope", "tag:infra", "team", "create", "infra", "--members", "bob,amy", "--lead", "admin-infra"]);
+
+    let sent = ok(dir, &["mail", "send", "@infra", "-s", "Freeze friday", "-b", "no deploys"]);
+    assert!(sent.contains("admin-infra, amy, bob"), "{}", sent);
+    assert!(!tt(dir, &["mail", "send", "@nobody", "-s", "x", "-b", "y"]).status.success());
+
+    let out = tt(dir, &["start", "P1", "bob"]);
+    assert!(String::from_utf8_lossy(&out.stderr).contains("bob is on team infra, which only takes tasks in tag:infra"));
+    ok(dir, &["start", "I1", "bob"]);
+    ok(dir, &["start", "P1", "carl"]);
+    let list = ok(dir, &["--scope", "team:infra", "task", "list"]);
+    assert!(list.contains("[I1]") && !list.contains("[P1]"), "{}", list);
+    let teams = ok(dir, &["team", "list"]);
+    assert!(teams.contains("infra (lead: admin-infra, scope: tag:infra)") && teams.contains("members: amy, bob"), "{}", teams);
+    assert!(teams.contains("1 in progress · 0 closed"), "{}", teams);
+    ok(dir, &["team", "delete", "infra"]);
+    assert!(ok(dir, &["team", "list"]).contains("No teams"));
+}
+
+#[test]
+fn delegation_chains_are_recorded_through_layers_of_agents() {
+    let ws = TempDir::new().unwrap();
+    let dir = ws.path();
+    ok(dir, &["task", "add", "T1", "Build the importer"]);
+    ok(dir, &["task", "add", "T2", "Write its tests"]);
+    ok(dir, &["admin", "start", "--name", "infra"]);
+    let session = fs::read_to_string(dir.join(".sessions/mock/hq-admin-infra")).unwrap();
+    assert!(session.contains("TT_AGENT=admin-infra\n"), "{}", session);
+
+    let as_agent = |agent: &str, session: &str, args: &[&str]| {
+        let out = Command::new(env!("CARGO_BIN_EXE_think-todo")).args(args).current_dir(dir)
+            .env("TT_SESSION_BACKEND", "mock").env("TT_AGENT", agent)
+            .env("TT_TOKEN", session_env(dir, session, "TT_TOKEN")).output().unwrap();
+        assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+    };
+    as_agent("admin-infra", "hq-admin-infra", &["start", "T1", "bob"]);
+    as_agent("bob", "worker-bob", &["start", "T2", "carl"]);
+    assert!(ok(dir, &["task", "show", "T1"]).contains("delegated: user → admin-infra → bob"));
+    assert!(ok(dir, &["task", "show", "T2"]).contains("delegated: user → admin-infra → bob → carl"));
+    ok(dir, &["task", "add", "T3", "Docs"]);
+    ok(dir, &["start", "T3", "dora"]);
+    assert!(ok(dir, &["task", "show", "T3"]).contains("delegated: user → dora"));
+    let trail = ok(dir, &["trail"]);
+    assert!(trail.contains("bob -> delegated on T2 (user → admin-infra → bob → carl)"), "{}", trail);
+}
+
+#[test]
+fn agents_need_their_session_token_to_change_anything() {
+    let ws = TempDir::new().unwrap();
+    let dir = ws.path();
+    ok(dir, &["task", "add", "T1", "Build the importer"]);
+    ok(dir, &["task", "add", "T2", "Write its tests"]);
+    ok(dir, &["start", "T1", "bob"]);
+    let token = session_env(dir, "worker-bob", "TT_TOKEN");
+    assert_eq!(token.len(), 32);
+    let run = |env: &[(&str, &str)], args: &[&str]| {
+        let mut cmd = Command::new(env!("CARGO_BIN_EXE_think-todo"));
+        cmd.args(args).current_dir(dir).env("TT_SESSION_BACKEND", "mock");
+        for (k, v) in env { cmd.env(k, v); }
+        cmd.output().unwrap()
+    };
+
+    // Claiming a name isn't enough to act, but is enough to look.
+    let out = run(&[("TT_AGENT", "bob")], &["done", "T1"]);
+    assert!(String::from_utf8_lossy(&out.stderr).contains("'bob' could not be verified"));
+    assert!(run(&[("TT_AGENT", "bob")], &["task", "list"]).status.success());
+    assert!(!run(&[("TT_AGENT", "bob"), ("TT_TOKEN", "forged")], &["task", "add", "X", "x"]).status.success());
+
+    // The token decides who acts, whatever TT_AGENT says.
+    let out = run(&[("TT_AGENT", "user"), ("TT_TOKEN", &token)], &["mail", "send", "admin", "-s", "hi", "-b", "from bob"]);
+    assert!(out.status.success());
+    assert!(ok(dir, &["mail", "inbox"]).contains("bob"));
+    assert!(!run(&[("TT_TOKEN", &token)], &["agent", "checkin", "--name", "amy"]).status.success());
+
+    // Respawning issues a new token; the old one stops working.
+    ok(dir, &["worker", "restart", "bob"]);
+    assert!(!run(&[("TT_TOKEN", &token)], &["agent", "checkin"]).status.success());
+    assert!(run(&[("TT_TOKEN", &session_env(dir, "worker-bob", "TT_TOKEN"))], &["agent", "checkin"]).status.success());
+}
+
+#[test]
+fn sandbox_prints_what_would_happen_and_leaves_the_workspace_alone() {
+    let ws = TempDir::new().unwrap();
+    let dir = ws.path();
+    ok(dir, &["task", "add", "T1", "Build the importer"]);
+    fs::create_dir_all(dir.join("prompts")).unwrap();
+    fs::write(dir.join("prompts/base.md"), "Trial prompt").unwrap();
+
+    let out = ok(dir, &["--sandbox", "start", "T1", "bob"]);
+    assert!(out.contains("🧪 SANDBOX: would start session 'worker-bob'"), "{}", out);
+    assert!(ok(dir, &["--sandbox", "nudge", "bob", "status?"]).contains("would show in 'worker-bob': !!! NUDGE: status? !!!"));
+    assert!(ok(dir, &["--sandbox", "task", "list"]).contains("(in_progress)"));
+    assert!(ok(dir, &["--sandbox", "task", "show", "T1", "--prompt"]).contains("Trial prompt"));
+    assert!(ok(dir, &["--sandbox", "done", "T1"]).contains("would kill session 'worker-bob'"));
+
+    // The real workspace never saw any of it.
+    assert_eq!(task_row(dir, "T1").0, "open");
+    assert!(!session_exists(dir, "worker-bob") && !dir.join("workers/bob").exists());
+    assert!(!tt(dir, &["--sandbox", "serve"]).status.success());
+
+    ok(dir, &["sandbox", "reset"]);
+    assert!(!dir.join(".sandbox").exists());
+    assert!(ok(dir, &["--sandbox", "task", "list"]).contains("(open)"));
+}
+
+#[test]
+fn replay_explains_daemon_decisions_from_their_inputs() {
+    let ws = TempDir::new().unwrap();
+    let dir = ws.path();
+    ok(dir, &["task", "add", "T1", "Build the importer"]);
+    ok(dir, &["task", "add", "T2", "Write its tests"]);
+    ok(dir, &["start", "T1", "bob"]);
+    ok(dir, &["start", "T2", "amy"]);
+    let db = Connection::open(dir.join("think.db")).unwrap();
+    db.execute("UPDATE agents SET spawned_at = spawned_at - 600 WHERE name = 'bob'", []).unwrap();
+    fs::write(dir.join(".logs/tasks/T2/amy.log"), "all green\n[TASK_DONE]\n").unwrap();
+    ok(dir, &["monitor", "scan"]);
+
+    let replay = ok(dir, &["replay", "--since", "1h"]);
+    assert!(replay.contains("2 decision(s)"), "{}", replay);
+    assert!(replay.contains("monitor launch_failed bob (no checkin within 300s)"), "{}", replay);
+    assert!(replay.contains("because spawned on [T1]") && replay.contains("and no checkin within 300s"), "{}", replay);
+    assert!(replay.contains("monitor task_closed T2 (success)\n   because a line in") && replay.contains("amy.log starts with [TASK_DONE]"), "{}", replay);
+    let only = ok(dir, &["replay", "--target", "T1"]);
+    assert!(only.contains("1 decision(s)") && only.contains("launch_failed bob"), "{}", only);
+}
+
+#[test]
+fn spawned_agents_check_in_or_are_flagged_as_failed_launches() {
+    let ws = TempDir::new().unwrap();
+    let dir = ws.path();
+    ok(dir, &["task", "add", "T1", "Write the parser"]);
+    ok(dir, &["task", "add", "T2", "Write the lexer"]);
+    ok(dir, &["start", "T1", "bob"]);
+    ok(dir, &["start", "T2", "amy"]);
+    let session = fs::read_to_string(dir.join(".sessions/mock/worker-bob")).unwrap();
+    assert!(session.contains("tt agent checkin") && session.contains("TT_AGENT=bob\nTT_TASK=T1\n"), "{}", session);
+
+    // The agent checks in from its worker directory, finding the workspace through its environment.
+    let out = Command::new(env!("CARGO_BIN_EXE_think-todo"))
+        .args(["agent", "checkin"])
+        .current_dir(dir.join("workers/bob"))
+        .env("TT_AGENT", "bob")
+        .env("TT_WORKSPACE", dir)
+        .env("TT_TOKEN", session_env(dir, "worker-bob", "TT_TOKEN"))
+        .output().unwrap();
+    assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+    assert!(!dir.join("workers/bob/think.db").exists());
+    assert!(!tt(dir, &["agent", "checkin", "--name", "ghost"]).status.success());
+
+    let db = Connection::open(dir.join("think.db")).unwrap();
+    db.execute("UPDATE agents SET spawned_at = spawned_at - 600", []).unwrap();
+    ok(dir, &["monitor", "scan"]);
+    ok(dir, &["monitor", "scan"]);
+    let list = ok(dir, &["agent", "list"]);
+    assert!(list.contains("bob [T1] gemini · running"), "{}", list);
+    assert!(list.contains("amy [T2] gemini · failed_launch") && list.contains("never checked in"), "{}", list);
+    let outcome: String = db.query_row("SELECT outcome FROM attempts WHERE agent_name = 'amy'", [], |r| r.get(0)).unwrap();
+    assert_eq!(outcome, "failed");
+    let mails: i64 = db.query_row("SELECT COUNT(*) FROM messages WHERE subject LIKE 'FAILED LAUNCH%'", [], |r| r.get(0)).unwrap();
+    assert_eq!(mails, 1);
+}
+
+#[test]
+fn engines_launched_through_tmux_that_never_check_in_are_flagged() {
+    if !has_tmux() { return; }
+    let ws = TempDir::new().unwrap();
+    let dir = ws.path();
+    fs::write(dir.join("think.toml"), "[session]\nstartup_secs = 0\n").unwrap();
+    ok(dir, &["task", "add", "T1", "Write the parser"]);
+
+    // The prompt asks for `tt agent checkin`; only the engine may run it, never the launching shell.
+    let out = live(dir, "tmux", &["start", "T1", "bob", "--engine", "claude"]);
+    assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+    assert!(live_prompt(dir).contains("run `tt agent checkin`"));
+    let db = Connection::open(dir.join("think.db")).unwrap();
+    db.execute("UPDATE agents SET spawned_at = spawned_at - 600", []).unwrap();
+    let out = live(dir, "tmux", &["monitor", "scan"]);
+    let _ = Command::new("tmux").arg("kill-server").env("TMUX_TMPDIR", dir).output();
+    assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+    let status: String = db.query_row("SELECT status FROM agents WHERE name = 'bob'", [], |r| r.get(0)).unwrap();
+    assert_eq!(status, "failed_launch");
+    assert!(ok(dir, &["trail"]).contains("launch_failed"));
+}
+
+#[test]
+fn monitor_reaps_idle_sessions_of_finished_agents() {
+    let ws = TempDir::new().unwrap();
+    let dir = ws.path();
+    ok(dir, &["task", "add", "T1", "Write the parser"]);
+    ok(dir, &["task", "add", "T2", "Write the lexer"]);
+    ok(dir, &["start", "T1", "bob"]);
+    ok(dir, &["start", "T2", "amy"]);
+    let log = dir.join(".logs/tasks/T1/bob.log");
+    fs::write(&log, "[TASK_DONE]\n").unwrap();
+    ok(dir, &["monitor", "scan"]);
+    assert_eq!(task_row(dir, "T1").0, "closed");
+    assert!(session_exists(dir, "worker-bob"), "closed but recently active");
+
+    let db = Connection::open(dir.join("think.db")).unwrap();
+    db.execute("UPDATE agents SET spawned_at = spawned_at - 3600", []).unwrap();
+    db.execute("UPDATE agents SET status = 'running', checked_in_at = strftime('%s','now') - 3600 WHERE name = 'amy'", []).unwrap();
+    let an_hour_ago = std::time::SystemTime::now() - std::time::Duration::from_secs(3600);
+    for agent in ["T1/bob", "T2/amy"] {
+        fs::File::options().append(true).open(dir.join(format!(".logs/tasks/{}.log", agent))).unwrap().set_modified(an_hour_ago).unwrap();
+    }
+    ok(dir, &["monitor", "scan"]);
+    assert!(!session_exists(dir, "worker-bob"));
+    assert!(session_exists(dir, "worker-amy"), "task still in progress");
+    assert!(dir.join("workers/bob").is_dir());
+    assert!(ok(dir, &["trail"]).contains("session_reaped on worker-bob"));
+    assert!(ok(dir, &["agent", "list"]).contains("bob [T1] gemini · reaped"));
+}
+
+#[test]
+fn idle_admin_is_stopped_and_comes_back_for_new_work() {
+    let ws = TempDir::new().unwrap();
+    let dir = ws.path();
+    fs::write(dir.join("think.toml"), "[session]\nsupervisor_idle_secs = 600\n").unwrap();
+    ok(dir, &["task", "add", "T1", "Write the parser"]);
+    ok(dir, &["admin", "start"]);
+    ok(dir, &["monitor", "scan"]);
+    let db = Connection::open(dir.join("think.db")).unwrap();
+    let quiet_for_an_hour = || db.execute("UPDATE settings SET value = substr(value, 1, instr(value, ':')) || (strftime('%s','now') - 3600) WHERE key = 'idle_seen:hq-admin'", []).unwrap();
+    quiet_for_an_hour();
+    ok(dir, &["monitor", "scan"]);
+    assert!(session_exists(dir, "hq-admin"), "a task is still open");
+
+    ok(dir, &["start", "T1", "bob"]);
+    ok(dir, &["done", "T1"]);
+    quiet_for_an_hour();
+    ok(dir, &["monitor", "scan"]);
+    assert!(!session_exists(dir, "hq-admin"));
+    assert!(ok(dir, &["trail"]).contains("supervisor_idle_stopped on hq-admin (idle for 1h 0m)"));
+    ok(dir, &["monitor", "scan"]);
+    assert!(!session_exists(dir, "hq-admin"), "nothing to do yet");
+
+    ok(dir, &["task", "add", "T2", "Write the lexer"]);
+    ok(dir, &["monitor", "scan"]);
+    assert!(session_exists(dir, "hq-admin"));
+    assert!(ok(dir, &["trail"]).contains("supervisor_restarted on hq-admin (1 task(s) waiting)"));
+
+    // Stopped by hand after going idle again, it stays stopped.
+    ok(dir, &["done", "T2"]);
+    ok(dir, &["monitor", "scan"]);
+    quiet_for_an_hour();
+    ok(dir, &["monitor", "scan"]);
+    assert!(!session_exists(dir, "hq-admin"));
+    ok(dir, &["admin", "stop"]);
+    ok(dir, &["task", "add", "T3", "Write the docs"]);
+    ok(dir, &["monitor", "scan"]);
+    assert!(!session_exists(dir, "hq-admin"));
+}
+
+#[test]
+fn worker_prompt_carries_generated_conventions_and_markers_are_acted_on() {
+    let ws = TempDir::new().unwrap();
+    let dir = ws.path();
+    ok(dir, &["task", "add", "T1", "Write the parser"]);
+    let preview = ok(dir, &["start", "T1", "bob", "--dry-run"]);
+    assert!(preview.contains("## TT CONVENTIONS") && preview.contains("`TT_AGENT=bob`"), "{}", preview);
+    assert!(preview.contains("`tt mail send <RECEIVER> --subject <SUBJECT> --body <BODY>`"), "{}", preview);
+    assert!(preview.contains("within 300s"));
+    fs::write(dir.join("think.toml"), "[prompt]\npreamble = false\n").unwrap();
+    assert!(!ok(dir, &["start", "T1", "bob", "--dry-run"]).contains("TT CONVENTIONS"));
+
+    ok(dir, &["start", "T1", "bob"]);
+    // The engine echoes its prompt, which mentions every marker mid-line.
+    let log = dir.join(".logs/tasks/T1/bob.log");
+    fs::write(&log, "- `[TASK_DONE] ...` closes your task\n[QUESTION] Postgres or SQLite?\n  [RESULT] parser handles nested lists\n").unwrap();
+    ok(dir, &["monitor", "scan"]);
+    ok(dir, &["monitor", "scan"]);
+    assert_eq!(task_row(dir, "T1").0, "in_progress");
+    let inbox = ok(dir, &["mail", "inbox"]);
+    assert_eq!(inbox.matches("QUESTION from bob [T1]").count(), 1, "{}", inbox);
+    let trail = ok(dir, &["trail"]);
+    assert_eq!(trail.matches("task_result on T1").count(), 1, "{}", trail);
+    assert!(trail.contains("parser handles nested lists"));
+}
+
+#[test]
+fn conventions_command_prints_what_worker_prompts_embed() {
+    let ws = TempDir::new().unwrap();
+    let dir = ws.path();
+    let md = ok(dir, &["conventions", "--format", "md"]);
+    assert!(md.starts_with("## TT CONVENTIONS") && md.contains("`TT_AGENT=<agent>`"), "{}", md);
+    ok(dir, &["task", "add", "T1", "Write the parser"]);
+    let preview = ok(dir, &["start", "T1", "bob", "--dry-run"]);
+    let filled = md.replace("<agent>", "bob").replace("<task_id>", "T1");
+    assert!(preview.contains(filled.trim_end()), "{}\n---\n{}", filled, preview);
+
+    ok(dir, &["conventions", "--format", "json", "-o", "conventions.json"]);
+    let json: serde_json::Value = serde_json::from_str(&fs::read_to_string(dir.join("conventions.json")).unwrap()).unwrap();
+    assert_eq!(json["markers"][0]["name"], "[TASK_DONE] ...");
+    assert!(json["commands"].as_array().unwrap().iter().any(|c| c["name"] == "tt agent checkin"));
+}
+
+#[test]
+fn nudge_without_session_falls_back_to_mail() {
+    let ws = TempDir::new().unwrap();
+    let dir = ws.path();
+    ok(dir, &["nudge", "ghost", "wake up"]);
+    assert!(ok(dir, &["mail", "inbox"]).contains("NUDGE: Action Required"));
+}
+
+#[test]
+fn failure_kinds_map_to_exit_codes() {
+    let ws = TempDir::new().unwrap();
+    let dir = ws.path();
+    assert_eq!(tt(dir, &["done", "nope"]).status.code(), Some(3));
+
+    ok(dir, &["task", "add", "T1", "one"]);
+    ok(dir, &["task", "add", "T2", "two"]);
+    ok(dir, &["start", "T1", "bob"]);
+    assert_eq!(tt(dir, &["start", "T2", "bob"]).status.code(), Some(4));
+    assert_eq!(tt(dir, &["start", "T2", "amy", "--engine", "nonexistent"]).status.code(), Some(7));
+}
+
+#[test]
+fn unsafe_ids_and_names_are_rejected() {
+    let ws = TempDir::new().unwrap();
+    let dir = ws.path();
+    let out = tt(dir, &["task", "add", "has space", "title"]);
+    assert_eq!(out.status.code(), Some(2));
+    assert!(String::from_utf8_lossy(&out.stderr).contains("invalid task id 'has space'"));
+
+    ok(dir, &["task", "add", "T1", "title"]);
+    assert_eq!(tt(dir, &["start", "T1", "../escape"]).status.code(), Some(2));
+    assert!(!dir.join("escape").exists());
+}
+
+#[test]
+fn duplicate_task_id_suggests_next_free_id() {
+    let ws = TempDir::new().unwrap();
+    let dir = ws.path();
+    ok(dir, &["task", "add", "T1", "first"]);
+    ok(dir, &["task", "add", "T2", "second"]);
+    let out = tt(dir, &["task", "add", "T1", "again"]);
+    assert_eq!(out.status.code(), Some(8));
+    let err = String::from_utf8_lossy(&out.stderr);
+    assert!(err.contains("task 'T1' already exists (status: open, assignee: none)"));
+    assert!(err.contains("next free id: T3"));
+
+    for (id, title) in [(format!("T{}", "9".repeat(63)), "Numbered to the limit"), ("l".repeat(64), "Named to the limit")] {
+        ok(dir, &["task", "add", &id, title]);
+        let err = String::from_utf8_lossy(&tt(dir, &["task", "add", &id, title]).stderr).to_string();
+        let next = err.rsplit("next free id: ").next().unwrap().trim().to_string();
+        assert!(next.len() <= 64 && next != id, "{}", err);
+        ok(dir, &["task", "add", &next, title, "--force"]);
+    }
+
+    ok(dir, &["task", "add", "T1", "renamed", "--force-title-update"]);
+    assert!(ok(dir, &["task", "list"]).contains("[T1] renamed (open)"));
+}
+
+#[test]
+fn respawning_a_live_session_requires_replace() {
+    let ws = TempDir::new().unwrap();
+    let dir = ws.path();
+    ok(dir, &["task", "add", "T1", "title"]);
+    ok(dir, &["start", "T1", "bob"]);
+
+    let out = tt(dir, &["start", "T1", "bob"]);
+    assert_eq!(out.status.code(), Some(9));
+    assert!(String::from_utf8_lossy(&out.stderr).contains("session 'worker-bob' already exists (running task 'T1')"));
+
+    ok(dir, &["start", "T1", "bob", "--engine", "claude", "--replace"]);
+    let cmd = fs::read_to_string(dir.join(".sessions/mock/worker-bob")).unwrap();
+    assert!(cmd.contains("claude "));
+}
+
+#[test]
+fn costs_list_filters_and_totals() {
+    let ws = TempDir::new().unwrap();
+    let dir = ws.path();
+    ok(dir, &["costs", "add", "T1", "bob", "claude", "100", "50", "0.5"]);
+    ok(dir, &["costs", "add", "T2", "amy", "gemini", "10", "5", "0.01"]);
+    ok(dir, &["costs", "add", "T1", "amy", "claude", "1", "1", "0.1"]);
+
+    let out = ok(dir, &["costs", "list", "--task", "T1"]);
+    assert!(!out.contains("gemini"));
+    assert!(out.contains("TOTAL (2 rows)"));
+    assert!(out.contains("$0.6000"));
+
+    let out = ok(dir, &["costs", "list", "--agent", "amy", "--limit", "1", "--since", "1d"]);
+    assert!(out.contains("TOTAL (1 of 2 rows shown)"));
+}
+
+#[test]
+fn daily_cap_freezes_dispatch_until_unfreeze() {
+    let ws = TempDir::new().unwrap();
+    let dir = ws.path();
+    fs::write(dir.join("think.toml"), "[budget]\ndaily_usd_cap = 1.0\n").unwrap();
+    ok(dir, &["task", "add", "T1", "one"]);
+    ok(dir, &["task", "add", "T2", "two"]);
+    ok(dir, &["start", "T1", "bob"]);
+    ok(dir, &["costs", "add", "T1", "bob", "claude", "1000", "1000", "1.5"]);
+
+    ok(dir, &["monitor", "scan"]);
+    assert!(fs::read_to_string(dir.join(".sessions/mock/worker-bob.notify")).unwrap().contains("Daily budget exceeded"));
+    assert_eq!(tt(dir, &["start", "T2", "amy"]).status.code(), Some(10));
+
+    ok(dir, &["unfreeze"]);
+    ok(dir, &["monitor", "scan"]);
+    ok(dir, &["start", "T2", "amy"]);
+    // Lifted by hand, the cap holds off until more is spent.
+    ok(dir, &["costs", "add", "T2", "amy", "claude", "10", "10", "0.1"]);
+    ok(dir, &["monitor", "scan"]);
+    ok(dir, &["task", "add", "T3", "three"]);
+    assert_eq!(tt(dir, &["start", "T3", "cy"]).status.code(), Some(10));
+}
+
+#[test]
+fn freeze_and_kill_switch_file_halt_every_launch_path() {
+    let ws = TempDir::new().unwrap();
+    let dir = ws.path();
+    ok(dir, &["task", "add", "T1", "one"]);
+    ok(dir, &["start", "T1", "bob"]);
+    ok(dir, &["freeze", "incident 42"]);
+    for args in [&["start", "T1", "amy"][..], &["worker", "spawn", "T1", "amy"], &["worker", "restart", "bob"], &["admin", "start"]] {
+        let out = tt(dir, args);
+        assert_eq!(out.status.code(), Some(10), "tt {:?}", args);
+        assert!(String::from_utf8_lossy(&out.stderr).contains("incident 42"));
+    }
+    ok(dir, &["unfreeze"]);
+
+    fs::write(dir.join(".tt-freeze"), "db migration\n").unwrap();
+    let out = tt(dir, &["worker", "restart", "bob"]);
+    assert!(String::from_utf8_lossy(&out.stderr).contains("dispatch is frozen (db migration)"));
+    assert!(ok(dir, &["board", "list"]).contains("FROZEN: db migration"));
+    ok(dir, &["unfreeze"]);
+    assert!(!dir.join(".tt-freeze").exists());
+    ok(dir, &["worker", "restart", "bob"]);
+}
+
+#[test]
+fn finalizing_a_task_locks_out_a_second_operator() {
+    let ws = TempDir::new().unwrap();
+    let dir = ws.path();
+    ok(dir, &["task", "add", "T1", "one"]);
+    ok(dir, &["start", "T1", "bob"]);
+    let db = Connection::open(dir.join("think.db")).unwrap();
+    // Another operator is halfway through `done` from the web console.
+    db.execute("INSERT INTO locks VALUES ('worker bob', 'web', 'done', strftime('%s','now'))", []).unwrap();
+    for args in [&["done", "T1"][..], &["worker", "nuke", "bob"]] {
+        let out = tt(dir, args);
+        assert_eq!(out.status.code(), Some(14), "tt {:?}", args);
+        assert!(String::from_utf8_lossy(&out.stderr).contains("worker bob is being finalized by web (done)"));
+    }
+    assert_eq!(task_row(dir, "T1").0, "in_progress");
+    assert!(session_exists(dir, "worker-bob"));
+
+    // A holder that died long ago doesn't block anyone.
+    db.execute("UPDATE locks SET acquired_at = acquired_at - 3600", []).unwrap();
+    ok(dir, &["done", "T1"]);
+    let held: i64 = db.query_row("SELECT COUNT(*) FROM locks", [], |r| r.get(0)).unwrap();
+    assert_eq!(held, 0);
+}
+
+#[test]
+fn monitor_daemon_keeps_going_after_a_failed_pass() {
+    let ws = TempDir::new().unwrap();
+    let dir = ws.path();
+    ok(dir, &["task", "add", "T1", "Write the parser"]);
+    ok(dir, &["start", "T1", "bob"]);
+    let mut daemon = Command::new(env!("CARGO_BIN_EXE_think-todo"))
+        .args(["monitor", "start"])
+        .current_dir(dir)
+        .env("TT_SESSION_BACKEND", "mock")
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .spawn()
+        .unwrap();
+    let db = Connection::open(dir.join("think.db")).unwrap();
+    let heartbeat = || db.query_row("SELECT value FROM settings WHERE key = 'monitor_heartbeat'", [], |r| r.get::<_, String>(0)).ok();
+    for _ in 0..50 {
+        if heartbeat().is_some() { break; }
+        std::thread::sleep(std::time::Duration::from_millis(100));
+    }
+    // Every check that reads think.toml now fails.
+    fs::write(dir.join("think.toml"), "[session\n").unwrap();
+    std::thread::sleep(std::time::Duration::from_secs(4));
+    let alive = daemon.try_wait().unwrap().is_none();
+
+    // Once think.toml is fixed, the next pass goes through.
+    fs::remove_file(dir.join("think.toml")).unwrap();
+    fs::write(dir.join(".logs/tasks/T1/bob.log"), "[TASK_DONE]\n").unwrap();
+    let mut closed = false;
+    for _ in 0..50 {
+        if task_row(dir, "T1").0 == "closed" { closed = true; break; }
+        std::thread::sleep(std::time::Duration::from_millis(200));
+    }
+    let _ = daemon.kill();
+    let _ = daemon.wait();
+    assert!(alive, "the monitor stopped after a failed pass");
+    assert!(closed);
+}
+
+#[test]
+fn runaway_task_logs_are_cut_back_to_their_quota() {
+    let ws = TempDir::new().unwrap();
+    let dir = ws.path();
+    fs::write(dir.join("think.toml"), "[logs]\ntask_max_mb = 1\n").unwrap();
+    ok(dir, &["task", "add", "T1", "one"]);
+    ok(dir, &["start", "T1", "bob"]);
+    let log = dir.join(".logs/tasks/T1/bob.log");
+    let spam = "still thinking...\n".repeat(100_000);
+    fs::write(&log, format!("{}latest line\n", spam)).unwrap();
+
+    ok(dir, &["monitor", "scan"]);
+    let kept = fs::read_to_string(&log).unwrap();
+    assert!(kept.len() <= 300 * 1024, "{} bytes kept", kept.len());
+    assert!(kept.starts_with("[tt] log over quota:") && kept.ends_with("still thinking...\nlatest line\n"));
+
+    fs::write(&log, &spam).unwrap();
+    ok(dir, &["monitor", "scan"]);
+    assert_eq!(ok(dir, &["trail"]).matches("log_truncated on T1").count(), 2);
+    let db = Connection::open(dir.join("think.db")).unwrap();
+    let mails: i64 = db.query_row("SELECT COUNT(*) FROM messages WHERE subject LIKE 'LOG QUOTA%'", [], |r| r.get(0)).unwrap();
+    assert_eq!(mails, 1);
+}
+
+#[test]
+fn markers_are_not_acted_on_again_after_the_log_quota_cuts_a_log() {
+    let ws = TempDir::new().unwrap();
+    let dir = ws.path();
+    fs::write(dir.join("think.toml"), "[logs]\ntask_max_mb = 1\n").unwrap();
+    ok(dir, &["task", "add", "T1", "one"]);
+    ok(dir, &["start", "T1", "bob"]);
+    let log = dir.join(".logs/tasks/T1/bob.log");
+    let spam = "still thinking...\n".repeat(100_000);
+    fs::write(&log, format!("{}[QUESTION] which grammar?\n[RESULT] parser done\n", spam)).unwrap();
+
+    // The first pass acts on the markers, then cuts the log back past them.
+    ok(dir, &["monitor", "scan"]);
+    assert!(fs::read_to_string(&log).unwrap().starts_with("[tt] log over quota:"));
+    ok(dir, &["monitor", "scan"]);
+    fs::File::options().append(true).open(&log).unwrap().write_all(b"[QUESTION] and the lexer?\n").unwrap();
+    ok(dir, &["monitor", "scan"]);
+
+    let db = Connection::open(dir.join("think.db")).unwrap();
+    let questions: Vec<String> = db.prepare("SELECT body FROM messages WHERE subject LIKE 'QUESTION from%' ORDER BY id").unwrap()
+        .query_map([], |r| r.get(0)).unwrap().collect::<Result<_, _>>().unwrap();
+    assert_eq!(questions, ["which grammar?", "and the lexer?"]);
+    assert_eq!(ok(dir, &["trail"]).matches("task_result on T1").count(), 1);
+}
+
+#[test]
+fn rig_prompts_and_digest_reach_the_worker() {
+    let ws = TempDir::new().unwrap();
+    let dir = ws.path();
+    let rig = TempDir::new().unwrap();
+    fs::create_dir_all(dir.join("prompts/roles")).unwrap();
+    fs::write(dir.join("prompts/base.md"), "WORKSPACE BASE").unwrap();
+    fs::create_dir_all(rig.path().join("prompts/roles")).unwrap();
+    fs::write(rig.path().join("prompts/roles/worker.md"), "Run cargo test before done.").unwrap();
+    fs::write(rig.path().join("README.md"), "# Web Frontend\nBuild with npm run build.").unwrap();
+    fs::create_dir_all(rig.path().join("src")).unwrap();
+    fs::write(rig.path().join("src/app.js"), "").unwrap();
+
+    assert_eq!(tt(dir, &["task", "add", "T1", "title", "--rig", "web"]).status.code(), Some(2));
+    ok(dir, &["rig", "add", "web", rig.path().to_str().unwrap()]);
+    ok(dir, &["task", "add", "T1", "title", "--rig", "web"]);
+    ok(dir, &["start", "T1", "bob"]);
+
+    let cmd = fs::read_to_string(dir.join(".sessions/mock/worker-bob")).unwrap();
+    assert!(cmd.contains("WORKSPACE BASE"));
+    assert!(cmd.contains("RIG CONVENTIONS (web)"));
+    assert!(cmd.contains("Run cargo test before done."));
+    assert!(cmd.contains("REPO DIGEST (web)"));
+    assert!(cmd.contains("# Web Frontend"));
+    assert!(cmd.contains("  app.js"));
+}
+
+#[test]
+fn dirty_rig_blocks_dispatch_when_policy_refuses() {
+    let ws = TempDir::new().unwrap();
+    let dir = ws.path();
+    let rig = TempDir::new().unwrap();
+    assert!(Command::new("git").args(["init", "-q"]).current_dir(rig.path()).status().unwrap().success());
+    fs::write(rig.path().join("scratch.txt"), "uncommitted").unwrap();
+    fs::write(dir.join("think.toml"), "[rig]\ndirty_policy = \"refuse\"\n").unwrap();
+    ok(dir, &["rig", "add", "web", rig.path().to_str().unwrap()]);
+    ok(dir, &["task", "add", "T1", "title", "--rig", "web"]);
+
+    let out = tt(dir, &["start", "T1", "bob"]);
+    assert_eq!(out.status.code(), Some(11));
+    assert!(String::from_utf8_lossy(&out.stderr).contains("rig 'web' has 1 uncommitted change(s)"));
+    ok(dir, &["start", "T1", "bob", "--allow-dirty"]);
+
+    fs::write(dir.join("think.toml"), "[rig]\ndirty_policy = \"refues\"\n").unwrap();
+    ok(dir, &["task", "add", "T2", "typo", "--rig", "web"]);
+    let out = tt(dir, &["start", "T2", "amy"]);
+    assert_eq!(out.status.code(), Some(2));
+    assert!(String::from_utf8_lossy(&out.stderr).contains("dirty_policy must be warn, refuse or ignore"));
+}
+
+fn git(dir: &Path, args: &[&str]) -> String {
+    let out = Command::new("git")
+        .args(["-c", "user.name=tt", "-c", "user.email=tt@example.com"])
+        .args(args)
+        .current_dir(dir)
+        .output()
+        .unwrap();
+    assert!(out.status.success(), "git {:?}: {}", args, String::from_utf8_lossy(&out.stderr));
+    String::from_utf8_lossy(&out.stdout).to_string()
+}
+
+fn git_rig() -> TempDir {
+    let rig = TempDir::new().unwrap();
+    git(rig.path(), &["init", "-q", "-b", "main"]);
+    git(rig.path(), &["config", "user.name", "tt"]);
+    git(rig.path(), &["config", "user.email", "tt@example.com"]);
+    fs::write(rig.path().join("app.txt"), "v1\n").unwrap();
+    git(rig.path(), &["add", "."]);
+    git(rig.path(), &["commit", "-qm", "init"]);
+    rig
+}
+
+#[test]
+fn merge_reports_conflicts_then_lands_clean_branches() {
+    let ws = TempDir::new().unwrap();
+    let dir = ws.path();
+    let rig = git_rig();
+    ok(dir, &["rig", "add", "web", rig.path().to_str().unwrap()]);
+    ok(dir, &["task", "add", "T1", "change app", "--rig", "web"]);
+    ok(dir, &["task", "add", "T2", "add docs", "--rig", "web"]);
+
+    // Each rig task gets a worktree on its own branch.
+    ok(dir, &["start", "T1", "bob"]);
+    let wt = dir.join("workers/bob");
+    assert_eq!(git(&wt, &["branch", "--show-current"]).trim(), "tt/T1");
+    fs::write(wt.join("app.txt"), "bob's version\n").unwrap();
+    git(&wt, &["commit", "-qam", "bob"]);
+    fs::write(rig.path().join("app.txt"), "human's version\n").unwrap();
+    git(rig.path(), &["commit", "-qam", "human"]);
+
+    let out = tt(dir, &["merge", "T1", "--check"]);
+    assert_eq!(out.status.code(), Some(12));
+    assert!(String::from_utf8_lossy(&out.stdout).contains("- app.txt"));
+    ok(dir, &["merge", "T1", "--resolve"]);
+    let brief = fs::read_to_string(dir.join(".sessions/mock/worker-resolver-T1")).unwrap();
+    assert!(brief.contains("MERGE CONFLICT RESOLUTION") && brief.contains("- app.txt"));
+
+    ok(dir, &["start", "T2", "amy"]);
+    fs::write(dir.join("workers/amy/DOCS.md"), "docs\n").unwrap();
+    git(&dir.join("workers/amy"), &["add", "."]);
+    git(&dir.join("workers/amy"), &["commit", "-qm", "docs"]);
+    ok(dir, &["done", "T2"]);
+    assert!(!dir.join("workers/amy").exists());
+    assert!(ok(dir, &["merge", "T2"]).contains("Merged tt/T2 into main"));
+    assert!(rig.path().join("DOCS.md").exists());
+}
+
+#[test]
+fn monitor_checkpoints_worker_progress_to_task_branch() {
+    let ws = TempDir::new().unwrap();
+    let dir = ws.path();
+    let rig = git_rig();
+    fs::write(dir.join("think.toml"), "[checkpoint]\ninterval_secs = 1\n").unwrap();
+    ok(dir, &["rig", "add", "web", rig.path().to_str().unwrap()]);
+    ok(dir, &["task", "add", "T1", "change app", "--rig", "web"]);
+    ok(dir, &["start", "T1", "bob"]);
+
+    fs::write(dir.join("workers/bob/notes.txt"), "half done\n").unwrap();
+    ok(dir, &["monitor", "scan"]);
+    let log = git(rig.path(), &["log", "--format=%s", "tt/T1"]);
+    assert!(log.lines().next().unwrap().starts_with("tt checkpoint: T1 @ "));
+    assert!(git(&dir.join("workers/bob"), &["status", "--porcelain"]).is_empty());
+}
+
+#[test]
+fn diff_shows_branch_commits_and_uncommitted_work() {
+    let ws = TempDir::new().unwrap();
+    let dir = ws.path();
+    let rig = git_rig();
+    ok(dir, &["rig", "add", "web", rig.path().to_str().unwrap()]);
+    ok(dir, &["task", "add", "T1", "change app", "--rig", "web"]);
+    ok(dir, &["start", "T1", "bob"]);
+    let wt = dir.join("workers/bob");
+    fs::write(wt.join("app.txt"), "v2\n").unwrap();
+    git(&wt, &["commit", "-qam", "bump"]);
+    fs::write(wt.join("app.txt"), "v3\n").unwrap();
+    fs::write(wt.join("new.txt"), "x\n").unwrap();
+
+    let out = ok(dir, &["diff", "T1"]);
+    assert!(out.contains("tt/T1 vs main"));
+    assert!(out.contains("+v2") && out.contains("+v3"));
+    assert!(out.contains("new.txt (untracked)"));
+    let out = ok(dir, &["diff", "T1", "--stat"]);
+    assert!(out.contains("app.txt |") && !out.contains("+v2"));
+
+    ok(dir, &["task", "add", "T2", "no rig"]);
+    assert_eq!(tt(dir, &["diff", "T2"]).status.code(), Some(2));
+}
+
+#[test]
+fn done_archives_task_logs_and_bulk_archive_skips_active_tasks() {
+    let ws = TempDir::new().unwrap();
+    let dir = ws.path();
+    ok(dir, &["task", "add", "T1", "Write the parser"]);
+    ok(dir, &["start", "T1", "bob"]);
+    fs::write(dir.join(".logs/tasks/T1/bob.log"), "working...\n").unwrap();
+    ok(dir, &["done", "T1"]);
+    assert!(!dir.join(".logs/tasks/T1").exists());
+    assert!(dir.join(".logs/archive/T1.tar.gz").is_file());
+
+    ok(dir, &["task", "add", "T2", "Old task"]);
+    ok(dir, &["task", "add", "T3", "Running task"]);
+    ok(dir, &["start", "T3", "amy"]);
+    fs::create_dir_all(dir.join(".logs/tasks/T2")).unwrap();
+    fs::write(dir.join(".logs/tasks/T2/old.log"), "stale\n").unwrap();
+
+    assert!(ok(dir, &["logs", "archive"]).contains("Nothing to archive"));
+    let out = ok(dir, &["logs", "archive", "--older-than", "2999-01-01"]);
+    assert!(out.contains("'T2'") && !out.contains("'T3'"));
+    assert!(dir.join(".logs/archive/T2.tar.gz").is_file());
+    assert!(dir.join(".logs/tasks/T3").exists());
+}
+
+#[test]
+fn logs_reads_live_and_archived_logs_with_filters() {
+    let ws = TempDir::new().unwrap();
+    let dir = ws.path();
+    ok(dir, &["task", "add", "T1", "Write the parser"]);
+    ok(dir, &["start", "T1", "bob"]);
+    fs::write(dir.join(".logs/tasks/T1/bob.log"), "step 1\nerror: boom\nstep 2\nstep 3\n").unwrap();
+    fs::write(dir.join(".logs/tasks/T1/amy.log"), "review ok\n").unwrap();
+
+    let out = ok(dir, &["logs", "T1"]);
+    assert!(out.contains("[bob] error: boom") && out.contains("[amy] review ok"));
+    assert_eq!(ok(dir, &["logs", "T1", "--agent", "bob", "--tail", "2"]), "step 2\nstep 3\n");
+    assert_eq!(ok(dir, &["logs", "T1", "--grep", "error"]), "[bob] error: boom\n");
+
+    ok(dir, &["done", "T1"]);
+    assert_eq!(ok(dir, &["logs", "T1", "--agent", "bob", "--tail", "1"]), "step 3\n");
+    assert_eq!(tt(dir, &["logs", "T9"]).status.code(), Some(3));
+    assert_eq!(tt(dir, &["logs", "T9", "--follow"]).status.code(), Some(3));
+}
+
+#[test]
+fn peek_lists_agent_tasks_and_disambiguates() {
+    let ws = TempDir::new().unwrap();
+    let dir = ws.path();
+    assert!(ok(dir, &["peek", "bob"]).contains("No log and no live session"));
+
+    ok(dir, &["task", "add", "T1", "Write the parser"]);
+    ok(dir, &["start", "T1", "bob"]);
+    fs::write(dir.join(".logs/tasks/T1/bob.log"), "parsing...\n").unwrap();
+    let out = ok(dir, &["peek", "bob"]);
+    assert!(out.contains("T1 [in_progress] Write the parser") && out.contains("parsing..."));
+
+    // Without a log file, peek shows the live session screen instead.
+    fs::remove_file(dir.join(".logs/tasks/T1/bob.log")).unwrap();
+    fs::write(dir.join(".sessions/mock/worker-bob.screen"), "> thinking\n").unwrap();
+    assert!(ok(dir, &["peek", "bob"]).contains("> thinking"));
+
+    // Two active tasks for one agent need --task.
+    ok(dir, &["task", "add", "T2", "Write the lexer"]);
+    Connection::open(dir.join("think.db")).unwrap()
+        .execute("UPDATE tasks SET status = 'in_progress', assignee = 'bob' WHERE id = 'T2'", []).unwrap();
+    fs::create_dir_all(dir.join(".logs/tasks/T2")).unwrap();
+    fs::write(dir.join(".logs/tasks/T2/bob.log"), "lexing...\n").unwrap();
+    assert_eq!(tt(dir, &["peek", "bob"]).status.code(), Some(2));
+    assert!(ok(dir, &["peek", "bob", "--task", "T2"]).contains("lexing..."));
+}
+
+#[test]
+fn dry_run_previews_spawn_without_launching() {
+    let ws = TempDir::new().unwrap();
+    let dir = ws.path();
+    fs::create_dir_all(dir.join("prompts/roles")).unwrap();
+    fs::write(dir.join("prompts/roles/worker.md"), "You write \"tests\".").unwrap();
+    ok(dir, &["task", "add", "T1", "Write the parser"]);
+
+    let out = ok(dir, &["start", "T1", "bob", "--engine", "claude", "--dry-run"]);
+    assert!(out.contains("Session:   worker-bob"));
+    assert!(out.contains("workers/bob"));
+    assert!(out.contains("Command:   claude '") && out.contains("You write \"tests\"."), "{}", out);
+    assert!(out.contains("--- INSTRUCTION ---") && out.contains("MISSION ID: T1"));
+    assert!(!session_exists(dir, "worker-bob"));
+    assert!(!dir.join("workers/bob").exists());
+    assert_eq!(task_row(dir, "T1"), ("open".to_string(), None));
+
+    assert!(ok(dir, &["worker", "spawn", "T1", "amy", "--dry-run"]).contains("gemini --approval-mode yolo"));
+    assert!(!session_exists(dir, "worker-amy"));
+}
+
+#[test]
+fn task_show_prompt_returns_each_spawned_instruction() {
+    let ws = TempDir::new().unwrap();
+    let dir = ws.path();
+    ok(dir, &["task", "add", "T1", "Write the parser"]);
+    assert!(ok(dir, &["task", "show", "T1"]).contains("attempts: 0"));
+    ok(dir, &["start", "T1", "bob", "--engine", "claude"]);
+    ok(dir, &["start", "T1", "bob", "--replace"]);
+
+    let out = ok(dir, &["task", "show", "T1"]);
+    assert!(out.contains("[T1] Write the parser (in_progress)") && out.contains("assignee: bob") && out.contains("attempts: 2"));
+    let out = ok(dir, &["task", "show", "T1", "--prompt"]);
+    assert!(out.contains("SPAWN #1 · bob · claude") && out.contains("SPAWN #2 · bob · gemini"));
+    assert!(out.contains("MISSION ID: T1") && out.contains("EXECUTE NOW."));
+    assert_eq!(tt(dir, &["task", "show", "T9"]).status.code(), Some(3));
+}
+
+#[test]
+fn attempts_record_each_spawn_and_how_it_ended() {
+    let ws = TempDir::new().unwrap();
+    let dir = ws.path();
+    ok(dir, &["task", "add", "T1", "Write the parser"]);
+    ok(dir, &["start", "T1", "bob", "--engine", "claude"]);
+    ok(dir, &["costs", "add", "T1", "bob", "sonnet", "100", "50", "0.25"]);
+    ok(dir, &["start", "T1", "bob", "--replace"]);
+    // Session lost in a reboot, then picked up again by recover.
+    fs::remove_file(dir.join(".sessions/mock/worker-bob")).unwrap();
+    ok(dir, &["recover", "--mode", "respawn"]);
+    ok(dir, &["done", "T1"]);
+
+    let out = ok(dir, &["task", "show", "T1"]);
+    assert!(out.contains("attempts: 3"));
+    let lines: Vec<&str> = out.lines().filter(|l| l.trim_start().starts_with('#')).collect();
+    assert!(lines[0].contains("bob · claude (sonnet)") && lines[0].contains("replaced") && lines[0].contains("$0.2500"));
+    assert!(lines[1].contains("bob · gemini") && lines[1].contains("lost"));
+    assert!(lines[2].contains("done"));
+}
+
+#[test]
+fn monitor_reads_usage_footers_from_worker_logs() {
+    let ws = TempDir::new().unwrap();
+    let dir = ws.path();
+    fs::write(dir.join("think.toml"), concat!(
+        "[usage.opencode]\n",
+        "pattern = 'tokens in=(?P<input>\\d+) out=(?P<output>\\d+)'\n",
+        "model = \"big-pickle\"\n",
+        "input_usd_per_mtok = 1.0\n",
+        "output_usd_per_mtok = 2.0\n",
+    )).unwrap();
+    ok(dir, &["task", "add", "T1", "one"]);
+    ok(dir, &["task", "add", "T2", "two"]);
+    ok(dir, &["start", "T1", "bob", "--engine", "claude"]);
+    ok(dir, &["start", "T2", "amy", "--engine", "opencode"]);
+    let claude_log = dir.join(".logs/tasks/T1/bob.log");
+    let footer = "Total cost:            $0.0421\nTotal duration (API):  1m 2s\nUsage:                 12,034 input, 1,820 output, 0 cache read\n";
+    fs::write(&claude_log, format!("working\n{}", footer)).unwrap();
+    fs::write(dir.join(".logs/tasks/T2/amy.log"), "tokens in=500000 out=250000\n").unwrap();
+
+    ok(dir, &["monitor", "scan"]);
+    ok(dir, &["monitor", "scan"]);
+    let db = Connection::open(dir.join("think.db")).unwrap();
+    let costs = |agent: &str| -> Vec<(String, i64, i64, f64)> {
+        let mut stmt = db.prepare("SELECT model, input_tokens, output_tokens, cost_usd FROM costs WHERE agent_name = ?1 AND attempt_id IS NOT NULL ORDER BY id").unwrap();
+        stmt.query_map([agent], |r| Ok((r.get(0)?, r.get(1)?, r.get(2)?, r.get(3)?))).unwrap().map(Result::unwrap).collect()
+    };
+    assert_eq!(costs("bob"), vec![("claude".to_string(), 12034, 1820, 0.0421)]);
+    assert_eq!(costs("amy"), vec![("big-pickle".to_string(), 500000, 250000, 1.0)]);
+
+    // A second session's footer is picked up by `done` before the logs are archived.
+    fs::OpenOptions::new().append(true).open(&claude_log).unwrap().write_all(footer.as_bytes()).unwrap();
+    ok(dir, &["done", "T1"]);
+    assert_eq!(costs("bob").len(), 2);
+}
+
+#[test]
+fn costs_attach_to_attempts_and_feed_engine_stats() {
+    let ws = TempDir::new().unwrap();
+    let dir = ws.path();
+    ok(dir, &["task", "add", "T1", "Write the parser"]);
+    ok(dir, &["task", "add", "T2", "Write the lexer"]);
+    ok(dir, &["start", "T1", "bob", "--engine", "claude"]);
+    ok(dir, &["costs", "add", "T1", "bob", "sonnet", "100", "50", "0.30"]);
+    ok(dir, &["start", "T1", "bob", "--engine", "claude", "--replace"]);
+    ok(dir, &["costs", "add", "T1", "bob", "sonnet", "100", "50", "0.10"]);
+    ok(dir, &["done", "T1"]);
+    ok(dir, &["start", "T2", "amy"]);
+    ok(dir, &["costs", "add", "T2", "amy", "gemini-pro", "10", "5", "0.05"]);
+
+    let show = ok(dir, &["task", "show", "T1"]);
+    let attempts: Vec<&str> = show.lines().filter(|l| l.trim_start().starts_with('#')).collect();
+    assert!(attempts[0].contains("replaced") && attempts[0].contains("$0.3000"));
+    assert!(attempts[1].contains("done") && attempts[1].contains("$0.1000"));
+
+    let out = ok(dir, &["stats", "engines"]);
+    let claude = out.lines().find(|l| l.starts_with("claude")).unwrap();
+    assert!(claude.contains("sonnet") && claude.contains("50%") && claude.contains("$0.2000") && claude.contains("$0.4000"));
+    let gemini = out.lines().find(|l| l.starts_with("gemini")).unwrap();
+    assert!(gemini.contains("gemini-pro") && gemini.contains(" - "));
+}
+
+#[test]
+fn schedule_export_writes_due_dates_as_ics() {
+    let ws = TempDir::new().unwrap();
+    let dir = ws.path();
+    ok(dir, &["task", "add", "T1", "Ship v1, finally"]);
+    ok(dir, &["task", "add", "T2", "No due date"]);
+    ok(dir, &["task", "add", "T3", "Already closed"]);
+    let db = Connection::open(dir.join("think.db")).unwrap();
+    db.execute("UPDATE tasks SET due_at = 1893456000 WHERE id IN ('T1', 'T3')", []).unwrap();
+    db.execute("UPDATE tasks SET status = 'closed' WHERE id = 'T3'", []).unwrap();
+
+    let feed = ok(dir, &["schedule", "export"]);
+    assert!(feed.starts_with("BEGIN:VCALENDAR\r\n") && feed.ends_with("END:VCALENDAR\r\n"));
+    assert!(feed.contains("UID:T1-due@think-todo") && feed.contains("SUMMARY:Due: [T1] Ship v1\\, finally"));
+    assert!(!feed.contains("[T2]") && !feed.contains("[T3]"), "{}", feed);
+
+    ok(dir, &["schedule", "export", "-o", "tt.ics"]);
+    assert_eq!(fs::read_to_string(dir.join("tt.ics")).unwrap().matches("BEGIN:VEVENT").count(), 1);
+}
+
+#[test]
+fn overdue_tasks_are_flagged_and_escalated_once() {
+    let ws = TempDir::new().unwrap();
+    let dir = ws.path();
+    ok(dir, &["task", "add", "T1", "Late work", "--due", "2020-01-01"]);
+    ok(dir, &["task", "add", "T2", "Future work", "--due", "2999-01-01 17:00"]);
+    ok(dir, &["task", "add", "T3", "Whenever"]);
+    assert_eq!(tt(dir, &["task", "add", "T4", "x", "--due", "soonish"]).status.code(), Some(2));
+
+    let list = ok(dir, &["task", "list"]);
+    assert!(list.contains("[T1] Late work (open) ⚠️ OVERDUE since 2020-01-01"));
+    assert!(list.contains("[T2] Future work (open) 📅 due 2999-01-01 17:00"));
+    assert!(list.contains("[T3] Whenever (open)\n"));
+    assert!(ok(dir, &["board", "list"]).contains("'T1' Late work — due 2020-01-01 (unassigned)"));
+
+    // Unassigned overdue work is visible but not escalated; assigned work mails the admin once.
+    ok(dir, &["monitor", "scan"]);
+    assert!(!ok(dir, &["mail", "inbox"]).contains("OVERDUE"));
+    ok(dir, &["start", "T1", "bob"]);
+    ok(dir, &["monitor", "scan"]);
+    ok(dir, &["monitor", "scan"]);
+    assert_eq!(ok(dir, &["mail", "inbox"]).matches("OVERDUE: [T1] Late work").count(), 1);
+    assert!(ok(dir, &["trail"]).contains("task_overdue on T1"));
+
+    ok(dir, &["task", "edit", "T1", "--no-due", "--title", "Late work, renegotiated"]);
+    assert!(ok(dir, &["task", "list"]).contains("[T1] Late work, renegotiated (in_progress)\n"));
+}
+
+#[test]
+fn sla_breaches_are_audited_and_mailed_once() {
+    let ws = TempDir::new().unwrap();
+    let dir = ws.path();
+    fs::write(dir.join("think.toml"), "[[sla]]\ntag = \"hotfix\"\nstart_within = \"30m\"\nfinish_within = \"4h\"\n").unwrap();
+    ok(dir, &["task", "add", "H1", "Prod is down", "--tag", "hotfix"]);
+    ok(dir, &["task", "add", "H2", "Prod is slow", "--tag", "hotfix"]);
+    ok(dir, &["task", "add", "T1", "Not urgent"]);
+    assert!(ok(dir, &["task", "list"]).contains("[H1] Prod is down (open) #hotfix"));
+
+    ok(dir, &["monitor", "scan"]);
+    assert!(!ok(dir, &["trail"]).contains("sla_breach"));
+
+    // H1 was filed an hour ago and nobody picked it up; H2 was filed five hours ago and is still running.
+    let db = Connection::open(dir.join("think.db")).unwrap();
+    db.execute("UPDATE tasks SET created_at = created_at - 3600 WHERE id IN ('H1', 'T1')", []).unwrap();
+    ok(dir, &["start", "H2", "bob"]);
+    db.execute("UPDATE tasks SET created_at = created_at - 18000 WHERE id = 'H2'", []).unwrap();
+    ok(dir, &["monitor", "scan"]);
+    ok(dir, &["monitor", "scan"]);
+
+    let trail = ok(dir, &["trail"]);
+    assert!(trail.contains("sla_breach on H1 (#hotfix: not started within 30m)"));
+    assert!(trail.contains("sla_breach on H2 (#hotfix: not finished within 4h)"));
+    assert!(trail.contains("sla_breach on H2 (#hotfix: not started within 30m)"));
+    assert!(!trail.contains("sla_breach on T1"));
+    assert_eq!(ok(dir, &["mail", "inbox"]).matches("SLA BREACH").count(), 3);
+
+    fs::write(dir.join("think.toml"), "[[sla]]\ntag = \"hotfix\"\nstart_within = \"soon\"\n").unwrap();
+    assert_eq!(tt(dir, &["monitor", "scan"]).status.code(), Some(2));
+}
+
+#[test]
+fn unanswered_questions_climb_the_escalation_ladder_once() {
+    let ws = TempDir::new().unwrap();
+    let dir = ws.path();
+    let (url, seen) = http_server("ok");
+    fs::write(dir.join("think.toml"), format!("[[escalation]]\nafter = \"2h\"\nwebhook = \"{}/hook\"\n\n[[escalation]]\nafter = \"30m\"\nnudge = true\n", url)).unwrap();
+    ok(dir, &["task", "list"]);
+    let db = Connection::open(dir.join("think.db")).unwrap();
+    db.execute("INSERT INTO messages (sender, receiver, subject, body, timestamp) VALUES
+        ('bob', 'admin', 'QUESTION from bob [T1]', 'Postgres or SQLite?', strftime('%s','now') - 2400),
+        ('bob', 'admin', 'QUESTION from bob [T2]', 'Tabs or spaces?', strftime('%s','now') - 9000),
+        ('bob', 'admin', 'Progress', 'halfway', strftime('%s','now') - 9000)", []).unwrap();
+
+    ok(dir, &["monitor", "scan"]);
+    ok(dir, &["monitor", "scan"]);
+    let trail = ok(dir, &["trail"]);
+    assert_eq!(trail.matches("escalated on 1 (after 30m").count(), 1, "{}", trail);
+    assert_eq!(trail.matches("escalated on 2 (after 30m").count(), 1, "{}", trail);
+    assert_eq!(trail.matches("escalated on 2 (after 2h: webhook)").count(), 1, "{}", trail);
+    assert!(!trail.contains("escalated on 1 (after 2h") && !trail.contains("escalated on 3"), "{}", trail);
+    let hooks = seen.lock().unwrap().clone();
+    assert_eq!(hooks.len(), 1, "{:?}", hooks);
+    assert!(hooks[0].1.contains("QUESTION from bob [T2]"), "{:?}", hooks);
+
+    // Answered before the next step: nothing more happens.
+    ok(dir, &["mail", "read", "1"]);
+    db.execute("UPDATE messages SET timestamp = timestamp - 7200 WHERE id = 1", []).unwrap();
+    ok(dir, &["monitor", "scan"]);
+    assert!(!ok(dir, &["trail"]).contains("escalated on 1 (after 2h"));
+    let steps = |id: i64| db.query_row("SELECT value FROM settings WHERE key = ?1", [format!("escalation:{}", id)], |r| r.get::<_, String>(0)).ok();
+    assert_eq!((steps(1), steps(2).as_deref()), (None, Some("2")));
+
+    // Once the task a question is about closes, it stops climbing and its progress is dropped.
+    ok(dir, &["task", "add", "T2", "Indentation"]);
+    ok(dir, &["done", "T2"]);
+    ok(dir, &["monitor", "scan"]);
+    assert_eq!(steps(2), None);
+
+    // A webhook that can't be reached is logged without the path that acts as its password.
+    fs::write(dir.join("think.toml"), "[[escalation]]\nafter = \"1m\"\nwebhook = \"http://127.0.0.1:9/services/T0/s3cret\"\n").unwrap();
+    db.execute("INSERT INTO messages (sender, receiver, subject, body, timestamp) VALUES
+        ('amy', 'admin', 'QUESTION from amy [T4]', 'Merge now?', strftime('%s','now') - 600)", []).unwrap();
+    ok(dir, &["monitor", "scan"]);
+    let trail = ok(dir, &["trail"]);
+    assert!(trail.contains("escalated on 4 (after 1m: webhook: http://127.0.0.1:9/***") && !trail.contains("s3cret"), "{}", trail);
+
+    fs::write(dir.join("think.toml"), "[[escalation]]\nafter = \"later\"\n").unwrap();
+    assert_eq!(tt(dir, &["monitor", "scan"]).status.code(), Some(2));
+}
+
+#[test]
+fn search_finds_tasks_mail_and_audit_by_word_prefix() {
+    let ws = TempDir::new().unwrap();
+    let dir = ws.path();
+    ok(dir, &["task", "add", "T1", "Write the parser"]);
+    ok(dir, &["task", "add", "T2", "Document the CLI"]);
+    ok(dir, &["mail", "send", "bob", "--subject", "Commas", "--body", "Should the parser accept trailing commas? (\"JSON-ish\")"]);
+    ok(dir, &["start", "T1", "bob"]);
+
+    let found = ok(dir, &["search", "pars"]);
+    assert!(found.contains("Tasks (1)\n  [T1] Write the parser (in_progress)"), "{}", found);
+    assert!(found.contains("Mail (1)\n  #1 user -> bob: Commas\n      Should the [parser] accept"), "{}", found);
+    assert!(!found.contains("T2"), "{}", found);
+    assert!(ok(dir, &["search", "t1"]).contains("delegated on T1"));
+    assert!(ok(dir, &["search", "\"json-ish"]).contains("#1 user -> bob"), "quotes are taken literally");
+
+    // The index follows renames and deletes, and is rebuilt for a database that predates it.
+    ok(dir, &["task", "edit", "T2", "--title", "Parse the flags"]);
+    ok(dir, &["task", "delete", "T1"]);
+    let db = Connection::open(dir.join("think.db")).unwrap();
+    db.execute_batch("DROP TABLE task_fts; DROP TABLE mail_fts; DROP TABLE audit_fts;").unwrap();
+    let found = ok(dir, &["search", "parse"]);
+    assert!(found.contains("[T2] Parse the flags (open)") && !found.contains("[T1]"), "{}", found);
+    assert!(found.contains("#1 user -> bob: Commas"), "{}", found);
+    assert!(ok(dir, &["search", "nothing"]).contains("Nothing matches 'nothing'."));
+}
+
+#[test]
+fn web_users_are_managed_by_the_user_only() {
+    let ws = TempDir::new().unwrap();
+    let dir = ws.path();
+    assert!(ok(dir, &["user", "list"]).contains("No web users"));
+    let token = ok(dir, &["user", "add", "alice"]).lines().last().unwrap().to_string();
+    assert_eq!(token.len(), 32, "{}", token);
+    let again = ok(dir, &["user", "add", "alice"]).lines().last().unwrap().to_string();
+    assert_ne!(token, again, "adding again replaces the token");
+    assert!(ok(dir, &["user", "list"]).starts_with("alice "));
+
+    ok(dir, &["task", "add", "T1", "Write the parser"]);
+    ok(dir, &["start", "T1", "bob"]);
+    let out = Command::new(env!("CARGO_BIN_EXE_think-todo")).args(["user", "add", "mallory"]).current_dir(dir)
+        .env("TT_SESSION_BACKEND", "mock").env("TT_AGENT", "bob")
+        .env("TT_TOKEN", session_env(dir, "worker-bob", "TT_TOKEN")).output().unwrap();
+    assert!(!out.status.success());
+    assert!(String::from_utf8_lossy(&out.stderr).contains("only the user at the terminal can manage web users, not 'bob'"));
+    // Dropping the session's variables doesn't make an agent the user.
+    let out = Command::new("sh").args(["-c", &format!("env -u TT_TOKEN {} user add mallory; true", env!("CARGO_BIN_EXE_think-todo"))])
+        .current_dir(dir).env("TT_SESSION_BACKEND", "mock").env("TT_TOKEN", session_env(dir, "worker-bob", "TT_TOKEN")).output().unwrap();
+    assert!(String::from_utf8_lossy(&out.stderr).contains("'unknown' could not be verified"), "{}", String::from_utf8_lossy(&out.stderr));
+    let out = Command::new(env!("CARGO_BIN_EXE_think-todo")).args(["user", "add", "mallory"]).current_dir(dir)
+        .env("TT_SESSION_BACKEND", "mock").env("TT_WORKSPACE", dir).output().unwrap();
+    assert!(!out.status.success());
+
+    ok(dir, &["user", "remove", "alice"]);
+    assert!(!tt(dir, &["user", "remove", "alice"]).status.success());
+    let trail = ok(dir, &["trail"]);
+    assert!(trail.contains("user -> user_added on alice") && trail.contains("user -> user_removed on alice"), "{}", trail);
+    assert!(!trail.contains("mallory"), "{}", trail);
+}
+
+#[test]
+fn web_changes_need_a_user_token_once_users_exist() {
+    let ws = TempDir::new().unwrap();
+    let dir = ws.path();
+    let server = Served::start(dir, &[]);
+    assert_eq!(server.call("POST", "/api/tasks", &[], Some(r#"{"id": "T1", "title": "Write the parser"}"#)).0, 200);
+
+    let token = ok(dir, &["user", "add", "alice"]).lines().last().unwrap().to_string();
+    let (status, body) = server.call("POST", "/api/tasks", &[], Some(r#"{"id": "T2", "title": "Write the lexer"}"#));
+    assert_eq!(status, 401, "{}", body);
+    assert!(body.contains("Bearer"));
+    assert_eq!(server.call("DELETE", "/api/tasks/T1", &[("Authorization", "Bearer forged")], None).0, 401);
+    assert_eq!(server.get("/api/dashboard").0, 200, "reads stay open");
+    assert_eq!(server.call("DELETE", "/api/tasks/T1", &[("Authorization", &format!("Bearer {}", token))], None).0, 200);
+    assert!(ok(dir, &["trail"]).contains("alice -> task_deleted on T1"));
+}
+
+#[test]
+fn serve_allows_only_the_configured_cors_origins() {
+    let ws = TempDir::new().unwrap();
+    let dir = ws.path();
+    fs::write(dir.join("think.toml"), "[server]\ncors_origins = [\"https://ops.example.com/\"]\n").unwrap();
+    let server = Served::start(dir, &[]);
+    let allowed = |origin: &str| server.send("GET", "/api/dashboard", &[("Origin", origin)], None)
+        .header("access-control-allow-origin").map(str::to_string);
+    assert_eq!(allowed("https://ops.example.com").as_deref(), Some("https://ops.example.com"));
+    assert_eq!(allowed("https://evil.example.com"), None);
+    let preflight = server.send("OPTIONS", "/api/tasks", &[("Origin", "https://ops.example.com"), ("Access-Control-Request-Method", "POST")], None);
+    assert!(preflight.header("access-control-allow-methods").is_some_and(|m| m.contains("POST")));
+
+    fs::write(dir.join("think.toml"), "[server]\ncors_origins = [\"not a header\\n\"]\n").unwrap();
+    let out = tt(dir, &["serve", "-p", "0"]);
+    assert!(String::from_utf8_lossy(&out.stderr).contains("invalid CORS origin"), "{}", String::from_utf8_lossy(&out.stderr));
+}
+
+#[test]
+fn serve_under_a_base_path_trusts_proxy_headers_only_when_told() {
+    let ws = TempDir::new().unwrap();
+    let dir = ws.path();
+    ok(dir, &["task", "add", "T1", "Write the parser"]);
+    let server = Served::start(dir, &["--base-path", "tt/"]);
+    let (status, body) = server.get("/tt/api/dashboard");
+    assert_eq!(status, 200);
+    assert!(body.contains("Write the parser"));
+    assert_eq!(server.get("/api/dashboard").0, 404);
+    let bare = server.send("GET", "/tt", &[("X-Forwarded-Prefix", "/proxy")], None);
+    assert_eq!((bare.status(), bare.header("location")), (308, Some("/tt/")), "X-Forwarded-Prefix is ignored by default");
+    drop(server);
+
+    fs::write(dir.join("think.toml"), "[server]\nbase_path = \"/tt\"\ntrust_forwarded = true\n").unwrap();
+    let server = Served::start(dir, &[]);
+    let bare = server.send("GET", "/tt", &[("X-Forwarded-Prefix", "/proxy/")], None);
+    assert_eq!((bare.status(), bare.header("location")), (308, Some("/proxy/tt/")));
+}
+
+#[test]
+fn serve_routes_hosted_workspaces_by_path_and_token() {
+    let ws = TempDir::new().unwrap();
+    let dir = ws.path();
+    ok(dir, &["task", "add", "T1", "Main workspace task"]);
+    for (name, title) in [("side", "Side workspace task"), ("open", "Open workspace task")] {
+        fs::create_dir(dir.join(name)).unwrap();
+        ok(&dir.join(name), &["task", "add", "T1", title]);
+    }
+    fs::write(dir.join("think.toml"), "[[server.tenants]]\nname = \"side\"\ndir = \"side\"\ntoken_env = \"SIDE_TOKEN\"\n\n\
+        [[server.tenants]]\nname = \"open\"\ndir = \"open\"\n").unwrap();
+    let side_user = ok(&dir.join("side"), &["user", "add", "carol"]).lines().last().unwrap().to_string();
+    let server = Served::start_with(dir, &[], &[("SIDE_TOKEN", "s3cret-side")]);
+    let dashboard = |path: &str, token: Option<&str>| {
+        let auth = token.map(|t| format!("Bearer {}", t));
+        let headers: Vec<(&str, &str)> = auth.iter().map(|a| ("Authorization", a.as_str())).collect();
+        server.call("GET", path, &headers, None)
+    };
+
+    assert!(dashboard("/api/dashboard", None).1.contains("Main workspace task"));
+    assert_eq!(dashboard("/w/side/api/dashboard", None).0, 401);
+    assert_eq!(dashboard("/w/side/api/dashboard", Some("s3cret-sidf")).0, 401);
+    let (status, body) = dashboard("/w/side/api/dashboard", Some("s3cret-side"));
+    assert_eq!(status, 200);
+    assert!(body.contains("Side workspace task") && !body.contains("Main workspace task"), "{}", body);
+    assert_eq!(dashboard("/w/side/api/dashboard", Some(&side_user)).0, 200, "the workspace's own users get in too");
+    assert!(dashboard("/api/dashboard", Some("s3cret-side")).1.contains("Side workspace task"), "the token picks the workspace");
+    assert!(dashboard("/w/open/api/dashboard", None).1.contains("Open workspace task"));
+    assert_eq!(dashboard("/w/nope/api/dashboard", None).0, 404);
+
+    // Changes there still need one of its users, as it has some.
+    let task = Some(r#"{"id": "T2", "title": "Added remotely"}"#);
+    assert_eq!(server.call("POST", "/w/side/api/tasks", &[("Authorization", "Bearer s3cret-side")], task).0, 401);
+    assert_eq!(server.call("POST", "/w/side/api/tasks", &[("Authorization", &format!("Bearer {}", side_user))], task).0, 200);
+    assert!(ok(&dir.join("side"), &["task", "list"]).contains("Added remotely"));
+    assert!(!ok(dir, &["task", "list"]).contains("Added remotely"));
+}
+
+#[test]
+fn serve_previews_worker_files_without_leaving_the_worker_directory() {
+    let ws = TempDir::new().unwrap();
+    let dir = ws.path();
+    ok(dir, &["task", "list"]);
+    let worker = dir.join("workers").join("bob");
+    fs::create_dir_all(worker.join("src")).unwrap();
+    fs::write(worker.join("src/main.rs"), "fn main() {}\n").unwrap();
+    fs::write(worker.join("big.log"), "x".repeat(300 * 1024)).unwrap();
+    fs::write(worker.join("blob.bin"), b"\x7fELF\x00\x01\x02").unwrap();
+    fs::write(dir.join("think.toml.secret"), "token = 42\n").unwrap();
+    std::os::unix::fs::symlink(dir, worker.join("up")).unwrap();
+    std::os::unix::fs::symlink(dir.join("think.toml.secret"), worker.join("notes.txt")).unwrap();
+    let server = Served::start(dir, &[]);
+    let file = |path: &str| {
+        let (status, body) = server.get(&format!("/api/agents/bob/file?path={}", path));
+        (status, serde_json::from_str::<serde_json::Value>(&body).unwrap_or_default(), body)
+    };
+
+    let (status, json, _) = file("src/main.rs");
+    assert_eq!(status, 200);
+    assert_eq!((json["content"].as_str(), json["language"].as_str(), json["truncated"].as_bool()), (Some("fn main() {}\n"), Some("rust"), Some(false)));
+    let (_, json, _) = file("big.log");
+    assert_eq!((json["size"].as_u64(), json["truncated"].as_bool()), (Some(300 * 1024), Some(true)));
+    assert_eq!(json["content"].as_str().map(str::len), Some(256 * 1024));
+    let (_, json, _) = file("blob.bin");
+    assert_eq!((json["text"].as_bool(), json["size"].as_u64()), (Some(false), Some(7)));
+    assert!(json["content"].is_null());
+
+    for path in ["../../think.toml.secret", "src/../../../think.toml.secret", &format!("{}/think.toml.secret", dir.display()), "up/think.toml.secret", "notes.txt", ""] {
+        let (status, _, body) = file(path);
+        assert_eq!(status, 400, "{}: {}", path, body);
+        assert!(!body.contains("token = 42"), "{}", body);
+    }
+    assert!(file("../x").2.contains("outside the worker directory"));
+    assert!(file("up/think.toml.secret").2.contains("outside the worker directory"));
+}
+
+#[test]
+fn serve_dashboard_reports_queue_budget_monitor_and_disk() {
+    let ws = TempDir::new().unwrap();
+    let dir = ws.path();
+    fs::write(dir.join("think.toml"), "[budget]\ndaily_usd_cap = 5.0\n").unwrap();
+    for id in ["T1", "T2", "T3"] { ok(dir, &["task", "add", id, &format!("Task {}", id), "--force"]); }
+    ok(dir, &["start", "T1", "bob"]);
+    ok(dir, &["costs", "add", "T1", "bob", "claude", "1000", "1000", "1.25"]);
+    fs::write(dir.join("workers/bob/notes.txt"), "x".repeat(4096)).unwrap();
+    let server = Served::start(dir, &[]);
+    let dashboard = || serde_json::from_str::<serde_json::Value>(&server.get("/api/dashboard").1).unwrap();
+
+    let health = dashboard()["health"].clone();
+    assert_eq!(health["queue_depth"], 2, "{}", health);
+    assert_eq!((health["budget"]["today_usd"].as_f64(), health["budget"]["daily_cap_usd"].as_f64()), (Some(1.25), Some(5.0)));
+    assert!(health["budget"]["frozen"].is_null());
+    assert_eq!((health["daemon"]["alive"].as_bool(), health["daemon"]["last_seen"].is_null()), (Some(false), true));
+    ok(dir, &["freeze", "incident 7"]);
+    ok(dir, &["monitor", "scan"]);
+    let health = dashboard()["health"].clone();
+    assert_eq!(health["budget"]["frozen"], "incident 7");
+    assert_eq!(health["daemon"]["alive"], true);
+    assert!(health["disk"]["workers_bytes"].as_u64().unwrap() >= 4096, "{}", health);
+    assert!(health["warnings"].is_array() && health["stalled"] == 0 && health["failed"] == 0, "{}", health);
+}
+
+#[test]
+fn serve_starts_and_stops_the_admin_and_restarts_and_nukes_workers() {
+    let ws = TempDir::new().unwrap();
+    let dir = ws.path();
+    ok(dir, &["task", "add", "T1", "Write the parser"]);
+    ok(dir, &["start", "T1", "bob"]);
+    let server = Served::start(dir, &[]);
+    let post = |path: &str| server.call("POST", path, &[], None);
+
+    assert_eq!(post("/api/admin/start").0, 200);
+    assert!(session_exists(dir, "hq-admin"));
+    assert_eq!(post("/api/admin/stop").0, 200);
+    assert!(!session_exists(dir, "hq-admin"));
+
+    fs::remove_file(dir.join(".sessions/mock/worker-bob")).unwrap();
+    let (status, body) = post("/api/workers/bob/restart");
+    assert_eq!(status, 200, "{}", body);
+    assert!(body.contains("\"task_id\":\"T1\"") && session_exists(dir, "worker-bob"), "{}", body);
+    assert_eq!(post("/api/workers/amy/restart").0 / 100, 4, "amy has no task to go back to");
+    assert_eq!(post("/api/workers/bad%20name/nuke").0, 400);
+    assert_eq!(post("/api/workers/bob/nuke").0, 200);
+    assert!(!session_exists(dir, "worker-bob") && !dir.join("workers/bob").exists());
+
+    let trail = ok(dir, &["trail"]);
+    for entry in ["web -> admin_started on hq-admin", "web -> admin_stopped on hq-admin", "web -> worker_restarted on bob (T1)"] {
+        assert!(trail.contains(entry), "{}: {}", entry, trail);
+    }
+}
+
+#[test]
+fn serve_shows_the_tail_of_a_worker_pane() {
+    let ws = TempDir::new().unwrap();
+    let dir = ws.path();
+    ok(dir, &["task", "add", "T1", "Write the parser"]);
+    ok(dir, &["start", "T1", "bob"]);
+    fs::write(dir.join(".sessions/mock/worker-bob.screen"), "$ cargo test\ntest result: ok\n").unwrap();
+    let server = Served::start(dir, &[]);
+
+    let (status, body) = server.get("/api/workers/bob/screen?lines=0");
+    assert_eq!(status, 200);
+    let screen: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!((screen["agent"].as_str(), screen["session"].as_str()), (Some("bob"), Some("worker-bob")));
+    assert!(screen["screen"].as_str().unwrap().contains("test result: ok"), "{}", body);
+    assert_eq!(server.get("/api/workers/amy/screen").0, 404);
+    assert_eq!(server.get("/api/workers/bad%20name/screen").0, 400);
+    assert_eq!(server.get("/api/workers/bob/screen?lines=lots").0, 400);
+}
+
+#[test]
+fn serve_describes_its_api_in_openapi_and_swagger_ui() {
+    let ws = TempDir::new().unwrap();
+    let dir = ws.path();
+    ok(dir, &["task", "list"]);
+    let server = Served::start(dir, &[]);
+
+    let (status, body) = server.get("/api/openapi.json");
+    assert_eq!(status, 200);
+    let spec: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert!(spec["openapi"].as_str().unwrap().starts_with("3."));
+    for (path, method) in [("/api/dashboard", "get"), ("/api/tasks", "post"), ("/api/workers/{name}/nuke", "post"), ("/api/workers/{name}/screen", "get"), ("/api/admin/start", "post")] {
+        assert!(spec["paths"][path][method].is_object(), "{} {} missing", method, path);
+    }
+    assert!(spec["components"]["schemas"]["DashboardData"].is_object());
+    let (status, page) = server.get("/api/docs/");
+    assert_eq!(status, 200);
+    assert!(page.contains("swagger"), "{}", page);
+    assert!(server.get("/api/docs/swagger-initializer.js").1.contains("../openapi.json"), "the docs page finds the spec under any base path");
+}
+
+#[test]
+fn serve_lists_task_artifacts_and_diffs_them_against_the_rig() {
+    let ws = TempDir::new().unwrap();
+    let dir = ws.path();
+    let rig = git_rig();
+    ok(dir, &["rig", "add", "web", rig.path().to_str().unwrap()]);
+    ok(dir, &["task", "add", "T1", "change app", "--rig", "web"]);
+    ok(dir, &["start", "T1", "bob"]);
+    let worker = dir.join("workers/bob");
+    fs::write(worker.join("app.txt"), "v2\n").unwrap();
+    fs::write(worker.join("NEW.md"), "notes\n").unwrap();
+    let out = Command::new(env!("CARGO_BIN_EXE_think-todo"))
+        .args(["mail", "send", "admin", "-s", "review", "-b", "please look", "--attach", "app.txt", "--attach", "NEW.md"])
+        .current_dir(&worker).env("TT_SESSION_BACKEND", "mock").env("TT_AGENT", "bob")
+        .env("TT_WORKSPACE", dir).env("TT_TOKEN", session_env(dir, "worker-bob", "TT_TOKEN")).output().unwrap();
+    assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+    fs::write(dir.join("loose.txt"), "not from a worker\n").unwrap();
+    ok(dir, &["mail", "send", "bob", "-s", "fyi", "-b", "x", "--attach", "loose.txt"]);
+    let loose = ok(dir, &["mail", "read", "2"]).split("artifact ").nth(1).unwrap()[..12].to_string();
+    let server = Served::start(dir, &[]);
+
+    let (status, body) = server.get("/api/tasks/T1/artifacts");
+    assert_eq!(status, 200);
+    let listed: serde_json::Value = serde_json::from_str(&body).unwrap();
+    let artifacts = listed["artifacts"].as_array().unwrap();
+    assert_eq!(artifacts.len(), 2, "{}", body);
+    assert_eq!((artifacts[0]["path"].as_str(), artifacts[0]["sender"].as_str(), artifacts[0]["message_id"].as_i64()), (Some("app.txt"), Some("bob"), Some(1)));
+    let id = |i: usize| artifacts[i]["artifact"].as_str().unwrap().to_string();
+
+    let diff: serde_json::Value = serde_json::from_str(&server.get(&format!("/api/artifacts/{}/diff", &id(0)[..8])).1).unwrap();
+    assert_eq!((diff["task"].as_str(), diff["rig"].as_str(), diff["file"].as_str(), diff["new_file"].as_bool()), (Some("T1"), Some("web"), Some("app.txt"), Some(false)));
+    let text = diff["diff"].as_str().unwrap();
+    assert!(text.contains("-v1") && text.contains("+v2") && text.contains("b/app.txt") && !text.contains(".logs"), "{}", text);
+    let diff: serde_json::Value = serde_json::from_str(&server.get(&format!("/api/artifacts/{}/diff", id(1))).1).unwrap();
+    assert_eq!(diff["new_file"], true);
+    assert!(diff["diff"].as_str().unwrap().contains("+notes"));
+
+    assert_eq!(server.get("/api/tasks/T9/artifacts").0, 404);
+    let (status, body) = server.get(&format!("/api/artifacts/{}/diff", loose));
+    assert_eq!(status, 400);
+    assert!(body.contains("wasn't attached while working on a task"), "{}", body);
+    assert_eq!(server.get("/api/artifacts/abc/diff").0 / 100, 4);
+}
+
+#[test]
+fn serve_lists_overdue_tasks_most_overdue_first() {
+    let ws = TempDir::new().unwrap();
+    let dir = ws.path();
+    ok(dir, &["task", "add", "T1", "Renew the certificate", "--due", "2001-01-01"]);
+    ok(dir, &["task", "add", "T2", "File the taxes", "--due", "2000-01-01"]);
+    ok(dir, &["task", "add", "T3", "Plan the offsite", "--due", "2999-01-01"]);
+    ok(dir, &["task", "add", "T4", "Book the venue", "--due", "2000-01-01"]);
+    ok(dir, &["task", "add", "T5", "Tidy the wiki"]);
+    ok(dir, &["start", "T1", "bob"]);
+    ok(dir, &["done", "T4"]);
+    let server = Served::start(dir, &[]);
+
+    let (status, body) = server.get("/api/tasks/overdue");
+    assert_eq!(status, 200);
+    let tasks = serde_json::from_str::<serde_json::Value>(&body).unwrap()["tasks"].as_array().unwrap().clone();
+    let ids: Vec<&str> = tasks.iter().map(|t| t["id"].as_str().unwrap()).collect();
+    assert_eq!(ids, ["T2", "T1"], "{}", body);
+    assert_eq!((tasks[1]["assignee"].as_str(), tasks[0]["assignee"].is_null()), (Some("bob"), true));
+    assert!(tasks[0]["overdue_secs"].as_i64().unwrap() > tasks[1]["overdue_secs"].as_i64().unwrap());
+    assert!(tasks[1]["overdue_secs"].as_i64().unwrap() > 0 && tasks[1]["due_at"].as_i64().is_some());
+}
+
+#[test]
+fn serve_status_page_is_opt_in_public_and_shows_counts_only() {
+    let ws = TempDir::new().unwrap();
+    let dir = ws.path();
+    ok(dir, &["task", "add", "T1", "Acquire Initech quietly"]);
+    ok(dir, &["task", "add", "T2", "Draft the layoff memo"]);
+    ok(dir, &["start", "T1", "bob"]);
+    ok(dir, &["costs", "add", "T1", "bob", "claude", "1000", "1000", "4.75"]);
+    ok(dir, &["done", "T1"]);
+    ok(dir, &["user", "add", "alice"]);
+    {
+        let server = Served::start(dir, &[]);
+        assert_eq!(server.get("/status").0, 404);
+    }
+
+    fs::write(dir.join("think.toml"), "[server]\nstatus_page = true\n").unwrap();
+    let server = Served::start(dir, &[]);
+    let (status, page) = server.get("/status");
+    assert_eq!(status, 200, "no sign-in needed even with users");
+    assert!(page.contains("Progress: 50% (1 of 2 tasks done)"), "{}", page);
+    for secret in ["Initech", "layoff", "bob", "alice", "4.75", "T1", "T2"] {
+        assert!(!page.contains(secret), "{} leaked: {}", secret, page);
+    }
+}
+
+#[test]
+fn serve_history_charts_cover_the_asked_days_within_bounds() {
+    let ws = TempDir::new().unwrap();
+    let dir = ws.path();
+    ok(dir, &["task", "add", "T1", "Write the parser"]);
+    ok(dir, &["start", "T1", "bob"]);
+    ok(dir, &["costs", "add", "T1", "bob", "claude", "1000", "1000", "2.5"]);
+    ok(dir, &["done", "T1"]);
+    let server = Served::start(dir, &[]);
+    let points = |path: &str| {
+        let (status, body) = server.get(path);
+        assert_eq!(status, 200, "{}: {}", path, body);
+        serde_json::from_str::<serde_json::Value>(&body).unwrap().as_array().unwrap().clone()
+    };
+
+    assert_eq!(points("/api/history/closed").len(), 30);
+    assert_eq!(points("/api/history/spend?days=366").len(), 366);
+    let today = points("/api/history/closed?days=1");
+    assert_eq!(today.len(), 1);
+    assert_eq!(today[0]["value"], 1.0);
+    assert_eq!(today[0]["day"].as_str().map(str::len), Some(10));
+    let spend = points("/api/history/spend?days=7");
+    assert_eq!((spend.len(), spend[6]["value"].as_f64()), (7, Some(2.5)));
+    assert!(spend[..6].iter().all(|p| p["value"] == 0.0));
+    assert!(points("/api/history/workers?days=1")[0]["value"].as_f64().unwrap() >= 1.0);
+
+    for path in ["/api/history/closed?days=0", "/api/history/spend?days=367", "/api/history/workers?days=-5"] {
+        let (status, body) = server.get(path);
+        assert_eq!(status, 400, "{}", path);
+        assert!(body.contains("days must be between 1 and 366"), "{}", body);
+    }
+    assert_eq!(server.get("/api/history/closed?days=week").0, 400);
+}
+
+#[test]
+fn closed_tasks_are_archived_by_hand_or_after_a_while() {
+    let ws = TempDir::new().unwrap();
+    let dir = ws.path();
+    fs::write(dir.join("think.toml"), "[archive]\nafter = \"30d\"\n").unwrap();
+    for (id, title) in [("T1", "Write the parser"), ("T2", "Write the lexer"), ("T3", "Write the docs")] {
+        ok(dir, &["task", "add", id, title]);
+    }
+    assert!(!tt(dir, &["task", "archive", "T1"]).status.success(), "only closed tasks can be archived");
+    ok(dir, &["done", "T1"]);
+    ok(dir, &["done", "T2"]);
+    ok(dir, &["task", "archive", "T1"]);
+    let list = ok(dir, &["task", "list"]);
+    assert!(!list.contains("T1") && list.contains("T2") && list.contains("T3"));
+    assert!(ok(dir, &["task", "list", "--include-archived"]).contains("[T1] Write the parser (closed, archived)"));
+
+    // The monitor archives T2 once it has been closed for longer than [archive] after.
+    ok(dir, &["monitor", "scan"]);
+    assert!(ok(dir, &["task", "list"]).contains("T2"));
+    let db = Connection::open(dir.join("think.db")).unwrap();
+    db.execute("UPDATE audit_logs SET timestamp = timestamp - 31 * 86400 WHERE target = 'T2' AND action = 'task_closed'", []).unwrap();
+    ok(dir, &["monitor", "scan"]);
+    assert!(!ok(dir, &["task", "list"]).contains("T2"));
+
+    // Unarchived by hand, it stays out of the archive; reopening unarchives too.
+    ok(dir, &["task", "unarchive", "T2"]);
+    ok(dir, &["monitor", "scan"]);
+    assert!(ok(dir, &["task", "list"]).contains("T2"));
+    ok(dir, &["undone", "T1"]);
+    assert!(ok(dir, &["task", "list"]).contains("[T1] Write the parser (open)"));
+    let trail = ok(dir, &["trail"]);
+    assert!(trail.contains("task_archived") && trail.contains("task_unarchived"));
+}
+
+#[test]
+fn aging_buckets_open_tasks_and_ranks_quiet_ones_in_progress() {
+    let ws = TempDir::new().unwrap();
+    let dir = ws.path();
+    for (id, title) in [("T1", "Fresh"), ("T2", "Old"), ("T3", "Ancient"), ("T4", "Busy"), ("T5", "Quiet")] {
+        ok(dir, &["task", "add", id, title]);
+    }
+    ok(dir, &["start", "T4", "bob"]);
+    ok(dir, &["start", "T5", "amy"]);
+    let db = Connection::open(dir.join("think.db")).unwrap();
+    db.execute("UPDATE tasks SET created_at = created_at - 3 * 86400 WHERE id = 'T2'", []).unwrap();
+    db.execute("UPDATE tasks SET created_at = created_at - 40 * 86400 WHERE id = 'T3'", []).unwrap();
+    // T5 started five hours ago and its worker has no log to show for it.
+    db.execute("UPDATE audit_logs SET timestamp = timestamp - 5 * 3600 WHERE action = 'task_started' AND target = 'T5'", []).unwrap();
+    fs::remove_dir_all(dir.join(".logs").join("tasks").join("T5")).unwrap();
+
+    let out = ok(dir, &["stats", "aging"]);
+    let (open, busy) = out.split_once("IN PROGRESS").unwrap();
+    let bucket = |label: &str| open.split(&format!("{} (", label)).nth(1).unwrap().lines().next().unwrap().to_string();
+    assert_eq!(bucket("< 1d"), "1)");
+    assert_eq!(bucket("1-7d"), "1)");
+    assert_eq!(bucket("7-30d"), "0)");
+    assert!(open.contains("[T3] Ancient (40d"), "{}", out);
+    let quiet: Vec<&str> = busy.lines().filter(|l| l.contains("- [")).collect();
+    assert!(quiet[0].contains("[T5] Quiet @amy (5h 0m)"), "{}", out);
+    assert!(quiet[1].contains("[T4] Busy @bob"));
+}
+
+#[test]
+fn near_duplicate_titles_are_refused_without_force() {
+    let ws = TempDir::new().unwrap();
+    let dir = ws.path();
+    ok(dir, &["task", "add", "T1", "Write the parser"]);
+    ok(dir, &["task", "add", "T2", "Fix bug 12"]);
+    let out = tt(dir, &["task", "add", "T3", "write  parser!"]);
+    assert_eq!(out.status.code(), Some(15));
+    let err = String::from_utf8_lossy(&out.stderr);
+    assert!(err.contains("- [T1] Write the parser") && !err.contains("T2"), "{}", err);
+    let db = Connection::open(dir.join("think.db")).unwrap();
+    assert_eq!(db.query_row("SELECT COUNT(*) FROM tasks WHERE id = 'T3'", [], |r| r.get::<_, i64>(0)).unwrap(), 0);
+
+    ok(dir, &["task", "add", "T3", "write  parser!", "--force"]);
+    ok(dir, &["task", "add", "T4", "Fix bug 13"]);
+    ok(dir, &["task", "add", "T5", "Write the lexer"]);
+    // Only unfinished tasks count, and retitling a task doesn't clash with itself.
+    ok(dir, &["done", "T2"]);
+    ok(dir, &["task", "add", "T6", "Fix bug 12"]);
+    ok(dir, &["task", "add", "T5", "Write the lexer.", "--force-title-update"]);
+}
+
+#[test]
+fn groom_patch_is_reviewed_then_applied_once() {
+    let ws = TempDir::new().unwrap();
+    let dir = ws.path();
+    ok(dir, &["task", "add", "T1", "Write the parser", "--tag", "core"]);
+    ok(dir, &["task", "add", "T2", "Parser tests"]);
+    ok(dir, &["task", "add", "T3", "Implement parsing", "--tag", "parser", "--priority", "urgent"]);
+    assert!(!tt(dir, &["groom", "apply"]).status.success(), "nothing to apply before a run");
+    assert!(ok(dir, &["groom"]).contains("Grooming the backlog as groom-"));
+    let run = fs::read_dir(dir.join("workers")).unwrap().next().unwrap().unwrap().path();
+    let backlog = fs::read_to_string(run.join("BACKLOG.md")).unwrap();
+    assert!(backlog.contains("- [T3] Implement parsing (open, urgent) #parser") && !backlog.contains("groom-"), "{}", backlog);
+    assert!(String::from_utf8_lossy(&tt(dir, &["groom", "show"]).stderr).contains("has no GROOM.txt yet (task in_progress)"));
+
+    fs::write(run.join("GROOM.txt"), "merge T3 into T1\nsome prose\n").unwrap();
+    assert!(String::from_utf8_lossy(&tt(dir, &["groom", "apply"]).stderr).contains("line 2: not a merge, priority, tag or depends line"));
+    fs::write(run.join("GROOM.txt"), "# T3 repeats T1\nmerge T3 into T1\ntag T2 testing\ndepends T2 on T1\ndepends T2 on T9\n").unwrap();
+    assert!(!tt(dir, &["groom", "apply"]).status.success(), "T9 doesn't exist");
+    assert_eq!(task_row(dir, "T3").0, "open", "nothing applied");
+    fs::write(run.join("GROOM.txt"), "merge T2 into T3\nmerge T3 into T1\n").unwrap();
+    assert!(String::from_utf8_lossy(&tt(dir, &["groom", "apply"]).stderr).contains("T3 is merged away by this patch"));
+    fs::write(run.join("GROOM.txt"), "depends T1 on T2\ndepends T2 on T3\ndepends T3 on T1\n").unwrap();
+    assert!(String::from_utf8_lossy(&tt(dir, &["groom", "apply"]).stderr).contains("'depends T3 on T1' would make a dependency cycle"));
+    assert!(String::from_utf8_lossy(&tt(dir, &["groom", "apply", "--run", "T1"]).stderr).contains("T1 is not a grooming run"));
+    assert!(!tt(dir, &["groom", "apply", "--run", "groom-../../x"]).status.success());
+    assert!(!ok(dir, &["task", "show", "T1"]).contains("depends on"), "nothing applied");
+    ok(dir, &["task", "add", "T4", "Parser docs"]);
+    ok(dir, &["done", "T4"]);
+    fs::write(run.join("GROOM.txt"), "merge T2 into T4\n").unwrap();
+    assert!(String::from_utf8_lossy(&tt(dir, &["groom", "apply"]).stderr).contains("'merge T2 into T4': T4 is closed"));
+
+    fs::write(run.join("GROOM.txt"), "# T3 repeats T1\nmerge T3 into T1\ntag T2 testing\ndepends T2 on T1\n").unwrap();
+    assert!(ok(dir, &["groom", "show"]).contains("3 change(s)"));
+    ok(dir, &["groom", "apply"]);
+    assert_eq!(task_row(dir, "T3").0, "closed");
+    let list = ok(dir, &["task", "list"]);
+    assert!(list.contains("[T1] Write the parser (open) !urgent #core #parser"), "{}", list);
+    assert!(list.contains("[T2] Parser tests (open) #testing"));
+    assert!(ok(dir, &["task", "show", "T2"]).contains("depends on: T1"));
+    assert!(ok(dir, &["trail"]).contains("task_merged on T3 (into T1)"));
+    assert!(!tt(dir, &["groom", "apply"]).status.success(), "applied once only");
+    assert!(ok(dir, &["trail"]).contains("task_closed on T3 (merged into T1)"));
+}
+
+#[test]
+fn tasks_added_without_an_id_are_numbered_in_sequence() {
+    let ws = TempDir::new().unwrap();
+    let dir = ws.path();
+    assert!(ok(dir, &["task", "add", "Write the parser"]).contains("[TT-001]"));
+    assert!(ok(dir, &["task", "add", "Write the lexer"]).contains("[TT-002]"));
+    ok(dir, &["task", "add", "T9", "Write the docs"]);
+    ok(dir, &["task", "delete", "TT-002"]);
+    assert!(ok(dir, &["task", "add", "Write the linter"]).contains("[TT-003]"), "a deleted id isn't reused");
+    assert_eq!(task_row(dir, "TT-003").0, "open");
+
+    fs::write(dir.join("think.toml"), "[tasks]\nid_prefix = \"WEB-\"\nid_digits = 1\n").unwrap();
+    assert!(ok(dir, &["task", "add", "Fix the css"]).contains("[WEB-1]"));
+    ok(dir, &["task", "add", "web-9", "Not in the sequence: ids are case-sensitive"]);
+    assert!(ok(dir, &["task", "add", "Fix the js"]).contains("[WEB-2]"));
+
+    let out = tt(dir, &["task", "add", "T5"]);
+    assert!(!out.status.success());
+    assert!(String::from_utf8_lossy(&out.stderr).contains("'T5' looks like a task id, not a title"));
+    assert!(!ok(dir, &["task", "list"]).contains("] T5 ("));
+    fs::write(dir.join("think.toml"), "[tasks]\nid_prefix = \"a b\"\n").unwrap();
+    assert!(!tt(dir, &["task", "add", "Anything"]).status.success());
+}
+
+#[test]
+fn plan_from_file_previews_then_adds_tasks_with_dependencies() {
+    let ws = TempDir::new().unwrap();
+    let dir = ws.path();
+    ok(dir, &["task", "add", "T1", "Set up the database schema"]);
+    fs::write(dir.join("SPEC.md"), "# Login\nUsers sign in with a password.\n").unwrap();
+    // The planner sees the spec and the backlog on stdin, and answers with chatter around the JSON.
+    fs::write(dir.join("think.toml"), "[plan]\ncommand = \"cat > prompt.txt; cat answer.txt\"\n").unwrap();
+    fs::write(dir.join("answer.txt"), r#"Sure:
+```json
+[{"key": "db", "title": "Set up the database schema", "tags": ["backend"], "estimate": "2h"},
+ {"key": "api", "title": "Write the login endpoint", "tags": ["backend"], "depends_on": ["db"]},
+ {"key": "ui", "title": "Build the login page", "depends_on": ["api", "db"], "estimate": "3pts"}]
+```"#).unwrap();
+
+    let out = ok(dir, &["plan", "from-file", "SPEC.md"]);
+    assert!(out.contains("- ui: Build the login page ~3pts") && out.contains("after: api, db"), "{}", out);
+    assert!(out.contains("like [T1] Set up the database schema"));
+    assert!(out.contains("re-run with --yes"));
+    let prompt = fs::read_to_string(dir.join("prompt.txt")).unwrap();
+    assert!(prompt.contains("Users sign in with a password.") && prompt.contains("- [T1] Set up the database schema"));
+    assert!(!ok(dir, &["task", "list"]).contains("TT-001"), "nothing added without --yes");
+
+    let out = ok(dir, &["plan", "from-file", "SPEC.md", "--yes"]);
+    assert!(out.contains("Task [TT-003] registered (ui)"), "{}", out);
+    let show = ok(dir, &["task", "show", "TT-003"]);
+    assert!(show.contains("depends on: TT-001, TT-002") && show.contains("estimate: 3pts"), "{}", show);
+    assert!(ok(dir, &["task", "list", "--tag", "backend"]).contains("[TT-002] Write the login endpoint"));
+    assert!(ok(dir, &["trail"]).contains("plan_imported on SPEC.md (3 task(s))"));
+
+    fs::write(dir.join("answer.txt"), r#"[{"key": "a", "title": "A", "depends_on": ["zzz"]}]"#).unwrap();
+    assert!(String::from_utf8_lossy(&tt(dir, &["plan", "from-file", "SPEC.md", "--yes"]).stderr).contains("'a' depends on 'zzz'"));
+    fs::write(dir.join("answer.txt"), r#"[{"key": "a", "title": "A", "depends_on": ["b"]}, {"key": "b", "title": "B", "depends_on": ["a"]}, {"key": "c", "title": "C"}]"#).unwrap();
+    assert!(String::from_utf8_lossy(&tt(dir, &["plan", "from-file", "SPEC.md", "--yes"]).stderr).contains("dependencies go in a circle: a, b"));
+    fs::write(dir.join("think.toml"), "[plan]\ncommand = \"echo overloaded >&2; exit 3\"\n").unwrap();
+    assert!(String::from_utf8_lossy(&tt(dir, &["plan", "from-file", "SPEC.md"]).stderr).contains("overloaded"));
+
+    // A planner that talks before it has read a long prompt, and one that never answers.
+    fs::write(dir.join("SPEC.md"), "Users sign in.\n".repeat(20_000)).unwrap();
+    fs::write(dir.join("think.toml"), "[plan]\ncommand = \"head -c 300000 /dev/zero >&2; cat > /dev/null; cat answer.txt\"\n").unwrap();
+    fs::write(dir.join("answer.txt"), r#"[{"key": "c", "title": "Sign-in copy"}]"#).unwrap();
+    assert!(ok(dir, &["plan", "from-file", "SPEC.md", "--dry-run"]).contains("- c: Sign-in copy"));
+    fs::write(dir.join("think.toml"), "[plan]\ncommand = \"sleep 30\"\ntimeout_secs = 1\n").unwrap();
+    let started = std::time::Instant::now();
+    assert!(String::from_utf8_lossy(&tt(dir, &["plan", "from-file", "SPEC.md"]).stderr).contains("gave no answer within 1s"));
+    assert!(started.elapsed() < std::time::Duration::from_secs(10));
+}
+
+#[test]
+fn estimates_are_compared_with_actuals_per_engine_and_agent() {
+    let ws = TempDir::new().unwrap();
+    let dir = ws.path();
+    assert!(!tt(dir, &["task", "add", "T0", "work", "--estimate", "soon"]).status.success());
+    ok(dir, &["task", "add", "T1", "parser", "--estimate", "120m"]);
+    ok(dir, &["task", "add", "T2", "lexer", "--estimate", "4h"]);
+    ok(dir, &["task", "add", "T3", "docs", "--estimate", "3pts"]);
+    ok(dir, &["task", "add", "T4", "release"]);
+    assert!(ok(dir, &["task", "list"]).contains("- [T1] parser (open) ~2h"));
+    ok(dir, &["task", "edit", "T4", "--estimate", "1d"]);
+    assert!(ok(dir, &["task", "show", "T4"]).contains("estimate: 1d"));
+    ok(dir, &["task", "edit", "T4", "--no-estimate"]);
+    assert!(!ok(dir, &["task", "show", "T4"]).contains("estimate:"));
+    assert!(ok(dir, &["stats", "estimates"]).contains("No estimated task closed yet"));
+
+    let db = Connection::open(dir.join("think.db")).unwrap();
+    let finish = |id: &str, agent: &str, engine: &str, secs: i64| {
+        ok(dir, &["start", id, agent, "--engine", engine]);
+        db.execute("UPDATE attempts SET started_at = started_at - ?1 WHERE task_id = ?2", rusqlite::params![secs, id]).unwrap();
+        ok(dir, &["done", id])
+    };
+    // bob took 3h on a 2h estimate and 2h on a 4h one; amy took 3h for 3 points.
+    assert!(finish("T1", "bob", "claude", 10800).contains("Took 3h 0m against an estimate of 2h (1.5x)."));
+    finish("T2", "bob", "claude", 7200);
+    ok(dir, &["start", "T3", "amy"]);
+    ok(dir, &["costs", "add", "T3", "amy", "flash", "1", "1", "0.90"]);
+    db.execute("UPDATE attempts SET started_at = started_at - 10800 WHERE task_id = 'T3'", []).unwrap();
+    assert!(ok(dir, &["done", "T3"]).contains("Took 3h 0m and $0.90 for 3pts."));
+
+    let out = ok(dir, &["stats", "estimates"]);
+    let row = |agent: &str| out.lines().find(|l| l.split_whitespace().nth(1) == Some(agent)).unwrap().split_whitespace().collect::<Vec<_>>();
+    assert_eq!(row("bob"), ["claude", "bob", "2", "1.0x", "2/2", "0", "-", "-"], "{}", out);
+    assert_eq!(row("amy"), ["gemini", "amy", "0", "-", "-", "3", "1h", "0m", "$0.30"], "{}", out);
+    assert!(out.contains("2 task(s)  1.0x"));
+    // An estimate tt can't read is shown as stored, and doesn't stop the task from closing.
+    ok(dir, &["task", "add", "T9", "Release notes"]);
+    db.execute("UPDATE tasks SET estimate = 'a while' WHERE id = 'T9'", []).unwrap();
+    assert!(ok(dir, &["task", "show", "T9"]).contains("estimate: a while"));
+    ok(dir, &["done", "T9"]);
+}
+
+#[test]
+fn stats_flow_reports_throughput_wip_and_medians() {
+    let ws = TempDir::new().unwrap();
+    let dir = ws.path();
+    for id in ["T1", "T2", "T3"] { ok(dir, &["task", "add", id, "work", "--force"]); }
+    ok(dir, &["start", "T1", "bob"]);
+    ok(dir, &["start", "T2", "amy"]);
+    ok(dir, &["done", "T1"]);
+    // T1 waited an hour and took two hours; T2 waited three hours and is still running.
+    let db = Connection::open(dir.join("think.db")).unwrap();
+    db.execute("UPDATE tasks SET created_at = created_at - 10800 WHERE id = 'T1'", []).unwrap();
+    db.execute("UPDATE audit_logs SET timestamp = timestamp - 7200 WHERE action = 'task_started' AND target = 'T1'", []).unwrap();
+    db.execute("UPDATE tasks SET created_at = created_at - 10800 WHERE id = 'T2'", []).unwrap();
+
+    let out = ok(dir, &["stats", "flow", "--since", "1d"]);
+    let today = out.lines().rfind(|l| l.starts_with("20")).unwrap();
+    let cols: Vec<&str> = today.split_whitespace().collect();
+    assert_eq!(&cols[2..], &["1", "1"], "{}", out);
+    assert!(out.contains("Total: 3 created, 1 closed"));
+    assert!(out.contains("Median cycle (in_progress → closed): 2h 0m"));
+    assert!(out.contains("open → in_progress):    2h 0m"));
+}
+
+#[test]
+fn plan_sizes_workers_per_engine_within_budget() {
+    let ws = TempDir::new().unwrap();
+    let dir = ws.path();
+    for id in ["T1", "T2", "T3", "T4", "T5"] { ok(dir, &["task", "add", id, "work", "--force"]); }
+    assert!(ok(dir, &["plan", "--by", "2d"]).contains("Not enough history"));
+
+    ok(dir, &["start", "T1", "bob", "--engine", "claude"]);
+    ok(dir, &["costs", "add", "T1", "bob", "sonnet", "1", "1", "2.00"]);
+    ok(dir, &["done", "T1"]);
+    ok(dir, &["start", "T2", "amy"]);
+    ok(dir, &["costs", "add", "T2", "amy", "flash", "1", "1", "0.50"]);
+    ok(dir, &["done", "T2"]);
+    // claude took a day per task, gemini two days.
+    let db = Connection::open(dir.join("think.db")).unwrap();
+    db.execute("UPDATE attempts SET started_at = ended_at - 86400 WHERE engine = 'claude'", []).unwrap();
+    db.execute("UPDATE attempts SET started_at = ended_at - 172800 WHERE engine = 'gemini'", []).unwrap();
+
+    // 3 tasks left over ~2 days: claude needs 2 workers ($6), gemini 3 ($1.50).
+    let out = ok(dir, &["plan", "--by", "2d"]);
+    assert!(out.contains("3 task(s) left (3 open, 0 in progress)"), "{}", out);
+    let claude = out.lines().find(|l| l.starts_with("claude")).unwrap();
+    assert!(claude.contains("1d 0h") && claude.contains("$2.0000") && claude.contains(" 2 ") && claude.contains("$6.00"));
+    assert!(out.contains("Recommended: gemini (flash) with max_workers = 3"));
+    assert!(ok(dir, &["plan", "--by", "2d", "--budget", "1"]).contains("No engine clears the backlog"));
+    assert_eq!(tt(dir, &["plan", "--by", "2020-01-01"]).status.code(), Some(2));
+}
+
+#[test]
+fn board_warnings_flag_what_needs_intervention() {
+    let ws = TempDir::new().unwrap();
+    let dir = ws.path();
+    fs::write(dir.join("think.toml"), "[budget]\ntask_usd_cap = 1.0\n[health]\nstall_minutes = 10\n").unwrap();
+    ok(dir, &["task", "add", "T1", "Quiet worker"]);
+    ok(dir, &["task", "add", "T2", "Lost worker"]);
+    ok(dir, &["task", "add", "T3", "Pricey"]);
+    ok(dir, &["start", "T1", "bob"]);
+    ok(dir, &["start", "T2", "amy"]);
+    ok(dir, &["start", "T3", "cy"]);
+    ok(dir, &["costs", "add", "T3", "cy", "opus", "1", "1", "3.0"]);
+    ok(dir, &["worker", "nuke", "amy"]);
+    ok(dir, &["mail", "send", "admin", "-s", "URGENT: prod", "-b", "look"]);
+
+    let board = ok(dir, &["board", "list"]);
+    assert!(board.contains("[WARNINGS]"));
+    assert!(board.contains("monitor has never run"));
+    assert!(board.contains("'amy' has no live session"));
+    assert!(board.contains("spent $3.00 of its $1.00 cap"));
+    assert!(board.contains("last attempt by 'amy' was aborted"));
+    assert!(board.contains("unread: URGENT: prod"));
+    assert!(!board.contains("'bob' has been silent"));
+
+    let log = fs::File::options().write(true).open(dir.join(".logs/tasks/T1/bob.log")).unwrap();
+    log.set_modified(std::time::SystemTime::now() - std::time::Duration::from_secs(3600)).unwrap();
+    ok(dir, &["monitor", "scan"]);
+    let board = ok(dir, &["board", "list"]);
+    assert!(board.contains("'bob' has been silent for 1h 0m"));
+    assert!(!board.contains("monitor has never run"));
+}
+
+#[test]
+fn board_shows_disk_usage_and_warns_past_the_limits() {
+    let ws = TempDir::new().unwrap();
+    let dir = ws.path();
+    ok(dir, &["task", "add", "T1", "one"]);
+    ok(dir, &["start", "T1", "bob"]);
+    fs::write(dir.join("workers/bob/big.bin"), vec![0u8; 3 * 1024 * 1024]).unwrap();
+    let board = ok(dir, &["board", "list"]);
+    assert!(board.contains("[DISK] Logs: 0.0 MB | Workers: 3.0 MB | Artifacts: 0.0 MB | Free:"), "{}", board);
+    assert!(!board.contains("logs, workers and artifacts take"));
+
+    fs::write(dir.join("think.toml"), "[health]\nmax_usage_mb = 2\nmin_free_mb = 1000000000\n").unwrap();
+    let board = ok(dir, &["board", "list"]);
+    assert!(board.contains("logs, workers and artifacts take 3.0 MB (limit 2 MB"), "{}", board);
+    // Sizes are walked by the monitor, not on every look at the board.
+    fs::write(dir.join("workers/bob/bigger.bin"), vec![0u8; 2 * 1024 * 1024]).unwrap();
+    assert!(ok(dir, &["board", "list"]).contains("Workers: 3.0 MB"));
+    ok(dir, &["monitor", "scan"]);
+    assert!(ok(dir, &["board", "list"]).contains("Workers: 5.0 MB"));
+    assert!(board.contains("free on the workspace's disk"), "{}", board);
+}
+
+#[test]
+fn serve_refuses_unreadable_tls_certificate() {
+    let ws = TempDir::new().unwrap();
+    let dir = ws.path();
+    let out = tt(dir, &["serve", "-p", "0", "--http-port", "0", "--tls-cert", "missing.pem", "--tls-key", "missing.pem"]);
+    assert!(!out.status.success());
+    assert!(String::from_utf8_lossy(&out.stderr).contains("cannot load TLS certificate"));
+}
+
+#[cfg(feature = "grpc")]
+mod pb {
+    tonic::include_proto!("thinktodo.v1");
+}
+
+#[cfg(feature = "grpc")]
+#[test]
+fn grpc_drives_the_task_lifecycle() {
+    use pb::think_todo_client::ThinkTodoClient;
+    let ws = TempDir::new().unwrap();
+    let dir = ws.path();
+    let free_port = || std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+    let (http, grpc) = (free_port(), free_port());
+    let mut server = Command::new(env!("CARGO_BIN_EXE_think-todo"))
+        .args(["serve", "-p", &http.to_string(), "--grpc-port", &grpc.to_string()])
+        .current_dir(dir)
+        .env("TT_SESSION_BACKEND", "mock")
+        .stdout(std::process::Stdio::null())
+        .spawn()
+        .unwrap();
+
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    let result = rt.block_on(async {
+        let mut client = None;
+        for _ in 0..50 {
+            if let Ok(c) = ThinkTodoClient::connect(format!("http://127.0.0.1:{}", grpc)).await { client = Some(c); break; }
+            tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        }
+        let mut client = client.expect("gRPC server did not come up");
+        let added = client.add_task(pb::AddTaskRequest { id: "T1".into(), title: "Write the parser".into(), tags: vec!["core".into()], estimate: Some("2h".into()), ..Default::default() })
+            .await.unwrap().into_inner();
+        assert_eq!(added.tags, vec!["core".to_string()]);
+        assert!(ok(dir, &["task", "show", "T1"]).contains("estimate: 2h"));
+        let vague = client.add_task(pb::AddTaskRequest { id: "T3".into(), title: "Polish".into(), estimate: Some("soonish".into()), ..Default::default() }).await.unwrap_err();
+        assert_eq!(vague.code(), tonic::Code::InvalidArgument);
+        let started = client.sling(pb::SlingRequest { task_id: "T1".into(), agent_name: "bob".into(), ..Default::default() }).await.unwrap().into_inner();
+        assert_eq!((started.status.as_str(), started.assignee.as_deref()), ("in_progress", Some("bob")));
+        let missing = client.done(pb::TaskRef { id: "T9".into() }).await.unwrap_err();
+        assert_eq!(missing.code(), tonic::Code::NotFound);
+        let closed = client.done(pb::TaskRef { id: "T1".into() }).await.unwrap().into_inner();
+        assert_eq!(closed.status, "closed");
+        let forged = client.send_mail(pb::SendMailRequest { sender: "admin".into(), receiver: "bob".into(), subject: "stop".into(), body: "x".into() }).await.unwrap_err();
+        assert_eq!(forged.code(), tonic::Code::PermissionDenied);
+        client.send_mail(pb::SendMailRequest { receiver: "bob".into(), subject: "hi".into(), body: "x".into(), ..Default::default() }).await.unwrap();
+        // Once there are users, changes need one's token.
+        let token = ok(dir, &["user", "add", "alice"]).lines().last().unwrap().to_string();
+        let denied = client.delete_task(pb::TaskRef { id: "T1".into() }).await.unwrap_err();
+        assert_eq!(denied.code(), tonic::Code::Unauthenticated);
+        let mut signed = tonic::Request::new(pb::AddTaskRequest { id: "T2".into(), title: "Write the lexer".into(), ..Default::default() });
+        signed.metadata_mut().insert("authorization", format!("Bearer {}", token).parse().unwrap());
+        client.add_task(signed).await.unwrap();
+        let mut mail = tonic::Request::new(pb::SendMailRequest { sender: "alice".into(), receiver: "bob".into(), subject: "review".into(), body: "x".into() });
+        mail.metadata_mut().insert("authorization", format!("Bearer {}", token).parse().unwrap());
+        client.send_mail(mail).await.unwrap();
+        let inbox = client.inbox(pb::InboxRequest { receiver: "bob".into(), ..Default::default() }).await.unwrap().into_inner().messages;
+        let senders: Vec<&str> = inbox.iter().map(|m| m.sender.as_str()).collect();
+        assert_eq!(senders, ["alice", "grpc"]);
+        let mut trail = client.watch_trail(pb::WatchTrailRequest { after_id: 0 }).await.unwrap().into_inner();
+        let first = trail.message().await.unwrap().expect("the trail has entries");
+        assert_eq!(first.target, "T1");
+        client.list_tasks(pb::ListTasksRequest { status: "closed".into() }).await.unwrap().into_inner().tasks.len()
+    });
+    server.kill().unwrap();
+    server.wait().unwrap();
+    assert_eq!(result, 1);
+}
+
+#[test]
+fn undone_restores_the_work_of_a_closed_task() {
+    let ws = TempDir::new().unwrap();
+    let dir = ws.path();
+    ok(dir, &["task", "add", "T1", "Write notes"]);
+    ok(dir, &["start", "T1", "bob"]);
+    fs::write(dir.join("workers/bob/notes.txt"), "draft\n").unwrap();
+    assert!(!tt(dir, &["undone", "T1"]).status.success());
+    ok(dir, &["done", "T1"]);
+    assert!(!dir.join("workers/bob").exists());
+
+    assert!(ok(dir, &["undone", "T1"]).contains("tt start T1 bob"));
+    assert_eq!(task_row(dir, "T1"), ("open".to_string(), Some("bob".to_string())));
+    assert_eq!(fs::read_to_string(dir.join("workers/bob/notes.txt")).unwrap(), "draft\n");
+    assert!(ok(dir, &["trail"]).contains("task_reopened on T1"));
+
+    // Rig tasks keep uncommitted work on their branch and get their worktree back.
+    let rig = git_rig();
+    ok(dir, &["rig", "add", "web", rig.path().to_str().unwrap()]);
+    ok(dir, &["task", "add", "T2", "change app", "--rig", "web"]);
+    ok(dir, &["start", "T2", "amy"]);
+    fs::write(dir.join("workers/amy/app.txt"), "unsaved\n").unwrap();
+    ok(dir, &["done", "T2"]);
+    ok(dir, &["undone", "T2"]);
+    let wt = dir.join("workers/amy");
+    assert_eq!(git(&wt, &["branch", "--show-current"]).trim(), "tt/T2");
+    assert_eq!(fs::read_to_string(wt.join("app.txt")).unwrap(), "unsaved\n");
+
+    fs::write(dir.join("think.toml"), "[undo]\nwindow = \"0s\"\n").unwrap();
+    ok(dir, &["done", "T2"]);
+    assert!(!tt(dir, &["undone", "T2"]).status.success());
+}
+
+#[test]
+fn chaos_mode_kills_and_freezes_workers_only_when_asked() {
+    let ws = TempDir::new().unwrap();
+    let dir = ws.path();
+    ok(dir, &["task", "add", "T1", "Build the importer"]);
+    ok(dir, &["start", "T1", "bob"]);
+    fs::write(dir.join("think.toml"), "[chaos]\nstall_chance = 1.0\nstall_secs = 600\n").unwrap();
+    ok(dir, &["monitor", "scan"]);
+    assert!(!dir.join(".sessions/mock/worker-bob.paused").exists());
+
+    ok(dir, &["monitor", "scan", "--chaos"]);
+    assert!(dir.join(".sessions/mock/worker-bob.paused").exists());
+    let replay = ok(dir, &["replay", "--target", "T1"]);
+    assert!(replay.contains("monitor chaos_stall worker-bob (frozen)") && replay.contains("stall_chance 1.0 for the worker on [T1]; frozen for 10m"), "{}", replay);
+    // A pass without --chaos never leaves a worker frozen.
+    ok(dir, &["monitor", "scan"]);
+    assert!(!dir.join(".sessions/mock/worker-bob.paused").exists());
+
+    fs::write(dir.join("think.toml"), "[chaos]\nkill_chance = 1.0\n").unwrap();
+    ok(dir, &["monitor", "scan", "--chaos"]);
+    assert!(!session_exists(dir, "worker-bob"));
+    assert!(ok(dir, &["replay"]).contains("monitor chaos_kill worker-bob (killed)"));
+    ok(dir, &["recover"]);
+    assert_eq!(task_row(dir, "T1").0, "open");
+
+    fs::write(dir.join("think.toml"), "[chaos]\nkill_chance = 2\n").unwrap();
+    let out = tt(dir, &["monitor", "scan", "--chaos"]);
+    assert!(String::from_utf8_lossy(&out.stderr).contains("kill_chance must be between 0 and 1"));
+}
+
+#[test]
+fn bench_runs_score_each_engine_with_the_witness() {
+    let ws = TempDir::new().unwrap();
+    let dir = ws.path();
+    fs::create_dir_all(dir.join(".sessions/mock")).unwrap();
+    fs::write(dir.join(".sessions/mock/opencode.missing"), "").unwrap();
+    let out = ok(dir, &["bench", "run", "--suite", "basic", "--engines", "claude,gemini,opencode"]);
+    assert!(out.contains("opencode   hello    not started"), "{}", out);
+    let run = out.split("Bench run ").nth(1).unwrap().split_whitespace().next().unwrap().to_string();
+    let task = |engine: &str, case: &str| format!("bench-{}-{}-{}", run, engine, case);
+    assert_eq!(task_row(dir, &task("claude", "sort")), ("in_progress".to_string(), Some(task("claude", "sort"))));
+    assert_eq!(fs::read_to_string(dir.join("workers").join(task("claude", "sort")).join("numbers.txt")).unwrap(), "42\n7\n-3\n19\n0\n108\n7\n");
+
+    // claude gets everything right; gemini sorts the wrong way round and never finishes the json case.
+    for engine in ["claude", "gemini"] {
+        let w = |case: &str| dir.join("workers").join(task(engine, case));
+        fs::write(w("hello").join("hello.txt"), "hello, bench\n").unwrap();
+        let sorted = if engine == "claude" { "-3\n0\n7\n7\n19\n42\n108\n" } else { "108\n42\n19\n7\n7\n0\n-3\n" };
+        fs::write(w("sort").join("sorted.txt"), sorted).unwrap();
+        let cases: &[&str] = if engine == "claude" { &["hello", "sort", "json"] } else { &["hello", "sort"] };
+        if engine == "claude" { fs::write(w("json").join("config.json"), "{\"name\": \"bench\", \"retries\": 3}\n").unwrap(); }
+        for case in cases {
+            fs::write(dir.join(".logs/tasks").join(task(engine, case)).join(format!("{}.log", task(engine, case))), "[TASK_DONE]\n").unwrap();
+        }
+    }
+    ok(dir, &["monitor", "scan"]);
+
+    let results = ok(dir, &["bench", "results"]);
+    assert!(results.contains(&format!("BENCH RUN {} (basic)", run)), "{}", results);
+    assert!(results.contains("claude     sort     pass"), "{}", results);
+    assert!(results.contains("gemini     sort     fail") && results.contains("witness failed: sort -n"), "{}", results);
+    assert!(results.contains("gemini     json     running"), "{}", results);
+    assert!(results.contains("opencode   hello    error"), "{}", results);
+    assert!(results.contains("claude          3      3     100%"), "{}", results);
+    assert!(results.contains("gemini          2      1      50%"), "{}", results);
+    assert_eq!(task_row(dir, &task("gemini", "json")).0, "in_progress");
+    assert!(!session_exists(dir, &format!("worker-{}", task("claude", "hello"))));
+    assert!(!tt(dir, &["bench", "run", "--suite", "huge"]).status.success());
+}
+
+#[test]
+fn exports_pick_up_where_the_last_one_stopped() {
+    let ws = TempDir::new().unwrap();
+    let dir = ws.path();
+    ok(dir, &["task", "add", "T1", "Build the importer"]);
+    ok(dir, &["start", "T1", "bob"]);
+    let out = ok(dir, &["export", "--since", "last"]);
+    assert!(out.contains("tasks") && out.contains("(full)"), "{}", out);
+    let files = |table: &str| -> Vec<std::path::PathBuf> {
+        let mut f: Vec<_> = fs::read_dir(dir.join("exports")).unwrap().flatten().map(|e| e.path())
+            .filter(|p| p.file_name().unwrap().to_string_lossy().starts_with(&format!("{}-", table))).collect();
+        f.sort();
+        f
+    };
+    let audit = fs::read_to_string(&files("audit_logs")[0]).unwrap();
+    let first: serde_json::Value = serde_json::from_str(audit.lines().next().unwrap()).unwrap();
+    assert_eq!(first["action"], "task_started");
+    assert!(fs::read_to_string(&files("tasks")[0]).unwrap().contains("\"id\":\"T1\""));
+
+    let export = |quiet: bool| {
+        let mut args = vec!["export", "--tables", "audit_logs", "--since", "last"];
+        if quiet { args.push("--quiet"); }
+        Command::new(env!("CARGO_BIN_EXE_think-todo")).args(&args).current_dir(dir)
+            .env("TT_SESSION_BACKEND", "mock").env("TT_PROGRESS", "always").output().unwrap()
+    };
+    let shown = export(false);
+    assert!(String::from_utf8_lossy(&shown.stderr).contains("0/1"), "{}", String::from_utf8_lossy(&shown.stderr));
+    let quiet = export(true);
+    assert!(String::from_utf8_lossy(&quiet.stdout).contains("nothing new") && quiet.stderr.is_empty());
+    ok(dir, &["done", "T1"]);
+    let out = ok(dir, &["export", "--tables", "audit_logs", "--since", "last"]);
+    assert!(!out.contains("(full)"), "{}", out);
+    let latest = fs::read_to_string(files("audit_logs").last().unwrap()).unwrap();
+    assert!(!latest.contains("task_started") && latest.contains("\"target\":\"T1\""), "{}", latest);
+
+    assert!(!tt(dir, &["export", "--tables", "messages"]).status.success());
+}
+
+#[test]
+fn telemetry_ships_new_rows_to_the_sink_and_prunes_what_it_shipped() {
+    let ws = TempDir::new().unwrap();
+    let dir = ws.path();
+    let (url, seen) = http_server("");
+    fs::write(dir.join("think.toml"), format!("[telemetry]\nsink = \"clickhouse\"\nurl = \"{}\"\ninterval_secs = 0\nprune_after = \"1h\"\n", url)).unwrap();
+    ok(dir, &["task", "add", "T1", "Build the importer"]);
+    ok(dir, &["start", "T1", "bob"]);
+    let db = Connection::open(dir.join("think.db")).unwrap();
+    db.execute("UPDATE audit_logs SET timestamp = timestamp - 7200", []).unwrap();
+    ok(dir, &["monitor", "scan"]);
+
+    let requests = seen.lock().unwrap().clone();
+    assert_eq!(requests.len(), 1, "{:?}", requests);
+    assert!(requests[0].0.starts_with("POST /?query=INSERT") && requests[0].0.contains("tt.events"), "{:?}", requests);
+    assert!(requests[0].1.lines().any(|l| l.contains("\"action\":\"task_started\"")), "{:?}", requests);
+    let left: i64 = db.query_row("SELECT COUNT(*) FROM audit_logs WHERE action = 'task_started'", [], |r| r.get(0)).unwrap();
+    assert_eq!(left, 0);
+
+    // Only what is new goes out next time; finished attempts are shipped once they end.
+    ok(dir, &["done", "T1"]);
+    ok(dir, &["monitor", "scan"]);
+    let requests = seen.lock().unwrap().clone();
+    assert!(!requests[1..].iter().any(|(_, body)| body.contains("task_started")), "{:?}", requests);
+    assert!(requests.iter().any(|(line, body)| line.contains("attempts") && body.contains("\"outcome\":\"done\"")), "{:?}", requests);
+
+    fs::write(dir.join("think.toml"), "[telemetry]\nsink = \"clickhouse\"\nurl = \"http://127.0.0.1:9\"\ninterval_secs = 0\n").unwrap();
+    ok(dir, &["task", "add", "T2", "Write its tests"]);
+    ok(dir, &["start", "T2", "amy"]);
+    ok(dir, &["monitor", "scan"]);
+    assert!(ok(dir, &["board", "list"]).contains("telemetry sink unreachable"));
+}
+
+#[test]
+fn email_bridge_sends_mail_to_people_and_files_their_replies() {
+    use std::io::{BufRead, BufReader};
+    use std::net::TcpListener;
+    let ws = TempDir::new().unwrap();
+    let dir = ws.path();
+    let sent: std::sync::Arc<std::sync::Mutex<Vec<String>>> = Default::default();
+
+    // SMTP: accepts everything and keeps each message's DATA.
+    let smtp = TcpListener::bind("127.0.0.1:0").unwrap();
+    let smtp_port = smtp.local_addr().unwrap().port();
+    let log = sent.clone();
+    std::thread::spawn(move || {
+        for stream in smtp.incoming().flatten() {
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut stream = stream;
+            write!(stream, "220 fake\r\n").unwrap();
+            let mut line = String::new();
+            while reader.read_line(&mut line).unwrap_or(0) > 0 {
+                let reply = match line.trim() {
+                    "DATA" => {
+                        write!(stream, "354 go\r\n").unwrap();
+                        let mut data = String::new();
+                        loop {
+                            let mut l = String::new();
+                            reader.read_line(&mut l).unwrap();
+                            if l == ".\r\n" { break; }
+                            data.push_str(&l);
+                        }
+                        log.lock().unwrap().push(data);
+                        "250 queued"
+                    }
+                    "QUIT" => "221 bye",
+                    _ => "250 ok",
+                };
+                write!(stream, "{}\r\n", reply).unwrap();
+                line.clear();
+            }
+        }
+    });
+
+    // IMAP: once something was mailed, offers a reply from the human, the same reply from a stranger,
+    // and one from the human's address without the signed tag.
+    let imap = TcpListener::bind("127.0.0.1:0").unwrap();
+    let imap_port = imap.local_addr().unwrap().port();
+    let log = sent.clone();
+    std::thread::spawn(move || {
+        let mut fetched = false;
+        for stream in imap.incoming().flatten() {
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut stream = stream;
+            write!(stream, "* OK fake\r\n").unwrap();
+            let mut line = String::new();
+            while reader.read_line(&mut line).unwrap_or(0) > 0 {
+                let (tag, command) = line.trim().split_once(' ').unwrap();
+                if command.starts_with("UID SEARCH") {
+                    let ready = !fetched && !log.lock().unwrap().is_empty();
+                    write!(stream, "* SEARCH{}\r\n", if ready { " 7 8 9" } else { "" }).unwrap();
+                } else if let Some(uid) = command.strip_prefix("UID FETCH ").and_then(|c| c.split(' ').next()) {
+                    let first = log.lock().unwrap()[0].clone();
+                    let tag = first.split("Subject: ").nth(1).and_then(|s| s.split(']').next()).unwrap().to_string() + "]";
+                    let raw = match uid {
+                        "7" => format!("From: Me <me@example.com>\r\nSubject: Re: {} =?UTF-8?B?5ZCI5bm2?=\r\nContent-Type: text/plain; charset=utf-8\r\nContent-Transfer-Encoding: quoted-printable\r\n\r\nShip it =\r\ntoday.\r\n\r\nOn Mon, Me wrote:\r\n> Ready?\r\n", tag),
+                        "8" => format!("From: spam@example.net\r\nSubject: {} hi\r\n\r\nbuy now\r\n", tag),
+                        _ => "From: me@example.com\r\nSubject: [tt #1] merge it\r\n\r\nforged\r\n".to_string(),
+                    };
+                    write!(stream, "* 1 FETCH (UID {} BODY[] {{{}}}\r\n{})\r\n", uid, raw.len(), raw).unwrap();
+                    fetched = uid == "9";
+                }
+                write!(stream, "{} OK done\r\n", tag).unwrap();
+                line.clear();
+            }
+        }
+    });
+
+    fs::write(dir.join("think.toml"), format!(
+        "[email]\nhuman = \"me@example.com\"\nfrom = \"tt@example.com\"\nsmtp_host = \"127.0.0.1\"\nsmtp_port = {}\nimap_host = \"127.0.0.1\"\nimap_port = {}\ntls = false\npoll_secs = 0\n",
+        smtp_port, imap_port)).unwrap();
+    ok(dir, &["monitor", "scan"]);
+    ok(dir, &["mail", "send", "human", "-s", "Ready?", "-b", "The release is cut.\n.done"]);
+    ok(dir, &["mail", "send", "ops@example.com", "-s", "Nightly\r\nBcc: evil@example.net", "-b", "Build failed"]);
+    ok(dir, &["monitor", "scan"]);
+
+    let mail = sent.lock().unwrap().clone();
+    assert_eq!(mail.len(), 2, "{:?}", mail);
+    assert!(mail[0].contains("To: me@example.com\r\n") && mail[0].contains("Subject: [tt #1.") && mail[0].contains("] Ready?\r\n"), "{}", mail[0]);
+    assert!(mail[0].contains("\r\n..done\r\n"), "{}", mail[0]);
+    assert!(mail[1].contains("To: ops@example.com\r\n"), "{}", mail[1]);
+    assert!(!mail[1].contains("\r\nBcc:") && mail[1].contains("Subject: =?UTF-8?B?"), "line breaks can't add headers: {}", mail[1]);
+
+    let db = Connection::open(dir.join("think.db")).unwrap();
+    let filed: i64 = db.query_row("SELECT COUNT(*) FROM messages WHERE id > 2", [], |r| r.get(0)).unwrap();
+    assert_eq!(filed, 1, "only the signed reply from the address it went to is heard");
+    let (sender, receiver, subject, body): (String, String, String, String) = db.query_row(
+        "SELECT sender, receiver, subject, body FROM messages WHERE id > 2", [], |r| Ok((r.get(0)?, r.get(1)?, r.get(2)?, r.get(3)?))).unwrap();
+    let asked: String = db.query_row("SELECT sender FROM messages WHERE id = 1", [], |r| r.get(0)).unwrap();
+    assert_eq!((sender.as_str(), receiver.as_str(), subject.as_str(), body.as_str()), ("human", asked.as_str(), "Re: 合并", "Ship it today."));
+    // Replies to other mail go out once; filed replies aren't mailed back.
+    ok(dir, &["monitor", "scan"]);
+    assert_eq!(sent.lock().unwrap().len(), 2);
+}
+
+#[test]
+fn telegram_bot_answers_allowed_chats_and_acts_on_their_commands() {
+    let ws = TempDir::new().unwrap();
+    let dir = ws.path();
+    let updates = r#"{"ok":true,"result":[
+        {"update_id":10,"message":{"chat":{"id":42},"from":{"username":"lin"},"text":"/beads"}},
+        {"update_id":11,"message":{"chat":{"id":42},"from":{"username":"lin"},"text":"/freeze deploy window"}},
+        {"update_id":12,"message":{"chat":{"id":42},"from":{"username":"lin"},"text":"/status@tt_bot"}},
+        {"update_id":13,"message":{"chat":{"id":42},"from":{"username":"lin"},"text":"/approve 1 go ahead"}},
+        {"update_id":14,"message":{"chat":{"id":42},"from":{"username":"lin"},"text":"/approve"}},
+        {"update_id":15,"message":{"chat":{"id":42},"from":{"username":"lin"},"text":"/done T1"}},
+        {"update_id":16,"message":{"chat":{"id":99},"text":"/done T2"}}
+    ]}"#;
+    let (url, seen) = http_server(updates);
+    fs::write(dir.join("think.toml"), format!("[telegram]\nchats = [42]\napi = \"{}\"\n", url)).unwrap();
+    ok(dir, &["task", "add", "T1", "Build the importer"]);
+    ok(dir, &["task", "add", "T2", "Write its tests"]);
+    ok(dir, &["start", "T1", "bob"]);
+    let db = Connection::open(dir.join("think.db")).unwrap();
+    db.execute("INSERT INTO messages (sender, receiver, subject, body, status, timestamp) VALUES ('monitor', 'admin', 'URGENT: raise the cap?', '', 'unread', 0)", []).unwrap();
+    ok(dir, &["bot", "telegram", "--token", "123:abc", "--once"]);
+
+    let requests = seen.lock().unwrap().clone();
+    assert!(requests[0].0.starts_with("GET /bot123:abc/getUpdates?offset=0"), "{:?}", requests);
+    let replies: Vec<String> = requests.iter().filter(|(line, _)| line.contains("/sendMessage"))
+        .map(|(_, body)| serde_json::from_str::<serde_json::Value>(body).unwrap()["text"].as_str().unwrap().to_string()).collect();
+    assert_eq!(replies.len(), 7, "{:?}", replies);
+    assert!(replies[0].contains("in_progress 1") && replies[0].contains("T1 Build the importer (bob)"), "{}", replies[0]);
+    assert!(replies[1].contains("Frozen: deploy window"), "{}", replies[1]);
+    assert!(replies[2].contains("Frozen: deploy window") && replies[2].contains("URGENT: raise the cap?"), "{}", replies[2]);
+    assert!(replies[3].contains("Approved #1"), "{}", replies[3]);
+    assert!(replies[4].contains("Unfrozen"), "{}", replies[4]);
+    assert!(replies[5].contains("T1 closed"), "{}", replies[5]);
+    assert!(replies[6].contains("(99) may not control tt"), "{}", replies[6]);
+
+    let answer: (String, String, String) = db.query_row("SELECT sender, receiver, body FROM messages WHERE id = 2", [], |r| Ok((r.get(0)?, r.get(1)?, r.get(2)?))).unwrap();
+    assert_eq!(answer, ("telegram:lin".to_string(), "monitor".to_string(), "Approved. go ahead".to_string()));
+    assert_eq!(task_row(dir, "T1").0, "closed");
+    assert_eq!(task_row(dir, "T2").0, "open");
+    // The next poll starts after what was handled.
+    ok(dir, &["bot", "telegram", "--token", "123:abc", "--once"]);
+    assert!(seen.lock().unwrap().iter().any(|(line, _)| line.contains("getUpdates?offset=17")));
+    // A failed poll names the URL, but not the token in it.
+    fs::write(dir.join("think.toml"), "[telegram]\nchats = [42]\napi = \"http://127.0.0.1:9\"\n").unwrap();
+    let out = tt(dir, &["bot", "telegram", "--token", "123:abc", "--once"]);
+    let err = String::from_utf8_lossy(&out.stderr);
+    assert!(!out.status.success() && err.contains("127.0.0.1:9/bot***/getUpdates") && !err.contains("123:abc"), "{}", err);
+}
+
+#[test]
+fn daily_digest_goes_out_once_a_day_by_mail_and_webhook() {
+    let ws = TempDir::new().unwrap();
+    let dir = ws.path();
+    let (url, seen) = http_server("ok");
+    fs::write(dir.join("think.toml"), format!("[daily]\nat = \"00:00\"\nmail = \"admin\"\nwebhook = \"{}/hook\"\n", url)).unwrap();
+    ok(dir, &["task", "add", "T1", "Build the importer"]);
+    ok(dir, &["task", "add", "T2", "Write its tests", "--due", "2000-01-01"]);
+    ok(dir, &["start", "T1", "bob"]);
+    ok(dir, &["done", "T1"]);
+    let preview = ok(dir, &["daily"]);
+    assert!(preview.contains("Completed (1)\n  [T1] Build the importer"), "{}", preview);
+    assert!(preview.contains("[T2] Write its tests 2000-01-01 (overdue)"), "{}", preview);
+
+    ok(dir, &["monitor", "scan"]);
+    ok(dir, &["monitor", "scan"]);
+    let db = Connection::open(dir.join("think.db")).unwrap();
+    let digests: Vec<(String, String)> = db.prepare("SELECT receiver, body FROM messages WHERE subject LIKE 'Daily digest %'").unwrap()
+        .query_map([], |r| Ok((r.get(0)?, r.get(1)?))).unwrap().map(Result::unwrap).collect();
+    assert_eq!(digests.len(), 1, "{:?}", digests);
+    assert_eq!(digests[0].0, "admin");
+    assert!(digests[0].1.contains("[T1] Build the importer"), "{}", digests[0].1);
+    let hooks = seen.lock().unwrap().clone();
+    assert_eq!(hooks.len(), 1, "{:?}", hooks);
+    assert!(hooks[0].0.starts_with("POST /hook") && hooks[0].1.contains("Daily digest"), "{:?}", hooks);
+    // The next digest starts where this one stopped.
+    assert!(ok(dir, &["daily"]).contains("Completed (0)"));
+}
+
+#[test]
+fn lang_zh_translates_cli_output_and_worker_prompts() {
+    let ws = TempDir::new().unwrap();
+    let dir = ws.path();
+    fs::write(dir.join("think.toml"), "lang = \"zh\"\n").unwrap();
+    assert!(ok(dir, &["task", "add", "T1", "Build the importer"]).contains("任务 [T1] 已登记"));
+    ok(dir, &["start", "T1", "bob"]);
+    let prompt = ok(dir, &["task", "show", "T1", "--prompt"]);
+    assert!(prompt.contains("## TT 约定") && prompt.contains("第一步：运行 `tt agent checkin`"), "{}", prompt);
+
+    let out = Command::new(env!("CARGO_BIN_EXE_think-todo"))
+        .args(["task", "add", "T2", "Write its tests"])
+        .current_dir(dir)
+        .env("TT_SESSION_BACKEND", "mock")
+        .env("TT_LANG", "en")
+        .output()
+        .unwrap();
+    assert!(String::from_utf8_lossy(&out.stdout).contains("Task [T2] registered."));
+
+    // Warnings are translated where people read them and stay in English in the API.
+    ok(dir, &["freeze", "incident 7"]);
+    let board = ok(dir, &["board", "list"]);
+    assert!(board.contains("派发已冻结：incident 7（tt unfreeze）"), "{}", board);
+    assert!(ok(dir, &["notifications", "list"]).contains("派发已冻结：incident 7（tt unfreeze）"));
+    let server = Served::start(dir, &[]);
+    for path in ["/api/dashboard", "/api/notifications"] {
+        let body = server.get(path).1;
+        assert!(body.contains("dispatch frozen: incident 7 (tt unfreeze)") && !body.contains("派发"), "{}", body);
+    }
+    drop(server);
+
+    fs::write(dir.join("think.toml"), "lang = \"fr\"\n").unwrap();
+    let out = tt(dir, &["conventions"]);
+    assert!(!out.status.success());
+    assert!(String::from_utf8_lossy(&out.stderr).contains("lang must be en or zh"));
+}
+
+#[test]
+fn timestamps_print_as_local_time_unless_utc_or_epoch_is_asked_for() {
+    let ws = TempDir::new().unwrap();
+    let dir = ws.path();
+    ok(dir, &["task", "add", "T1", "Build the importer"]);
+    ok(dir, &["start", "T1", "bob"]);
+    let db = Connection::open(dir.join("think.db")).unwrap();
+    db.execute("UPDATE audit_logs SET timestamp = 1720000000", []).unwrap();
+
+    let local = chrono::DateTime::from_timestamp(1720000000, 0).unwrap().with_timezone(&chrono::Local);
+    assert!(ok(dir, &["trail"]).contains(&format!("[{}]", local.format("%Y-%m-%d %H:%M:%S"))));
+    assert!(ok(dir, &["trail", "--utc"]).contains("[2024-07-03 09:46:40Z]"));
+    assert!(ok(dir, &["--epoch", "trail"]).contains("[1720000000]"));
+    fs::write(dir.join("think.toml"), "time = \"epoch\"\n").unwrap();
+    assert!(ok(dir, &["trail"]).contains("[1720000000]"));
+    assert!(ok(dir, &["trail", "--utc"]).contains("[2024-07-03 09:46:40Z]"));
+}
+
+#[test]
+fn theme_can_drop_emoji_and_draw_the_board_in_ascii() {
+    let ws = TempDir::new().unwrap();
+    let dir = ws.path();
+    ok(dir, &["task", "add", "T1", "Build the importer", "--due", "2000-01-01"]);
+    ok(dir, &["start", "T1", "bob"]);
+    let board = ok(dir, &["board", "list"]);
+    assert!(board.contains("║ 💠 THINK-TODO BOARD") && board.contains("→ Agent 'bob'"), "{}", board);
+
+    fs::write(dir.join("think.toml"), "[theme]\nemoji = false\nascii = true\n").unwrap();
+    let board = ok(dir, &["board", "list"]);
+    assert!(board.starts_with(&format!("+{}+\n| THINK-TODO BOARD (SYSTEM PULSE){}|\n", "-".repeat(74), " ".repeat(42))), "{}", board);
+    assert!(board.contains("  -> Agent 'bob' is working on 'T1'") && board.contains("Total: 1 | Open: 0 | Active: 1 | Done: 0"), "{}", board);
+    assert!(!board.chars().any(|c| c == '║' || c == '•' || c == '⚠'), "{}", board);
+    assert!(ok(dir, &["task", "list"]).contains("- [T1] Build the importer (in_progress) OVERDUE since 2000-01-01"));
+    // Piped output is never colored.
+    assert!(!ok(dir, &["trail"]).contains('\x1b'));
+
+    fs::write(dir.join("think.toml"), "[theme]\naccent = \"plaid\"\n").unwrap();
+    assert!(!tt(dir, &["conventions"]).status.success());
+}
+
+#[test]
+fn completion_offers_task_ids_agents_and_rigs_from_the_workspace() {
+    let ws = TempDir::new().unwrap();
+    let dir = ws.path();
+    ok(dir, &["task", "add", "T1", "Build the importer"]);
+    ok(dir, &["task", "add", "T2", "Write its tests"]);
+    ok(dir, &["start", "T1", "bob"]);
+    ok(dir, &["rig", "add", "core", "/srv/core"]);
+    let complete = |line: &[&str]| -> String {
+        let out = Command::new(env!("CARGO_BIN_EXE_think-todo"))
+            .arg("--").args(line)
+            .current_dir(dir)
+            .env("COMPLETE", "fish")
+            .output()
+            .unwrap();
+        String::from_utf8_lossy(&out.stdout).to_string()
+    };
+    let tasks = complete(&["tt", "task", "show", ""]);
+    assert!(tasks.contains("T1\tBuild the importer (in_progress)\n") && tasks.contains("T2\tWrite its tests (open)\n"), "{}", tasks);
+    assert!(complete(&["tt", "nudge", ""]).contains("bob\tT1\n"));
+    assert!(complete(&["tt", "rig", "status", ""]).contains("core\t/srv/core\n"));
+
+    let script = ok(dir, &["completions", "bash"]);
+    assert!(script.contains("COMPLETE=\"bash\""), "{}", script);
+    // Completing outside a workspace creates nothing.
+    let empty = TempDir::new().unwrap();
+    ok(empty.path(), &["completions", "zsh"]);
+    assert!(fs::read_dir(empty.path()).unwrap().next().is_none());
+}
+
+#[test]
+fn short_and_fuzzy_ids_resolve_when_they_match_one_task_or_agent() {
+    let ws = TempDir::new().unwrap();
+    let dir = ws.path();
+    ok(dir, &["task", "add", "TT-auth-refactor-42", "Refactor auth"]);
+    ok(dir, &["task", "add", "TT-billing-1", "Invoices"]);
+    ok(dir, &["task", "add", "TT-billing-2", "Refunds"]);
+    ok(dir, &["start", "auth", "bob"]);
+    assert_eq!(task_row(dir, "TT-auth-refactor-42"), ("in_progress".to_string(), Some("bob".to_string())));
+    assert!(ok(dir, &["task", "show", "tar42"]).contains("Refactor auth"));
+    assert!(ok(dir, &["nudge", "bo", "wrap up"]).contains("'bob'"));
+
+    let out = tt(dir, &["task", "show", "bill"]);
+    assert!(!out.status.success());
+    assert!(String::from_utf8_lossy(&out.stderr).contains("'bill' is ambiguous, it matches TT-billing-1, TT-billing-2"));
+    // New ids are never rewritten into existing ones.
+    ok(dir, &["task", "add", "TT-billing", "Dunning"]);
+    assert!(!tt(dir, &["worker", "nuke", "bo"]).status.success());
+    assert!(session_exists(dir, "worker-bob"));
+    // Closing, merging or removing takes only a whole id or a prefix, and a prefix needs --yes here.
+    let out = tt(dir, &["done", "tar42"]);
+    assert!(!out.status.success() && String::from_utf8_lossy(&out.stderr).contains("not found"), "{:?}", out);
+    let out = tt(dir, &["done", "TT-auth"]);
+    let err = String::from_utf8_lossy(&out.stderr);
+    assert!(!out.status.success() && err.contains("→ resolved TT-auth to TT-auth-refactor-42") && err.contains("pass --yes"), "{}", err);
+    assert_eq!(task_row(dir, "TT-auth-refactor-42").0, "in_progress");
+    let out = tt(dir, &["done", "tt-auth", "--yes"]);
+    assert!(out.status.success() && String::from_utf8_lossy(&out.stderr).contains("→ resolved tt-auth to TT-auth-refactor-42"));
+    assert_eq!(task_row(dir, "TT-auth-refactor-42").0, "closed");
+    assert_eq!(task_row(dir, "TT-billing").0, "open");
+    // Without an id there is only a picker to fall back on, and no terminal to show it in.
+    let out = tt(dir, &["done"]);
+    assert!(!out.status.success() && String::from_utf8_lossy(&out.stderr).contains("give a task id"));
+}
+
+#[test]
+fn filters_act_on_every_matching_task_or_agent() {
+    let ws = TempDir::new().unwrap();
+    let dir = ws.path();
+    ok(dir, &["task", "add", "D1", "Write guide", "--tag", "docs"]);
+    ok(dir, &["task", "add", "D2", "Write FAQ", "--tag", "docs"]);
+    ok(dir, &["task", "add", "C1", "Fix parser"]);
+    ok(dir, &["start", "D1", "bob"]);
+    ok(dir, &["start", "C1", "amy"]);
+
+    let out = ok(dir, &["task", "tag", "--filter", "assignee=bob", "cleanup"]);
+    assert!(out.contains("D1") && !out.contains("C1"));
+    let listed = ok(dir, &["--scope", "tag:cleanup", "task", "list"]);
+    assert!(listed.contains("D1") && !listed.contains("C1"));
+
+    let out = ok(dir, &["nudge", "--filter", "tag=docs", "wrap up"]);
+    assert!(out.contains("'bob'") && !out.contains("'amy'"));
+
+    ok(dir, &["done", "--filter", "status=in_progress,tag=docs"]);
+    assert_eq!(task_row(dir, "D1").0, "closed");
+    assert_eq!(task_row(dir, "D2").0, "open");
+    assert_eq!(task_row(dir, "C1").0, "in_progress");
+    assert!(ok(dir, &["done", "--filter", "status=in_progress,tag=docs"]).contains("No unfinished task matches"));
+    assert!(!tt(dir, &["done", "C1", "--filter", "tag=docs"]).status.success());
+}
+
+#[test]
+fn watch_reruns_read_commands_and_refuses_the_rest() {
+    let ws = TempDir::new().unwrap();
+    let dir = ws.path();
+    ok(dir, &["task", "add", "W1", "Watch me"]);
+    let out = ok(dir, &["watch", "-n", "1s", "--count", "2", "--", "task", "list"]);
+    assert_eq!(out.matches("Every 1s: tt task list").count(), 2);
+    assert_eq!(out.matches("[W1] Watch me").count(), 2);
+
+    let out = tt(dir, &["watch", "--", "done", "W1"]);
+    assert!(String::from_utf8_lossy(&out.stderr).contains("only reruns commands that change nothing"));
+    assert_eq!(task_row(dir, "W1").0, "open");
+}
+
+#[test]
+fn spawn_and_done_audits_carry_time_taken_and_spend() {
+    let ws = TempDir::new().unwrap();
+    let dir = ws.path();
+    ok(dir, &["task", "add", "L1", "Long haul"]);
+    ok(dir, &["start", "L1", "bob"]);
+    ok(dir, &["costs", "add", "L1", "bob", "m", "10", "10", "4.2"]);
+    let conn = Connection::open(dir.join("think.db")).unwrap();
+    conn.execute("UPDATE attempts SET started_at = started_at - 3 * 3600 WHERE task_id = 'L1'", []).unwrap();
+    ok(dir, &["done", "L1"]);
+
+    let meta: String = conn.query_row("SELECT metadata FROM audit_logs WHERE action = 'task_closed'", [], |r| r.get(0)).unwrap();
+    let meta: serde_json::Value = serde_json::from_str(&meta).unwrap();
+    assert!(meta["duration_secs"].as_i64().unwrap() >= 3 * 3600);
+    assert_eq!(meta["cost_usd"].as_f64(), Some(4.2));
+    assert!(ok(dir, &["trail"]).contains("took 3h 0m and $4.20"));
+    assert!(ok(dir, &["task", "show", "L1"]).contains("took 3h 0m and $4.20"));
+}
+
+#[test]
+fn worker_exec_runs_in_the_agents_directory_and_environment() {
+    let ws = TempDir::new().unwrap();
+    let dir = ws.path();
+    ok(dir, &["task", "add", "X1", "Exec me"]);
+    ok(dir, &["start", "X1", "bob"]);
+    let out = ok(dir, &["worker", "exec", "bob", "--", "sh", "-c", "pwd; echo $TT_AGENT $TT_TASK"]);
+    assert!(out.contains("workers/bob"));
+    assert!(out.contains("bob X1"));
+
+    let bin = env!("CARGO_BIN_EXE_think-todo");
+    assert!(ok(dir, &["worker", "exec", "bob", "--", bin, "task", "show", "X1"]).contains("Exec me"));
+    assert!(!tt(dir, &["worker", "exec", "bob", "--", bin, "done", "X1"]).status.success());
+    assert_eq!(task_row(dir, "X1").0, "in_progress");
+
+    let out = tt(dir, &["worker", "exec", "bob", "--", "sh", "-c", "exit 3"]);
+    assert_eq!(out.status.code(), Some(3));
+    assert!(!tt(dir, &["worker", "exec", "amy", "--", "true"]).status.success());
+}
+
+#[test]
+fn higher_priority_tasks_list_first() {
+    let ws = TempDir::new().unwrap();
+    let dir = ws.path();
+    ok(dir, &["task", "add", "P1", "Routine"]);
+    ok(dir, &["task", "add", "P2", "Fire", "--priority", "urgent"]);
+    ok(dir, &["task", "add", "P3", "Someday", "--priority", "low"]);
+    ok(dir, &["task", "add", "P4", "Soon", "--priority", "high"]);
+    let list = ok(dir, &["task", "list"]);
+    let at = |id: &str| list.find(&format!("[{}]", id)).unwrap();
+    assert!(at("P2") < at("P4") && at("P4") < at("P1") && at("P1") < at("P3"));
+    assert!(list.contains("[P2] Fire (open) !urgent"));
+
+    ok(dir, &["task", "edit", "P3", "--priority", "urgent"]);
+    let list = ok(dir, &["task", "list"]);
+    assert!(list.find("[P2]").unwrap() < list.find("[P3]").unwrap() && list.find("[P3]").unwrap() < list.find("[P4]").unwrap());
+    assert!(ok(dir, &["task", "show", "P3"]).contains("[P3] Someday (open) !urgent"));
+    assert!(!tt(dir, &["task", "add", "P5", "Bad", "--priority", "asap"]).status.success());
+}
+
+#[test]
+fn notifications_stay_read_until_the_problem_comes_back() {
+    let ws = TempDir::new().unwrap();
+    let dir = ws.path();
+    assert!(ok(dir, &["notifications", "list"]).contains("Nothing needs your attention"));
+    ok(dir, &["freeze", "over budget"]);
+    let out = ok(dir, &["notifications", "list"]);
+    assert!(out.contains("#1") && out.contains("[frozen] workspace: dispatch frozen: over budget") && out.contains("(unread)"));
+
+    ok(dir, &["notifications", "ack", "1"]);
+    assert!(ok(dir, &["notifications", "list"]).contains("Nothing needs your attention"));
+    assert!(ok(dir, &["notifications", "list", "--all"]).contains("(read)"));
+    assert!(!tt(dir, &["notifications", "ack", "9"]).status.success());
+
+    ok(dir, &["unfreeze"]);
+    assert!(ok(dir, &["notifications", "list", "--all"]).contains("(resolved)"));
+    ok(dir, &["freeze", "again"]);
+    let out = ok(dir, &["notifications", "list"]);
+    assert!(out.contains("#2") && out.contains("dispatch frozen: again"));
+
+    // Listing brings the table up to date first, so only the user may.
+    ok(dir, &["unfreeze"]);
+    let out = Command::new(env!("CARGO_BIN_EXE_think-todo")).args(["notifications", "list"]).current_dir(dir)
+        .env("TT_SESSION_BACKEND", "mock").env("TT_AGENT", "bob").output().unwrap();
+    assert!(String::from_utf8_lossy(&out.stderr).contains("'bob' could not be verified"));
+    let resolved = || Connection::open(dir.join("think.db")).unwrap()
+        .query_row("SELECT resolved_at IS NOT NULL FROM notifications WHERE id = 2", [], |r| r.get::<_, bool>(0)).unwrap();
+    assert!(!resolved());
+    ok(dir, &["notifications", "list"]);
+    assert!(resolved());
+}
+
+#[test]
+fn task_list_filters_by_every_given_tag() {
+    let ws = TempDir::new().unwrap();
+    let dir = ws.path();
+    ok(dir, &["task", "add", "B1", "Split auth", "--tag", "backend", "--tag", "urgent"]);
+    ok(dir, &["task", "add", "B2", "Cache queries", "--tag", "backend"]);
+    ok(dir, &["task", "add", "F1", "Fix layout", "--tag", "frontend"]);
+    let list = ok(dir, &["task", "list", "--tag", "backend"]);
+    assert!(list.contains("[B1] Split auth (open) #backend #urgent") && list.contains("[B2]") && !list.contains("[F1]"));
+    let list = ok(dir, &["task", "list", "--tag", "backend", "--tag", "urgent"]);
+    assert!(list.contains("[B1]") && !list.contains("[B2]"));
+    assert!(!ok(dir, &["task", "list", "--tag", "docs"]).contains("[B1]"));
+}